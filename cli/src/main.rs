@@ -30,6 +30,7 @@ fn main() {
         (@arg server: -s --server +takes_value "Server root")
         (@arg verbose: -v --verbose +multiple "verbose logging")
         (@arg identity: -i --identity +takes_value "Storage directory for identity, including keys (defaults to .sda)")
+        (@arg auth: --auth possible_value[token signature] default_value[token] "authentication with a token shared with the service, or by signing requests")
         (@subcommand ping => (about: "check service availablity"))
         (@subcommand agent =>
             (about: "identity management")
@@ -37,14 +38,27 @@ fn main() {
             (@subcommand create =>
                 (@arg force: -f --force "Overwrite any existing identity")
             )
+            (@subcommand rotate =>
+                (about: "replace the verification key, signing the new key with the current one")
+            )
             (@subcommand keys =>
                 (@subcommand show =>)
                 (@subcommand create =>)
+                (@subcommand rotate =>
+                    (about: "create and upload a new key, revoking all previous ones")
+                )
+            )
+            (@subcommand token =>
+                (@subcommand rotate =>
+                    (about: "replace the auth token shared with the service")
+                )
             )
         )
         (@subcommand clerk =>
             (about: "run a clerk in a loop")
             (@arg once: -o --once "Run just once and leave")
+            (@arg capacity: --capacity +takes_value "maximum number of committees to sit in at once")
+            (@arg recipients: --recipient +takes_value +multiple "only sit in committees of these recipients")
         )
         (@subcommand aggregations =>
             (about: "aggregations command")
@@ -57,21 +71,62 @@ fn main() {
                 (@arg key: +required "key to use for recipient encryption")
                 (@arg share_count: +required "number of shares (and clerks)")
                 (@arg id: --id +takes_value "aggregation id")
-                (@arg mask: --mask possible_value[none full chacha] default_value[none] "mask scheme")
-                (@arg sharing: --sharing possible_value[add shamir] default_value[add] "sharing scheme")
+                (@arg mask: --mask possible_value[none full chacha pairwise] default_value[none] "mask scheme")
+                (@arg sharing: --sharing possible_value[add shamir verifiable] default_value[add] "sharing scheme")
+                (@arg threshold: --threshold +takes_value "privacy threshold for shamir and verifiable sharing (defaults to half the share count)")
+                (@arg bounds: --bounds +takes_value "inclusive range MIN:MAX for every input component; requires verifiable sharing and no masking")
+                (@arg onehot: --onehot conflicts_with[bounds] "require inputs to be one-hot vectors; requires verifiable sharing and no masking")
+                (@arg packing: --packing +takes_value "number of secrets packed together for shamir sharing; a suitable prime no smaller than the modulus is generated when above 1")
+                (@arg noise: --noise possible_value[laplace gaussian] requires[epsilon] "differentially private noise added to the output by the committee; requires additive sharing")
+                (@arg epsilon: --epsilon +takes_value "privacy parameter epsilon of the noise")
+                (@arg delta: --delta +takes_value default_value[0] "privacy parameter delta of gaussian noise")
+                (@arg budget: --budget +takes_value requires[noise] "total privacy loss EPSILON:DELTA allowed over all snapshots; unlimited by default")
+                (@arg sensitivity: --sensitivity +takes_value default_value[1] "largest change a single participation can make to the output")
+                (@arg policy: --policy possible_value[reject latest all] default_value[all] "handling of several participations from the same participant")
+                (@arg opens_at: --opens_at +takes_value "time before which participations are refused, in seconds since the Unix epoch")
+                (@arg closes_at: --closes_at +takes_value "time at which a last snapshot is taken and participations stop, in seconds since the Unix epoch")
+                (@arg min_participations: --min_participations +takes_value default_value[0] "participations needed before the service takes snapshots on its own")
+                (@arg snapshot_interval: --snapshot_interval +takes_value "seconds between snapshots taken by the service while participations are accepted")
+                (@arg incremental: --incremental "snapshots taken by the service only cover participations received since the previous one")
             )
             (@subcommand begin =>
                 (about: "autoselect a committee for the aggregation")
                 (@arg id: +required "aggregation id")
             )
+            (@subcommand cohort =>
+                (about: "close enrollment and form the masking cohort of a pairwise masked aggregation")
+                (@arg aggregation_id: +required "aggregation id")
+            )
             (@subcommand end =>
                 (about: "create an aggregation snapshot and clerking jobs")
                 (@arg aggregation_id: +required "aggregation id")
             )
+            (@subcommand snapshot =>
+                (about: "create an incremental snapshot of the participations received since the previous one, leaving the aggregation open")
+                (@arg aggregation_id: +required "aggregation id")
+            )
+            (@subcommand close =>
+                (about: "stop accepting participations without creating a snapshot")
+                (@arg aggregation_id: +required "aggregation id")
+            )
+            (@subcommand archive =>
+                (about: "archive an aggregation, refusing any further operation on it")
+                (@arg aggregation_id: +required "aggregation id")
+            )
             (@subcommand reveal =>
                 (about: "reveal an aggregation result")
                 (@arg aggregation_id: +required "aggregation id")
+                (@arg snapshot: --snapshot +takes_value "snapshot to reveal (defaults to the first ready one)")
             )
+            (@subcommand contribute =>
+                (about: "reveal our shares of an aggregation result to its recipient, as a recipient group member")
+                (@arg aggregation_id: +required "aggregation id")
+                (@arg snapshot: --snapshot +takes_value "snapshot to reveal (defaults to the first ready one)")
+            )
+        )
+        (@subcommand enroll =>
+            (about: "enroll in the masking cohort of a pairwise masked aggregation")
+            (@arg id: +required "aggregation id")
         )
         (@subcommand participate =>
             (about: "contribute a participation vector to an aggregation")
@@ -96,20 +151,6 @@ fn main() {
 
 fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
 
-    let service = {
-        let server_root = {
-            let root = matches.value_of("server").unwrap_or("http://localhost:8888");
-            debug!("Using service at {}", root);
-            root
-        };
-        let authstore = {
-            let path = matches.value_of("identity").unwrap_or(".sda");
-            debug!("Using authorisation at {}", path);
-            Filebased::new(path)?
-        };
-        SdaHttpClient::new(server_root, authstore)?
-    };
-
     let identity_path = PathBuf::from(matches.value_of("identity")
         .unwrap_or(".sda"));
 
@@ -124,6 +165,27 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
         Arc::new(Filebased::new(&keystore_path)?)
     };
 
+    let server_root = {
+        let root = matches.value_of("server").unwrap_or("http://localhost:8888");
+        debug!("Using service at {}", root);
+        root
+    };
+
+    let service: Arc<SdaService> = match matches.value_of("auth").unwrap() {
+        "signature" => {
+            debug!("Authenticating by signature");
+            Arc::new(SdaHttpClient::new(server_root, SignatureAuthenticator::new(keystore.clone()))?)
+        }
+        _ => {
+            let authstore = {
+                let path = matches.value_of("identity").unwrap_or(".sda");
+                debug!("Using authorisation at {}", path);
+                Filebased::new(path)?
+            };
+            Arc::new(SdaHttpClient::new(server_root, authstore)?)
+        }
+    };
+
     use sda_client_store::Store;
     let agent = identitystore.get_aliased("agent")?;
 
@@ -153,10 +215,19 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                         info!("Created new agent with id {:?}", &agent.id);
                         agent
                     };
-                    let client = SdaClient::new(agent, keystore, Arc::new(service));
+                    let client = SdaClient::new(agent, keystore, service.clone());
                     Ok(client.upload_agent()?)
                 }
 
+                ("rotate", Some(_)) => {
+                    let agent = agent.ok_or("Agent missing")?;
+                    let client = SdaClient::new(agent, keystore, service.clone());
+                    let agent = client.rotate_verification_key()?;
+                    identitystore.put_aliased("agent", &agent)?;
+                    info!("Rotated verification key of agent {:?} to {:?}", &agent.id, &agent.verification_key.id);
+                    Ok(())
+                }
+
                 ("show", Some(_)) => {
                     match agent {
                         None => {
@@ -172,7 +243,7 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
 
                 ("keys", Some(matches)) => {
                     let agent = agent.ok_or("Agent missing")?;
-                    let client = SdaClient::new(agent, keystore, Arc::new(service));
+                    let client = SdaClient::new(agent, keystore, service.clone());
 
                     match matches.subcommand() {
                         ("create", Some(_)) => {
@@ -182,6 +253,28 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                             Ok(())
                         }
 
+                        ("rotate", Some(_)) => {
+                            let key = client.rotate_encryption_key()?;
+                            info!("Created and uploaded key: {}, previous keys revoked", &key.to_string());
+                            Ok(())
+                        }
+
+                        (cmd, _) => Err(format!("Unknown subcommand {}", cmd))?,
+                    }
+                }
+
+                ("token", Some(matches)) => {
+                    let agent = agent.ok_or("Agent missing")?;
+
+                    match matches.subcommand() {
+                        ("rotate", Some(_)) => {
+                            // tokens are only used, and thus rotated, by the http client
+                            let authstore = Filebased::new(&identity_path)?;
+                            SdaHttpClient::new(server_root, authstore)?.rotate_auth_token(&agent)?;
+                            info!("Rotated auth token of agent {:?}", &agent.id);
+                            Ok(())
+                        }
+
                         (cmd, _) => Err(format!("Unknown subcommand {}", cmd))?,
                     }
                 }
@@ -194,7 +287,19 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
         ("clerk", Some(matches)) => {
             let agent = agent.ok_or("Agent is needed. Maybe run \"sda agent create\" ?")?;
             service.ping()?;
-            let client = SdaClient::new(agent, keystore, Arc::new(service));
+            let client = SdaClient::new(agent.clone(), keystore, service.clone());
+            client.register_as_clerk(&ClerkRegistration {
+                clerk: agent.id,
+                capacity: match matches.value_of("capacity") {
+                    Some(_) => Some(value_t!(matches.value_of("capacity"), usize).unwrap_or_else(|e| e.exit())),
+                    None => None,
+                },
+                supported_schemes: vec![],
+                allowed_recipients: match matches.values_of("recipients") {
+                    Some(_) => values_t!(matches.values_of("recipients"), AgentId).unwrap_or_else(|e| e.exit()),
+                    None => vec![],
+                },
+            })?;
             loop {
                 debug!("Polling for clerking job");
                 client.run_chores(-1)?;
@@ -208,7 +313,7 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
         ("aggregations", Some(matches)) => {
             let agent = agent.ok_or("Agent is needed. Maybe run \"sda agent create\" ?")?;
             service.ping()?;
-            let client = SdaClient::new(agent.clone(), keystore, Arc::new(service));
+            let client = SdaClient::new(agent.clone(), keystore, service.clone());
             match matches.subcommand() {
                 ("create", Some(matches)) => {
                     use std::str::FromStr;
@@ -216,6 +321,8 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                         .unwrap_or_else(|e| e.exit());
                     let share_count = value_t!(matches.value_of("share_count"), usize)
                         .unwrap_or_else(|e| e.exit());
+                    let dimension = value_t!(matches.value_of("dimension"), usize)
+                        .unwrap_or_else(|e| e.exit());
                     let sharing = match matches.value_of("sharing").unwrap() {
                         "add" => {
                             LinearSecretSharingScheme::Additive {
@@ -223,21 +330,122 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                                 share_count: share_count,
                             }
                         }
-                        "shamir" => unimplemented!(),
+                        "shamir" => {
+                            let packing = match matches.value_of("packing") {
+                                Some(_) => value_t!(matches.value_of("packing"), usize)
+                                    .unwrap_or_else(|e| e.exit()),
+                                None => 1,
+                            };
+                            let privacy_threshold = match matches.value_of("threshold") {
+                                Some(_) => value_t!(matches.value_of("threshold"), usize)
+                                    .unwrap_or_else(|e| e.exit()),
+                                // packed sharing needs packing + threshold + 1 to be a power of 2
+                                None if packing > 1 => (packing + share_count / 2 + 1).next_power_of_two() - packing - 1,
+                                None => share_count / 2,
+                            };
+                            if packing > 1 {
+                                LinearSecretSharingScheme::new_packed_shamir_with_modulus(packing, share_count, privacy_threshold, modulus)?
+                            } else {
+                                LinearSecretSharingScheme::BasicShamir {
+                                    share_count: share_count,
+                                    privacy_threshold: privacy_threshold,
+                                    prime_modulus: modulus,
+                                }
+                            }
+                        }
+                        "verifiable" => {
+                            let privacy_threshold = match matches.value_of("threshold") {
+                                Some(_) => value_t!(matches.value_of("threshold"), usize)
+                                    .unwrap_or_else(|e| e.exit()),
+                                None => share_count / 2,
+                            };
+                            LinearSecretSharingScheme::new_verifiable_shamir(share_count, privacy_threshold, modulus)?
+                        }
                         _ => panic!(),
                     };
+                    // packed sharing dictates its own prime field
+                    let modulus = match sharing {
+                        LinearSecretSharingScheme::PackedShamir { prime_modulus, .. } => {
+                            info!("Using generated prime modulus {}", prime_modulus);
+                            prime_modulus
+                        }
+                        _ => modulus,
+                    };
                     let masking = match matches.value_of("mask").unwrap() {
                         "none" => LinearMaskingScheme::None,
                         "full" => LinearMaskingScheme::Full { modulus: modulus },
                         "chacha" => {
                             LinearMaskingScheme::ChaCha {
                                 modulus: modulus,
-                                dimension: share_count,
+                                dimension: dimension,
                                 seed_bitsize: 128,
                             }
                         }
+                        "pairwise" => {
+                            LinearMaskingScheme::Pairwise {
+                                modulus: modulus,
+                                dimension: dimension,
+                            }
+                        }
                         _ => panic!(),
                     };
+                    let input_bounds = match matches.value_of("bounds") {
+                        Some(value) => {
+                            let mut parts = value.splitn(2, ':');
+                            let min = parts.next().and_then(|v| i64::from_str(v).ok())
+                                .ok_or("Bounds must be given as MIN:MAX")?;
+                            let max = parts.next().and_then(|v| i64::from_str(v).ok())
+                                .ok_or("Bounds must be given as MIN:MAX")?;
+                            Some(InputBounds::Range {
+                                min: vec![min; dimension],
+                                max: vec![max; dimension],
+                            })
+                        }
+                        None if matches.is_present("onehot") => Some(InputBounds::OneHot),
+                        None => None,
+                    };
+                    let privacy_budget = match matches.value_of("budget") {
+                        Some(value) => {
+                            let mut parts = value.splitn(2, ':');
+                            let epsilon = parts.next().and_then(|v| f64::from_str(v).ok())
+                                .ok_or("Budget must be given as EPSILON:DELTA")?;
+                            let delta = parts.next().and_then(|v| f64::from_str(v).ok())
+                                .ok_or("Budget must be given as EPSILON:DELTA")?;
+                            Some((epsilon, delta))
+                        }
+                        None => None,
+                    };
+                    let differential_privacy = match matches.value_of("noise") {
+                        Some(noise) => Some(DifferentialPrivacy {
+                            epsilon: value_t!(matches.value_of("epsilon"), f64).unwrap_or_else(|e| e.exit()),
+                            delta: value_t!(matches.value_of("delta"), f64).unwrap_or_else(|e| e.exit()),
+                            sensitivity: value_t!(matches.value_of("sensitivity"), f64).unwrap_or_else(|e| e.exit()),
+                            mechanism: match noise {
+                                "laplace" => NoiseMechanism::DiscreteLaplace,
+                                "gaussian" => NoiseMechanism::DiscreteGaussian,
+                                _ => panic!(),
+                            },
+                            budget: privacy_budget,
+                        }),
+                        None => None,
+                    };
+                    let schedule = if matches.is_present("opens_at") || matches.is_present("closes_at")
+                                      || matches.is_present("snapshot_interval") {
+                        let time = |name: &str| match matches.value_of(name) {
+                            Some(_) => Some(value_t!(matches.value_of(name), u64).unwrap_or_else(|e| e.exit())),
+                            None => None,
+                        };
+                        Some(AggregationSchedule {
+                            opens_at: time("opens_at"),
+                            closes_at: time("closes_at"),
+                            min_participations: value_t!(matches.value_of("min_participations"), usize)
+                                .unwrap_or_else(|e| e.exit()),
+                            snapshot_interval: time("snapshot_interval"),
+                            incremental_snapshots: matches.is_present("incremental"),
+                        })
+                    } else {
+                        None
+                    };
                     let id = match matches.value_of("id") {
                         Some(value) => AggregationId::from_str(value)?,
                         None => AggregationId::random(),
@@ -245,8 +453,7 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                     let agg = Aggregation {
                         id: id,
                         title: matches.value_of("title").unwrap().to_string(),
-                        vector_dimension: value_t!(matches.value_of("dimension"), usize)
-                            .unwrap_or_else(|e| e.exit()),
+                        vector_dimension: dimension,
                         modulus: modulus,
                         recipient: agent.id,
                         recipient_key: value_t!(matches.value_of("key"), EncryptionKeyId)
@@ -255,6 +462,16 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                         masking_scheme: masking,
                         recipient_encryption_scheme: AdditiveEncryptionScheme::Sodium,
                         committee_encryption_scheme: AdditiveEncryptionScheme::Sodium,
+                        input_bounds: input_bounds,
+                        differential_privacy: differential_privacy,
+                        numeric_representation: NumericRepresentation::I64,
+                        recipient_group: None,
+                        participation_policy: match matches.value_of("policy").unwrap() {
+                            "reject" => ParticipationPolicy::Reject,
+                            "latest" => ParticipationPolicy::KeepLatest,
+                            _ => ParticipationPolicy::KeepAll,
+                        },
+                        schedule: schedule,
                     };
                     client.upload_aggregation(&agg)?;
                     info!("aggregation created. id: {}", agg.id().to_string());
@@ -265,24 +482,74 @@ fn run(matches: &clap::ArgMatches) -> SdaCliResult<()> {
                             .unwrap_or_else(|e| e.exit()))?;
                     Ok(())
                 }
+                ("cohort", Some(matches)) => {
+                    client.form_masking_cohort(&value_t!(matches.value_of("aggregation_id"), AggregationId)
+                            .unwrap_or_else(|e| e.exit()))?;
+                    Ok(())
+                }
                 ("end", Some(matches)) => {
                     client.end_aggregation(&value_t!(matches.value_of("aggregation_id"), AggregationId)
                             .unwrap_or_else(|e| e.exit()))?;
                     Ok(())
                 }
-                ("reveal", Some(matches)) => {
-                    let result = client.reveal_aggregation(&value_t!(matches.value_of("aggregation_id"), AggregationId)
+                ("snapshot", Some(matches)) => {
+                    let snapshot = client.snapshot_new_participations(&value_t!(matches.value_of("aggregation_id"), AggregationId)
                             .unwrap_or_else(|e| e.exit()))?;
+                    info!("snapshot created. id: {}", snapshot.to_string());
+                    Ok(())
+                }
+                ("close", Some(matches)) => {
+                    client.close_aggregation(&value_t!(matches.value_of("aggregation_id"), AggregationId)
+                            .unwrap_or_else(|e| e.exit()))?;
+                    Ok(())
+                }
+                ("archive", Some(matches)) => {
+                    client.archive_aggregation(&value_t!(matches.value_of("aggregation_id"), AggregationId)
+                            .unwrap_or_else(|e| e.exit()))?;
+                    Ok(())
+                }
+                ("reveal", Some(matches)) => {
+                    let aggregation = value_t!(matches.value_of("aggregation_id"), AggregationId)
+                            .unwrap_or_else(|e| e.exit());
+                    let result = match matches.value_of("snapshot") {
+                        Some(_) => client.reveal_snapshot(&aggregation, &value_t!(matches.value_of("snapshot"), SnapshotId)
+                            .unwrap_or_else(|e| e.exit()))?,
+                        None => client.reveal_aggregation(&aggregation)?,
+                    };
                     println!("result: {:?}", result.positive());
                     Ok(())
                 }
+                ("contribute", Some(matches)) => {
+                    let aggregation = value_t!(matches.value_of("aggregation_id"), AggregationId)
+                            .unwrap_or_else(|e| e.exit());
+                    let snapshot = match matches.value_of("snapshot") {
+                        Some(_) => value_t!(matches.value_of("snapshot"), SnapshotId).unwrap_or_else(|e| e.exit()),
+                        None => {
+                            let status = service.get_aggregation_status(&client.agent, &aggregation)?
+                                .ok_or("Unknown aggregation")?;
+                            status.snapshots.iter()
+                                .find(|snapshot| snapshot.result_ready)
+                                .ok_or("Aggregation not ready")?
+                                .id
+                        }
+                    };
+                    client.contribute_to_reveal(&aggregation, &snapshot)?;
+                    Ok(())
+                }
                 (cmd, _) => Err(format!("Unknown command {}", cmd))?,
             }
         }
 
+        ("enroll", Some(matches)) => {
+            let agent = agent.ok_or("Agent is needed. Maybe run \"sda agent create\" ?")?;
+            let client = SdaClient::new(agent, keystore, service.clone());
+            client.enroll(&value_t!(matches.value_of("id"), AggregationId).unwrap_or_else(|e| e.exit()))?;
+            Ok(())
+        }
+
         ("participate", Some(matches)) => {
             let agent = agent.ok_or("Agent is needed. Maybe run \"sda agent create\" ?")?;
-            let client = SdaClient::new(agent, keystore, Arc::new(service));
+            let client = SdaClient::new(agent, keystore, service.clone());
             client.participate(
                 values_t!(matches.values_of("values"), i64).unwrap_or_else(|e| e.exit()),
                 &value_t!(matches.value_of("id"), AggregationId).unwrap_or_else(|e| e.exit())
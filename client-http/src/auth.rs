@@ -0,0 +1,70 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sda_protocol::*;
+use sda_client::{AgentSigner, Keystore};
+use reqwest::{Url, Method, RequestBuilder};
+use reqwest::header::*;
+use sodiumoxide::crypto::hash::sha256;
+
+use errors::*;
+use tokenstore::*;
+
+/// Strategy for authenticating the requests made on behalf of a caller.
+pub trait Authenticator {
+    /// Add credentials for `caller` to a request with the given body.
+    fn authenticate(&self, request: RequestBuilder, caller: &Agent, method: &Method, url: &Url, body: &[u8]) -> SdaHttpClientResult<RequestBuilder>;
+}
+
+/// Authentication by a secret token, shared with the service in Basic mode.
+impl<S: TokenStore> Authenticator for S {
+    fn authenticate(&self, request: RequestBuilder, caller: &Agent, _method: &Method, _url: &Url, _body: &[u8]) -> SdaHttpClientResult<RequestBuilder> {
+        let auth_token = self.get()?;
+        Ok(request
+            .header(Authorization(Basic {
+                username: caller.id.to_string(),
+                password: Some(auth_token),
+            })))
+    }
+}
+
+/// Authentication by signing a digest of each request with the verification key of the caller.
+///
+/// No secret is shared with the service, but clocks must roughly agree. Signatures cover the body
+/// of the request, and the service refuses to see the same one twice.
+pub struct SignatureAuthenticator<K> {
+    keystore: Arc<K>,
+}
+
+impl<K: Keystore + 'static> SignatureAuthenticator<K> {
+    pub fn new(keystore: Arc<K>) -> SignatureAuthenticator<K> {
+        SignatureAuthenticator {
+            keystore: keystore,
+        }
+    }
+}
+
+impl<K: Keystore + 'static> Authenticator for SignatureAuthenticator<K> {
+    fn authenticate(&self, request: RequestBuilder, caller: &Agent, method: &Method, url: &Url, body: &[u8]) -> SdaHttpClientResult<RequestBuilder> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_err(|_| "Clock set before Unix epoch")?
+            .as_secs();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let digest = RequestDigest {
+            agent: caller.id,
+            method: method.to_string(),
+            path: path,
+            timestamp: timestamp,
+            body_hash: byte_arrays::B32(sha256::hash(body).0),
+            nonce: ::rand::random(),
+        };
+        let signer = AgentSigner::new(self.keystore.clone());
+        let signed_digest = signer.sign(caller, digest)?;
+        let encoded = ::data_encoding::base64::encode(&*::serde_json::to_vec(&signed_digest)?);
+        Ok(request
+            .header(Authorization(format!("SdaSignature {}", encoded))))
+    }
+}
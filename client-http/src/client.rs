@@ -2,39 +2,36 @@ use sda_protocol::*;
 use reqwest::{self, Url, StatusCode, Method, RequestBuilder, Response};
 use reqwest::header::*;
 use serde;
+use serde_json;
 
 use errors::*;
+use auth::*;
 use tokenstore::*;
 
 pub struct SdaHttpClient<S> {
     client: reqwest::Client,
     server_root: Url,
-    token_store: S,
+    authenticator: S,
 }
 
-impl<S: TokenStore> SdaHttpClient<S> {
+impl<S: Authenticator> SdaHttpClient<S> {
 
-    pub fn new(server_root: &str, token_store: S) -> SdaHttpClientResult<SdaHttpClient<S>> {
+    pub fn new(server_root: &str, authenticator: S) -> SdaHttpClientResult<SdaHttpClient<S>> {
         Ok(SdaHttpClient {
             client: reqwest::Client::new()?,
             server_root: Url::parse(server_root)?,
-            token_store: token_store,
+            authenticator: authenticator,
         })
     }
 
-    fn decorate(&self, mut request: RequestBuilder, caller: Option<&Agent>) -> SdaHttpClientResult<RequestBuilder> {
+    fn decorate(&self, mut request: RequestBuilder, method: Method, url: &Url, caller: Option<&Agent>, body: &[u8]) -> SdaHttpClientResult<RequestBuilder> {
         // user agent
         request = request
             .header(UserAgent("SDA CLI client".to_string()));
 
-        // auth token
+        // credentials
         if let Some(agent) = caller {
-            let auth_token = self.token_store.get()?;
-            request = request
-                .header(Authorization(Basic {
-                    username: agent.id.to_string(),
-                    password: Some(auth_token),
-                }));
+            request = self.authenticator.authenticate(request, agent, &method, url, body)?;
         }
 
         Ok(request)
@@ -99,9 +96,9 @@ impl<S: TokenStore> SdaHttpClient<S> {
         where U: serde::Deserialize
     {
         let request = self.client
-            .get(url);
+            .get(url.clone());
 
-        let response = self.decorate(request, caller)?.send()?;
+        let response = self.decorate(request, Method::Get, &url, caller, &[])?.send()?;
         self.process(response)
     }
 
@@ -110,11 +107,14 @@ impl<S: TokenStore> SdaHttpClient<S> {
             T: serde::Serialize,
             U: serde::Deserialize,
     {
+        // serialized up front, as signatures cover the exact body sent
+        let body = serde_json::to_vec(body)?;
         let request = self.client
-            .post(url)
-            .json(body);
+            .post(url.clone())
+            .header(ContentType::json())
+            .body(body.clone());
 
-        let response = self.decorate(request, caller)?.send()?;
+        let response = self.decorate(request, Method::Post, &url, caller, &body)?.send()?;
         self.process(response)
     }
 
@@ -122,9 +122,9 @@ impl<S: TokenStore> SdaHttpClient<S> {
         where U: serde::Deserialize
     {
         let request = self.client
-            .request(Method::Delete, url);
+            .request(Method::Delete, url.clone());
 
-        let response = self.decorate(request, caller)?.send()?;
+        let response = self.decorate(request, Method::Delete, &url, caller, &[])?.send()?;
         self.process(response)
     }
 
@@ -137,6 +137,20 @@ impl<S: TokenStore> SdaHttpClient<S> {
 
 }
 
+impl<S: Authenticator + TokenStore> SdaHttpClient<S> {
+
+    /// Replace the auth token of the caller by a fresh one, first on the service then locally.
+    pub fn rotate_auth_token(&self, caller: &Agent) -> SdaHttpClientResult<()> {
+        let token = Labelled {
+            id: caller.id,
+            body: new_token()?,
+        };
+        self.post::<_, ()>(Some(caller), self.url("/v1/agents/me/auth_token")?, &token)?;
+        Ok(self.authenticator.replace(&token.body)?)
+    }
+
+}
+
 macro_rules! wrap_empty {
     ($e:expr) => {
         match $e {
@@ -171,11 +185,11 @@ macro_rules! wrap_option_payload {
 }
 
 impl<S> SdaService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {}
 
 impl<S> SdaBaseService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
     fn ping(&self) -> SdaResult<Pong> {
         wrap_payload! { self.get(
@@ -186,7 +200,7 @@ impl<S> SdaBaseService for SdaHttpClient<S>
 }
 
 impl<S> SdaAgentService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
 
     fn create_agent(&self, caller: &Agent, agent: &Agent) -> SdaResult<()> {
@@ -204,6 +218,21 @@ impl<S> SdaAgentService for SdaHttpClient<S>
         ) }
     }
 
+    fn rotate_verification_key(&self, caller: &Agent, rotation: &SignedVerificationKeyRotation) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedVerificationKeyRotation, ()>(
+            Some(caller),
+            self.url("/v1/agents/me/verification_keys")?,
+            rotation
+        ) }
+    }
+
+    fn get_verification_key_history(&self, caller: &Agent, owner: &AgentId) -> SdaResult<Vec<SignedVerificationKeyRotation>> {
+        wrap_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/agents/{}/verification_keys", owner.to_string()))?
+        ) }
+    }
+
     fn upsert_profile(&self, caller: &Agent, profile: &Profile) -> SdaResult<()> {
         wrap_empty! { self.post::<Profile, ()>(
             Some(caller),
@@ -234,10 +263,25 @@ impl<S> SdaAgentService for SdaHttpClient<S>
         ) }
     }
 
+    fn list_encryption_keys(&self, caller: &Agent, owner: &AgentId) -> SdaResult<Vec<EncryptionKeyId>> {
+        wrap_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/agents/{}/keys", owner.to_string()))?
+        ) }
+    }
+
+    fn revoke_encryption_key(&self, caller: &Agent, revocation: &SignedEncryptionKeyRevocation) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedEncryptionKeyRevocation, ()>(
+            Some(caller),
+            self.url("/v1/agents/me/keys/revocations")?,
+            revocation
+        ) }
+    }
+
 }
 
 impl<S> SdaAggregationService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
 
     fn list_aggregations(&self, caller: &Agent, filter: Option<&str>, recipient: Option<&AgentId>) -> SdaResult<Vec<AggregationId>> {
@@ -259,38 +303,60 @@ impl<S> SdaAggregationService for SdaHttpClient<S>
         ) }
     }
 
-    fn get_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<Aggregation>> {
+    fn get_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<SignedAggregation>> {
         wrap_option_payload! { self.get(
             Some(caller),
             self.url(format!("/v1/aggregations/{}", aggregation.to_string()))?
         ) }
     }
 
-    fn get_committee(&self, caller: &Agent, owner: &AggregationId) -> SdaResult<Option<Committee>> {
+    fn get_committee(&self, caller: &Agent, owner: &AggregationId) -> SdaResult<Option<SignedCommittee>> {
         wrap_option_payload! { self.get(
             Some(caller),
             self.url(format!("/v1/aggregations/{}/committee", owner.to_string()))?
         ) }
     }
 
+    fn get_snapshot_participations(&self, caller: &Agent, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaResult<Vec<SignedParticipation>> {
+        wrap_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/snapshots/{}/participations", aggregation.to_string(), snapshot.to_string()))?
+        ) }
+    }
+
 }
 
 impl<S> SdaParticipationService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
 
-    fn create_participation(&self, caller: &Agent, participation: &Participation) -> SdaResult<()> {
-        wrap_empty! { self.post::<Participation, ()>(
+    fn create_participation(&self, caller: &Agent, participation: &SignedParticipation) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedParticipation, ()>(
             Some(caller),
             self.url("/v1/aggregations/participations")?,
             participation
         ) }
     }
 
+    fn create_enrollment(&self, caller: &Agent, enrollment: &Enrollment) -> SdaResult<()> {
+        wrap_empty! { self.post::<Enrollment, ()>(
+            Some(caller),
+            self.url("/v1/aggregations/enrollments")?,
+            enrollment
+        ) }
+    }
+
+    fn get_masking_cohort(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<MaskingCohort>> {
+        wrap_option_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/cohort", aggregation.to_string()))?
+        ) }
+    }
+
 }
 
 impl<S> SdaClerkingService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
 
     fn get_clerking_job(&self, caller: &Agent, _clerk: &AgentId) -> SdaResult<Option<ClerkingJob>> {
@@ -300,10 +366,48 @@ impl<S> SdaClerkingService for SdaHttpClient<S>
         ) }
     }
 
-    fn create_clerking_result(&self, caller: &Agent, result: &ClerkingResult) -> SdaResult<()> {
-        wrap_empty! { self.post::<ClerkingResult, ()>(
+    fn clerk_ping(&self, caller: &Agent) -> SdaResult<Pong> {
+        wrap_payload! { self.post::<(), Pong>(
+            Some(caller),
+            self.url("/v1/aggregations/any/jobs/ping")?,
+            &()
+        ) }
+    }
+
+    fn create_clerk_registration(&self, caller: &Agent, registration: &SignedClerkRegistration) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedClerkRegistration, ()>(
+            Some(caller),
+            self.url("/v1/agents/me/clerk_registration")?,
+            registration
+        ) }
+    }
+
+    fn get_clerk_registration(&self, caller: &Agent, clerk: &AgentId) -> SdaResult<Option<SignedClerkRegistration>> {
+        wrap_option_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/agents/{}/clerk_registration", clerk.to_string()))?
+        ) }
+    }
+
+    fn list_pending_seats(&self, caller: &Agent, _clerk: &AgentId) -> SdaResult<Vec<AggregationId>> {
+        wrap_payload! { self.get(
             Some(caller),
-            self.url(format!("/v1/aggregations/implied/jobs/{}/result", result.job.to_string()))?,
+            self.url("/v1/aggregations/any/seats")?
+        ) }
+    }
+
+    fn create_seat_acceptance(&self, caller: &Agent, acceptance: &SignedSeatAcceptance) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedSeatAcceptance, ()>(
+            Some(caller),
+            self.url("/v1/aggregations/implied/seats")?,
+            acceptance
+        ) }
+    }
+
+    fn create_clerking_result(&self, caller: &Agent, result: &SignedClerkingResult) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedClerkingResult, ()>(
+            Some(caller),
+            self.url(format!("/v1/aggregations/implied/jobs/{}/result", result.body.job.to_string()))?,
             result
         ) }
     }
@@ -311,32 +415,57 @@ impl<S> SdaClerkingService for SdaHttpClient<S>
 }
 
 impl<S> SdaRecipientService for SdaHttpClient<S>
-    where S: Send + Sync + TokenStore
+    where S: Send + Sync + Authenticator
 {
 
-    fn create_aggregation(&self, caller: &Agent, aggregation: &Aggregation) -> SdaResult<()> {
-        wrap_empty! { self.post::<Aggregation, ()>(
+    fn create_aggregation(&self, caller: &Agent, aggregation: &SignedAggregation) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedAggregation, ()>(
             Some(caller),
             self.url("/v1/aggregations")?,
             aggregation
         ) }
     }
 
-    fn suggest_committee(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Vec<ClerkCandidate>> {
+    fn suggest_committee(&self, caller: &Agent, aggregation: &AggregationId, criteria: &CommitteeCriteria) -> SdaResult<Vec<ClerkCandidate>> {
+
+        let mut url = self.url(format!("/v1/aggregations/{}/committee/suggestions", aggregation.to_string()))?;
+
+        if let Some(count) = criteria.count {
+            url.query_pairs_mut().append_pair("count", &count.to_string());
+        }
+        if let Some(max_staleness) = criteria.max_staleness {
+            url.query_pairs_mut().append_pair("max_staleness", &max_staleness.to_string());
+        }
+        for &(name, flag) in &[("suitable_keys", criteria.suitable_keys),
+                               ("exclude_recipient", criteria.exclude_recipient),
+                               ("randomize", criteria.randomize)] {
+            if flag {
+                url.query_pairs_mut().append_pair(name, "true");
+            }
+        }
+
         wrap_payload! { self.get(
             Some(caller),
-            self.url(format!("/v1/aggregations/{}/committee/suggestions", aggregation.to_string()))?
+            url
         ) }
     }
 
-    fn create_committee(&self, caller: &Agent, committee: &Committee) -> SdaResult<()> {
-        wrap_empty! { self.post::<Committee, ()>(
+    fn create_committee(&self, caller: &Agent, committee: &SignedCommittee) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedCommittee, ()>(
             Some(caller),
             self.url("/v1/aggregations/implied/committee")?,
             committee
         ) }
     }
 
+    fn create_masking_cohort(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        wrap_empty! { self.post::<AggregationId, ()>(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/cohort", aggregation.to_string()))?,
+            aggregation
+        ) }
+    }
+
     fn get_aggregation_status(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<AggregationStatus>> {
         wrap_option_payload! { self.get(
             Some(caller), 
@@ -352,6 +481,22 @@ impl<S> SdaRecipientService for SdaHttpClient<S>
         ) }
     }
 
+    fn close_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        wrap_empty! { self.post::<AggregationId, ()>(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/close", aggregation.to_string()))?,
+            aggregation
+        ) }
+    }
+
+    fn archive_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        wrap_empty! { self.post::<AggregationId, ()>(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/archive", aggregation.to_string()))?,
+            aggregation
+        ) }
+    }
+
     fn get_snapshot_result(&self, caller: &Agent, aggregation: &AggregationId, snapshot:&SnapshotId) -> SdaResult<Option<SnapshotResult>> {
         wrap_payload! { self.get(
             Some(caller),
@@ -367,4 +512,20 @@ impl<S> SdaRecipientService for SdaHttpClient<S>
         ) }
     }
 
+    fn create_reveal_contribution(&self, caller: &Agent, contribution: &SignedRevealContribution) -> SdaResult<()> {
+        wrap_empty! { self.post::<SignedRevealContribution, ()>(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/snapshots/{}/reveal",
+                contribution.body.aggregation.to_string(), contribution.body.snapshot.to_string()))?,
+            contribution
+        ) }
+    }
+
+    fn get_reveal_contributions(&self, caller: &Agent, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaResult<Vec<SignedRevealContribution>> {
+        wrap_payload! { self.get(
+            Some(caller),
+            self.url(format!("/v1/aggregations/{}/snapshots/{}/reveal", aggregation.to_string(), snapshot.to_string()))?
+        ) }
+    }
+
 }
@@ -7,6 +7,7 @@ error_chain!{
     }
     foreign_links {
         Store(::sda_client_store::SdaClientStoreError);
+        Client(::sda_client::SdaClientError);
         SerdeJson(::serde_json::Error);
         Http(::reqwest::Error);
         Url(::reqwest::UrlError);
@@ -1,18 +1,24 @@
 //! This crate provides HTTP access to the SDA services for clients.
 
+extern crate data_encoding;
 extern crate rand;
 #[macro_use]
 extern crate error_chain;
 extern crate serde;
 extern crate serde_json;
 extern crate reqwest;
+extern crate sodiumoxide;
 
 extern crate sda_protocol;
+extern crate sda_client;
 extern crate sda_client_store;
 
 mod errors;
 mod tokenstore;
+mod auth;
 mod client;
 
+pub use auth::{Authenticator, SignatureAuthenticator};
 pub use client::{SdaHttpClient};
+pub use tokenstore::TokenStore;
 pub use errors::{SdaHttpClientError};
\ No newline at end of file
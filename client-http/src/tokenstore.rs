@@ -3,6 +3,16 @@ use sda_client_store::{Store, SdaClientStoreResult};
 
 pub trait TokenStore {
     fn get(&self) -> SdaClientStoreResult<String>;
+    fn replace(&self, token: &str) -> SdaClientStoreResult<()>;
+}
+
+/// Generate a fresh random token.
+pub fn new_token() -> SdaClientStoreResult<String> {
+    let mut rng = OsRng::new().map_err(|_| "Could not get randomness source")?;
+    Ok(rng
+        .gen_ascii_chars()
+        .take(32)
+        .collect::<String>())
 }
 
 impl<S: Store> TokenStore for S {
@@ -10,14 +20,14 @@ impl<S: Store> TokenStore for S {
         match self.get("auth_token")? {
             Some(existing) => Ok(existing),
             None => {
-                let mut rng = OsRng::new().map_err(|_| "Could not get randomness source")?;
-                let new_token = rng
-                    .gen_ascii_chars()
-                    .take(32)
-                    .collect::<String>();
+                let new_token = new_token()?;
                 self.put("auth_token", &new_token)?;
                 Ok(new_token)
             }
         }
     }
-}
\ No newline at end of file
+
+    fn replace(&self, token: &str) -> SdaClientStoreResult<()> {
+        self.put("auth_token", &token)
+    }
+}
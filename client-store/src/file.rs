@@ -70,4 +70,13 @@ impl KeyStorage<VerificationKeyId, SignatureKeypair> for Filebased {
     }
 }
 
+impl KeyStorage<AgentId, ClerkRegistration> for Filebased {
+    fn put(&self, id: &AgentId, obj: &ClerkRegistration) -> SdaClientResult<()> {
+        wrap! { <Self as Store>::put(self, &format!("clerk_registration_{}", id.to_string()), obj) }
+    }
+    fn get(&self, id: &AgentId) -> SdaClientResult<Option<ClerkRegistration>> {
+        wrap! { <Self as Store>::get(self, &format!("clerk_registration_{}", id.to_string())) }
+    }
+}
+
 impl Keystore for Filebased {}
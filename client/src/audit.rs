@@ -0,0 +1,34 @@
+//! Functionality for auditing resources kept by the service.
+
+use SdaClient;
+use crypto::*;
+use errors::SdaClientResult;
+
+use sda_protocol::*;
+
+/// Checks available to the recipient and the clerks of an aggregation.
+pub trait Auditing {
+
+    /// Download the participations included in a snapshot, checking that each was signed by its participant.
+    fn audit_participations(&self, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaClientResult<Vec<SignedParticipation>>;
+
+}
+
+impl Auditing for SdaClient {
+
+    fn audit_participations(&self, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaClientResult<Vec<SignedParticipation>> {
+        let participations = self.service.get_snapshot_participations(&self.agent, aggregation, snapshot)?;
+        for participation in &participations {
+            if participation.signer != participation.body.participant {
+                Err(format!("Participation {:?} not signed by its participant", participation.body.id))?
+            }
+            let participant = self.service.get_agent(&self.agent, &participation.signer)?
+                .ok_or("Unknown participant")?;
+            if !self.signed_by(&participant, participation)? {
+                Err(format!("Signature verification failed for participation {:?}", participation.body.id))?
+            }
+        }
+        Ok(participations)
+    }
+
+}
@@ -1,18 +1,33 @@
 //! Specific functionality for clerking.
 
 use SdaClient;
+use audit::Auditing;
 use crypto::*;
 use errors::SdaClientResult;
 
 use sda_protocol::*;
 
+use num::BigUint;
+
 /// Basic tasks typically performed by a clerk.
 pub trait Clerking {
 
     /// Execute clerking process once: download, process, and upload the next job pending on the service, if any.
     fn clerk_once(&self) -> SdaClientResult<bool>;
 
-    /// This is the typical method clerks should use. Execute routine chores, including registration and clerking.
+    /// Sign clerking result and upload it to the service.
+    fn upload_clerking_result(&self, result: &ClerkingResult) -> SdaClientResult<()>;
+
+    /// Sign registration as clerk and upload it to the service, replacing any earlier one.
+    fn register_as_clerk(&self, registration: &ClerkRegistration) -> SdaClientResult<()>;
+
+    /// Accept the seats offered in committees, after checking they were set by the recipient.
+    ///
+    /// Seats are only accepted for aggregations admitted by the registration held in the keystore, and
+    /// with one of our own encryption keys; other seats are left pending. Returns the number of seats accepted.
+    fn accept_committee_seats(&self) -> SdaClientResult<usize>;
+
+    /// This is the typical method clerks should use. Execute routine chores, including seat acceptance and clerking.
     ///
     /// Note that a negative `max_iterations` will continue the clerking process until the service 
     /// reports that there are no more jobs.
@@ -30,13 +45,51 @@ impl Clerking for SdaClient {
             },
             Some(job) => {
                 let result = self.process_clerking_job(&job)?;
-                self.service.create_clerking_result(&self.agent, &result)?;
+                self.upload_clerking_result(&result)?;
                 Ok(true)
             }
         }
     }
 
+    fn upload_clerking_result(&self, result: &ClerkingResult) -> SdaClientResult<()> {
+        let signed_result = self.crypto.sign(&self.agent, result.clone())?;
+        Ok(self.service.create_clerking_result(&self.agent, &signed_result)?)
+    }
+
+    fn register_as_clerk(&self, registration: &ClerkRegistration) -> SdaClientResult<()> {
+        let signed_registration = self.crypto.sign(&self.agent, registration.clone())?;
+        self.service.create_clerk_registration(&self.agent, &signed_registration)?;
+        // kept locally as well, so that seats are checked against what we signed rather than what the service says
+        self.keystore.put(&self.agent.id, registration)
+    }
+
+    fn accept_committee_seats(&self) -> SdaClientResult<usize> {
+        let registration: ClerkRegistration = match self.keystore.get(&self.agent.id)? {
+            Some(registration) => registration,
+            None => return Ok(0),
+        };
+        let pending = self.service.list_pending_seats(&self.agent, &self.agent.id)?;
+        let mut accepted = 0;
+        for aggregation_id in pending.iter() {
+            // the service also counts seats held in other aggregations, we only make sure not to exceed
+            // the capacity with the seats offered at once
+            if registration.capacity.map_or(false, |capacity| accepted >= capacity) {
+                break;
+            }
+            // a seat we cannot vouch for is left pending rather than failing the other chores
+            let acceptance = match self.acceptable_seat(aggregation_id, &registration) {
+                Ok(Some(acceptance)) => acceptance,
+                Ok(None) | Err(_) => continue,
+            };
+            let signed_acceptance = self.crypto.sign(&self.agent, acceptance)?;
+            self.service.create_seat_acceptance(&self.agent, &signed_acceptance)?;
+            accepted += 1;
+        }
+        Ok(accepted)
+    }
+
     fn run_chores(&self, max_iterations: isize) -> SdaClientResult<()> {
+        self.accept_committee_seats()?;
         // repeatedly process jobs
         if max_iterations < 0 {
             // loop until there's no more work
@@ -60,13 +113,43 @@ impl Clerking for SdaClient {
 
 impl SdaClient {
 
+    /// Acceptance of our seat in the committee of an aggregation, if both the aggregation and the seat are acceptable.
+    fn acceptable_seat(&self, aggregation_id: &AggregationId, registration: &ClerkRegistration) -> SdaClientResult<Option<SeatAcceptance>> {
+        let aggregation = self.verified_aggregation(aggregation_id)?;
+        if !registration.admits(&aggregation) {
+            return Ok(None);
+        }
+        let committee = self.verified_committee(&aggregation)?;
+        let key = match committee.clerks_and_keys.iter().find(|&&(clerk, _)| clerk == self.agent.id) {
+            Some(&(_, key)) => key,
+            None => return Ok(None),
+        };
+        let keypair: Option<EncryptionKeypair> = self.keystore.get(&key)?;
+        if keypair.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(SeatAcceptance {
+            aggregation: aggregation.id,
+            clerk: self.agent.id,
+            key: key,
+        }))
+    }
+
     fn process_clerking_job(&self, job: &ClerkingJob) -> SdaClientResult<ClerkingResult> {
+        let aggregation = self.verified_aggregation(&job.aggregation)?;
+        match aggregation.numeric_representation {
+            NumericRepresentation::I64 => self.process_numeric_clerking_job::<i64>(job, &aggregation),
+            NumericRepresentation::U128 { .. } => self.process_numeric_clerking_job::<u128>(job, &aggregation),
+            NumericRepresentation::BigUint { .. } => self.process_numeric_clerking_job::<BigUint>(job, &aggregation),
+        }
+    }
+
+    /// Process a clerking job of an aggregation using values of numeric type `T`.
+    fn process_numeric_clerking_job<T: Numeric>(&self, job: &ClerkingJob, aggregation: &Aggregation) -> SdaClientResult<ClerkingResult> {
 
-        let aggregation = self.service.get_aggregation(&self.agent, &job.aggregation)?
-            .ok_or("Unknown aggregation")?;
-        
-        let committee = self.service.get_committee(&self.agent, &job.aggregation)?
-            .ok_or("Unknown committee")?;
+        let modulus = T::aggregation_modulus(aggregation)?;
+
+        let committee = self.verified_committee(aggregation)?;
 
         // FIXME there is some waste in the following split between decrypting and combining
         //  - this could be improved by e.g. allowing an accumulating combiner
@@ -75,35 +158,97 @@ impl SdaClient {
         let own_signed_encryption_key_id = committee.clerks_and_keys.iter().find(|&&(id,_)| id == self.agent.id)
             .ok_or("Could not find own encryption key in keyset")?.1;
 
-        // decrypt shares from participants
-        let share_decryptor = self.crypto.new_share_decryptor(&own_signed_encryption_key_id, &aggregation.committee_encryption_scheme)?;
-        let partially_combined_shares = job.encryptions.iter()
-            .map(|encryption| Ok(share_decryptor.decrypt(encryption)?))
-            .collect::<SdaClientResult<Vec<Vec<Share>>>>()?;
+        // decrypt shares from participants, leaving out those not matching their commitments if any
+        let share_decryptor: Box<ShareDecryptor<T>> = self.crypto.new_share_decryptor(&own_signed_encryption_key_id, &aggregation.committee_encryption_scheme)?;
+        let (partially_combined_shares, commitments, rejected_participations) = if aggregation.committee_sharing_scheme.is_verifiable() {
+            self.verified_participation_shares(job, aggregation, &committee, &*share_decryptor)?
+        } else {
+            let shares = job.encryptions.iter()
+                .map(|encryption| Ok(share_decryptor.decrypt(encryption)?))
+                .collect::<SdaClientResult<Vec<Vec<T>>>>()?;
+            (shares, vec![], vec![])
+        };
 
         // sum up shares
-        let share_combiner = self.crypto.new_share_combiner(&aggregation.committee_sharing_scheme)?;
-        let fully_combined_shares: Vec<Share> = share_combiner.combine(&partially_combined_shares)?;
-
-        // fetch recipient's encryption key and verify signature
-        let recipient_id = &aggregation.recipient;
-        let recipient = self.service.get_agent(&self.agent, recipient_id)?
-            .ok_or("Unknown recipient")?;
-        let recipient_signed_encryption_key = self.service.get_encryption_key(&self.agent, &aggregation.recipient_key)?
-            .ok_or("Unknown recipient encryption key")?;
-        if !recipient.signature_is_valid(&recipient_signed_encryption_key)? {
-            Err("Signature verification failed for recipient key")?
+        let share_combiner = self.crypto.new_share_combiner(&aggregation.committee_sharing_scheme, &modulus)?;
+        let mut fully_combined_shares: Vec<T> = share_combiner.combine(&partially_combined_shares)?;
+
+        // add our part of the noise making the output differentially private
+        if let Some(ref privacy) = aggregation.differential_privacy {
+            let mut noise_generator = self.crypto.new_noise_generator(privacy, aggregation.committee_sharing_scheme.output_size())?;
+            let noise = noise_generator.generate(fully_combined_shares.len());
+            let share_modulus = T::scheme_modulus(aggregation.committee_sharing_scheme.modulus(), &modulus);
+            for (share, noise) in fully_combined_shares.iter_mut().zip(noise) {
+                *share = share.add_mod(&T::from_i64(noise, &share_modulus), &share_modulus);
+            }
         }
-        let recipient_encryption_key = recipient_signed_encryption_key.body.body;
-        // .. and re-encrypt summed shares
-        let share_encryptor = self.crypto.new_share_encryptor(&recipient_encryption_key, &aggregation.recipient_encryption_scheme)?;
+
+        // re-encrypt summed shares for the recipient, using verified keys
+        let share_encryptor = self.recipient_share_encryptor(aggregation, &modulus)?;
         let recipient_encryption: Encryption = share_encryptor.encrypt(&fully_combined_shares)?;
 
+        // re-encrypt our shares of the masking secrets of participants that dropped out, if any
+        let recovery_encryptions = job.recovery_encryptions.iter()
+            .map(|&(participant, ref encryption)| {
+                let shares = share_decryptor.decrypt(encryption)?;
+                Ok((participant, share_encryptor.encrypt(&shares)?))
+            })
+            .collect::<SdaClientResult<Vec<(AgentId, Encryption)>>>()?;
+
         Ok(ClerkingResult {
             job: job.id.clone(),
             clerk: job.clerk,
+            aggregation: job.aggregation,
+            snapshot: job.snapshot,
             encryption: recipient_encryption,
+            commitments: commitments,
+            recovery_encryptions: recovery_encryptions,
+            rejected_participations: rejected_participations,
         })
     }
 
+    /// Decrypt our shares of the participations in the snapshot of a job, checking them against the commitments of
+    /// each participation.
+    ///
+    /// Participations whose shares do not match are left out and returned separately, as including them would make
+    /// our combined shares fail the check of the recipient. The commitments of the others are returned combined.
+    fn verified_participation_shares<T: Numeric>(&self,
+                                                 job: &ClerkingJob,
+                                                 aggregation: &Aggregation,
+                                                 committee: &Committee,
+                                                 share_decryptor: &ShareDecryptor<T>)
+                                                 -> SdaClientResult<(Vec<Vec<T>>, Vec<Commitment>, Vec<ParticipationId>)> {
+        let clerk_index = committee.clerks_and_keys.iter().position(|&(id, _)| id == self.agent.id)
+            .ok_or("Could not find own encryption key in keyset")?;
+        let share_verifier: Box<ShareVerifier<T>> = self.crypto.new_share_verifier(&aggregation.committee_sharing_scheme, aggregation.vector_dimension)?;
+
+        // taken from the participations rather than the job, as only these tie our shares to the signed commitments
+        let participations = self.audit_participations(&job.aggregation, &job.snapshot)?;
+        let mut shares = vec![];
+        let mut commitments = vec![];
+        let mut rejected = vec![];
+        for participation in participations {
+            let participation = participation.body;
+            let valid_shares = participation.clerk_encryptions.iter()
+                .find(|&&(id, _)| id == self.agent.id)
+                .and_then(|&(_, ref encryption)| share_decryptor.decrypt(encryption).ok())
+                .and_then(|participation_shares| {
+                    if share_verifier.verify(clerk_index, &participation_shares, &participation.commitments) {
+                        Some(participation_shares)
+                    } else {
+                        None
+                    }
+                });
+            match valid_shares {
+                Some(participation_shares) => {
+                    shares.push(participation_shares);
+                    commitments.push(participation.commitments);
+                }
+                None => rejected.push(participation.id),
+            }
+        }
+        let commitment_combiner = self.crypto.new_commitment_combiner(&aggregation.committee_sharing_scheme)?;
+        Ok((shares, commitment_combiner.combine(&commitments)?, rejected))
+    }
+
 }
@@ -1,12 +1,17 @@
 //! Code for encryption.
 
 mod sodium;
+mod paillier;
 
 use super::*;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum DecryptionKey {
-    Sodium(::sda_protocol::byte_arrays::B32)
+    Sodium(::sda_protocol::byte_arrays::B32),
+    PackedPaillier {
+        p: Binary,
+        q: Binary,
+    },
 }
 
 /// Description of an encryption keypair.
@@ -16,44 +21,75 @@ pub struct EncryptionKeypair {
     pub dk: DecryptionKey,
 }
 
-pub trait EncryptorConstruction<S> {
+pub trait EncryptionKeyGeneration<S> {
+    /// Create a new encryption keypair suitable for the specified scheme, and save it in the keystore.
+    fn new_encryption_key(&self, scheme: &S) -> SdaClientResult<EncryptionKeyId>;
+}
+
+pub trait EncryptorConstruction<S, T: Numeric = Share> {
     /// Create a new encryptor for the specificed encryption key and scheme.
-    fn new_share_encryptor(&self, ek: &EncryptionKey, scheme: &S) -> SdaClientResult<Box<ShareEncryptor>>;
+    fn new_share_encryptor(&self, ek: &EncryptionKey, scheme: &S) -> SdaClientResult<Box<ShareEncryptor<T>>>;
 }
 
-pub trait ShareEncryptor {
+pub trait ShareEncryptor<T = Share> {
     /// Encrypt shares.
-    fn encrypt(&self, shares: &[Share]) -> SdaClientResult<Encryption>;
+    fn encrypt(&self, shares: &[T]) -> SdaClientResult<Encryption>;
 }
 
-pub trait DecryptorConstruction<ID, S> {
+pub trait DecryptorConstruction<ID, S, T: Numeric = Share> {
     /// Create a new decryptor for the specified keypair and scheme.
-    fn new_share_decryptor(&self, id: &ID, scheme: &S) -> SdaClientResult<Box<ShareDecryptor>>;
+    fn new_share_decryptor(&self, id: &ID, scheme: &S) -> SdaClientResult<Box<ShareDecryptor<T>>>;
 }
 
-pub trait ShareDecryptor {
+pub trait ShareDecryptor<T = Share> {
     /// Decrypt shares.
-    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<Share>>;
+    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<T>>;
 }
 
-impl EncryptorConstruction<AdditiveEncryptionScheme> for CryptoModule {
-    fn new_share_encryptor(&self, ek: &EncryptionKey, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<Box<ShareEncryptor>> {
+impl<T: Numeric> EncryptorConstruction<AdditiveEncryptionScheme, T> for CryptoModule {
+    fn new_share_encryptor(&self, ek: &EncryptionKey, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<Box<ShareEncryptor<T>>> {
         match *scheme {
             AdditiveEncryptionScheme::Sodium => {
                 let encryptor = sodium::Encryptor::new(ek)?;
                 Ok(Box::new(encryptor))
             }
+            AdditiveEncryptionScheme::PackedPaillier { component_count, component_bitsize, max_value_bitsize, .. } => {
+                let encryptor = paillier::Encryptor::new(ek, component_count, component_bitsize, max_value_bitsize)?;
+                Ok(Box::new(encryptor))
+            }
         }
     }
 }
 
-impl DecryptorConstruction<EncryptionKeyId, AdditiveEncryptionScheme> for CryptoModule {
-    fn new_share_decryptor(&self, id: &EncryptionKeyId, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<Box<ShareDecryptor>> {
+impl<T: Numeric> DecryptorConstruction<EncryptionKeyId, AdditiveEncryptionScheme, T> for CryptoModule {
+    fn new_share_decryptor(&self, id: &EncryptionKeyId, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<Box<ShareDecryptor<T>>> {
         match *scheme {
             AdditiveEncryptionScheme::Sodium => {
                 let decryptor = sodium::Decryptor::new(id, &self.keystore)?;
                 Ok(Box::new(decryptor))
             }
+            AdditiveEncryptionScheme::PackedPaillier { component_count, component_bitsize, .. } => {
+                let decryptor = paillier::Decryptor::new(id, &self.keystore, component_count, component_bitsize)?;
+                Ok(Box::new(decryptor))
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+impl EncryptionKeyGeneration<AdditiveEncryptionScheme> for CryptoModule {
+    fn new_encryption_key(&self, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<EncryptionKeyId> {
+        match *scheme {
+            AdditiveEncryptionScheme::Sodium => {
+                self.new_key()
+            }
+            AdditiveEncryptionScheme::PackedPaillier { component_count, component_bitsize, min_modulus_bitsize, .. } => {
+                // the plaintext space must be large enough to hold all packed components
+                let modulus_bitsize = ::std::cmp::max(min_modulus_bitsize, component_count * component_bitsize + 1);
+                let keypair = paillier::new_keypair(modulus_bitsize)?;
+                let id = EncryptionKeyId::random();
+                self.keystore.put(&id, &keypair)?;
+                Ok(id)
+            }
+        }
+    }
+}
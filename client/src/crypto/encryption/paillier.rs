@@ -0,0 +1,257 @@
+//! Packed Paillier encryption, allowing ciphertexts to be summed without decryption.
+
+use super::*;
+
+use num::{BigInt, BigUint, Integer, One, Zero, FromPrimitive};
+use num::bigint::Sign;
+use rand::{Rng, OsRng};
+use std::sync::Arc;
+
+
+pub struct Encryptor {
+    n: BigUint,
+    nn: BigUint,
+    component_count: usize,
+    component_bitsize: usize,
+    max_value_bitsize: usize,
+}
+
+impl Encryptor {
+    pub fn new(ek: &EncryptionKey, component_count: usize, component_bitsize: usize, max_value_bitsize: usize) -> SdaClientResult<Encryptor> {
+        match *ek {
+            EncryptionKey::PackedPaillier(ref raw_n) => {
+                let n = BigUint::from_bytes_be(&raw_n.0);
+                if n.bits() < component_count * component_bitsize {
+                    Err("Paillier modulus too small for packing")?
+                }
+                Ok(Encryptor {
+                    nn: &n * &n,
+                    n: n,
+                    component_count: component_count,
+                    component_bitsize: component_bitsize,
+                    max_value_bitsize: max_value_bitsize,
+                })
+            }
+            _ => Err("Expected a Paillier encryption key")?
+        }
+    }
+}
+
+impl<T: Numeric> ShareEncryptor<T> for Encryptor {
+    fn encrypt(&self, shares: &[T]) -> SdaClientResult<Encryption> {
+        let mut rng = OsRng::new().map_err(|_| "Unable to get randomness source")?;
+        let mut ciphertexts = vec![];
+        for batch in shares.chunks(self.component_count) {
+            // pack
+            let mut plaintext = BigUint::zero();
+            for (ix, share) in batch.iter().enumerate() {
+                let value = share.to_biguint().ok_or("Value out of range for Paillier packing")?;
+                if value.bits() > self.max_value_bitsize {
+                    Err("Value out of range for Paillier packing")?
+                }
+                plaintext = plaintext + (value << (ix * self.component_bitsize));
+            }
+            // encrypt, using generator n+1
+            let r = random_below(&mut rng, &self.n);
+            let gm = (BigUint::one() + &plaintext * &self.n) % &self.nn;
+            let rn = mod_pow(&r, &self.n, &self.nn);
+            let ciphertext = (gm * rn) % &self.nn;
+            ciphertexts.push(Binary(ciphertext.to_bytes_be()));
+        }
+        Ok(Encryption::PackedPaillier {
+            value_count: shares.len(),
+            ciphertexts: ciphertexts,
+        })
+    }
+}
+
+
+pub struct Decryptor {
+    n: BigUint,
+    nn: BigUint,
+    lambda: BigUint,
+    mu: BigUint,
+    component_count: usize,
+    component_bitsize: usize,
+}
+
+impl Decryptor {
+    pub fn new(id: &EncryptionKeyId, keystore: &Arc<Keystore>, component_count: usize, component_bitsize: usize) -> SdaClientResult<Decryptor> {
+        let keypair = keystore.get(id)?.ok_or("Could not load keypair for decryption")?;
+        match keypair {
+            EncryptionKeypair { ek: EncryptionKey::PackedPaillier(raw_n), dk: DecryptionKey::PackedPaillier { p: raw_p, q: raw_q } } => {
+                let n = BigUint::from_bytes_be(&raw_n.0);
+                let p = BigUint::from_bytes_be(&raw_p.0);
+                let q = BigUint::from_bytes_be(&raw_q.0);
+                if &p * &q != n {
+                    Err("Inconsistent Paillier keypair")?
+                }
+                let lambda = (&p - BigUint::one()).lcm(&(&q - BigUint::one()));
+                let mu = mod_inverse(&lambda, &n).ok_or("Inconsistent Paillier keypair")?;
+                Ok(Decryptor {
+                    nn: &n * &n,
+                    n: n,
+                    lambda: lambda,
+                    mu: mu,
+                    component_count: component_count,
+                    component_bitsize: component_bitsize,
+                })
+            }
+            _ => Err("Expected a Paillier keypair")?
+        }
+    }
+}
+
+impl<T: Numeric> ShareDecryptor<T> for Decryptor {
+    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<T>> {
+        let (value_count, ciphertexts) = match encryption {
+            &Encryption::PackedPaillier { value_count, ref ciphertexts } => (value_count, ciphertexts),
+            _ => Err("Expected a Paillier encryption")?
+        };
+        let component_modulus = BigUint::one() << self.component_bitsize;
+        let mut decoded_shares = Vec::with_capacity(ciphertexts.len() * self.component_count);
+        for raw in ciphertexts {
+            // decrypt
+            let ciphertext = BigUint::from_bytes_be(&raw.0);
+            let u = mod_pow(&ciphertext, &self.lambda, &self.nn);
+            let l = (u - BigUint::one()) / &self.n;
+            let plaintext = (l * &self.mu) % &self.n;
+            // unpack
+            for ix in 0..self.component_count {
+                let component = (&plaintext >> (ix * self.component_bitsize)) % &component_modulus;
+                let share = T::from_biguint(&component).ok_or("Decrypted value out of range")?;
+                decoded_shares.push(share);
+            }
+        }
+        if decoded_shares.len() < value_count {
+            Err("Paillier encryption holds too few values")?
+        }
+        decoded_shares.truncate(value_count);
+        Ok(decoded_shares)
+    }
+}
+
+
+/// Generate a fresh keypair with a modulus of at least `min_modulus_bitsize` bits.
+pub fn new_keypair(min_modulus_bitsize: usize) -> SdaClientResult<EncryptionKeypair> {
+    let mut rng = OsRng::new().map_err(|_| "Unable to get randomness source")?;
+    // with the top bit of both primes set their product has at least 2*(prime_bitsize-1) bits
+    let prime_bitsize = min_modulus_bitsize / 2 + 2;
+    let p = random_prime(&mut rng, prime_bitsize);
+    let mut q = random_prime(&mut rng, prime_bitsize);
+    while q == p {
+        q = random_prime(&mut rng, prime_bitsize);
+    }
+    let n = &p * &q;
+    Ok(EncryptionKeypair {
+        ek: EncryptionKey::PackedPaillier(Binary(n.to_bytes_be())),
+        dk: DecryptionKey::PackedPaillier {
+            p: Binary(p.to_bytes_be()),
+            q: Binary(q.to_bytes_be()),
+        },
+    })
+}
+
+
+fn mod_pow(base: &BigUint, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+    let mut result = BigUint::one();
+    let mut base = base % modulus;
+    for byte in exponent.to_bytes_le() {
+        for bit in 0..8 {
+            if (byte >> bit) & 1 == 1 {
+                result = (&result * &base) % modulus;
+            }
+            base = (&base * &base) % modulus;
+        }
+    }
+    result
+}
+
+fn mod_inverse(a: &BigUint, modulus: &BigUint) -> Option<BigUint> {
+    let m = BigInt::from_biguint(Sign::Plus, modulus.clone());
+    let (mut old_r, mut r) = (BigInt::from_biguint(Sign::Plus, a.clone()), m.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    while !r.is_zero() {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = r;
+        r = next_r;
+        let next_s = &old_s - &quotient * &s;
+        old_s = s;
+        s = next_s;
+    }
+    if old_r != BigInt::one() {
+        return None;
+    }
+    (((old_s % &m) + &m) % &m).to_biguint()
+}
+
+fn random_bits<R: Rng>(rng: &mut R, bitsize: usize) -> BigUint {
+    let mut bytes = vec![0_u8; (bitsize + 7) / 8];
+    rng.fill_bytes(&mut bytes);
+    let excess_bits = bytes.len() * 8 - bitsize;
+    bytes[0] &= 0xff >> excess_bits;
+    BigUint::from_bytes_be(&bytes)
+}
+
+fn random_below<R: Rng>(rng: &mut R, bound: &BigUint) -> BigUint {
+    loop {
+        let candidate = random_bits(rng, bound.bits());
+        if !candidate.is_zero() && candidate < *bound {
+            return candidate;
+        }
+    }
+}
+
+fn random_prime<R: Rng>(rng: &mut R, bitsize: usize) -> BigUint {
+    let top_bit = BigUint::one() << (bitsize - 1);
+    loop {
+        let candidate = random_bits(rng, bitsize) | &top_bit | BigUint::one();
+        if is_probable_prime(rng, &candidate) {
+            return candidate;
+        }
+    }
+}
+
+const SMALL_PRIMES: [u32; 24] = [3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97];
+
+/// Miller-Rabin test, preceded by trial division.
+fn is_probable_prime<R: Rng>(rng: &mut R, candidate: &BigUint) -> bool {
+    for &small_prime in SMALL_PRIMES.iter() {
+        let small_prime = BigUint::from_u32(small_prime).unwrap();
+        if *candidate == small_prime {
+            return true;
+        }
+        if (candidate % &small_prime).is_zero() {
+            return false;
+        }
+    }
+
+    let one = BigUint::one();
+    let candidate_minus_one = candidate - &one;
+    let mut d = candidate_minus_one.clone();
+    let mut s = 0;
+    while d.is_even() {
+        d = d >> 1;
+        s += 1;
+    }
+
+    'witness: for _ in 0..40 {
+        let a = random_below(rng, &candidate_minus_one);
+        if a <= one {
+            continue;
+        }
+        let mut x = mod_pow(&a, &d, candidate);
+        if x == one || x == candidate_minus_one {
+            continue;
+        }
+        for _ in 1..s {
+            x = (&x * &x) % candidate;
+            if x == candidate_minus_one {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
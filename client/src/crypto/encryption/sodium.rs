@@ -2,7 +2,6 @@ use super::*;
 
 use sodiumoxide;
 use std::sync::{Arc, Once, ONCE_INIT};
-use integer_encoding::VarInt;
 
 
 static SODIUM_INITIALIZED: Once = ONCE_INIT;
@@ -26,18 +25,17 @@ impl Encryptor {
                     pk: pk
                 })
             }
+            _ => Err("Expected a Sodium encryption key")?
         }
     }
 }
 
-impl ShareEncryptor for Encryptor {
-    fn encrypt(&self, shares: &[Share]) -> SdaClientResult<Encryption> {
+impl<T: Numeric> ShareEncryptor<T> for Encryptor {
+    fn encrypt(&self, shares: &[T]) -> SdaClientResult<Encryption> {
         // encode
         let mut encoded_shares = vec![];
-        let mut buf = [0; 128];
-        for &share in shares {
-            let size = share.encode_var(&mut buf);
-            encoded_shares.extend(&buf[0..size]);
+        for share in shares {
+            share.encode(&mut encoded_shares);
         }
         // encrypt
         let raw_data = sodiumoxide::crypto::sealedbox::seal(&*encoded_shares, &self.pk);
@@ -65,14 +63,16 @@ impl Decryptor {
                     sk: sk,
                 })
             }
+            _ => Err("Expected a Sodium keypair")?
         }
     }
 }
 
-impl ShareDecryptor for Decryptor {
-    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<Share>> {
+impl<T: Numeric> ShareDecryptor<T> for Decryptor {
+    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<T>> {
         let encryption = match encryption {
             &Encryption::Sodium(ref raw) => raw,
+            _ => Err("Expected a Sodium encryption")?
         };
         // decrypt
         let raw_data = match sodiumoxide::crypto::sealedbox::open(&encryption.0[..], &self.pk, &self.sk) {
@@ -83,7 +83,7 @@ impl ShareDecryptor for Decryptor {
         let mut reader = &raw_data[..];
         let mut decoded_shares = vec![];
         while reader.len() > 0 {
-            let (i, size) = Share::decode_var(reader);
+            let (i, size) = T::decode(reader)?;
             decoded_shares.push(i);
             reader = &reader[size..];
         }
@@ -107,11 +107,3 @@ impl KeyGeneration<EncryptionKeyId> for CryptoModule {
         Ok(id)
     }
 }
-
-impl Suitable<AdditiveEncryptionScheme> for EncryptionKeyId {
-    fn suitable_for(&self, scheme: &AdditiveEncryptionScheme) -> bool {
-        match *scheme {
-            AdditiveEncryptionScheme::Sodium => true
-        }
-    }
-}
@@ -0,0 +1,103 @@
+//! Code for encryptions shared among the members of a recipient group.
+
+use super::*;
+
+use super::sharing::ShareGenerator;
+
+use std::cell::RefCell;
+
+pub trait GroupEncryptionConstruction<T: Numeric = Share> {
+    /// Create a new encryptor sharing values modulo `modulus` among the members of a recipient group, given their keys in share order.
+    fn new_group_share_encryptor(&self, group: &RecipientGroup, member_keys: &[EncryptionKey], scheme: &AdditiveEncryptionScheme, modulus: &T) -> SdaClientResult<Box<ShareEncryptor<T>>>;
+
+    /// Create a new decryptor reconstructing values modulo `modulus` from the shares revealed by members of a recipient group.
+    ///
+    /// Each member, given by its index in the group, reveals pairs of encryptions intended for it and their decryption.
+    fn new_group_share_decryptor(&self, group: &RecipientGroup, revealed: Vec<(usize, Vec<(Encryption, Vec<T>)>)>, modulus: &T) -> SdaClientResult<Box<ShareDecryptor<T>>>;
+}
+
+impl<T: Numeric> GroupEncryptionConstruction<T> for CryptoModule {
+    fn new_group_share_encryptor(&self, group: &RecipientGroup, member_keys: &[EncryptionKey], scheme: &AdditiveEncryptionScheme, modulus: &T) -> SdaClientResult<Box<ShareEncryptor<T>>> {
+        if member_keys.len() != group.members.len() {
+            Err("Expected a key for every member of the recipient group")?
+        }
+        let member_encryptors = member_keys.iter()
+            .map(|key| self.new_share_encryptor(key, scheme))
+            .collect::<SdaClientResult<Vec<Box<ShareEncryptor<T>>>>>()?;
+        Ok(Box::new(Encryptor {
+            share_generator: RefCell::new(self.new_share_generator(&group.sharing_scheme, modulus)?),
+            member_encryptors: member_encryptors,
+        }))
+    }
+
+    fn new_group_share_decryptor(&self, group: &RecipientGroup, revealed: Vec<(usize, Vec<(Encryption, Vec<T>)>)>, modulus: &T) -> SdaClientResult<Box<ShareDecryptor<T>>> {
+        if revealed.len() < group.threshold() {
+            Err(format!("Expected shares from at least {} members of the recipient group, found {}",
+                group.threshold(), revealed.len()))?
+        }
+        Ok(Box::new(Decryptor {
+            crypto: CryptoModule::new(self.keystore.clone()),
+            sharing_scheme: group.sharing_scheme.clone(),
+            modulus: modulus.clone(),
+            threshold: group.threshold(),
+            revealed: revealed,
+        }))
+    }
+}
+
+struct Encryptor<T> {
+    share_generator: RefCell<Box<ShareGenerator<T>>>,
+    member_encryptors: Vec<Box<ShareEncryptor<T>>>,
+}
+
+impl<T: Numeric> ShareEncryptor<T> for Encryptor<T> {
+    fn encrypt(&self, values: &[T]) -> SdaClientResult<Encryption> {
+        let shares_per_member = self.share_generator.borrow_mut().generate(values)?;
+        let encryptions = shares_per_member.iter()
+            .zip(&self.member_encryptors)
+            .map(|(shares, encryptor)| encryptor.encrypt(shares))
+            .collect::<SdaClientResult<Vec<Encryption>>>()?;
+        Ok(Encryption::Shared(encryptions))
+    }
+}
+
+struct Decryptor<T> {
+    crypto: CryptoModule,
+    sharing_scheme: LinearSecretSharingScheme,
+    modulus: T,
+    threshold: usize,
+    revealed: Vec<(usize, Vec<(Encryption, Vec<T>)>)>,
+}
+
+impl<T: Numeric> ShareDecryptor<T> for Decryptor<T> {
+    fn decrypt(&self, encryption: &Encryption) -> SdaClientResult<Vec<T>> {
+        let member_encryptions = match *encryption {
+            Encryption::Shared(ref member_encryptions) => member_encryptions,
+            _ => Err("Expected an encryption shared with the recipient group")?,
+        };
+
+        // collect the shares members revealed for this particular encryption
+        let indexed_shares: Vec<(usize, Vec<T>)> = self.revealed.iter()
+            .filter_map(|&(member_index, ref decryptions)| {
+                member_encryptions.get(member_index).and_then(|member_encryption| {
+                    decryptions.iter()
+                        .find(|&&(ref revealed_encryption, _)| revealed_encryption == member_encryption)
+                        .map(|&(_, ref shares)| (member_index, shares.clone()))
+                })
+            })
+            .take(self.threshold)
+            .collect();
+        if indexed_shares.len() < self.threshold {
+            Err(format!("Only {} members of the recipient group revealed their shares, {} needed",
+                indexed_shares.len(), self.threshold))?
+        }
+
+        let dimension = indexed_shares[0].1.len();
+        let reconstructor = self.crypto.new_secret_reconstructor(&self.sharing_scheme, dimension, &self.modulus)?;
+        let modulus = T::scheme_modulus(self.sharing_scheme.modulus(), &self.modulus);
+        Ok(reconstructor.reconstruct(&indexed_shares)?
+            .into_iter()
+            .map(|value| value.reduce(&modulus))
+            .collect())
+    }
+}
@@ -2,15 +2,15 @@
 
 use super::*;
 
-use rand::{Rng, OsRng};
+use rand::OsRng;
 
-pub struct Masker {
-    modulus: i64,
+pub struct Masker<T> {
+    modulus: T,
     rng: OsRng,
 }
 
-impl Masker {
-    pub fn new(modulus: i64) -> Masker {
+impl<T: Numeric> Masker<T> {
+    pub fn new(modulus: T) -> Masker<T> {
         Masker {
             modulus: modulus,
             rng: OsRng::new().expect("Unable to get randomness source"),
@@ -18,32 +18,32 @@ impl Masker {
     }
 }
 
-impl SecretMasker for Masker {
-    fn mask(&mut self, secrets: &[Secret]) -> (Vec<Mask>, Vec<MaskedSecret>) {
+impl<T: Numeric> SecretMasker<T> for Masker<T> {
+    fn mask(&mut self, secrets: &[T]) -> (Vec<T>, Vec<T>) {
 
-        let masks: Vec<Mask> = (0..secrets.len())
-            .map(|_| self.rng.gen_range(0_i64, self.modulus))
+        let masks: Vec<T> = (0..secrets.len())
+            .map(|_| T::random_below(&mut self.rng, &self.modulus))
             .collect();
 
         let masked_secrets = secrets.iter()
             .zip(&masks)
-            .map(|(secret, mask)| (secret + mask) % self.modulus)
+            .map(|(secret, mask)| secret.reduce(&self.modulus).add_mod(mask, &self.modulus))
             .collect();
 
         (masks, masked_secrets)
     }
 }
 
-impl MaskCombiner for Masker {
-    fn combine(&self, masks: &Vec<Vec<Mask>>) -> Vec<Mask> {
+impl<T: Numeric> MaskCombiner<T> for Masker<T> {
+    fn combine(&self, masks: &Vec<Vec<T>>) -> Vec<T> {
         let dimension: usize = masks.get(0).map_or(0, Vec::len);
 
-        let mut result: Vec<Share> = vec![0; dimension];
+        let mut result: Vec<T> = vec![T::zero_value(); dimension];
         for mask in masks {
             assert_eq!(mask.len(), dimension);
             for (ix, value) in mask.iter().enumerate() {
-                result[ix] += *value;
-                result[ix] %= self.modulus;
+                // masks summed by homomorphic encryption may exceed the modulus
+                result[ix] = result[ix].add_mod(&value.reduce(&self.modulus), &self.modulus);
             }
         }
 
@@ -51,15 +51,15 @@ impl MaskCombiner for Masker {
     }
 }
 
-impl SecretUnmasker for Masker {
-    fn unmask(&self, values: &(Vec<Mask>, Vec<MaskedSecret>)) -> Vec<Secret> {
+impl<T: Numeric> SecretUnmasker<T> for Masker<T> {
+    fn unmask(&self, values: &(Vec<T>, Vec<T>)) -> Vec<T> {
         let ref masks = values.0;
         let ref masked_secrets = values.1;
         assert_eq!(masks.len(), masked_secrets.len());
 
         let secrets = masked_secrets.iter()
             .zip(masks)
-            .map(|(masked_secret, mask)| (masked_secret - mask) % self.modulus)
+            .map(|(masked_secret, mask)| masked_secret.sub_mod(mask, &self.modulus))
             .collect();
 
         secrets
@@ -3,92 +3,167 @@
 mod none;
 mod full;
 mod chacha;
+mod pairwise;
 
 use super::*;
 
-pub trait SecretMaskerConstruction<S> {
-    fn new_secret_masker(&self, scheme: &S) -> SdaClientResult<Box<SecretMasker>>;
+/// Constructions of maskers of values of numeric type `T`, modulo `modulus`.
+///
+/// The modulus is that of the aggregation; schemes only supporting 64 bit values use their own.
+pub trait SecretMaskerConstruction<S, T: Numeric = Secret> {
+    fn new_secret_masker(&self, scheme: &S, modulus: &T) -> SdaClientResult<Box<SecretMasker<T>>>;
 }
 
-pub trait SecretMasker {
-    fn mask(&mut self, secrets: &[Secret]) -> (Vec<Mask>, Vec<MaskedSecret>);
+pub trait SecretMasker<T = Secret> {
+    fn mask(&mut self, secrets: &[T]) -> (Vec<T>, Vec<T>);
 }
 
-pub trait MaskCombinerConstruction<S> {
-    fn new_mask_combiner(&self, scheme: &S) -> SdaClientResult<Box<MaskCombiner>>;
+pub trait MaskCombinerConstruction<S, T: Numeric = Mask> {
+    fn new_mask_combiner(&self, scheme: &S, modulus: &T) -> SdaClientResult<Box<MaskCombiner<T>>>;
 }
 
-pub trait MaskCombiner {
-    fn combine(&self, masks: &Vec<Vec<Mask>>) -> Vec<Mask>;
+pub trait MaskCombiner<T = Mask> {
+    fn combine(&self, masks: &Vec<Vec<T>>) -> Vec<T>;
 }
 
-pub trait SecretUnmaskerConstruction<S> {
-    fn new_secret_unmasker(&self, scheme: &S) -> SdaClientResult<Box<SecretUnmasker>>;
+pub trait SecretUnmaskerConstruction<S, T: Numeric = Secret> {
+    fn new_secret_unmasker(&self, scheme: &S, modulus: &T) -> SdaClientResult<Box<SecretUnmasker<T>>>;
 }
 
-pub trait SecretUnmasker {
-    fn unmask(&self, values: &(Vec<Mask>, Vec<MaskedSecret>)) -> Vec<Secret>;
+pub trait SecretUnmasker<T = Secret> {
+    fn unmask(&self, values: &(Vec<T>, Vec<T>)) -> Vec<T>;
 }
 
-impl SecretMaskerConstruction<LinearMaskingScheme> for CryptoModule {
-    fn new_secret_masker(&self, scheme: &LinearMaskingScheme) -> SdaClientResult<Box<SecretMasker>> {
+/// Constructions for schemes where masks are agreed between the members of a masking cohort.
+pub trait PairwiseMaskingConstruction<S> {
+    /// Encode the secret part of an own masking keypair as values suitable for secret sharing with the committee.
+    fn export_masking_secret(&self, scheme: &S, own_key: &EncryptionKeyId) -> SdaClientResult<Vec<Secret>>;
+
+    /// Create a masker agreeing masks with each of the given peers using an own masking keypair.
+    fn new_pairwise_masker<T: Numeric>(&self, scheme: &S, own_key: &EncryptionKeyId, peer_keys: &[EncryptionKey]) -> SdaClientResult<Box<SecretMasker<T>>>;
+
+    /// Create a combiner turning the exported secrets of missing members into the masks left behind for the remaining members.
+    fn new_pairwise_recovery<T: Numeric>(&self, scheme: &S, remaining_keys: &[EncryptionKey], missing_keys: &[EncryptionKey]) -> SdaClientResult<Box<MaskCombiner<T>>>;
+
+    /// Number of values output by `export_masking_secret`.
+    fn masking_secret_size(&self, scheme: &S) -> SdaClientResult<usize>;
+}
+
+impl<T: Numeric> SecretMaskerConstruction<LinearMaskingScheme, T> for CryptoModule {
+    fn new_secret_masker(&self, scheme: &LinearMaskingScheme, modulus: &T) -> SdaClientResult<Box<SecretMasker<T>>> {
         match *scheme {
             LinearMaskingScheme::None => {
                 let masker = none::Masker::new();
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::Full { modulus } => {
-                let masker = full::Masker::new(modulus);
+            LinearMaskingScheme::Full { modulus: scheme_modulus } => {
+                let masker = full::Masker::new(T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::ChaCha { modulus, dimension, seed_bitsize } => {
-                let masker = chacha::Masker::new(modulus, dimension, seed_bitsize);
-                Ok(Box::new(masker))
+            LinearMaskingScheme::ChaCha { modulus: scheme_modulus, dimension, seed_bitsize } => {
+                let masker = chacha::Masker::new(scheme_modulus, dimension, seed_bitsize);
+                narrow::<SecretMasker, _>(Box::new(masker))
+            },
+
+            LinearMaskingScheme::Pairwise { .. } => {
+                Err("Pairwise masking requires the masking cohort")?
             },
         }
     }
 }
 
-impl MaskCombinerConstruction<LinearMaskingScheme> for CryptoModule {
-    fn new_mask_combiner(&self, scheme: &LinearMaskingScheme) -> SdaClientResult<Box<MaskCombiner>> {
+impl<T: Numeric> MaskCombinerConstruction<LinearMaskingScheme, T> for CryptoModule {
+    fn new_mask_combiner(&self, scheme: &LinearMaskingScheme, modulus: &T) -> SdaClientResult<Box<MaskCombiner<T>>> {
         match *scheme {
             LinearMaskingScheme::None => {
                 let masker = none::Masker::new();
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::Full { modulus } => {
-                let masker = full::Masker::new(modulus);
+            LinearMaskingScheme::Full { modulus: scheme_modulus } => {
+                let masker = full::Masker::new(T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::ChaCha { modulus, dimension, seed_bitsize } => {
-                let masker = chacha::Masker::new(modulus, dimension, seed_bitsize);
-                Ok(Box::new(masker))
+            LinearMaskingScheme::ChaCha { modulus: scheme_modulus, dimension, seed_bitsize } => {
+                let masker = chacha::Masker::new(scheme_modulus, dimension, seed_bitsize);
+                narrow::<MaskCombiner, _>(Box::new(masker))
+            },
+
+            LinearMaskingScheme::Pairwise { .. } => {
+                Err("Pairwise masking requires the masking cohort")?
             },
         }
     }
 }
 
-impl SecretUnmaskerConstruction<LinearMaskingScheme> for CryptoModule {
-    fn new_secret_unmasker(&self, scheme: &LinearMaskingScheme) -> SdaClientResult<Box<SecretUnmasker>> {
+impl<T: Numeric> SecretUnmaskerConstruction<LinearMaskingScheme, T> for CryptoModule {
+    fn new_secret_unmasker(&self, scheme: &LinearMaskingScheme, modulus: &T) -> SdaClientResult<Box<SecretUnmasker<T>>> {
         match *scheme {
             LinearMaskingScheme::None => {
                 let masker = none::Masker::new();
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::Full { modulus } => {
-                let masker = full::Masker::new(modulus);
+            LinearMaskingScheme::Full { modulus: scheme_modulus } => {
+                let masker = full::Masker::new(T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(masker))
             },
 
-            LinearMaskingScheme::ChaCha { modulus, dimension, seed_bitsize } => {
-                let masker = chacha::Masker::new(modulus, dimension, seed_bitsize);
+            LinearMaskingScheme::ChaCha { modulus: scheme_modulus, dimension, seed_bitsize } => {
+                let masker = chacha::Masker::new(scheme_modulus, dimension, seed_bitsize);
+                narrow::<SecretUnmasker, _>(Box::new(masker))
+            },
+
+            LinearMaskingScheme::Pairwise { modulus: scheme_modulus, .. } => {
+                // what is left after recovery is an ordinary mask
+                let masker = full::Masker::new(T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(masker))
             },
         }
     }
 }
+
+impl PairwiseMaskingConstruction<LinearMaskingScheme> for CryptoModule {
+    fn export_masking_secret(&self, scheme: &LinearMaskingScheme, own_key: &EncryptionKeyId) -> SdaClientResult<Vec<Secret>> {
+        match *scheme {
+            LinearMaskingScheme::Pairwise { modulus, .. } => {
+                let keypair: Option<EncryptionKeypair> = self.keystore.get(own_key)?;
+                let keypair = keypair.ok_or("Could not load masking keypair")?;
+                pairwise::encode_secret_key(&keypair, modulus)
+            },
+            _ => Err("Not a pairwise masking scheme")?,
+        }
+    }
+
+    fn new_pairwise_masker<T: Numeric>(&self, scheme: &LinearMaskingScheme, own_key: &EncryptionKeyId, peer_keys: &[EncryptionKey]) -> SdaClientResult<Box<SecretMasker<T>>> {
+        match *scheme {
+            LinearMaskingScheme::Pairwise { modulus, dimension } => {
+                let keypair: Option<EncryptionKeypair> = self.keystore.get(own_key)?;
+                let keypair = keypair.ok_or("Could not load masking keypair")?;
+                let masker = pairwise::Masker::new(modulus, dimension, &keypair, peer_keys)?;
+                narrow::<SecretMasker, _>(Box::new(masker))
+            },
+            _ => Err("Not a pairwise masking scheme")?,
+        }
+    }
+
+    fn new_pairwise_recovery<T: Numeric>(&self, scheme: &LinearMaskingScheme, remaining_keys: &[EncryptionKey], missing_keys: &[EncryptionKey]) -> SdaClientResult<Box<MaskCombiner<T>>> {
+        match *scheme {
+            LinearMaskingScheme::Pairwise { modulus, dimension } => {
+                let recovery = pairwise::Recovery::new(modulus, dimension, remaining_keys, missing_keys)?;
+                narrow::<MaskCombiner, _>(Box::new(recovery))
+            },
+            _ => Err("Not a pairwise masking scheme")?,
+        }
+    }
+
+    fn masking_secret_size(&self, scheme: &LinearMaskingScheme) -> SdaClientResult<usize> {
+        match *scheme {
+            LinearMaskingScheme::Pairwise { modulus, .. } => Ok(pairwise::encoded_secret_key_size(modulus)),
+            _ => Err("Not a pairwise masking scheme")?,
+        }
+    }
+}
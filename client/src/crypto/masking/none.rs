@@ -10,23 +10,23 @@ impl Masker {
     }
 }
 
-impl SecretMasker for Masker {
-    fn mask(&mut self, secrets: &[Secret]) -> (Vec<Mask>, Vec<MaskedSecret>) {
+impl<T: Numeric> SecretMasker<T> for Masker {
+    fn mask(&mut self, secrets: &[T]) -> (Vec<T>, Vec<T>) {
         let mask = vec![];
         let masked_secrets = secrets.to_vec();
         (mask, masked_secrets)
     }
 }
 
-impl MaskCombiner for Masker {
-    fn combine(&self, masks: &Vec<Vec<Mask>>) -> Vec<Mask> {
+impl<T: Numeric> MaskCombiner<T> for Masker {
+    fn combine(&self, masks: &Vec<Vec<T>>) -> Vec<T> {
         assert!(masks.iter().all(|mask| mask.len() == 0));
         vec![]
     }
 }
 
-impl SecretUnmasker for Masker {
-    fn unmask(&self, values: &(Vec<Mask>, Vec<MaskedSecret>)) -> Vec<Secret> {
+impl<T: Numeric> SecretUnmasker<T> for Masker {
+    fn unmask(&self, values: &(Vec<T>, Vec<T>)) -> Vec<T> {
         assert_eq!(values.0.len(), 0);
         values.1.to_vec()
     }
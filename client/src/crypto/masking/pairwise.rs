@@ -0,0 +1,169 @@
+//! Pairwise masker, using masks agreed between participants that cancel out in the sum.
+
+use super::*;
+
+use rand::{Rng, SeedableRng, ChaChaRng};
+use sodiumoxide::crypto::box_;
+
+pub struct Masker {
+    modulus: i64,
+    dimension: usize,
+    pk: box_::PublicKey,
+    sk: box_::SecretKey,
+    peers: Vec<box_::PublicKey>,
+}
+
+impl Masker {
+    pub fn new(modulus: i64, dimension: usize, own_keypair: &EncryptionKeypair, peer_keys: &[EncryptionKey]) -> SdaClientResult<Masker> {
+        let (pk, sk) = parse_keypair(own_keypair)?;
+        let peers = peer_keys.iter()
+            .map(parse_public_key)
+            .collect::<SdaClientResult<Vec<box_::PublicKey>>>()?;
+        if peers.contains(&pk) {
+            Err("Own masking key found among peers")?
+        }
+        Ok(Masker {
+            modulus: modulus,
+            dimension: dimension,
+            pk: pk,
+            sk: sk,
+            peers: peers,
+        })
+    }
+}
+
+impl SecretMasker for Masker {
+    fn mask(&mut self, secrets: &[Secret]) -> (Vec<Mask>, Vec<MaskedSecret>) {
+        assert_eq!(self.dimension, secrets.len());
+
+        let mut mask: Vec<Mask> = vec![0; self.dimension];
+        for peer in &self.peers {
+            let shared = box_::precompute(peer, &self.sk);
+            accumulate_pairwise_mask(&mut mask, self.modulus, &shared, &self.pk, peer);
+        }
+
+        let masked_secrets = secrets.iter()
+            .zip(&mask)
+            .map(|(secret, mask)| (secret + mask) % self.modulus)
+            .collect();
+
+        // nothing for the recipient: masks cancel out in the sum
+        (vec![], masked_secrets)
+    }
+}
+
+/// Recovery of the masks left behind by cohort members missing from a snapshot.
+///
+/// Combines the secret keys of the missing members, as reconstructed from the clerks' shares,
+/// into the sum of the masks they agreed with the remaining members.
+pub struct Recovery {
+    modulus: i64,
+    dimension: usize,
+    remaining: Vec<box_::PublicKey>,
+    missing: Vec<box_::PublicKey>,
+}
+
+impl Recovery {
+    pub fn new(modulus: i64, dimension: usize, remaining_keys: &[EncryptionKey], missing_keys: &[EncryptionKey]) -> SdaClientResult<Recovery> {
+        Ok(Recovery {
+            modulus: modulus,
+            dimension: dimension,
+            remaining: remaining_keys.iter().map(parse_public_key).collect::<SdaClientResult<_>>()?,
+            missing: missing_keys.iter().map(parse_public_key).collect::<SdaClientResult<_>>()?,
+        })
+    }
+}
+
+impl MaskCombiner for Recovery {
+    fn combine(&self, encoded_secret_keys: &Vec<Vec<Mask>>) -> Vec<Mask> {
+        assert_eq!(self.missing.len(), encoded_secret_keys.len());
+
+        let mut result: Vec<Mask> = vec![0; self.dimension];
+        for (missing_pk, encoded_sk) in self.missing.iter().zip(encoded_secret_keys) {
+            let sk = box_::SecretKey::from_slice(&decode_secret_key(encoded_sk, self.modulus))
+                .expect("Secret key of fixed length");
+            // the remaining members included these masks from their side of the pair
+            for remaining_pk in &self.remaining {
+                let shared = box_::precompute(remaining_pk, &sk);
+                accumulate_pairwise_mask(&mut result, self.modulus, &shared, remaining_pk, missing_pk);
+            }
+        }
+
+        result
+    }
+}
+
+/// Encode a secret masking key as values modulo `modulus`, suitable for secret sharing.
+pub fn encode_secret_key(keypair: &EncryptionKeypair, modulus: i64) -> SdaClientResult<Vec<Secret>> {
+    let (_, sk) = parse_keypair(keypair)?;
+    let digit_bitsize = digit_bitsize(modulus);
+    let bits: Vec<u8> = sk.0.iter()
+        .flat_map(|byte| (0..8).map(move |ix| (byte >> ix) & 1))
+        .collect();
+    Ok(bits.chunks(digit_bitsize)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |digit, (ix, &bit)| digit | ((bit as i64) << ix)))
+        .collect())
+}
+
+/// Number of values used for encoding a secret masking key modulo `modulus`.
+pub fn encoded_secret_key_size(modulus: i64) -> usize {
+    let digit_bitsize = digit_bitsize(modulus);
+    (box_::SECRETKEYBYTES * 8 + digit_bitsize - 1) / digit_bitsize
+}
+
+fn decode_secret_key(digits: &[Secret], modulus: i64) -> Vec<u8> {
+    let digit_bitsize = digit_bitsize(modulus);
+    let mut bytes = vec![0_u8; box_::SECRETKEYBYTES];
+    for (digit_index, &digit) in digits.iter().enumerate() {
+        // reconstruction may yield negative representatives
+        let digit = ((digit % modulus) + modulus) % modulus;
+        for ix in 0..digit_bitsize {
+            let bit_index = digit_index * digit_bitsize + ix;
+            if bit_index < bytes.len() * 8 && (digit >> ix) & 1 == 1 {
+                bytes[bit_index / 8] |= 1 << (bit_index % 8);
+            }
+        }
+    }
+    bytes
+}
+
+/// Number of bits that fit in a value modulo `modulus`.
+fn digit_bitsize(modulus: i64) -> usize {
+    ::std::cmp::min(63 - modulus.leading_zeros() as usize, 32)
+}
+
+/// Add the mask derived from `shared` to `mask`, with sign given by the order of the two public keys.
+fn accumulate_pairwise_mask(mask: &mut [Mask], modulus: i64, shared: &box_::PrecomputedKey, own: &box_::PublicKey, peer: &box_::PublicKey) {
+    let seed: Vec<u32> = shared.0.chunks(4)
+        .map(|word| word.iter().enumerate().fold(0, |acc, (ix, &byte)| acc | ((byte as u32) << (8 * ix))))
+        .collect();
+    let mut mask_generator = ChaChaRng::from_seed(&seed);
+    let positive = own.0 < peer.0;
+    for value in mask.iter_mut() {
+        let m = mask_generator.gen_range(0_i64, modulus);
+        *value = if positive { (*value + m) % modulus } else { (*value - m + modulus) % modulus };
+    }
+}
+
+fn parse_keypair(keypair: &EncryptionKeypair) -> SdaClientResult<(box_::PublicKey, box_::SecretKey)> {
+    match *keypair {
+        EncryptionKeypair { ek: EncryptionKey::Sodium(ref raw_ek), dk: DecryptionKey::Sodium(ref raw_dk) } => {
+            let pk = box_::PublicKey::from_slice(&**raw_ek)
+                .ok_or("Failed to parse Sodium public key")?;
+            let sk = box_::SecretKey::from_slice(&**raw_dk)
+                .ok_or("Failed to parse Sodium secret key")?;
+            Ok((pk, sk))
+        }
+        _ => Err("Expected a Sodium keypair for masking")?
+    }
+}
+
+fn parse_public_key(key: &EncryptionKey) -> SdaClientResult<box_::PublicKey> {
+    match *key {
+        EncryptionKey::Sodium(ref raw_ek) => {
+            Ok(box_::PublicKey::from_slice(&**raw_ek).ok_or("Failed to parse Sodium public key")?)
+        }
+        _ => Err("Expected a Sodium key for masking")?
+    }
+}
+
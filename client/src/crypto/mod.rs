@@ -4,31 +4,54 @@ mod signing;
 mod masking;
 mod sharing;
 mod encryption;
+mod noise;
+mod numeric;
+mod group;
 
 use sda_protocol::*;
 use errors::SdaClientResult;
 
+use std::any::Any;
 use std::sync::Arc;
 
 pub use self::signing::{
     SignatureKeypair, 
     SignExport, 
+    MessageSigning,
     SignatureVerification};
     
 pub use self::masking::{
     SecretMaskerConstruction,
     MaskCombinerConstruction,
-    SecretUnmaskerConstruction};
+    SecretUnmaskerConstruction,
+    PairwiseMaskingConstruction,
+    SecretMasker,
+    MaskCombiner};
     
 pub use self::sharing::{
     ShareGeneratorConstruction,
     ShareCombinerConstruction,
+    CommitmentCombinerConstruction,
+    ShareVerifierConstruction,
+    ShareVerifier,
     SecretReconstructorConstruction};
     
 pub use self::encryption::{
     EncryptionKeypair, 
+    DecryptionKey,
+    EncryptionKeyGeneration,
     EncryptorConstruction, 
-    DecryptorConstruction};
+    DecryptorConstruction,
+    ShareEncryptor,
+    ShareDecryptor};
+
+pub use self::group::GroupEncryptionConstruction;
+
+pub use self::noise::{
+    NoiseGeneratorConstruction,
+    NoiseGenerator};
+
+pub use self::numeric::Numeric;
 
 pub type Secret = i64;
 pub type Mask = i64;
@@ -46,14 +69,15 @@ pub trait KeyStorage<ID, K> {
 }
 
 /// Requirements for any keystore used by the client.
+///
+/// Besides keys, it holds the clerk registrations of its agents, against which offered seats are checked.
 pub trait Keystore :
     KeyStorage<EncryptionKeyId, EncryptionKeypair>
     + KeyStorage<VerificationKeyId, SignatureKeypair>
+    + KeyStorage<AgentId, ClerkRegistration>
 {}
 
-pub trait Suitable<S> {
-    fn suitable_for(&self, scheme: &S) -> bool;
-}
+pub use sda_protocol::Suitable;
 
 pub struct CryptoModule {
     keystore: Arc<Keystore>
@@ -64,3 +88,14 @@ impl CryptoModule {
         CryptoModule { keystore: keystore }
     }
 }
+
+/// Pass on a primitive of a scheme only supporting 64 bit values as the primitive expected for values of some numeric type.
+///
+/// Fails unless that type is `i64`, i.e. for aggregations using a wide numeric representation.
+fn narrow<P: ?Sized + 'static, Q: ?Sized + 'static>(primitive: Box<P>) -> SdaClientResult<Box<Q>> {
+    let primitive: Box<Any> = Box::new(primitive);
+    match primitive.downcast::<Box<Q>>() {
+        Ok(primitive) => Ok(*primitive),
+        Err(_) => Err("Scheme only supports 64 bit values")?,
+    }
+}
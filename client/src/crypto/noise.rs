@@ -0,0 +1,123 @@
+//! Noise for differentially private outputs, split between the clerks of a committee.
+//!
+//! Each clerk samples its part independently; only the sum over the whole committee follows the
+//! distribution calibrated to the privacy guarantee, so a recipient colluding with some clerks
+//! faces correspondingly less noise.
+
+use super::*;
+
+use rand::{Rng, OsRng};
+use rand::distributions::{Gamma, IndependentSample};
+
+pub trait NoiseGeneratorConstruction<S> {
+    /// Create a generator for the part of the noise of one out of `share_count` clerks.
+    fn new_noise_generator(&self, scheme: &S, share_count: usize) -> SdaClientResult<Box<NoiseGenerator>>;
+}
+
+pub trait NoiseGenerator {
+    /// Generate noise for each of `dimension` outputs.
+    fn generate(&mut self, dimension: usize) -> Vec<Secret>;
+}
+
+impl NoiseGeneratorConstruction<DifferentialPrivacy> for CryptoModule {
+    fn new_noise_generator(&self, privacy: &DifferentialPrivacy, share_count: usize) -> SdaClientResult<Box<NoiseGenerator>> {
+        privacy.validate()?;
+        if share_count == 0 { Err("Noise must be split between at least one clerk")? }
+        let rng = OsRng::new().map_err(|_| "Unable to get randomness source")?;
+        let scale = privacy.noise_scale();
+        match privacy.mechanism {
+
+            NoiseMechanism::DiscreteLaplace => {
+                Ok(Box::new(LaplaceShare {
+                    rng: rng,
+                    share_count: share_count,
+                    decay: (-1.0 / scale).exp(),
+                }))
+            },
+
+            NoiseMechanism::DiscreteGaussian => {
+                Ok(Box::new(GaussianShare {
+                    rng: rng,
+                    sigma: scale / (share_count as f64).sqrt(),
+                }))
+            },
+
+        }
+    }
+}
+
+/// Part of discrete Laplace noise.
+///
+/// Discrete Laplace noise is the difference of two geometric variables, each of which is the sum
+/// of `share_count` Pólya variables; every clerk thus contributes the difference of two of those.
+struct LaplaceShare {
+    rng: OsRng,
+    share_count: usize,
+    decay: f64,
+}
+
+impl LaplaceShare {
+    /// Sample from the Pólya distribution with parameters `1 / share_count` and `decay`, as a Poisson-Gamma mixture.
+    fn polya(&mut self) -> i64 {
+        let gamma = Gamma::new(1.0 / self.share_count as f64, self.decay / (1.0 - self.decay));
+        let rate = gamma.ind_sample(&mut self.rng);
+        poisson(&mut self.rng, rate)
+    }
+}
+
+impl NoiseGenerator for LaplaceShare {
+    fn generate(&mut self, dimension: usize) -> Vec<Secret> {
+        (0..dimension).map(|_| self.polya() - self.polya()).collect()
+    }
+}
+
+/// Part of discrete Gaussian noise.
+///
+/// The sum of independent discrete Gaussians is not exactly a discrete Gaussian, but is very close
+/// to one with the summed variance as soon as the individual deviations are not tiny.
+struct GaussianShare {
+    rng: OsRng,
+    sigma: f64,
+}
+
+impl NoiseGenerator for GaussianShare {
+    fn generate(&mut self, dimension: usize) -> Vec<Secret> {
+        (0..dimension).map(|_| discrete_gaussian(&mut self.rng, self.sigma)).collect()
+    }
+}
+
+/// Sample from the discrete Gaussian by rejection from the discrete Laplace, following Canonne, Kamath and Steinke.
+fn discrete_gaussian<R: Rng>(rng: &mut R, sigma: f64) -> i64 {
+    let t = sigma.floor() + 1.0;
+    let decay = (-1.0 / t).exp();
+    loop {
+        let candidate = geometric(rng, decay) - geometric(rng, decay);
+        let distance = candidate.abs() as f64 - sigma * sigma / t;
+        let acceptance = (-distance * distance / (2.0 * sigma * sigma)).exp();
+        if rng.gen::<f64>() < acceptance {
+            return candidate;
+        }
+    }
+}
+
+/// Sample `k` with probability proportional to `decay^k`.
+fn geometric<R: Rng>(rng: &mut R, decay: f64) -> i64 {
+    let uniform = 1.0 - rng.gen::<f64>();
+    (uniform.ln() / decay.ln()).floor() as i64
+}
+
+fn poisson<R: Rng>(rng: &mut R, mut rate: f64) -> i64 {
+    let mut count = 0;
+    // Knuth's method, in steps small enough for exp(-step) not to underflow
+    while rate > 0.0 {
+        let step = rate.min(500.0);
+        rate -= step;
+        let limit = (-step).exp();
+        let mut product = rng.gen::<f64>();
+        while product > limit {
+            count += 1;
+            product *= rng.gen::<f64>();
+        }
+    }
+    count
+}
@@ -0,0 +1,263 @@
+//! Numeric types for values of aggregations, allowing moduli beyond 64 bits.
+//!
+//! The original `i64` representation remains the one supported by all schemes; the wide
+//! representations are supported by the schemes whose arithmetic does not depend on 64 bit
+//! moduli, i.e. additive sharing, no or full masking, and encryption.
+
+use super::*;
+
+use integer_encoding::VarInt;
+use num::{BigUint, Zero, FromPrimitive, ToPrimitive};
+use rand::Rng;
+use std::fmt::Debug;
+
+/// Values modulo some modulus, as used for secrets, masks and shares.
+pub trait Numeric: Clone + Debug + PartialEq + 'static {
+    /// Modulus of the aggregation, if it uses the representation matching this type.
+    fn aggregation_modulus(aggregation: &Aggregation) -> SdaClientResult<Self>;
+
+    /// Modulus to use for a scheme with the given 64 bit modulus, in an aggregation with modulus `modulus`.
+    ///
+    /// The 64 bit moduli of schemes are only used by the `i64` representation; wide representations use the
+    /// modulus of the aggregation throughout.
+    fn scheme_modulus(scheme_modulus: i64, modulus: &Self) -> Self;
+
+    /// Additive identity; named so as not to clash with `num::Zero`.
+    fn zero_value() -> Self;
+
+    /// Representative in `0..modulus` of a possibly negative 64 bit value, such as noise.
+    fn from_i64(value: i64, modulus: &Self) -> Self;
+
+    /// Value as an arbitrary precision integer, if not negative.
+    fn to_biguint(&self) -> Option<BigUint>;
+
+    /// Value of an arbitrary precision integer, if it fits.
+    fn from_biguint(value: &BigUint) -> Option<Self>;
+
+    /// Representative of the value in `0..modulus`.
+    fn reduce(&self, modulus: &Self) -> Self;
+
+    /// Sum of two values in `0..modulus`, modulo `modulus`.
+    fn add_mod(&self, other: &Self, modulus: &Self) -> Self;
+
+    /// Difference of two values in `0..modulus`, modulo `modulus`.
+    fn sub_mod(&self, other: &Self, modulus: &Self) -> Self;
+
+    /// Random value in `0..modulus`.
+    fn random_below<R: Rng>(rng: &mut R, modulus: &Self) -> Self;
+
+    /// Append the variable-length encoding of the value to `buffer`.
+    fn encode(&self, buffer: &mut Vec<u8>);
+
+    /// Decode a value from the start of `bytes`, also returning the number of bytes read.
+    fn decode(bytes: &[u8]) -> SdaClientResult<(Self, usize)>;
+}
+
+impl Numeric for i64 {
+    fn aggregation_modulus(aggregation: &Aggregation) -> SdaClientResult<i64> {
+        match aggregation.numeric_representation {
+            NumericRepresentation::I64 => Ok(aggregation.modulus),
+            _ => Err("Aggregation does not use 64 bit values")?,
+        }
+    }
+
+    fn scheme_modulus(scheme_modulus: i64, _modulus: &i64) -> i64 {
+        scheme_modulus
+    }
+
+    fn zero_value() -> i64 {
+        0
+    }
+
+    fn from_i64(value: i64, modulus: &i64) -> i64 {
+        value.reduce(modulus)
+    }
+
+    fn to_biguint(&self) -> Option<BigUint> {
+        // not `BigUint::from_i64`, which would be ambiguous with `Numeric::from_i64`
+        FromPrimitive::from_i64(*self)
+    }
+
+    fn from_biguint(value: &BigUint) -> Option<i64> {
+        value.to_i64()
+    }
+
+    fn reduce(&self, modulus: &i64) -> i64 {
+        ((self % modulus) + modulus) % modulus
+    }
+
+    fn add_mod(&self, other: &i64, modulus: &i64) -> i64 {
+        (self + other) % modulus
+    }
+
+    fn sub_mod(&self, other: &i64, modulus: &i64) -> i64 {
+        (self - other + modulus) % modulus
+    }
+
+    fn random_below<R: Rng>(rng: &mut R, modulus: &i64) -> i64 {
+        rng.gen_range(0, *modulus)
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        let mut buf = [0; 10];
+        let size = self.encode_var(&mut buf);
+        buffer.extend(&buf[0..size]);
+    }
+
+    fn decode(bytes: &[u8]) -> SdaClientResult<(i64, usize)> {
+        let (value, size) = i64::decode_var(bytes);
+        if size == 0 { Err("Malformed encoding")? }
+        Ok((value, size))
+    }
+}
+
+impl Numeric for u128 {
+    fn aggregation_modulus(aggregation: &Aggregation) -> SdaClientResult<u128> {
+        match aggregation.numeric_representation {
+            NumericRepresentation::U128 { ref modulus } => {
+                let significant: Vec<u8> = modulus.0.iter().cloned().skip_while(|&byte| byte == 0).collect();
+                if significant.len() > 16 { Err("Modulus does not fit in 128 bits")? }
+                Ok(significant.iter().fold(0, |acc, &byte| (acc << 8) | byte as u128))
+            }
+            _ => Err("Aggregation does not use 128 bit values")?,
+        }
+    }
+
+    fn scheme_modulus(_scheme_modulus: i64, modulus: &u128) -> u128 {
+        *modulus
+    }
+
+    fn zero_value() -> u128 {
+        0
+    }
+
+    fn from_i64(value: i64, modulus: &u128) -> u128 {
+        let magnitude = (value as i128).abs() as u128 % modulus;
+        if value < 0 { (modulus - magnitude) % modulus } else { magnitude }
+    }
+
+    fn to_biguint(&self) -> Option<BigUint> {
+        let bytes: Vec<u8> = (0..16).rev().map(|ix| (self >> (8 * ix)) as u8).collect();
+        Some(BigUint::from_bytes_be(&bytes))
+    }
+
+    fn from_biguint(value: &BigUint) -> Option<u128> {
+        let bytes = value.to_bytes_be();
+        if bytes.len() > 16 { return None }
+        Some(bytes.iter().fold(0, |acc, &byte| (acc << 8) | byte as u128))
+    }
+
+    fn reduce(&self, modulus: &u128) -> u128 {
+        self % modulus
+    }
+
+    fn add_mod(&self, other: &u128, modulus: &u128) -> u128 {
+        // the modulus has at most 127 bits, so the sum cannot overflow
+        (self + other) % modulus
+    }
+
+    fn sub_mod(&self, other: &u128, modulus: &u128) -> u128 {
+        if self >= other { self - other } else { modulus - other + self }
+    }
+
+    fn random_below<R: Rng>(rng: &mut R, modulus: &u128) -> u128 {
+        // rejection sampling from the smallest power of two above the modulus
+        let shift = modulus.leading_zeros();
+        loop {
+            let candidate = (((rng.next_u64() as u128) << 64) | rng.next_u64() as u128) >> shift;
+            if candidate < *modulus {
+                return candidate;
+            }
+        }
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        // unsigned LEB128, like the varint encoding of 64 bit values
+        let mut value = *self;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buffer.push(byte);
+                break;
+            }
+            buffer.push(byte | 0x80);
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> SdaClientResult<(u128, usize)> {
+        let mut value: u128 = 0;
+        for (ix, &byte) in bytes.iter().enumerate().take(19) {
+            value |= ((byte & 0x7f) as u128) << (7 * ix);
+            if byte & 0x80 == 0 {
+                return Ok((value, ix + 1));
+            }
+        }
+        Err("Malformed encoding")?
+    }
+}
+
+impl Numeric for BigUint {
+    fn aggregation_modulus(aggregation: &Aggregation) -> SdaClientResult<BigUint> {
+        match aggregation.numeric_representation {
+            NumericRepresentation::BigUint { ref modulus } => Ok(BigUint::from_bytes_be(&modulus.0)),
+            _ => Err("Aggregation does not use arbitrary precision values")?,
+        }
+    }
+
+    fn scheme_modulus(_scheme_modulus: i64, modulus: &BigUint) -> BigUint {
+        modulus.clone()
+    }
+
+    fn zero_value() -> BigUint {
+        BigUint::zero()
+    }
+
+    fn from_i64(value: i64, modulus: &BigUint) -> BigUint {
+        let magnitude = BigUint::from_u64((value as i128).abs() as u64).unwrap() % modulus;
+        if value < 0 { (modulus - magnitude) % modulus } else { magnitude }
+    }
+
+    fn to_biguint(&self) -> Option<BigUint> {
+        Some(self.clone())
+    }
+
+    fn from_biguint(value: &BigUint) -> Option<BigUint> {
+        Some(value.clone())
+    }
+
+    fn reduce(&self, modulus: &BigUint) -> BigUint {
+        self % modulus
+    }
+
+    fn add_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+        (self + other) % modulus
+    }
+
+    fn sub_mod(&self, other: &BigUint, modulus: &BigUint) -> BigUint {
+        (self + modulus - other) % modulus
+    }
+
+    fn random_below<R: Rng>(rng: &mut R, modulus: &BigUint) -> BigUint {
+        // reducing 64 bits more than needed makes the bias negligible
+        let mut bytes = vec![0; modulus.to_bytes_be().len() + 8];
+        rng.fill_bytes(&mut bytes);
+        BigUint::from_bytes_be(&bytes) % modulus
+    }
+
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        // length followed by big-endian bytes
+        let bytes = self.to_bytes_be();
+        let mut buf = [0; 10];
+        let size = (bytes.len() as u64).encode_var(&mut buf);
+        buffer.extend(&buf[0..size]);
+        buffer.extend(bytes);
+    }
+
+    fn decode(bytes: &[u8]) -> SdaClientResult<(BigUint, usize)> {
+        let (length, size) = u64::decode_var(bytes);
+        if size == 0 || bytes.len() < size + length as usize { Err("Malformed encoding")? }
+        let end = size + length as usize;
+        Ok((BigUint::from_bytes_be(&bytes[size..end]), end))
+    }
+}
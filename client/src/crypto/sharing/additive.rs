@@ -1,16 +1,16 @@
 use super::*;
-use super::batched::BatchShareGenerator;
 
-use rand::{Rng, OsRng};
+use rand::OsRng;
 
-pub struct AdditiveSecretSharing {
+/// Additive sharing of values of any numeric type, where all shares are needed to reconstruct.
+pub struct AdditiveSecretSharing<T> {
     share_count: usize,
-    modulus: i64,
+    modulus: T,
     rng: OsRng,
 }
 
-impl AdditiveSecretSharing {
-    pub fn new(share_count: usize, modulus: i64) -> AdditiveSecretSharing {
+impl<T: Numeric> AdditiveSecretSharing<T> {
+    pub fn new(share_count: usize, modulus: T) -> AdditiveSecretSharing<T> {
         AdditiveSecretSharing {
             share_count: share_count,
             modulus: modulus,
@@ -19,52 +19,38 @@ impl AdditiveSecretSharing {
     }
 }
 
-impl BatchShareGenerator for AdditiveSecretSharing {
-
-    fn batch_input_size(&self) -> usize {
-        1
-    }
-
-    fn batch_output_size(&self) -> usize {
-        self.share_count
-    }
-
-    fn generate_for_batch(&mut self, batch_input: &[Secret]) -> SdaClientResult<Vec<Share>> {
-        if batch_input.len() != self.batch_input_size() { Err("Batch input wrong length")? }
-        
-        let secret = batch_input[0];
-
-        // NOTE
-        // we assume that the values are really i32 to prevent overflow -- make this explicit!!
-        // specifically, that self.modulus fits within i32
-
-        // pick share_count - 1 random values from group
-        let mut shares: Vec<Share> = (0..self.share_count - 1)
-            .map(|_| self.rng.gen_range(0_i64, self.modulus))
-            .collect();
-
-        // compute the last share as the secret minus the sum of all other shares
-        let last_share = shares.iter().fold(secret, |sum, &x| { (sum - x) % self.modulus });
-        shares.push(last_share);
-
-        Ok(shares)
+impl<T: Numeric> ShareGenerator<T> for AdditiveSecretSharing<T> {
+    fn generate(&mut self, secrets: &[T]) -> SdaClientResult<Vec<Vec<T>>> {
+        let mut shares_per_clerk: Vec<Vec<T>> = vec![Vec::with_capacity(secrets.len()); self.share_count];
+        for secret in secrets {
+            // pick share_count - 1 random values from group, and compute the last share as the secret minus their sum
+            // .. keeping it within the group representatives expected by e.g. packing encryption schemes
+            let mut last_share = secret.reduce(&self.modulus);
+            for clerk_shares in shares_per_clerk.iter_mut().take(self.share_count - 1) {
+                let share = T::random_below(&mut self.rng, &self.modulus);
+                last_share = last_share.sub_mod(&share, &self.modulus);
+                clerk_shares.push(share);
+            }
+            shares_per_clerk[self.share_count - 1].push(last_share);
+        }
+        Ok(shares_per_clerk)
     }
-
 }
 
-impl SecretReconstructor for AdditiveSecretSharing {
-    fn reconstruct(&self, indexed_shares: &Vec<(usize, Vec<Share>)>) -> SdaClientResult<Vec<Secret>> {
+impl<T: Numeric> SecretReconstructor<T> for AdditiveSecretSharing<T> {
+    fn reconstruct(&self, indexed_shares: &Vec<(usize, Vec<T>)>) -> SdaClientResult<Vec<T>> {
+        if indexed_shares.len() != self.share_count { Err("Missing shares")? }
         let dimension: usize = match indexed_shares.get(0) {
             None => 0,
             Some(head) => head.1.len()
         };
 
-        let mut result: Vec<Share> = vec![0; dimension];
+        let mut result: Vec<T> = vec![T::zero_value(); dimension];
         for &(_, ref shares) in indexed_shares {
             if shares.len() != dimension { Err("Mismatching dimension")? }
             for (ix, share) in shares.iter().enumerate() {
-                result[ix] += *share;
-                result[ix] %= self.modulus;
+                // shares summed by homomorphic encryption may exceed the modulus
+                result[ix] = result[ix].add_mod(&share.reduce(&self.modulus), &self.modulus);
             }
         }
 
@@ -0,0 +1,77 @@
+use super::*;
+use super::batched::{BatchShareGenerator, BatchSecretReconstructor};
+
+use tss;
+
+pub struct Generator {
+    share_count: usize,
+    sss_instance: tss::shamir::ShamirSecretSharing,
+}
+
+impl Generator {
+    pub fn new(threshold: usize, share_count: usize, prime_modulus: i64) -> Generator {
+        let sss = tss::shamir::ShamirSecretSharing {
+            threshold: threshold,
+            share_count: share_count,
+            prime: prime_modulus,
+        };
+        Generator {
+            share_count: share_count,
+            sss_instance: sss,
+        }
+    }
+}
+
+impl BatchShareGenerator for Generator {
+
+    fn batch_input_size(&self) -> usize {
+        1
+    }
+
+    fn batch_output_size(&self) -> usize {
+        self.share_count
+    }
+
+    fn generate_for_batch(&mut self, batch_input: &[Secret]) -> SdaClientResult<Vec<Share>> {
+        if batch_input.len() != self.batch_input_size() { Err("Sharing failed for basic secret sharing scheme")? }
+        Ok(self.sss_instance.share(batch_input[0]))
+    }
+
+}
+
+pub struct Reconstructor {
+    output_size: usize,
+    sss_instance: tss::shamir::ShamirSecretSharing,
+}
+
+impl Reconstructor {
+    pub fn new(dimension: usize, threshold: usize, share_count: usize, prime_modulus: i64) -> Reconstructor {
+        let sss = tss::shamir::ShamirSecretSharing {
+            threshold: threshold,
+            share_count: share_count,
+            prime: prime_modulus,
+        };
+        Reconstructor {
+            output_size: dimension,
+            sss_instance: sss,
+        }
+    }
+}
+
+impl BatchSecretReconstructor for Reconstructor {
+
+    fn reconstruct_for_batch(&self, indices: &[usize], batch_shares: &[Secret]) -> SdaClientResult<Vec<Share>> {
+        if batch_shares.len() != indices.len() { Err("Inputs must have same length")? }
+        if batch_shares.len() < self.sss_instance.reconstruct_limit() { Err("Not enough shares to reconstruct")? }
+        Ok(vec![self.sss_instance.reconstruct(indices, batch_shares)])
+    }
+
+    fn batch_output_size(&self) -> usize {
+        1
+    }
+
+    fn output_size(&self) -> usize {
+        self.output_size
+    }
+
+}
@@ -0,0 +1,138 @@
+//! Zero-knowledge proofs that shared secrets satisfy the input bounds of an aggregation.
+//!
+//! Proofs are about the Pedersen commitments to the secrets made by the verifiable Shamir scheme,
+//! and are made non-interactive by deriving challenges from a hash of the context and announcements.
+
+use super::*;
+use super::verifiable_shamir::{self, CommitmentGroup};
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand::Rng;
+use sodiumoxide::crypto::hash::sha512;
+
+/// Prove that `secrets`, committed to in `commitments` using `blindings`, satisfy `bounds`.
+pub fn prove<R: Rng>(rng: &mut R,
+                     group: &CommitmentGroup,
+                     bounds: &InputBounds,
+                     context: &[u8],
+                     secrets: &[Secret],
+                     blindings: &[Scalar],
+                     commitments: &[Commitment]) -> SdaClientResult<InputProof> {
+    match *bounds {
+
+        InputBounds::Range { ref min, ref max } => {
+            if min.len() != secrets.len() || max.len() != secrets.len() { Err("Input bounds do not match the input")? }
+            let mut lower = Vec::with_capacity(secrets.len());
+            let mut upper = Vec::with_capacity(secrets.len());
+            for (ix, &secret) in secrets.iter().enumerate() {
+                if secret < min[ix] || secret > max[ix] {
+                    Err(format!("Input component {} outside of bounds [{}, {}]", ix, min[ix], max[ix]))?
+                }
+                let bitsize = InputBounds::range_bitsize(min[ix], max[ix]);
+                // secret - min is committed to by commitment - g * min, with the same blinding
+                lower.push(prove_range(rng, group, context, secret - min[ix], &blindings[ix], bitsize));
+                // max - secret is committed to by g * max - commitment, with the opposite blinding
+                upper.push(prove_range(rng, group, context, max[ix] - secret, &-blindings[ix], bitsize));
+            }
+            Ok(InputProof::Range {
+                lower: lower,
+                upper: upper,
+            })
+        },
+
+        InputBounds::OneHot => {
+            if secrets.iter().any(|&secret| secret != 0 && secret != 1) || secrets.iter().sum::<i64>() != 1 {
+                Err("Input is not one-hot")?
+            }
+            let mut bits = Vec::with_capacity(secrets.len());
+            for ((&secret, blinding), commitment) in secrets.iter().zip(blindings).zip(commitments) {
+                let commitment = verifiable_shamir::decode_point(commitment).ok_or("Invalid commitment")?;
+                bits.push(prove_bit(rng, group, context, &commitment, secret, blinding));
+            }
+            let blinding_sum = blindings.iter().fold(Scalar::zero(), |acc, blinding| acc + blinding);
+            Ok(InputProof::OneHot {
+                bits: bits,
+                blinding_sum: verifiable_shamir::encode_scalar(&blinding_sum),
+            })
+        },
+
+    }
+}
+
+/// Prove that `value` lies in `[0, 2^bitsize)`, given that it is committed to with `blinding`.
+fn prove_range<R: Rng>(rng: &mut R, group: &CommitmentGroup, context: &[u8], value: i64, blinding: &Scalar, bitsize: usize) -> RangeProof {
+
+    // blindings of the bits must be such that the weighted sum of their commitments matches the value's
+    let mut bit_blindings: Vec<Scalar> = (0..bitsize - 1).map(|_| verifiable_shamir::random_scalar(rng)).collect();
+    let partial_sum = bit_blindings.iter().enumerate()
+        .fold(Scalar::zero(), |acc, (ix, bit_blinding)| acc + Scalar::from(1_u64 << ix) * bit_blinding);
+    let top_weight_inverse = Scalar::from(1_u64 << (bitsize - 1)).invert();
+    bit_blindings.push((blinding - partial_sum) * top_weight_inverse);
+
+    let mut bit_commitments = Vec::with_capacity(bitsize);
+    let mut bit_proofs = Vec::with_capacity(bitsize);
+    for (ix, bit_blinding) in bit_blindings.iter().enumerate() {
+        let bit = (value >> ix) & 1;
+        let bit_commitment = group.commit(&Scalar::from(bit as u64), bit_blinding);
+        bit_proofs.push(prove_bit(rng, group, context, &bit_commitment, bit, bit_blinding));
+        bit_commitments.push(verifiable_shamir::encode_point(&bit_commitment));
+    }
+
+    RangeProof {
+        bit_commitments: bit_commitments,
+        bit_proofs: bit_proofs,
+    }
+}
+
+/// Prove that `commitment` is to `bit`, being either 0 or 1, without revealing which.
+///
+/// The branch for the actual bit is proven honestly, the other is simulated using a challenge
+/// chosen in advance; the verifier only learns that the challenges sum to the hash.
+fn prove_bit<R: Rng>(rng: &mut R, group: &CommitmentGroup, context: &[u8], commitment: &RistrettoPoint, bit: i64, blinding: &Scalar) -> BitProof {
+
+    let simulated_challenge = verifiable_shamir::random_scalar(rng);
+    let simulated_response = verifiable_shamir::random_scalar(rng);
+    let simulated_announcement = announcement(group, commitment, 1 - bit, &simulated_challenge, &simulated_response);
+
+    let nonce = verifiable_shamir::random_scalar(rng);
+    let honest_announcement = group.blinding_generator * nonce;
+
+    let announcements = if bit == 0 {
+        [honest_announcement, simulated_announcement]
+    } else {
+        [simulated_announcement, honest_announcement]
+    };
+    let challenge = challenge(context, &[*commitment, announcements[0], announcements[1]]);
+    let honest_challenge = challenge - simulated_challenge;
+    let honest_response = nonce + honest_challenge * blinding;
+
+    let honest = (verifiable_shamir::encode_scalar(&honest_challenge), verifiable_shamir::encode_scalar(&honest_response));
+    let simulated = (verifiable_shamir::encode_scalar(&simulated_challenge), verifiable_shamir::encode_scalar(&simulated_response));
+    if bit == 0 {
+        BitProof {
+            challenges: (honest.0, simulated.0),
+            responses: (honest.1, simulated.1),
+        }
+    } else {
+        BitProof {
+            challenges: (simulated.0, honest.0),
+            responses: (simulated.1, honest.1),
+        }
+    }
+}
+
+/// Announcement `h * response - (commitment - g * value) * challenge` matching a challenge and response for the claim that `commitment` is to `value`.
+fn announcement(group: &CommitmentGroup, commitment: &RistrettoPoint, value: i64, challenge: &Scalar, response: &Scalar) -> RistrettoPoint {
+    let unblinded = commitment - group.generator * Scalar::from(value as u64);
+    group.blinding_generator * response - unblinded * challenge
+}
+
+/// Challenge derived from the context and the given group elements.
+fn challenge(context: &[u8], elements: &[RistrettoPoint]) -> Scalar {
+    let mut input = context.to_vec();
+    for element in elements {
+        input.extend_from_slice(element.compress().as_bytes());
+    }
+    Scalar::from_bytes_mod_order_wide(&sha512::hash(&input).0)
+}
@@ -1,30 +1,30 @@
 use super::*;
 
-pub struct Combiner {
-    modulus: i64,
+pub struct Combiner<T> {
+    modulus: T,
 }
 
-impl Combiner {
-    pub fn new(modulus: i64) -> Combiner {
+impl<T: Numeric> Combiner<T> {
+    pub fn new(modulus: T) -> Combiner<T> {
         Combiner {
             modulus: modulus
         }
     }
 }
 
-impl ShareCombiner for Combiner {
-    fn combine(&self, shares: &Vec<Vec<Share>>) -> SdaClientResult<Vec<Share>> {
+impl<T: Numeric> ShareCombiner<T> for Combiner<T> {
+    fn combine(&self, shares: &Vec<Vec<T>>) -> SdaClientResult<Vec<T>> {
         let dimension: usize = shares.get(0).map_or(0, Vec::len);
 
-        let mut result: Vec<Share> = vec![0; dimension];
+        let mut result: Vec<T> = vec![T::zero_value(); dimension];
         for share in shares {
             if share.len() != dimension { Err("Wrong dimension")? }
             for (ix, value) in share.iter().enumerate() {
-                result[ix] += *value;
-                result[ix] %= self.modulus;
+                // shares summed by homomorphic encryption may exceed the modulus
+                result[ix] = result[ix].add_mod(&value.reduce(&self.modulus), &self.modulus);
             }
         }
 
         Ok(result)
     }
-}
\ No newline at end of file
+}
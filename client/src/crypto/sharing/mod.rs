@@ -3,83 +3,160 @@
 mod batched;
 mod combiner;
 mod additive;
+mod basic_shamir;
 mod packed_shamir;
+mod verifiable_shamir;
+mod bounds;
 
 use super::*;
 
-pub trait ShareGeneratorConstruction<S> {
-    fn new_share_generator(&self, scheme: &S) -> SdaClientResult<Box<ShareGenerator>>;
+/// Constructions of generators of shares of values of numeric type `T`, modulo `modulus`.
+///
+/// The modulus is that of the aggregation; schemes only supporting 64 bit values use their own.
+pub trait ShareGeneratorConstruction<S, T: Numeric = Secret> {
+    fn new_share_generator(&self, scheme: &S, modulus: &T) -> SdaClientResult<Box<ShareGenerator<T>>>;
 }
 
-pub trait ShareGenerator {
+pub trait ShareGenerator<T = Share> {
     /// Generate shares for secrets.
-    fn generate(&mut self, secrets: &[Secret]) -> SdaClientResult<Vec<Vec<Share>>>;
+    fn generate(&mut self, secrets: &[T]) -> SdaClientResult<Vec<Vec<T>>>;
+
+    /// Commitments to the sharing polynomials used in the last call to `generate`; empty unless the scheme is verifiable.
+    fn commitments(&self) -> Vec<Commitment> {
+        vec![]
+    }
+
+    /// Prove that the secrets shared in the last call to `generate` satisfy the given bounds.
+    ///
+    /// Only supported by verifiable schemes, as proofs refer to the commitments.
+    fn prove_bounds(&mut self, _bounds: &InputBounds, _context: &[u8]) -> SdaClientResult<InputProof> {
+        Err("Sharing scheme does not support input proofs")?
+    }
+}
+
+pub trait ShareCombinerConstruction<S, T: Numeric = Share> {
+    fn new_share_combiner(&self, scheme: &S, modulus: &T) -> SdaClientResult<Box<ShareCombiner<T>>>;
+}
+
+pub trait ShareCombiner<T = Share> {
+    fn combine(&self, shares: &Vec<Vec<T>>) -> SdaClientResult<Vec<T>>;
 }
 
-pub trait ShareCombinerConstruction<S> {
-    fn new_share_combiner(&self, scheme: &S) -> SdaClientResult<Box<ShareCombiner>>;
+pub trait CommitmentCombinerConstruction<S> {
+    fn new_commitment_combiner(&self, scheme: &S) -> SdaClientResult<Box<CommitmentCombiner>>;
 }
 
-pub trait ShareCombiner {
-    fn combine(&self, shares: &Vec<Vec<Share>>) -> SdaClientResult<Vec<Share>>;
+pub trait CommitmentCombiner {
+    /// Combine commitments into commitments matching the combined shares.
+    fn combine(&self, commitments: &Vec<Vec<Commitment>>) -> SdaClientResult<Vec<Commitment>>;
 }
 
-pub trait SecretReconstructorConstruction<S> {
-    fn new_secret_reconstructor(&self, scheme: &S, dimension: usize) -> SdaClientResult<Box<SecretReconstructor>>;
+pub trait ShareVerifierConstruction<S, T: Numeric = Share> {
+    /// Create a verifier of the shares a single participation gave to a clerk.
+    fn new_share_verifier(&self, scheme: &S, dimension: usize) -> SdaClientResult<Box<ShareVerifier<T>>>;
 }
 
-pub trait SecretReconstructor {
-    fn reconstruct(&self, indexed_shares: &Vec<(usize, Vec<Share>)>) -> SdaClientResult<Vec<Secret>>;
+pub trait ShareVerifier<T = Share> {
+    /// Check the shares of the clerk at `clerk_index` against the commitments published with them.
+    fn verify(&self, clerk_index: usize, shares: &[T], commitments: &[Commitment]) -> bool;
 }
 
-impl ShareGeneratorConstruction<LinearSecretSharingScheme> for CryptoModule {
-    fn new_share_generator(&self, scheme: &LinearSecretSharingScheme) -> SdaClientResult<Box<ShareGenerator>> {
+pub trait SecretReconstructorConstruction<S, T: Numeric = Secret> {
+    fn new_secret_reconstructor(&self, scheme: &S, dimension: usize, modulus: &T) -> SdaClientResult<Box<SecretReconstructor<T>>>;
+
+    /// Create a reconstructor that first checks the shares of every clerk against the combined commitments,
+    /// failing with `SdaClientErrorKind::InvalidShares` if any do not match.
+    fn new_verifying_secret_reconstructor(&self, scheme: &S, dimension: usize, commitments: &[Commitment], modulus: &T) -> SdaClientResult<Box<SecretReconstructor<T>>>;
+}
+
+pub trait SecretReconstructor<T = Secret> {
+    fn reconstruct(&self, indexed_shares: &Vec<(usize, Vec<T>)>) -> SdaClientResult<Vec<T>>;
+}
+
+impl<T: Numeric> ShareGeneratorConstruction<LinearSecretSharingScheme, T> for CryptoModule {
+    fn new_share_generator(&self, scheme: &LinearSecretSharingScheme, modulus: &T) -> SdaClientResult<Box<ShareGenerator<T>>> {
         match *scheme {
 
-            LinearSecretSharingScheme::Additive { share_count, modulus } => {
-                let generator = additive::AdditiveSecretSharing::new(share_count, modulus);
+            LinearSecretSharingScheme::Additive { share_count, modulus: scheme_modulus } => {
+                let generator = additive::AdditiveSecretSharing::new(share_count, T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(generator))
             },
 
+            LinearSecretSharingScheme::BasicShamir { share_count, privacy_threshold, prime_modulus } => {
+                let generator = basic_shamir::Generator::new(
+                    privacy_threshold,
+                    share_count,
+                    prime_modulus);
+                narrow::<ShareGenerator, _>(Box::new(generator))
+            },
+
             LinearSecretSharingScheme::PackedShamir { prime_modulus, omega_secrets, omega_shares, .. } => {
                 let generator = packed_shamir::Generator::new(
                     scheme.privacy_threshold(),
                     scheme.output_size(),
                     scheme.input_size(),
                     prime_modulus, omega_secrets, omega_shares);
-                Ok(Box::new(generator))
+                narrow::<ShareGenerator, _>(Box::new(generator))
+            },
+
+            LinearSecretSharingScheme::VerifiableShamir { share_count, privacy_threshold, .. } => {
+                let generator = verifiable_shamir::Generator::new(
+                    privacy_threshold,
+                    share_count,
+                    verifiable_shamir::CommitmentGroup::new())?;
+                narrow::<ShareGenerator, _>(Box::new(generator))
             },
 
         }
     }
 }
 
-impl ShareCombinerConstruction<LinearSecretSharingScheme> for CryptoModule {
-    fn new_share_combiner(&self, scheme: &LinearSecretSharingScheme) -> SdaClientResult<Box<ShareCombiner>> {
+impl<T: Numeric> ShareCombinerConstruction<LinearSecretSharingScheme, T> for CryptoModule {
+    fn new_share_combiner(&self, scheme: &LinearSecretSharingScheme, modulus: &T) -> SdaClientResult<Box<ShareCombiner<T>>> {
         match *scheme {
 
-            LinearSecretSharingScheme::Additive { modulus, .. } => {
-                let combiner = combiner::Combiner::new(modulus);
+            LinearSecretSharingScheme::Additive { modulus: scheme_modulus, .. } => {
+                let combiner = combiner::Combiner::new(T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(combiner))
             },
 
+            LinearSecretSharingScheme::BasicShamir { prime_modulus, .. } => {
+                let combiner = combiner::Combiner::new(prime_modulus);
+                narrow::<ShareCombiner, _>(Box::new(combiner))
+            },
+
             LinearSecretSharingScheme::PackedShamir { prime_modulus, .. } => {
                 let combiner = combiner::Combiner::new(prime_modulus);
-                Ok(Box::new(combiner))
+                narrow::<ShareCombiner, _>(Box::new(combiner))
+            },
+
+            LinearSecretSharingScheme::VerifiableShamir { .. } => {
+                // shares and blindings are both scalars spread over several limbs
+                let combiner = verifiable_shamir::ScalarCombiner::new();
+                narrow::<ShareCombiner, _>(Box::new(combiner))
             },
 
         }
     }
 }
 
-impl SecretReconstructorConstruction<LinearSecretSharingScheme> for CryptoModule {
-    fn new_secret_reconstructor(&self, scheme: &LinearSecretSharingScheme, dimension: usize) -> SdaClientResult<Box<SecretReconstructor>> {
+impl<T: Numeric> SecretReconstructorConstruction<LinearSecretSharingScheme, T> for CryptoModule {
+    fn new_secret_reconstructor(&self, scheme: &LinearSecretSharingScheme, dimension: usize, modulus: &T) -> SdaClientResult<Box<SecretReconstructor<T>>> {
         match *scheme {
 
-            LinearSecretSharingScheme::Additive { share_count, modulus } => {
-                let reconstructor = additive::AdditiveSecretSharing::new(share_count, modulus);
+            LinearSecretSharingScheme::Additive { share_count, modulus: scheme_modulus } => {
+                let reconstructor = additive::AdditiveSecretSharing::new(share_count, T::scheme_modulus(scheme_modulus, modulus));
                 Ok(Box::new(reconstructor))
             },
+
+            LinearSecretSharingScheme::BasicShamir { share_count, privacy_threshold, prime_modulus } => {
+                let reconstructor = basic_shamir::Reconstructor::new(
+                    dimension,
+                    privacy_threshold,
+                    share_count,
+                    prime_modulus);
+                narrow::<SecretReconstructor, _>(Box::new(reconstructor))
+            },
             
             LinearSecretSharingScheme::PackedShamir { prime_modulus, omega_secrets, omega_shares, .. } => {
                 let reconstructor = packed_shamir::Reconstructor::new(
@@ -88,9 +165,70 @@ impl SecretReconstructorConstruction<LinearSecretSharingScheme> for CryptoModule
                     scheme.output_size(),
                     scheme.input_size(),
                     prime_modulus, omega_secrets, omega_shares);
-                Ok(Box::new(reconstructor))
+                narrow::<SecretReconstructor, _>(Box::new(reconstructor))
+            },
+
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, modulus: scheme_modulus, .. } => {
+                let reconstructor = verifiable_shamir::Reconstructor::new(
+                    dimension,
+                    privacy_threshold,
+                    scheme_modulus,
+                    verifiable_shamir::CommitmentGroup::new(),
+                    None)?;
+                narrow::<SecretReconstructor, _>(Box::new(reconstructor))
+            },
+
+        }
+    }
+
+    fn new_verifying_secret_reconstructor(&self, scheme: &LinearSecretSharingScheme, dimension: usize, commitments: &[Commitment], _modulus: &T) -> SdaClientResult<Box<SecretReconstructor<T>>> {
+        match *scheme {
+
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, modulus, .. } => {
+                let reconstructor = verifiable_shamir::Reconstructor::new(
+                    dimension,
+                    privacy_threshold,
+                    modulus,
+                    verifiable_shamir::CommitmentGroup::new(),
+                    Some(commitments.to_vec()))?;
+                narrow::<SecretReconstructor, _>(Box::new(reconstructor))
+            },
+
+            _ => Err("Sharing scheme is not verifiable")?,
+
+        }
+    }
+}
+
+impl<T: Numeric> ShareVerifierConstruction<LinearSecretSharingScheme, T> for CryptoModule {
+    fn new_share_verifier(&self, scheme: &LinearSecretSharingScheme, dimension: usize) -> SdaClientResult<Box<ShareVerifier<T>>> {
+        match *scheme {
+
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, .. } => {
+                let verifier = verifiable_shamir::Verifier::new(
+                    dimension,
+                    privacy_threshold,
+                    verifiable_shamir::CommitmentGroup::new());
+                narrow::<ShareVerifier, _>(Box::new(verifier))
             },
 
+            _ => Err("Sharing scheme is not verifiable")?,
+
+        }
+    }
+}
+
+impl CommitmentCombinerConstruction<LinearSecretSharingScheme> for CryptoModule {
+    fn new_commitment_combiner(&self, scheme: &LinearSecretSharingScheme) -> SdaClientResult<Box<CommitmentCombiner>> {
+        match *scheme {
+
+            LinearSecretSharingScheme::VerifiableShamir { .. } => {
+                let combiner = verifiable_shamir::Combiner::new();
+                Ok(Box::new(combiner))
+            },
+
+            _ => Err("Sharing scheme is not verifiable")?,
+
         }
     }
 }
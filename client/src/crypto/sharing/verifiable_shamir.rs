@@ -0,0 +1,379 @@
+//! Verifiable Shamir sharing, using Pedersen commitments to the sharing polynomials.
+//!
+//! Sharing happens in the scalar field of the Ristretto group, in which the commitments live. For each
+//! secret the shares of a clerk are the evaluations of both the sharing polynomial and a blinding
+//! polynomial, stored next to each other as `SCALAR_LIMBS` limbs each; commitments are stored per
+//! secret, one for each pair of coefficients. Reconstructed secrets are reduced modulo the output modulus.
+
+use super::*;
+use super::bounds;
+use errors::SdaClientErrorKind;
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use rand::{Rng, OsRng};
+
+/// Number of limbs taken by a scalar in share vectors.
+pub const SCALAR_LIMBS: usize = 4;
+
+/// Generators of the Pedersen commitments, as derived by `sda_protocol::CommitmentGenerators`.
+#[derive(Clone, Copy)]
+pub struct CommitmentGroup {
+    pub generator: RistrettoPoint,
+    pub blinding_generator: RistrettoPoint,
+}
+
+impl CommitmentGroup {
+    pub fn new() -> CommitmentGroup {
+        let generators = CommitmentGenerators::new();
+        CommitmentGroup {
+            generator: generators.generator,
+            blinding_generator: generators.blinding_generator,
+        }
+    }
+
+    /// Commitment to `value` blinded by `blinding`.
+    pub fn commit(&self, value: &Scalar, blinding: &Scalar) -> RistrettoPoint {
+        self.generator * value + self.blinding_generator * blinding
+    }
+
+    /// Check a share and its blinding against the commitments to the coefficients of the polynomials at `point`.
+    fn verify(&self, point: &Scalar, share: &Scalar, blinding: &Scalar, commitments: &[RistrettoPoint]) -> bool {
+        let mut expected = RistrettoPoint::identity();
+        let mut power = Scalar::one();
+        for commitment in commitments {
+            expected += commitment * power;
+            power *= point;
+        }
+        self.commit(share, blinding) == expected
+    }
+}
+
+pub struct Generator {
+    threshold: usize,
+    share_count: usize,
+    group: CommitmentGroup,
+    rng: OsRng,
+    commitments: Vec<Commitment>,
+    // kept from the last call to `generate` for proving input bounds
+    secrets: Vec<Secret>,
+    constant_blindings: Vec<Scalar>,
+}
+
+impl Generator {
+    pub fn new(threshold: usize, share_count: usize, group: CommitmentGroup) -> SdaClientResult<Generator> {
+        Ok(Generator {
+            threshold: threshold,
+            share_count: share_count,
+            group: group,
+            rng: OsRng::new().map_err(|_| "Unable to get randomness source")?,
+            commitments: vec![],
+            secrets: vec![],
+            constant_blindings: vec![],
+        })
+    }
+
+    fn random_polynomial(&mut self, constant: Scalar) -> Vec<Scalar> {
+        let mut coefficients = vec![constant];
+        for _ in 0..self.threshold {
+            coefficients.push(random_scalar(&mut self.rng));
+        }
+        coefficients
+    }
+}
+
+impl ShareGenerator for Generator {
+    fn generate(&mut self, secrets: &[Secret]) -> SdaClientResult<Vec<Vec<Share>>> {
+        let mut shares_per_clerk: Vec<Vec<Share>> = vec![Vec::with_capacity(2 * SCALAR_LIMBS * secrets.len()); self.share_count];
+        self.commitments = Vec::with_capacity(secrets.len() * (self.threshold + 1));
+        self.secrets = secrets.to_vec();
+        self.constant_blindings = Vec::with_capacity(secrets.len());
+
+        for secret in secrets {
+            let sharing = self.random_polynomial(scalar_from_i64(*secret));
+            // the blinding of the constant term is what keeps the commitment to the secret hiding
+            let constant_blinding = random_scalar(&mut self.rng);
+            let blinding = self.random_polynomial(constant_blinding);
+            self.constant_blindings.push(constant_blinding);
+            for (coefficient, blinding_coefficient) in sharing.iter().zip(&blinding) {
+                let commitment = self.group.commit(coefficient, blinding_coefficient);
+                self.commitments.push(encode_point(&commitment));
+            }
+            for (clerk_index, clerk_shares) in shares_per_clerk.iter_mut().enumerate() {
+                let point = Scalar::from(clerk_index as u64 + 1);
+                push_limbs(clerk_shares, &evaluate(&sharing, &point));
+                push_limbs(clerk_shares, &evaluate(&blinding, &point));
+            }
+        }
+
+        Ok(shares_per_clerk)
+    }
+
+    fn commitments(&self) -> Vec<Commitment> {
+        self.commitments.clone()
+    }
+
+    fn prove_bounds(&mut self, bounds: &InputBounds, context: &[u8]) -> SdaClientResult<InputProof> {
+        let constant_commitments: Vec<Commitment> = (0..self.secrets.len())
+            .map(|secret_index| self.commitments[secret_index * (self.threshold + 1)])
+            .collect();
+        bounds::prove(&mut self.rng, &self.group, bounds, context, &self.secrets, &self.constant_blindings, &constant_commitments)
+    }
+}
+
+/// Combiner of shares, adding up the scalars held by their limbs.
+pub struct ScalarCombiner;
+
+impl ScalarCombiner {
+    pub fn new() -> ScalarCombiner {
+        ScalarCombiner
+    }
+}
+
+impl ShareCombiner for ScalarCombiner {
+    fn combine(&self, shares: &Vec<Vec<Share>>) -> SdaClientResult<Vec<Share>> {
+        let dimension: usize = shares.get(0).map_or(0, Vec::len);
+        if dimension % SCALAR_LIMBS != 0 { Err("Wrong dimension")? }
+
+        let mut sums: Vec<Scalar> = vec![Scalar::zero(); dimension / SCALAR_LIMBS];
+        for share in shares {
+            if share.len() != dimension { Err("Wrong dimension")? }
+            for (sum, limbs) in sums.iter_mut().zip(share.chunks(SCALAR_LIMBS)) {
+                *sum += scalar_from_limbs(limbs);
+            }
+        }
+
+        let mut result: Vec<Share> = Vec::with_capacity(dimension);
+        for sum in &sums {
+            push_limbs(&mut result, sum);
+        }
+        Ok(result)
+    }
+}
+
+pub struct Combiner;
+
+impl Combiner {
+    pub fn new() -> Combiner {
+        Combiner
+    }
+}
+
+impl CommitmentCombiner for Combiner {
+    fn combine(&self, commitments: &Vec<Vec<Commitment>>) -> SdaClientResult<Vec<Commitment>> {
+        let dimension: usize = commitments.get(0).map_or(0, Vec::len);
+
+        // commitments are homomorphic: the sum commits to the sum
+        let mut result: Vec<RistrettoPoint> = vec![RistrettoPoint::identity(); dimension];
+        for commitment in commitments {
+            if commitment.len() != dimension { Err("Wrong dimension")? }
+            for (ix, value) in commitment.iter().enumerate() {
+                result[ix] += decode_point(value).ok_or("Invalid commitment")?;
+            }
+        }
+
+        Ok(result.iter().map(encode_point).collect())
+    }
+}
+
+pub struct Reconstructor {
+    dimension: usize,
+    threshold: usize,
+    modulus: i64,
+    group: CommitmentGroup,
+    commitments: Option<Vec<RistrettoPoint>>,
+}
+
+impl Reconstructor {
+    /// Reconstructor of secrets modulo `modulus`, checking shares against `commitments` if given.
+    pub fn new(dimension: usize, threshold: usize, modulus: i64, group: CommitmentGroup, commitments: Option<Vec<Commitment>>) -> SdaClientResult<Reconstructor> {
+        let commitments = match commitments {
+            None => None,
+            Some(commitments) => {
+                if commitments.len() != dimension * (threshold + 1) {
+                    Err("Wrong number of commitments")?
+                }
+                let points = commitments.iter()
+                    .map(|commitment| Ok(decode_point(commitment).ok_or("Invalid commitment")?))
+                    .collect::<SdaClientResult<Vec<RistrettoPoint>>>()?;
+                Some(points)
+            }
+        };
+        Ok(Reconstructor {
+            dimension: dimension,
+            threshold: threshold,
+            modulus: modulus,
+            group: group,
+            commitments: commitments,
+        })
+    }
+
+    fn shares_are_valid(&self, clerk_index: usize, shares: &[Share]) -> bool {
+        if shares.len() != 2 * SCALAR_LIMBS * self.dimension {
+            return false;
+        }
+        match self.commitments {
+            None => true,
+            Some(ref commitments) => shares_match_commitments(&self.group, self.dimension, self.threshold, clerk_index, shares, commitments),
+        }
+    }
+}
+
+/// Verifier of the shares of a single participation, as done by clerks before combining them.
+pub struct Verifier {
+    dimension: usize,
+    threshold: usize,
+    group: CommitmentGroup,
+}
+
+impl Verifier {
+    pub fn new(dimension: usize, threshold: usize, group: CommitmentGroup) -> Verifier {
+        Verifier {
+            dimension: dimension,
+            threshold: threshold,
+            group: group,
+        }
+    }
+}
+
+impl ShareVerifier for Verifier {
+    fn verify(&self, clerk_index: usize, shares: &[Share], commitments: &[Commitment]) -> bool {
+        if shares.len() != 2 * SCALAR_LIMBS * self.dimension || commitments.len() != self.dimension * (self.threshold + 1) {
+            return false;
+        }
+        let points: Option<Vec<RistrettoPoint>> = commitments.iter().map(decode_point).collect();
+        match points {
+            None => false,
+            Some(points) => shares_match_commitments(&self.group, self.dimension, self.threshold, clerk_index, shares, &points),
+        }
+    }
+}
+
+/// Check the shares of the clerk at `clerk_index` against the commitments to the polynomials of each secret.
+fn shares_match_commitments(group: &CommitmentGroup, dimension: usize, threshold: usize, clerk_index: usize, shares: &[Share], commitments: &[RistrettoPoint]) -> bool {
+    let point = Scalar::from(clerk_index as u64 + 1);
+    (0..dimension).all(|secret_index| {
+        let secret_commitments = &commitments[secret_index * (threshold + 1) .. (secret_index + 1) * (threshold + 1)];
+        let offset = 2 * SCALAR_LIMBS * secret_index;
+        let share = scalar_from_limbs(&shares[offset .. offset + SCALAR_LIMBS]);
+        let blinding = scalar_from_limbs(&shares[offset + SCALAR_LIMBS .. offset + 2 * SCALAR_LIMBS]);
+        group.verify(&point, &share, &blinding, secret_commitments)
+    })
+}
+
+impl SecretReconstructor for Reconstructor {
+    fn reconstruct(&self, indexed_shares: &Vec<(usize, Vec<Share>)>) -> SdaClientResult<Vec<Secret>> {
+
+        let invalid: Vec<usize> = indexed_shares.iter()
+            .filter(|&&(clerk_index, ref shares)| !self.shares_are_valid(clerk_index, shares))
+            .map(|&(clerk_index, _)| clerk_index)
+            .collect();
+        if !invalid.is_empty() {
+            Err(SdaClientErrorKind::InvalidShares(invalid))?
+        }
+
+        if indexed_shares.len() < self.threshold + 1 { Err("Not enough shares to reconstruct")? }
+        let used_shares = &indexed_shares[0..self.threshold + 1];
+        let points: Vec<Scalar> = used_shares.iter().map(|&(clerk_index, _)| Scalar::from(clerk_index as u64 + 1)).collect();
+        let weights = lagrange_weights_at_zero(&points);
+
+        let secrets = (0..self.dimension)
+            .map(|secret_index| {
+                let offset = 2 * SCALAR_LIMBS * secret_index;
+                let secret = used_shares.iter()
+                    .zip(&weights)
+                    .fold(Scalar::zero(), |acc, (&(_, ref shares), weight)| {
+                        acc + scalar_from_limbs(&shares[offset .. offset + SCALAR_LIMBS]) * weight
+                    });
+                reduce_scalar(&secret, self.modulus)
+            })
+            .collect();
+
+        Ok(secrets)
+    }
+}
+
+fn evaluate(coefficients: &[Scalar], point: &Scalar) -> Scalar {
+    coefficients.iter().rev().fold(Scalar::zero(), |acc, coefficient| acc * point + coefficient)
+}
+
+/// Weights for interpolating the value at zero of a polynomial from its values at `points`.
+fn lagrange_weights_at_zero(points: &[Scalar]) -> Vec<Scalar> {
+    points.iter()
+        .map(|point| {
+            let mut numerator = Scalar::one();
+            let mut denominator = Scalar::one();
+            for other in points {
+                if other != point {
+                    numerator *= -other;
+                    denominator *= point - other;
+                }
+            }
+            numerator * denominator.invert()
+        })
+        .collect()
+}
+
+/// Uniformly random scalar.
+pub fn random_scalar<R: Rng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0_u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+/// Scalar representing `value`, negative values being mapped to their additive inverses.
+pub fn scalar_from_i64(value: i64) -> Scalar {
+    if value < 0 {
+        -Scalar::from(value.wrapping_neg() as u64)
+    } else {
+        Scalar::from(value as u64)
+    }
+}
+
+/// Representative modulo `modulus` of the integer held by `scalar`, reading scalars above half the
+/// group order as negative.
+fn reduce_scalar(scalar: &Scalar, modulus: i64) -> i64 {
+    let negated = -scalar;
+    // encodings are little-endian, so compare from the most significant byte
+    let negative = negated.as_bytes().iter().rev().lt(scalar.as_bytes().iter().rev());
+    let magnitude = if negative { negated } else { *scalar };
+    let remainder = magnitude.as_bytes().iter().rev()
+        .fold(0_u128, |acc, &byte| ((acc << 8) | byte as u128) % modulus as u128) as i64;
+    if negative {
+        (modulus - remainder) % modulus
+    } else {
+        remainder
+    }
+}
+
+/// Append the limbs holding `scalar`, least significant first.
+fn push_limbs(limbs: &mut Vec<Share>, scalar: &Scalar) {
+    for chunk in scalar.as_bytes().chunks(8) {
+        let limb = chunk.iter().rev().fold(0_u64, |acc, &byte| (acc << 8) | byte as u64);
+        limbs.push(limb as i64);
+    }
+}
+
+/// Scalar held by `SCALAR_LIMBS` limbs, reduced modulo the group order.
+fn scalar_from_limbs(limbs: &[Share]) -> Scalar {
+    let mut bytes = [0_u8; 32];
+    for (ix, &limb) in limbs.iter().enumerate() {
+        for shift in 0..8 {
+            bytes[8 * ix + shift] = ((limb as u64) >> (8 * shift)) as u8;
+        }
+    }
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+pub fn encode_point(point: &RistrettoPoint) -> Commitment {
+    Commitment::from(point.compress().to_bytes())
+}
+
+pub fn decode_point(commitment: &Commitment) -> Option<RistrettoPoint> {
+    CompressedRistretto(commitment.0).decompress()
+}
+
+pub fn encode_scalar(scalar: &Scalar) -> CommitmentScalar {
+    CommitmentScalar::from(scalar.to_bytes())
+}
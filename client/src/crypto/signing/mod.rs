@@ -13,6 +13,13 @@ pub trait SignExport<I, O>
     fn sign_export(&self, signer: &Agent, id: &I) -> SdaClientResult<Option<Signed<O>>>;
 }
 
+pub trait MessageSigning<M>
+    where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+{
+    /// Sign a message on behalf of the agent, using its signature keypair.
+    fn sign(&self, signer: &Agent, message: M) -> SdaClientResult<Signed<M>>;
+}
+
 pub trait SignatureVerification<O> {
     fn signature_is_valid(&self, object: &O) -> SdaClientResult<bool>;
 }
@@ -83,26 +90,53 @@ impl SignExport<EncryptionKeyId, Labelled<EncryptionKeyId, EncryptionKey>> for C
             }
         };
         // signature
-        let signature_keypair: Option<SignatureKeypair> = self.keystore.get(&signer.verification_key.id)?;
-        let signature = match signature_keypair {
+        let signature = match self.signature(signer, &message_to_be_signed.canonical()?)? {
             None => { return Ok(None) },
-            Some(SignatureKeypair{ sk: SigningKey::Sodium(raw_sk), .. }) => {
-                let sk = sodiumoxide::crypto::sign::SecretKey(*raw_sk);
-                let msg = &message_to_be_signed.canonical()?;
-                let signature = sodiumoxide::crypto::sign::sign_detached(msg, &sk);
-                Signature::Sodium(signature.0.into())
-            }
+            Some(signature) => signature,
         };
         // wrapper
         Ok(Some(Signed {
             signature: signature,
             signer: signer.id().clone(),
+            key: Some(signer.verification_key.id),
             body: message_to_be_signed,
         }))
     }
 }
 
 
+impl<M> MessageSigning<M> for CryptoModule
+    where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+{
+    fn sign(&self, signer: &Agent, message: M) -> SdaClientResult<Signed<M>> {
+        let signature = self.signature(signer, &message.canonical()?)?
+            .ok_or("Could not find signature keypair")?;
+        Ok(Signed {
+            signature: signature,
+            signer: signer.id().clone(),
+            key: Some(signer.verification_key.id),
+            body: message,
+        })
+    }
+}
+
+
+impl CryptoModule {
+    /// Detached signature of `msg` by `signer`, if its signature keypair is in the keystore.
+    fn signature(&self, signer: &Agent, msg: &[u8]) -> SdaClientResult<Option<Signature>> {
+        let signature_keypair: Option<SignatureKeypair> = self.keystore.get(&signer.verification_key.id)?;
+        match signature_keypair {
+            None => Ok(None),
+            Some(SignatureKeypair{ sk: SigningKey::Sodium(raw_sk), .. }) => {
+                let sk = sodiumoxide::crypto::sign::SecretKey(*raw_sk);
+                let signature = sodiumoxide::crypto::sign::sign_detached(msg, &sk);
+                Ok(Some(Signature::Sodium(signature.0.into())))
+            }
+        }
+    }
+}
+
+
 impl<M> SignatureVerification<Signed<M>> for Agent
     where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
 {
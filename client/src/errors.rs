@@ -9,4 +9,14 @@ error_chain!{
         NumParseInt(::std::num::ParseIntError);
         TimeSystemTime(::std::time::SystemTimeError);
     }
+    errors {
+        InvalidShares(clerks: Vec<usize>) {
+            description("shares failed verification against commitments")
+            display("shares of clerks at committee positions {:?} failed verification against commitments", clerks)
+        }
+        MisbehavingClerks(clerks: Vec<::sda_protocol::AgentId>) {
+            description("clerks returned incorrect results")
+            display("clerks {:?} returned incorrect results", clerks)
+        }
+    }
 }
\ No newline at end of file
@@ -11,6 +11,8 @@ extern crate threshold_secret_sharing as tss;
 extern crate sodiumoxide;
 extern crate integer_encoding;
 extern crate rand;
+extern crate num;
+extern crate curve25519_dalek;
 
 extern crate sda_protocol;
 
@@ -20,16 +22,19 @@ mod profile;
 mod participate;
 mod clerk;
 mod receive;
+mod audit;
 
-pub use errors::{SdaClientResult, SdaClientError};
-pub use crypto::{Keystore, KeyStorage, EncryptionKeypair, SignatureKeypair};
+pub use errors::{SdaClientResult, SdaClientError, SdaClientErrorKind};
+pub use crypto::{Keystore, KeyStorage, EncryptionKeypair, SignatureKeypair, Numeric};
 pub use profile::{Maintenance};
 pub use participate::{Participating, ParticipantInput};
 pub use clerk::Clerking;
-pub use receive::Receiving;
+pub use receive::{Receiving, RecipientOutput};
+pub use audit::Auditing;
 
 use sda_protocol::*;
-use crypto::CryptoModule;
+use crypto::{CryptoModule, MessageSigning, SignatureVerification, EncryptorConstruction, GroupEncryptionConstruction,
+             ShareEncryptor};
 
 use std::sync::Arc;
 
@@ -40,17 +45,150 @@ pub struct SdaClient {
     /// Agent to be used when e.g. identifying with the service.
     pub agent: Agent,
     crypto: CryptoModule,
+    keystore: Arc<Keystore>,
     service: Arc<SdaService>,
 }
 
+/// Signing on behalf of agents whose signature keypairs are held in a keystore, independently of any service.
+///
+/// Allows for instance transports to authenticate requests by signature.
+pub struct AgentSigner {
+    crypto: CryptoModule,
+}
+
+impl AgentSigner {
+    pub fn new(keystore: Arc<Keystore>) -> AgentSigner {
+        AgentSigner {
+            crypto: CryptoModule::new(keystore),
+        }
+    }
+
+    /// Sign a message on behalf of the agent, using its signature keypair.
+    pub fn sign<M>(&self, signer: &Agent, message: M) -> SdaClientResult<Signed<M>>
+        where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+    {
+        self.crypto.sign(signer, message)
+    }
+}
+
 impl SdaClient {
     /// Create a new client.
     pub fn new(agent: Agent, keystore: Arc<Keystore>, service: Arc<SdaService>) -> SdaClient
     {
         SdaClient {
             agent: agent,
-            crypto: CryptoModule::new(keystore),
+            crypto: CryptoModule::new(keystore.clone()),
+            keystore: keystore,
             service: service,
         }
     }
+
+    /// Sign a resource on behalf of the agent, e.g. before handing it to the service directly.
+    pub fn sign<M>(&self, message: M) -> SdaClientResult<Signed<M>>
+        where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+    {
+        self.crypto.sign(&self.agent, message)
+    }
+
+    /// Check that a resource was signed by an agent, with the verification key the resource names.
+    ///
+    /// The service refuses resources signed with retired keys, so a retired key is only accepted for resources
+    /// stored before its rotation; it is moreover only trusted through the chain of rotations leading to the
+    /// current key, each signed with the key it replaced. Resources naming no key are checked against the
+    /// current key only.
+    fn signed_by<M>(&self, signer: &Agent, signed: &Signed<M>) -> SdaClientResult<bool>
+        where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+    {
+        let key = match signed.key {
+            Some(key) if key != signer.verification_key.id => key,
+            _ => return signer.signature_is_valid(signed),
+        };
+        let history = self.service.get_verification_key_history(&self.agent, &signer.id)?;
+        let mut replaced_key = &signer.verification_key;
+        for rotation in history.iter().rev() {
+            if rotation.body.agent != signer.id || rotation.body.new_key != *replaced_key {
+                Err(format!("Inconsistent verification key history for {:?}", signer.id))?
+            }
+            let previous = Agent {
+                id: signer.id,
+                verification_key: rotation.body.previous_key.clone(),
+            };
+            if !previous.signature_is_valid(rotation)? {
+                Err(format!("Signature verification failed for verification key rotation of {:?}", signer.id))?
+            }
+            if previous.verification_key.id == key {
+                return previous.signature_is_valid(signed);
+            }
+            replaced_key = &rotation.body.previous_key;
+        }
+        Ok(false)
+    }
+
+    /// Fetch an aggregation, checking that it was signed by its recipient.
+    fn verified_aggregation(&self, aggregation_id: &AggregationId) -> SdaClientResult<Aggregation> {
+        let signed_aggregation = self.service.get_aggregation(&self.agent, aggregation_id)?
+            .ok_or(format!("Unknown aggregation, {:?}", aggregation_id))?;
+        if signed_aggregation.body.id != *aggregation_id {
+            Err(format!("Service returned another aggregation than {:?}", aggregation_id))?
+        }
+        let recipient = self.service.get_agent(&self.agent, &signed_aggregation.body.recipient)?
+            .ok_or("Unknown recipient")?;
+        if !self.signed_by(&recipient, &signed_aggregation)? {
+            Err("Signature verification failed for aggregation")?
+        }
+        Ok(signed_aggregation.body)
+    }
+
+    /// Fetch the committee of an aggregation, checking that it was signed by the recipient of the aggregation.
+    fn verified_committee(&self, aggregation: &Aggregation) -> SdaClientResult<Committee> {
+        let signed_committee = self.service.get_committee(&self.agent, &aggregation.id)?
+            .ok_or(format!("Unknown committee, {:?}", aggregation.id))?;
+        if signed_committee.body.aggregation != aggregation.id {
+            Err(format!("Service returned the committee of another aggregation than {:?}", aggregation.id))?
+        }
+        let recipient = self.service.get_agent(&self.agent, &aggregation.recipient)?
+            .ok_or("Unknown recipient")?;
+        if !self.signed_by(&recipient, &signed_committee)? {
+            Err("Signature verification failed for committee")?
+        }
+        Ok(signed_committee.body)
+    }
+
+    /// Fetch the encryption key of the recipient of an aggregation, checking that it was signed by the recipient.
+    fn verified_recipient_encryption_key(&self, aggregation: &Aggregation) -> SdaClientResult<EncryptionKey> {
+        let recipient_signed_encryption_key = self.service.get_encryption_key(&self.agent, &aggregation.recipient_key)?
+            .ok_or("Unknown recipient encryption key")?;
+        let recipient = self.service.get_agent(&self.agent, &aggregation.recipient)?
+            .ok_or("Unknown recipient")?;
+        if !self.signed_by(&recipient, &recipient_signed_encryption_key)? {
+            Err("Signature verification failed for recipient key")?
+        }
+        Ok(recipient_signed_encryption_key.body.body)
+    }
+
+    /// Create an encryptor for values modulo `modulus` intended for the recipient of an aggregation, using verified keys.
+    ///
+    /// With a recipient group, values are instead shared among the members.
+    fn recipient_share_encryptor<T: Numeric>(&self, aggregation: &Aggregation, modulus: &T) -> SdaClientResult<Box<ShareEncryptor<T>>> {
+        let group = match aggregation.recipient_group {
+            None => {
+                let recipient_encryption_key = self.verified_recipient_encryption_key(aggregation)?;
+                return self.crypto.new_share_encryptor(&recipient_encryption_key, &aggregation.recipient_encryption_scheme);
+            }
+            Some(ref group) => group,
+        };
+        let member_keys = group.members.iter()
+            .map(|&(ref member_id, ref key_id)| {
+                let member_signed_encryption_key = self.service.get_encryption_key(&self.agent, key_id)?
+                    .ok_or("Unknown recipient group encryption key")?;
+                let member = self.service.get_agent(&self.agent, member_id)?
+                    .ok_or("Unknown recipient group member")?;
+                if member_signed_encryption_key.signer != *member_id || !self.signed_by(&member, &member_signed_encryption_key)? {
+                    Err("Signature verification failed for recipient group key")?
+                }
+                Ok(member_signed_encryption_key.body.body)
+            })
+            .collect::<SdaClientResult<Vec<EncryptionKey>>>()?;
+        self.crypto.new_group_share_encryptor(group, &member_keys, &aggregation.recipient_encryption_scheme, modulus)
+    }
 }
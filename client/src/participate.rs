@@ -18,62 +18,132 @@ pub trait Participating {
     /// without risk of recomputation and double participation.
     fn new_participation(&self, input: &ParticipantInput, aggregation: &AggregationId) -> SdaClientResult<Participation>;
 
-    /// Upload participation to the service.
+    /// Sign participation and upload it to the service.
+    ///
+    /// Signatures are deterministic, so retrying an upload yields the same signed participation.
     fn upload_participation(&self, input: &Participation) -> SdaClientResult<()>;
 
     /// Helper method combining `new_participation` and `upload_participation`.
     fn participate(&self, input: Vec<i64>, aggregation: &AggregationId) -> SdaClientResult<()>;
 
+    /// Enroll in the masking cohort of an aggregation using pairwise masking.
+    ///
+    /// Must be done after the committee has been elected and before the recipient forms the cohort;
+    /// only members of the cohort may participate.
+    fn enroll(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
+
+    /// Create a new participation to an aggregation using values of numeric type `T`.
+    ///
+    /// `T` must match the numeric representation of the aggregation, i.e. `i64`, `u128` or `num::BigUint`;
+    /// `new_participation` is the same for `i64`.
+    fn new_numeric_participation<T: Numeric>(&self, input: &[T], aggregation: &AggregationId) -> SdaClientResult<Participation>;
+
+    /// Helper method combining `new_numeric_participation` and `upload_participation`.
+    fn participate_numeric<T: Numeric>(&self, input: Vec<T>, aggregation: &AggregationId) -> SdaClientResult<()>;
+
 }
 
 impl Participating for SdaClient {
 
     fn participate(&self, input: Vec<i64>, aggregation: &AggregationId) -> SdaClientResult<()> {
-        let input = ParticipantInput(input);
-        let participation = self.new_participation(&input, &aggregation)?;
-        self.upload_participation(&participation)
+        self.participate_numeric(input, aggregation)
     }
 
     fn new_participation(&self, input: &ParticipantInput, aggregation_id: &AggregationId) -> SdaClientResult<Participation> {
+        self.new_numeric_participation::<i64>(&input.0, aggregation_id)
+    }
+
+    fn upload_participation(&self, input: &Participation) -> SdaClientResult<()> {
+        let signed_participation = self.crypto.sign(&self.agent, input.clone())?;
+        Ok(self.service.create_participation(&self.agent, &signed_participation)?)
+    }
 
-        let secrets = &input.0;
+    fn enroll(&self, aggregation_id: &AggregationId) -> SdaClientResult<()> {
 
         // load aggregation
-        let aggregation = self.service.get_aggregation(&self.agent, aggregation_id)?
-            .ok_or("Could not find aggregation")?;
+        let aggregation = self.verified_aggregation(aggregation_id)?;
+        if !aggregation.masking_scheme.is_pairwise() {
+            Err("Aggregation does not use pairwise masking")?
+        }
+
+        // load committee
+        let committee: Committee = self.verified_committee(&aggregation)?;
+
+        // fresh masking keypair, used for this aggregation only
+        let masking_key_id = self.crypto.new_key()?;
+        let masking_key = self.crypto.sign_export(&self.agent, &masking_key_id)?
+            .ok_or("Could not sign masking key")?;
+
+        // share the secret part of the masking keypair with the committee, for recovery in case we drop out;
+        // pairwise masking only supports 64 bit values
+        let masking_secret = self.crypto.export_masking_secret(&aggregation.masking_scheme, &masking_key_id)?;
+        let mut share_generator = self.crypto.new_share_generator(&aggregation.committee_sharing_scheme, &aggregation.modulus)?;
+        let shares_per_clerk: Vec<Vec<Share>> = share_generator.generate(&masking_secret)?;
+
+        let mut clerk_encryptions: Vec<(AgentId, Encryption)> = vec![];
+        for clerk_index in 0..shares_per_clerk.len() {
+            let clerk_id = &committee.clerks_and_keys[clerk_index].0;
+            let clerk_encryption_key = self.verified_clerk_encryption_key(&committee, clerk_index)?;
+            let share_encryptor: Box<ShareEncryptor> = self.crypto.new_share_encryptor(&clerk_encryption_key, &aggregation.committee_encryption_scheme)?;
+            let clerk_encryption: Encryption = share_encryptor.encrypt(&*shares_per_clerk[clerk_index])?;
+            clerk_encryptions.push((clerk_id.clone(), clerk_encryption));
+        }
+
+        let enrollment = Enrollment {
+            participant: self.agent.id.clone(),
+            aggregation: aggregation.id.clone(),
+            masking_key: masking_key,
+            clerk_encryptions: clerk_encryptions,
+        };
+        Ok(self.service.create_enrollment(&self.agent, &enrollment)?)
+    }
+
+    fn participate_numeric<T: Numeric>(&self, input: Vec<T>, aggregation: &AggregationId) -> SdaClientResult<()> {
+        let participation = self.new_numeric_participation(&input, aggregation)?;
+        self.upload_participation(&participation)
+    }
+
+    fn new_numeric_participation<T: Numeric>(&self, secrets: &[T], aggregation_id: &AggregationId) -> SdaClientResult<Participation> {
+
+        // load aggregation
+        let aggregation = self.verified_aggregation(aggregation_id)?;
         if secrets.len() != aggregation.vector_dimension {
             Err("The input length does not match the aggregation.")?
         }
+        // fails unless `T` matches the numeric representation of the aggregation
+        let modulus = T::aggregation_modulus(&aggregation)?;
 
         // load committee
-        let committee: Committee = self.service.get_committee(&self.agent, aggregation_id)?
-            .ok_or("Could not find committee")?;
+        let committee: Committee = self.verified_committee(&aggregation)?;
         
         // mask the secrets
-        let mut secret_masker = self.crypto.new_secret_masker(&aggregation.masking_scheme)?;
+        let mut secret_masker: Box<SecretMasker<T>> = if aggregation.masking_scheme.is_pairwise() {
+            self.cohort_masker(&aggregation)?
+        } else {
+            self.crypto.new_secret_masker(&aggregation.masking_scheme, &modulus)?
+        };
         let (recipient_mask, committee_masked_secrets) = secret_masker.mask(secrets);
 
         let recipient_encryption: Option<Encryption> = if recipient_mask.len() == 0 {
             None
         } else {
-            // fetch and verify recipient's encryption key
-            let recipient_id = &aggregation.recipient;
-            let recipient_signed_encryption_key = self.service.get_encryption_key(&self.agent, &aggregation.recipient_key)?
-                .ok_or("Unknown recipient encryption key")?;
-            let recipient = self.service.get_agent(&self.agent, recipient_id)?
-                .ok_or("Unknown recipient")?;
-            if !recipient.signature_is_valid(&recipient_signed_encryption_key)? {
-                Err("Signature verification failed for recipient key")?
-            }
-            let recipient_encryption_key = recipient_signed_encryption_key.body.body;
-            // .. encrypt the recipient's mask using it
-            let mask_encryptor = self.crypto.new_share_encryptor(&recipient_encryption_key, &aggregation.recipient_encryption_scheme)?;
+            // encrypt the recipient's mask, using verified keys
+            let mask_encryptor = self.recipient_share_encryptor(&aggregation, &modulus)?;
             Some(mask_encryptor.encrypt(&*recipient_mask)?)
         };
 
         // share the committee's masked secrets: each inner vector corresponds to the shares of a single clerk
-        let mut share_generator = self.crypto.new_share_generator(&aggregation.committee_sharing_scheme)?;
-        let committee_shares_per_clerk: Vec<Vec<Share>> = share_generator.generate(&committee_masked_secrets)?;
+        let mut share_generator = self.crypto.new_share_generator(&aggregation.committee_sharing_scheme, &modulus)?;
+        let committee_shares_per_clerk: Vec<Vec<T>> = share_generator.generate(&committee_masked_secrets)?;
+
+        // prove that the input respects the bounds of the aggregation, if any
+        let input_proof = match aggregation.input_bounds {
+            None => None,
+            Some(ref bounds) => {
+                let context = InputProof::context(&self.agent.id, &aggregation.id);
+                Some(share_generator.prove_bounds(bounds, &context)?)
+            }
+        };
 
         // encryptions for the participation; we'll fill this one up as we go along
         let mut clerk_encryptions: Vec<(AgentId, Encryption)> = vec![];
@@ -84,17 +154,9 @@ impl Participating for SdaClient {
             let clerk_id = &committee.clerks_and_keys[clerk_index].0;
 
             // fetch and verify clerk's encryption key
-            let clerk_signed_encryption_key_id = committee.clerks_and_keys[clerk_index].1;
-            let clerk_signed_encryption_key = self.service.get_encryption_key(&self.agent, &clerk_signed_encryption_key_id)?
-                .ok_or("Unknown clerk encryption key")?;
-            let clerk = self.service.get_agent(&self.agent, clerk_id)?
-                .ok_or("Unknown clerk")?;
-            if !clerk.signature_is_valid(&clerk_signed_encryption_key)? {
-                Err("Signature verification failed for clerk key")?
-            }
-            let clerk_encryption_key = clerk_signed_encryption_key.body.body;
+            let clerk_encryption_key = self.verified_clerk_encryption_key(&committee, clerk_index)?;
             // .. encrypt the clerk's shares using it
-            let share_encryptor = self.crypto.new_share_encryptor(&clerk_encryption_key, &aggregation.committee_encryption_scheme)?;
+            let share_encryptor: Box<ShareEncryptor<T>> = self.crypto.new_share_encryptor(&clerk_encryption_key, &aggregation.committee_encryption_scheme)?;
             let clerk_encryption: Encryption = share_encryptor.encrypt(&*clerk_shares)?;
             // .. and add result to collection
             clerk_encryptions.push((clerk_id.clone(), clerk_encryption));
@@ -109,11 +171,51 @@ impl Participating for SdaClient {
             aggregation: aggregation.id.clone(),
             recipient_encryption: recipient_encryption,
             clerk_encryptions: clerk_encryptions,
+            commitments: share_generator.commitments(),
+            input_proof: input_proof,
         })
     }
 
-    fn upload_participation(&self, input: &Participation) -> SdaClientResult<()> {
-        Ok(self.service.create_participation(&self.agent, input)?)
+}
+
+impl SdaClient {
+
+    /// Fetch the encryption key of a committee member, checking that it was signed by the clerk.
+    fn verified_clerk_encryption_key(&self, committee: &Committee, clerk_index: usize) -> SdaClientResult<EncryptionKey> {
+        let (ref clerk_id, clerk_signed_encryption_key_id) = committee.clerks_and_keys[clerk_index];
+        let clerk_signed_encryption_key = self.service.get_encryption_key(&self.agent, &clerk_signed_encryption_key_id)?
+            .ok_or("Unknown clerk encryption key")?;
+        let clerk = self.service.get_agent(&self.agent, clerk_id)?
+            .ok_or("Unknown clerk")?;
+        if !self.signed_by(&clerk, &clerk_signed_encryption_key)? {
+            Err("Signature verification failed for clerk key")?
+        }
+        Ok(clerk_signed_encryption_key.body.body)
+    }
+
+    /// Build a masker agreeing masks with the other members of the aggregation's masking cohort.
+    fn cohort_masker<T: Numeric>(&self, aggregation: &Aggregation) -> SdaClientResult<Box<SecretMasker<T>>> {
+        let cohort = self.service.get_masking_cohort(&self.agent, &aggregation.id)?
+            .ok_or("Masking cohort not yet formed")?;
+
+        let mut own_key_id = None;
+        let mut peer_keys = vec![];
+        for &(ref member_id, ref member_key) in &cohort.members {
+            if *member_id == self.agent.id {
+                own_key_id = Some(member_key.body.id.clone());
+                continue
+            }
+            // verify that each peer key was signed by its member
+            let member = self.service.get_agent(&self.agent, member_id)?
+                .ok_or("Unknown cohort member")?;
+            if !self.signed_by(&member, member_key)? {
+                Err("Signature verification failed for masking key")?
+            }
+            peer_keys.push(member_key.body.body.clone());
+        }
+        let own_key_id = own_key_id.ok_or("Not a member of the masking cohort")?;
+
+        self.crypto.new_pairwise_masker(&aggregation.masking_scheme, &own_key_id, &peer_keys)
     }
 
 }
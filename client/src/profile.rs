@@ -23,12 +23,26 @@ pub trait Maintenance {
     /// Upload agent to service.
     fn upload_agent(&self) -> SdaClientResult<()>;
 
+    /// Replace the verification key of the agent by a new one, signing the rotation with the current key.
+    ///
+    /// Returns the updated agent, to be used from now on; the previous signature keypair is kept in the keystore.
+    fn rotate_verification_key(&self) -> SdaClientResult<Agent>;
+
     /// Create new encryption key in keystore.
     fn new_encryption_key(&self) -> SdaClientResult<EncryptionKeyId>;
 
+    /// Create new encryption key in keystore, suitable for the given scheme.
+    fn new_encryption_key_for_scheme(&self, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<EncryptionKeyId>;
+
     /// Upload encryption key to service.
     fn upload_encryption_key(&self, key: &EncryptionKeyId) -> SdaClientResult<()>;
 
+    /// Revoke encryption key on the service; the keypair is kept in the keystore for past aggregations.
+    fn revoke_encryption_key(&self, key: &EncryptionKeyId) -> SdaClientResult<()>;
+
+    /// Create and upload a new encryption key, revoking all previous ones.
+    fn rotate_encryption_key(&self) -> SdaClientResult<EncryptionKeyId>;
+
 }
 
 impl Maintenance for SdaClient
@@ -37,14 +51,49 @@ impl Maintenance for SdaClient
         Ok(self.service.create_agent(&self.agent, &self.agent)?)
     }
 
+    fn rotate_verification_key(&self) -> SdaClientResult<Agent> {
+        let new_key: Labelled<VerificationKeyId, VerificationKey> = self.crypto.new_key()?;
+        let rotation = VerificationKeyRotation {
+            agent: self.agent.id,
+            previous_key: self.agent.verification_key.clone(),
+            new_key: new_key.clone(),
+        };
+        let signed_rotation = self.crypto.sign(&self.agent, rotation)?;
+        self.service.rotate_verification_key(&self.agent, &signed_rotation)?;
+        Ok(Agent {
+            id: self.agent.id,
+            verification_key: new_key,
+        })
+    }
+
     fn new_encryption_key(&self) -> SdaClientResult<EncryptionKeyId> {
         let key_id = self.crypto.new_key()?;
         Ok(key_id)
     }
 
+    fn new_encryption_key_for_scheme(&self, scheme: &AdditiveEncryptionScheme) -> SdaClientResult<EncryptionKeyId> {
+        let key_id = self.crypto.new_encryption_key(scheme)?;
+        Ok(key_id)
+    }
+
     fn upload_encryption_key(&self, key: &EncryptionKeyId) -> SdaClientResult<()> {
         let signed_key = self.crypto.sign_export(&self.agent, key)?
             .ok_or("Could not sign encryption key")?;
         Ok(self.service.create_encryption_key(&self.agent, &signed_key)?)
     }
+
+    fn revoke_encryption_key(&self, key: &EncryptionKeyId) -> SdaClientResult<()> {
+        let revocation = self.crypto.sign(&self.agent, EncryptionKeyRevocation { key: *key })?;
+        Ok(self.service.revoke_encryption_key(&self.agent, &revocation)?)
+    }
+
+    fn rotate_encryption_key(&self) -> SdaClientResult<EncryptionKeyId> {
+        let previous_keys = self.service.list_encryption_keys(&self.agent, &self.agent.id)?;
+        let key = self.new_encryption_key()?;
+        self.upload_encryption_key(&key)?;
+        for previous_key in previous_keys {
+            self.revoke_encryption_key(&previous_key)?;
+        }
+        Ok(key)
+    }
 }
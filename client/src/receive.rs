@@ -1,13 +1,18 @@
 use SdaClient;
+use audit::Auditing;
 use crypto::*;
-use errors::SdaClientResult;
+use errors::{SdaClientResult, SdaClientError, SdaClientErrorKind};
 
 use sda_protocol::*;
 
+use std::collections::HashMap;
+
+use num::BigUint;
+
 #[derive(Debug)]
-pub struct RecipientOutput {
-    pub modulus: i64,
-    pub values: Vec<i64>,
+pub struct RecipientOutput<T = i64> {
+    pub modulus: T,
+    pub values: Vec<T>,
 }
 
 impl RecipientOutput {
@@ -23,7 +28,7 @@ impl RecipientOutput {
 /// Basic tasks typically performed by a recipient.
 pub trait Receiving {
 
-    /// Upload aggregation to service.
+    /// Sign aggregation and upload it to service, after checking that its parameters are consistent.
     fn upload_aggregation(&self, aggregation: &Aggregation) -> SdaClientResult<()>;
 
     /// Opens the aggregation for participations.
@@ -31,100 +36,94 @@ pub trait Receiving {
     /// Assigns a service chosen committee to the aggregation if none if elected already.
     fn begin_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
 
+    /// Closes enrollment for an aggregation using pairwise masking, forming its masking cohort.
+    ///
+    /// Participants that have not enrolled by then will not be able to participate.
+    fn form_masking_cohort(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
+
     /// Closes the aggregation for participations.
     fn end_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
 
-    /// Downloads result from service and decrypts it.
-    fn reveal_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<RecipientOutput>;
+    /// Takes an incremental snapshot, covering the participations received since the previous snapshot.
+    ///
+    /// The aggregation stays open, and the result of the snapshot can be revealed with `reveal_snapshot`.
+    fn snapshot_new_participations(&self, aggregation: &AggregationId) -> SdaClientResult<SnapshotId>;
 
-}
+    /// Closes the aggregation for participations without taking a snapshot, e.g. to abandon it.
+    fn close_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
 
-impl Receiving for SdaClient {
+    /// Archives the aggregation once its results are no longer needed; the service then refuses any further operation.
+    fn archive_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<()>;
 
-    fn upload_aggregation(&self, aggregation: &Aggregation) -> SdaClientResult<()> {
-        Ok(self.service.create_aggregation(&self.agent, aggregation)?)
-    }
+    /// Downloads result from service and decrypts it.
+    ///
+    /// With a recipient group, this needs the reveal contributions of enough members.
+    fn reveal_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<RecipientOutput>;
 
-    fn begin_aggregation(&self, aggregation_id: &AggregationId) -> SdaClientResult<()> {
-        let aggregation = self.service.get_aggregation(&self.agent, aggregation_id)?
-            .ok_or(format!("Unknown aggregation, {:?}", aggregation_id))?;
-        let candidates = self.service.suggest_committee(&self.agent, &aggregation_id)?;
-        // select suitable committee, following service suggestion blindly
-        let selected_clerks = candidates.iter()
-            .take(aggregation.committee_sharing_scheme.output_size())
-            .map(|candidate| (candidate.id, candidate.keys[0]) )
-            .collect();
-        let committee = Committee {
-            aggregation: aggregation_id.clone(),
-            clerks_and_keys: selected_clerks,
-        };
-        Ok(self.service.create_committee(&self.agent, &committee)?)
+    /// Downloads the result of a given snapshot from service and decrypts it.
+    fn reveal_snapshot(&self, aggregation_id: &AggregationId, snapshot: &SnapshotId) -> SdaClientResult<RecipientOutput> {
+        self.reveal_numeric_snapshot(aggregation_id, snapshot)
     }
 
-    fn end_aggregation(&self, aggregation: &AggregationId) -> SdaClientResult<()> {
-        let status = self.service.get_aggregation_status(&self.agent, aggregation)?
-            .ok_or("Unknown aggregation")?;
-
-        if status.snapshots.len() >= 1 {
-            return Ok(());
+    fn contribute_to_reveal(&self, aggregation_id: &AggregationId, snapshot: &SnapshotId) -> SdaClientResult<()> {
+        let aggregation = self.verified_aggregation(aggregation_id)?;
+        match aggregation.numeric_representation {
+            NumericRepresentation::I64 => self.contribute_numeric_to_reveal::<i64>(&aggregation, snapshot),
+            NumericRepresentation::U128 { .. } => self.contribute_numeric_to_reveal::<u128>(&aggregation, snapshot),
+            NumericRepresentation::BigUint { .. } => self.contribute_numeric_to_reveal::<BigUint>(&aggregation, snapshot),
         }
+    }
 
-        // create new snapshot
-        let snapshot = Snapshot {
-            id: SnapshotId::random(),
-            aggregation: aggregation.clone(),
-        };
-        Ok(self.service.create_snapshot(&self.agent, &snapshot)?)
+    fn reveal_numeric_aggregation<T: Numeric>(&self, aggregation_id: &AggregationId) -> SdaClientResult<RecipientOutput<T>> {
+        // take result of first ready snapshot
+        let snapshot = self.first_ready_snapshot(aggregation_id)?;
+        self.reveal_numeric_snapshot(aggregation_id, &snapshot)
     }
 
-    fn reveal_aggregation(&self, aggregation_id: &AggregationId) -> SdaClientResult<RecipientOutput> {
+}
+
+impl SdaClient {
+
+    /// Downloads the result of a given snapshot of an aggregation using values of numeric type `T` and decrypts it.
+    fn reveal_numeric_snapshot<T: Numeric>(&self, aggregation_id: &AggregationId, snapshot: &SnapshotId) -> SdaClientResult<RecipientOutput<T>> {
 
         // we'll need these guys later
-        let aggregation = self.service.get_aggregation(&self.agent, aggregation_id)?
-            .ok_or(format!("Unknown aggregation, {:?}", aggregation_id))?;
-        let committee = self.service.get_committee(&self.agent, aggregation_id)?
-            .ok_or(format!("Unknown committee, {:?}", aggregation_id))?;
+        let aggregation = self.verified_aggregation(aggregation_id)?;
+        let committee = self.verified_committee(&aggregation)?;
+        // fails unless `T` matches the numeric representation of the aggregation
+        let modulus = T::aggregation_modulus(&aggregation)?;
 
-        // take result of first ready snapshot
-        let status = self.service.get_aggregation_status(&self.agent, aggregation_id)?
-            .ok_or("Unknown aggregation")?;
-        let snapshot = status.snapshots.iter()
-            .filter(|snapshot| snapshot.result_ready)
-            .nth(0)
-            .ok_or("Aggregation not ready")?;
-        let result = self.service.get_snapshot_result(&self.agent, aggregation_id, &snapshot.id)?
+        let result = self.service.get_snapshot_result(&self.agent, aggregation_id, snapshot)?
             .ok_or("Missing aggregation result")?;
 
         let encrypted_masks = result.recipient_encryptions;
-        let encrypted_masked_output_shares = result.clerk_encryptions;
+        let encrypted_masked_output_shares = self.verified_clerking_results(&committee, snapshot, result.clerk_encryptions)?;
+
+        let recipient_decryptor = self.recipient_share_decryptor(&aggregation, &result.snapshot, &modulus)?;
 
         // decrypt and combine masks
-        let mask: Vec<Mask> = match encrypted_masks {
+        let mask: Vec<T> = match encrypted_masks {
+            None if aggregation.masking_scheme.is_pairwise() => {
+                self.recover_pairwise_mask(&aggregation, &committee, &encrypted_masked_output_shares, &modulus)?
+            },
             None => vec![],
             Some(encrypted_masks) => {
-                let mask_decryptor = self.crypto.new_share_decryptor(
-                    &aggregation.recipient_key,
-                    &aggregation.recipient_encryption_scheme)?;
-
                 let decrypted_masks = encrypted_masks.iter()
-                    .map(|encryption| Ok(mask_decryptor.decrypt(encryption)?))
-                    .collect::<SdaClientResult<Vec<Vec<Mask>>>>()?;
+                    .map(|encryption| Ok(recipient_decryptor.decrypt(encryption)?))
+                    .collect::<SdaClientResult<Vec<Vec<T>>>>()?;
 
                 let mask_combiner = self.crypto.new_mask_combiner(
-                    &aggregation.masking_scheme)?;
+                    &aggregation.masking_scheme,
+                    &modulus)?;
 
                 mask_combiner.combine(&decrypted_masks)
             }
         };
 
         // decrypt and reconstruct shares
-        let masked_output: Vec<MaskedSecret> = {
+        let masked_output: Vec<T> = {
 
-            let share_decryptor = self.crypto.new_share_decryptor(
-                &aggregation.recipient_key,
-                &aggregation.recipient_encryption_scheme)?;
-
-            let masked_output_shares: Vec<(usize, Vec<Share>)> = encrypted_masked_output_shares.iter()
+            let masked_output_shares: Vec<(usize, Vec<T>)> = encrypted_masked_output_shares.iter()
                 .map(|clerking_result| {
 
                     // TODO we could avoid this scan if the server is guaranteed to result in right order
@@ -132,34 +131,263 @@ impl Receiving for SdaClient {
                         .position(|&(id,_)| clerking_result.clerk == id)
                         .ok_or(format!("Missing clerk, {:?}", clerking_result.clerk))?;
 
-                    let shares = share_decryptor.decrypt(&clerking_result.encryption)?;
+                    let shares = recipient_decryptor.decrypt(&clerking_result.encryption)?;
                     Ok((clerk_index, shares))
                 })
-                .collect::<SdaClientResult<Vec<(usize, Vec<Share>)>>>()?;
-
-            let secret_reconstructor = self.crypto.new_secret_reconstructor(
-                &aggregation.committee_sharing_scheme,
-                aggregation.vector_dimension)?;
-
-            let masked_output = secret_reconstructor.reconstruct(&masked_output_shares)?;
+                .collect::<SdaClientResult<Vec<(usize, Vec<T>)>>>()?;
+
+            let (masked_output_shares, secret_reconstructor) = if aggregation.committee_sharing_scheme.is_verifiable() {
+                // taken from the participations rather than the clerks, as only participants can sign these
+                let participations = self.audit_participations(aggregation_id, snapshot)?;
+                let commitment_combiner = self.crypto.new_commitment_combiner(&aggregation.committee_sharing_scheme)?;
+                let share_verifier: Box<ShareVerifier<T>> = self.crypto.new_share_verifier(
+                    &aggregation.committee_sharing_scheme,
+                    aggregation.vector_dimension)?;
+
+                // a clerk misbehaves if its commitments are not those of the participations it did not leave out, or
+                // if its shares do not match its commitments; clerks leaving out participations whose shares did not
+                // match are not at fault, but only the shares of clerks agreeing on the commitments can be combined
+                let mut misbehaving: Vec<AgentId> = vec![];
+                let mut agreeing: Vec<(Vec<Commitment>, Vec<(usize, Vec<T>)>)> = vec![];
+                for (clerking_result, (clerk_index, shares)) in encrypted_masked_output_shares.iter().zip(masked_output_shares) {
+                    let expected_commitments = commitment_combiner.combine(&participations.iter()
+                        .filter(|participation| !clerking_result.rejected_participations.contains(&participation.body.id))
+                        .map(|participation| participation.body.commitments.clone())
+                        .collect())?;
+                    if clerking_result.commitments != expected_commitments
+                        || !share_verifier.verify(clerk_index, &shares, &clerking_result.commitments) {
+                        misbehaving.push(clerking_result.clerk);
+                        continue;
+                    }
+                    match agreeing.iter().position(|&(ref commitments, _)| *commitments == clerking_result.commitments) {
+                        Some(position) => agreeing[position].1.push((clerk_index, shares)),
+                        None => agreeing.push((clerking_result.commitments.clone(), vec![(clerk_index, shares)])),
+                    }
+                }
+                if !misbehaving.is_empty() {
+                    Err(SdaClientErrorKind::MisbehavingClerks(misbehaving))?
+                }
+
+                let (commitments, masked_output_shares) = agreeing.into_iter()
+                    .max_by_key(|&(_, ref indexed_shares)| indexed_shares.len())
+                    .ok_or("Missing clerking results")?;
+                let secret_reconstructor = self.crypto.new_verifying_secret_reconstructor(
+                    &aggregation.committee_sharing_scheme,
+                    aggregation.vector_dimension,
+                    &commitments,
+                    &modulus)?;
+                (masked_output_shares, secret_reconstructor)
+            } else {
+                let secret_reconstructor = self.crypto.new_secret_reconstructor(
+                    &aggregation.committee_sharing_scheme,
+                    aggregation.vector_dimension,
+                    &modulus)?;
+                (masked_output_shares, secret_reconstructor)
+            };
+
+            let masked_output = secret_reconstructor.reconstruct(&masked_output_shares)
+                .map_err(|err| {
+                    // name the clerks rather than their positions
+                    let clerks = match *err.kind() {
+                        SdaClientErrorKind::InvalidShares(ref positions) => {
+                            Some(positions.iter().map(|&ix| committee.clerks_and_keys[ix].0).collect())
+                        }
+                        _ => None,
+                    };
+                    match clerks {
+                        Some(clerks) => SdaClientError::from(SdaClientErrorKind::MisbehavingClerks(clerks)),
+                        None => err,
+                    }
+                })?;
             masked_output
         };
 
         // unmask secrets
         let secret_unmasker = self.crypto.new_secret_unmasker(
-            &aggregation.masking_scheme)?;
+            &aggregation.masking_scheme,
+            &modulus)?;
 
         let output = secret_unmasker.unmask(&(mask, masked_output));
         Ok(RecipientOutput {
-            modulus: aggregation.modulus,
+            modulus: modulus,
             values: output,
         })
     }
 
-}
+    /// Reveal our shares of the result of a given snapshot of an aggregation using values of numeric type `T`.
+    fn contribute_numeric_to_reveal<T: Numeric>(&self, aggregation: &Aggregation, snapshot: &SnapshotId) -> SdaClientResult<()> {
 
-impl SdaClient {
+        let committee = self.verified_committee(aggregation)?;
+        let (member_index, member_key) = {
+            let group = aggregation.recipient_group.as_ref()
+                .ok_or("The aggregation has no recipient group")?;
+            let member_index = group.position(&self.agent.id)
+                .ok_or("Not a member of the recipient group")?;
+            (member_index, group.members[member_index].1)
+        };
+
+        let result = self.service.get_snapshot_result(&self.agent, &aggregation.id, snapshot)?
+            .ok_or("Missing aggregation result")?;
+        let clerking_results = self.verified_clerking_results(&committee, snapshot, result.clerk_encryptions)?;
+        let encrypted_masks = result.recipient_encryptions.unwrap_or_else(Vec::new);
+
+        // decrypt our shares of masks and clerk outputs, and re-encrypt them for the recipient
+        let member_decryptor: Box<ShareDecryptor<T>> = self.crypto.new_share_decryptor(
+            &member_key,
+            &aggregation.recipient_encryption_scheme)?;
+        let recipient_encryption_key = self.verified_recipient_encryption_key(aggregation)?;
+        let recipient_encryptor: Box<ShareEncryptor<T>> = self.crypto.new_share_encryptor(
+            &recipient_encryption_key,
+            &aggregation.recipient_encryption_scheme)?;
+
+        let decryptions = encrypted_masks.iter()
+            .chain(clerking_results.iter().map(|clerking_result| &clerking_result.encryption))
+            .map(|encryption| {
+                let member_encryption = match *encryption {
+                    Encryption::Shared(ref member_encryptions) => member_encryptions.get(member_index)
+                        .ok_or("Missing share for recipient group member")?,
+                    _ => Err("Expected an encryption shared with the recipient group")?,
+                };
+                let shares = member_decryptor.decrypt(member_encryption)?;
+                Ok((member_encryption.clone(), recipient_encryptor.encrypt(&shares)?))
+            })
+            .collect::<SdaClientResult<Vec<(Encryption, Encryption)>>>()?;
+
+        let contribution = RevealContribution {
+            aggregation: aggregation.id,
+            snapshot: result.snapshot,
+            member: self.agent.id,
+            decryptions: decryptions,
+        };
+        let signed_contribution = self.crypto.sign(&self.agent, contribution)?;
+        Ok(self.service.create_reveal_contribution(&self.agent, &signed_contribution)?)
+    }
+
+    /// Check that each clerking result was signed by a member of the committee, as the clerk it claims to be from,
+    /// for the given aggregation and snapshot, and that no clerk returned more than one result.
+    fn verified_clerking_results(&self,
+                                 committee: &Committee,
+                                 snapshot: &SnapshotId,
+                                 signed_results: Vec<SignedClerkingResult>)
+                                 -> SdaClientResult<Vec<ClerkingResult>> {
+        let mut seen: Vec<AgentId> = vec![];
+        signed_results.into_iter()
+            .map(|signed_result| {
+                let clerk_id = signed_result.body.clerk;
+                if signed_result.signer != clerk_id || !committee.clerks_and_keys.iter().any(|&(id, _)| id == clerk_id) {
+                    Err(format!("Clerking result not signed by a committee member, {:?}", clerk_id))?
+                }
+                let clerk = self.service.get_agent(&self.agent, &clerk_id)?
+                    .ok_or("Unknown clerk")?;
+                if !self.signed_by(&clerk, &signed_result)? {
+                    Err(format!("Signature verification failed for clerking result, {:?}", clerk_id))?
+                }
+                if signed_result.body.aggregation != committee.aggregation || signed_result.body.snapshot != *snapshot {
+                    Err(format!("Clerking result for another aggregation or snapshot, {:?}", clerk_id))?
+                }
+                if seen.contains(&clerk_id) {
+                    Err(format!("More than one clerking result from the same clerk, {:?}", clerk_id))?
+                }
+                seen.push(clerk_id);
+                Ok(signed_result.body)
+            })
+            .collect()
+    }
+
+    /// Create a decryptor for values intended for the recipient of an aggregation.
+    ///
+    /// With a recipient group, values are reconstructed from the shares revealed by the members for the snapshot.
+    fn recipient_share_decryptor<T: Numeric>(&self, aggregation: &Aggregation, snapshot: &SnapshotId, modulus: &T) -> SdaClientResult<Box<ShareDecryptor<T>>> {
+        let share_decryptor: Box<ShareDecryptor<T>> = self.crypto.new_share_decryptor(
+            &aggregation.recipient_key,
+            &aggregation.recipient_encryption_scheme)?;
+        let group = match aggregation.recipient_group {
+            None => return Ok(share_decryptor),
+            Some(ref group) => group,
+        };
+
+        let mut revealed: Vec<(usize, Vec<(Encryption, Vec<T>)>)> = vec![];
+        for signed_contribution in self.service.get_reveal_contributions(&self.agent, &aggregation.id, snapshot)? {
+            let member_id = signed_contribution.body.member;
+            let member_index = group.position(&member_id)
+                .ok_or(format!("Reveal contribution not from a recipient group member, {:?}", member_id))?;
+            if signed_contribution.signer != member_id
+                || signed_contribution.body.aggregation != aggregation.id
+                || signed_contribution.body.snapshot != *snapshot {
+                Err(format!("Reveal contribution does not match the snapshot, {:?}", member_id))?
+            }
+            let member = self.service.get_agent(&self.agent, &member_id)?
+                .ok_or("Unknown recipient group member")?;
+            if !self.signed_by(&member, &signed_contribution)? {
+                Err(format!("Signature verification failed for reveal contribution, {:?}", member_id))?
+            }
+            if revealed.iter().any(|&(index, _)| index == member_index) {
+                continue;
+            }
+            let decryptions = signed_contribution.body.decryptions.iter()
+                .map(|&(ref member_encryption, ref recipient_encryption)| {
+                    Ok((member_encryption.clone(), share_decryptor.decrypt(recipient_encryption)?))
+                })
+                .collect::<SdaClientResult<Vec<(Encryption, Vec<T>)>>>()?;
+            revealed.push((member_index, decryptions));
+        }
 
-    // pub fn reveal_snapshot()
+        self.crypto.new_group_share_decryptor(group, revealed, modulus)
+    }
+
+    /// Find the first snapshot of an aggregation that is ready.
+    fn first_ready_snapshot(&self, aggregation_id: &AggregationId) -> SdaClientResult<SnapshotId> {
+        let status = self.service.get_aggregation_status(&self.agent, aggregation_id)?
+            .ok_or("Unknown aggregation")?;
+        let snapshot = status.snapshots.iter()
+            .filter(|snapshot| snapshot.result_ready)
+            .nth(0)
+            .ok_or("Aggregation not ready")?;
+        Ok(snapshot.id)
+    }
+
+    /// Recover the masks that cohort members missing from the snapshot left behind in the participations of the others.
+    fn recover_pairwise_mask<T: Numeric>(&self, aggregation: &Aggregation, committee: &Committee, clerking_results: &[ClerkingResult], modulus: &T) -> SdaClientResult<Vec<T>> {
+
+        let cohort = self.service.get_masking_cohort(&self.agent, &aggregation.id)?
+            .ok_or("Masking cohort not yet formed")?;
+
+        let share_decryptor: Box<ShareDecryptor<T>> = self.crypto.new_share_decryptor(
+            &aggregation.recipient_key,
+            &aggregation.recipient_encryption_scheme)?;
+
+        // collect the clerks' shares of the masking secrets of missing members
+        let mut recovery_shares: HashMap<AgentId, Vec<(usize, Vec<T>)>> = HashMap::new();
+        for clerking_result in clerking_results {
+            let clerk_index = committee.clerks_and_keys.iter()
+                .position(|&(id,_)| clerking_result.clerk == id)
+                .ok_or(format!("Missing clerk, {:?}", clerking_result.clerk))?;
+            for &(participant, ref encryption) in &clerking_result.recovery_encryptions {
+                let shares = share_decryptor.decrypt(encryption)?;
+                recovery_shares.entry(participant).or_insert_with(Vec::new).push((clerk_index, shares));
+            }
+        }
+
+        let secret_reconstructor = self.crypto.new_secret_reconstructor(
+            &aggregation.committee_sharing_scheme,
+            self.crypto.masking_secret_size(&aggregation.masking_scheme)?,
+            modulus)?;
+
+        let mut remaining_keys = vec![];
+        let mut missing_keys = vec![];
+        let mut missing_secrets = vec![];
+        for &(ref member, ref member_key) in &cohort.members {
+            match recovery_shares.get(member) {
+                None => remaining_keys.push(member_key.body.body.clone()),
+                Some(shares) => {
+                    missing_keys.push(member_key.body.body.clone());
+                    missing_secrets.push(secret_reconstructor.reconstruct(shares)?);
+                }
+            }
+        }
+
+        let recovery = self.crypto.new_pairwise_recovery(&aggregation.masking_scheme, &remaining_keys, &missing_keys)?;
+        Ok(recovery.combine(&missing_secrets))
+    }
 
 }
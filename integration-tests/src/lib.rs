@@ -66,6 +66,7 @@ pub fn new_key_for_agent(alice: &Agent) -> SignedEncryptionKey {
             body: EncryptionKey::Sodium(B32::default()),
         },
         signer: alice.id,
+        key: None,
         signature: Signature::Sodium(B64::default()),
     }
 }
@@ -84,6 +85,23 @@ pub fn new_client(identity: &::tempdir::TempDir, service: &Arc<SdaService>) -> S
     SdaClient::new(agent, keystore.clone(), service.clone())
 }
 
+/// Create a client with an encryption key, registered as willing to clerk for anyone.
+pub fn new_full_client(identity: &::tempdir::TempDir, service: &Arc<SdaService>) -> (SdaClient, EncryptionKeyId) {
+    let client = new_client(identity, service);
+    client.upload_agent().unwrap();
+    let key = client.new_encryption_key().unwrap();
+    client.upload_encryption_key(&key).unwrap();
+    register_clerk(&client);
+    (client, key)
+}
+
+pub fn register_clerk(client: &SdaClient) {
+    client.register_as_clerk(&ClerkRegistration {
+        clerk: client.agent.id,
+        ..ClerkRegistration::default()
+    }).unwrap();
+}
+
 
 pub struct TestContext {
     pub server: Arc<SdaServerService>,
@@ -1,7 +1,9 @@
 extern crate sda_protocol;
 extern crate sda_server;
 extern crate sda_tests;
+extern crate tempdir;
 use sda_protocol::*;
+use sda_server::auth::Credentials;
 use sda_tests::*;
 
 
@@ -95,6 +97,7 @@ pub fn encryption_key_crud() {
                 body: sda_protocol::EncryptionKey::Sodium(B32::default()),
             },
             signer: alice.id,
+            key: None,
             signature: sda_protocol::Signature::Sodium(B64::default()),
         };
         ctx.server.create_encryption_key(&alice, &alice_key).unwrap();
@@ -105,29 +108,96 @@ pub fn encryption_key_crud() {
 
 #[test]
 pub fn auth_tokens_crud() {
-    use sda_server::stores::AuthToken;
+    use sda_server::stores::{AuthToken, AuthTokensStore, HashedAuthToken};
     with_service(|ctx| {
         let alice = new_agent();
         let alice_token = AuthToken {
             id: alice.id,
             body: "tok".into(),
         };
-        assert!(ctx.server.0.check_auth_token(&alice_token).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token.clone())).is_err());
         // TODO check error kind is InvalidCredentials
         ctx.server.create_agent(&alice, &alice).unwrap();
         ctx.server.0.upsert_auth_token(&alice_token).unwrap();
-        assert!(ctx.server.0.check_auth_token(&alice_token).is_ok());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token.clone())).is_ok());
         let alice_token_new = AuthToken {
             id: alice.id,
             body: "token".into(),
         };
-        assert!(ctx.server.0.check_auth_token(&alice_token_new).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token_new.clone())).is_err());
         ctx.server.0.upsert_auth_token(&alice_token_new).unwrap();
-        assert!(ctx.server.0.check_auth_token(&alice_token_new).is_ok());
-        assert!(ctx.server.0.check_auth_token(&alice_token).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token_new.clone())).is_ok());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token.clone())).is_err());
+        // only a salted hash is kept at rest
+        let stored = ctx.server.0.auth_tokens_store.get_auth_token(&alice.id).unwrap().unwrap();
+        assert!(!stored.hash.contains(&*alice_token_new.body));
+        let other_stored = HashedAuthToken::new(&alice_token_new).unwrap();
+        assert!(stored.hash != other_stored.hash);
+        // agents can rotate their own token only
+        let bob = new_agent();
+        let rotated = AuthToken {
+            id: alice.id,
+            body: "rotated".into(),
+        };
+        assert!(ctx.server.0.rotate_auth_token(&bob, &rotated).is_err());
+        ctx.server.0.rotate_auth_token(&alice, &rotated).unwrap();
+        assert!(ctx.server.0.authenticate(&Credentials::Token(rotated.clone())).is_ok());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token_new.clone())).is_err());
         ctx.server.0.delete_auth_token(&alice_token.id).unwrap();
-        assert!(ctx.server.0.check_auth_token(&alice_token_new).is_err());
-        assert!(ctx.server.0.check_auth_token(&alice_token).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(rotated.clone())).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(alice_token.clone())).is_err());
+    });
+}
+
+#[test]
+pub fn signature_authentication() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use sda_server::stores::AuthToken;
+    with_service(|ctx| {
+        let identity = tempdir::TempDir::new("sda-tests-crud-alice").unwrap();
+        let (alice, _) = new_full_client(&identity, &ctx.service);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let digest = |timestamp: u64, nonce: u64| RequestDigest {
+            agent: alice.agent.id,
+            method: "GET".into(),
+            path: "/ping".into(),
+            timestamp: timestamp,
+            body_hash: byte_arrays::B32::default(),
+            nonce: nonce,
+        };
+        let valid = alice.sign(digest(now, 0)).unwrap();
+        let agent = ctx.server.0.authenticate(&Credentials::Signature(valid.clone())).unwrap();
+        assert_eq!(alice.agent, agent);
+        // recent requests can not be replayed either, but may be repeated with a fresh nonce
+        assert!(ctx.server.0.authenticate(&Credentials::Signature(valid.clone())).is_err());
+        let repeated = alice.sign(digest(now, 1)).unwrap();
+        assert!(ctx.server.0.authenticate(&Credentials::Signature(repeated)).is_ok());
+        // stale requests can not be replayed
+        let stale = alice.sign(digest(now - 3600, 2)).unwrap();
+        assert!(ctx.server.0.authenticate(&Credentials::Signature(stale)).is_err());
+        // the digest must be signed by the agent it is presented for
+        let mut forged = valid.clone();
+        forged.body.path = "/agents/me".into();
+        assert!(ctx.server.0.authenticate(&Credentials::Signature(forged)).is_err());
+        let bob_identity = tempdir::TempDir::new("sda-tests-crud-bob").unwrap();
+        let bob = new_client(&bob_identity, &ctx.service);
+        let mut impersonation = bob.sign(digest(now, 3)).unwrap();
+        impersonation.signer = alice.agent.id;
+        assert!(ctx.server.0.authenticate(&Credentials::Signature(impersonation)).is_err());
+        // enrolling can not take over the token of an existing agent
+        let carol = new_agent();
+        let token = AuthToken {
+            id: carol.id,
+            body: "tok".into(),
+        };
+        ctx.server.0.enroll(&carol, &Credentials::Token(token.clone())).unwrap();
+        ctx.server.create_agent(&carol, &carol).unwrap();
+        let takeover = AuthToken {
+            id: carol.id,
+            body: "other".into(),
+        };
+        assert!(ctx.server.0.enroll(&carol, &Credentials::Token(takeover)).is_err());
+        assert!(ctx.server.0.authenticate(&Credentials::Token(token)).is_ok());
     });
 }
 
@@ -135,7 +205,11 @@ pub fn auth_tokens_crud() {
 pub fn aggregation_crud() {
     with_service(|ctx| {
         use sda_protocol as p;
-        let (alice, alice_key) = new_full_agent(&ctx.service);
+        // aggregations must be signed, so the recipient needs an actual signature keypair
+        let identity = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let (alice_client, alice_key) = new_full_client(&identity, &ctx.service);
+        let alice = alice_client.agent.clone();
+        let sign = |agg: &p::Aggregation| alice_client.sign(agg.clone()).unwrap();
         assert_eq!(0,
                    ctx.service.list_aggregations(&alice, None, None).unwrap().len());
         let agg = sda_protocol::Aggregation {
@@ -144,7 +218,7 @@ pub fn aggregation_crud() {
             vector_dimension: 4,
             modulus: 13,
             recipient: alice.id,
-            recipient_key: alice_key.id,
+            recipient_key: alice_key,
             masking_scheme: p::LinearMaskingScheme::None,
             committee_sharing_scheme: p::LinearSecretSharingScheme::Additive {
                 share_count: 3,
@@ -152,8 +226,22 @@ pub fn aggregation_crud() {
             },
             recipient_encryption_scheme: p::AdditiveEncryptionScheme::Sodium,
             committee_encryption_scheme: p::AdditiveEncryptionScheme::Sodium,
+            input_bounds: None,
+            differential_privacy: None,
+            numeric_representation: p::NumericRepresentation::I64,
+            recipient_group: None,
+            participation_policy: p::ParticipationPolicy::KeepAll,
+            schedule: None,
         };
-        ctx.service.create_aggregation(&alice, &agg).unwrap();
+        // only aggregations signed by the recipient are accepted
+        let bob_identity = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let (bob_client, _) = new_full_client(&bob_identity, &ctx.service);
+        assert!(ctx.service.create_aggregation(&alice, &bob_client.sign(agg.clone()).unwrap()).is_err());
+        let mut tampered = sign(&agg);
+        tampered.body.title = "bar".into();
+        assert!(ctx.service.create_aggregation(&alice, &tampered).is_err());
+
+        ctx.service.create_aggregation(&alice, &sign(&agg)).unwrap();
         assert_eq!(0,
                    ctx.service.list_aggregations(&alice, Some("bar"), None).unwrap().len());
         assert_eq!(1,
@@ -173,8 +261,295 @@ pub fn aggregation_crud() {
                        .len());
 
         let agg2 = ctx.service.get_aggregation(&alice, &agg.id).unwrap();
-        assert_eq!(Some(&agg), agg2.as_ref());
+        assert_eq!(Some(&agg), agg2.as_ref().map(|signed| &signed.body));
+
+        // uploading the same aggregation again is harmless, changing it is refused
+        ctx.service.create_aggregation(&alice, &sign(&agg)).unwrap();
+        let changed = p::Aggregation { title: "bar".into(), ..agg.clone() };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&changed)).is_err());
+        let agg3 = ctx.service.get_aggregation(&alice, &agg.id).unwrap();
+        assert_eq!(Some(&agg), agg3.as_ref().map(|signed| &signed.body));
 
         ctx.service.delete_aggregation(&alice, &agg.id).unwrap();
     });
 }
+
+#[test]
+pub fn aggregation_validation() {
+    with_service(|ctx| {
+        use sda_protocol as p;
+        // aggregations must be signed, so the recipient needs an actual signature keypair
+        let identity = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let (alice_client, alice_key) = new_full_client(&identity, &ctx.service);
+        let alice = alice_client.agent.clone();
+        let sign = |agg: &p::Aggregation| alice_client.sign(agg.clone()).unwrap();
+        let (bob, bob_key) = new_full_agent(&ctx.service);
+        let agg = sda_protocol::Aggregation {
+            id: sda_protocol::AggregationId::default(),
+            title: "foo".into(),
+            vector_dimension: 4,
+            modulus: 13,
+            recipient: alice.id,
+            recipient_key: alice_key,
+            masking_scheme: p::LinearMaskingScheme::ChaCha {
+                modulus: 13,
+                dimension: 4,
+                seed_bitsize: 128,
+            },
+            committee_sharing_scheme: p::LinearSecretSharingScheme::Additive {
+                share_count: 3,
+                modulus: 13,
+            },
+            recipient_encryption_scheme: p::AdditiveEncryptionScheme::Sodium,
+            committee_encryption_scheme: p::AdditiveEncryptionScheme::Sodium,
+            input_bounds: None,
+            differential_privacy: None,
+            numeric_representation: p::NumericRepresentation::I64,
+            recipient_group: None,
+            participation_policy: p::ParticipationPolicy::KeepAll,
+            schedule: None,
+        };
+        assert!(agg.validate().is_ok());
+
+        let mismatched_modulus = p::Aggregation {
+            id: p::AggregationId::default(),
+            committee_sharing_scheme: p::LinearSecretSharingScheme::Additive {
+                share_count: 3,
+                modulus: 17,
+            },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&mismatched_modulus)).is_err());
+
+        let mismatched_dimension = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::ChaCha {
+                modulus: 13,
+                dimension: 3,
+                seed_bitsize: 128,
+            },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&mismatched_dimension)).is_err());
+
+        let unreachable_threshold = p::Aggregation {
+            id: p::AggregationId::default(),
+            committee_sharing_scheme: p::LinearSecretSharingScheme::BasicShamir {
+                share_count: 3,
+                privacy_threshold: 3,
+                prime_modulus: 13,
+            },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&unreachable_threshold)).is_err());
+
+        let foreign_key = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_key: bob_key.id,
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&foreign_key)).is_err());
+
+        let unsuitable_key = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_encryption_scheme: p::AdditiveEncryptionScheme::PackedPaillier {
+                component_count: 2,
+                component_bitsize: 32,
+                max_value_bitsize: 16,
+                min_modulus_bitsize: 512,
+            },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&unsuitable_key)).is_err());
+
+        let unverifiable_bounds = p::Aggregation {
+            id: p::AggregationId::default(),
+            input_bounds: Some(p::InputBounds::OneHot),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&unverifiable_bounds)).is_err());
+
+        let too_wide_bounds = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::None,
+            committee_sharing_scheme: p::LinearSecretSharingScheme::new_verifiable_shamir(3, 1, 13).unwrap(),
+            input_bounds: Some(p::InputBounds::Range { min: vec![0; 4], max: vec![12; 4] }),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&too_wide_bounds)).is_err());
+
+        let noisy_shamir = p::Aggregation {
+            id: p::AggregationId::default(),
+            committee_sharing_scheme: p::LinearSecretSharingScheme::BasicShamir {
+                share_count: 3,
+                privacy_threshold: 1,
+                prime_modulus: 13,
+            },
+            differential_privacy: Some(p::DifferentialPrivacy {
+                epsilon: 1.0,
+                delta: 0.0,
+                sensitivity: 1.0,
+                mechanism: p::NoiseMechanism::DiscreteLaplace,
+                budget: None,
+            }),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&noisy_shamir)).is_err());
+
+        let gaussian_without_delta = p::Aggregation {
+            id: p::AggregationId::default(),
+            differential_privacy: Some(p::DifferentialPrivacy {
+                epsilon: 0.5,
+                delta: 0.0,
+                sensitivity: 1.0,
+                mechanism: p::NoiseMechanism::DiscreteGaussian,
+                budget: None,
+            }),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&gaussian_without_delta)).is_err());
+
+        let budget_below_release = p::Aggregation {
+            id: p::AggregationId::default(),
+            differential_privacy: Some(p::DifferentialPrivacy {
+                epsilon: 1.0,
+                delta: 0.0,
+                sensitivity: 1.0,
+                mechanism: p::NoiseMechanism::DiscreteLaplace,
+                budget: Some((0.5, 0.0)),
+            }),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&budget_below_release)).is_err());
+
+        let wide_chacha = p::Aggregation {
+            id: p::AggregationId::default(),
+            numeric_representation: p::NumericRepresentation::U128 { modulus: p::Binary(vec![1; 12]) },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&wide_chacha)).is_err());
+
+        let wide_shamir = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::None,
+            committee_sharing_scheme: p::LinearSecretSharingScheme::BasicShamir {
+                share_count: 3,
+                privacy_threshold: 1,
+                prime_modulus: 13,
+            },
+            numeric_representation: p::NumericRepresentation::BigUint { modulus: p::Binary(vec![1; 32]) },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&wide_shamir)).is_err());
+
+        let too_wide_u128 = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::None,
+            numeric_representation: p::NumericRepresentation::U128 { modulus: p::Binary(vec![0xff; 16]) },
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&too_wide_u128)).is_err());
+
+        let wide_paillier = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::None,
+            committee_encryption_scheme: p::AdditiveEncryptionScheme::PackedPaillier {
+                component_count: 4,
+                component_bitsize: 32,
+                max_value_bitsize: 16,
+                min_modulus_bitsize: 512,
+            },
+            numeric_representation: p::NumericRepresentation::U128 { modulus: p::Binary(vec![1; 12]) },
+            ..agg.clone()
+        };
+        match ctx.service.create_aggregation(&alice, &sign(&wide_paillier)) {
+            Err(p::SdaError(p::SdaErrorKind::Invalid(ref msg), _)) => {
+                assert_eq!("Committee encryption scheme cannot hold the values of the aggregation", msg)
+            }
+            e => panic!("unexpected result: {:?}", e),
+        }
+
+        let wide_paillier_fitting = p::Aggregation {
+            id: p::AggregationId::default(),
+            committee_encryption_scheme: p::AdditiveEncryptionScheme::PackedPaillier {
+                component_count: 4,
+                component_bitsize: 100,
+                max_value_bitsize: 89,
+                min_modulus_bitsize: 512,
+            },
+            ..wide_paillier.clone()
+        };
+        assert!(wide_paillier_fitting.validate().is_ok());
+
+        let too_wide_paillier_components = p::Aggregation {
+            id: p::AggregationId::default(),
+            committee_encryption_scheme: p::AdditiveEncryptionScheme::PackedPaillier {
+                component_count: 2,
+                component_bitsize: 160,
+                max_value_bitsize: 89,
+                min_modulus_bitsize: 512,
+            },
+            ..wide_paillier.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&too_wide_paillier_components)).is_err());
+
+        let group = p::RecipientGroup {
+            members: vec![(alice.id, alice_key), (bob.id, bob_key.id)],
+            sharing_scheme: p::LinearSecretSharingScheme::Additive {
+                share_count: 2,
+                modulus: 13,
+            },
+        };
+        let grouped = p::Aggregation {
+            id: p::AggregationId::default(),
+            masking_scheme: p::LinearMaskingScheme::Full { modulus: 13 },
+            recipient_group: Some(group.clone()),
+            ..agg.clone()
+        };
+        assert!(grouped.validate().is_ok());
+
+        let grouped_chacha = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_group: Some(group.clone()),
+            ..agg.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&grouped_chacha)).is_err());
+
+        let grouped_small_modulus = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_group: Some(p::RecipientGroup {
+                sharing_scheme: p::LinearSecretSharingScheme::Additive {
+                    share_count: 2,
+                    modulus: 11,
+                },
+                ..group.clone()
+            }),
+            ..grouped.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&grouped_small_modulus)).is_err());
+
+        let grouped_duplicate = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_group: Some(p::RecipientGroup {
+                members: vec![(alice.id, alice_key), (alice.id, alice_key)],
+                ..group.clone()
+            }),
+            ..grouped.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&grouped_duplicate)).is_err());
+
+        let grouped_foreign_key = p::Aggregation {
+            id: p::AggregationId::default(),
+            recipient_group: Some(p::RecipientGroup {
+                members: vec![(alice.id, alice_key), (bob.id, alice_key)],
+                ..group.clone()
+            }),
+            ..grouped.clone()
+        };
+        assert!(ctx.service.create_aggregation(&alice, &sign(&grouped_foreign_key)).is_err());
+
+        assert_eq!(0, ctx.service.list_aggregations(&alice, None, None).unwrap().len());
+        ctx.service.create_aggregation(&alice, &sign(&agg)).unwrap();
+        assert_eq!(1, ctx.service.list_aggregations(&alice, None, None).unwrap().len());
+    });
+}
@@ -4,6 +4,7 @@ extern crate sda_client;
 extern crate sda_client_store;
 extern crate sda_tests;
 extern crate tempdir;
+extern crate num;
 use sda_protocol::*;
 use sda_client::*;
 use sda_tests::*;
@@ -23,6 +24,12 @@ fn agg_default() -> Aggregation {
         },
         recipient_encryption_scheme: AdditiveEncryptionScheme::Sodium,
         committee_encryption_scheme: AdditiveEncryptionScheme::Sodium,
+        input_bounds: None,
+        differential_privacy: None,
+        numeric_representation: NumericRepresentation::I64,
+        recipient_group: None,
+        participation_policy: ParticipationPolicy::KeepAll,
+        schedule: None,
     }
 }
 
@@ -51,6 +58,18 @@ pub fn with_chachamask() {
     });
 }
 
+#[test]
+pub fn with_basicshamir() {
+    check_full_aggregation(Aggregation {
+        committee_sharing_scheme: LinearSecretSharingScheme::BasicShamir {
+            share_count: 8,
+            privacy_threshold: 4,
+            prime_modulus: 433,
+        },
+        ..agg_default()
+    });
+}
+
 #[test]
 pub fn with_packedshamir() {
     check_full_aggregation(Aggregation {
@@ -66,8 +85,64 @@ pub fn with_packedshamir() {
     });
 }
 
+#[test]
+pub fn with_verifiableshamir() {
+    check_full_aggregation(Aggregation {
+        committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(8, 4, 433).unwrap(),
+        ..agg_default()
+    });
+}
 
-pub fn check_full_aggregation(aggregation: Aggregation) {
+#[test]
+pub fn with_input_range_bounds() {
+    check_full_aggregation(Aggregation {
+        committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(8, 4, 433).unwrap(),
+        input_bounds: Some(InputBounds::Range { min: vec![0; 4], max: vec![5; 4] }),
+        ..agg_default()
+    });
+}
+
+#[test]
+pub fn with_packedpaillier() {
+    let scheme = AdditiveEncryptionScheme::PackedPaillier {
+        component_count: 4,
+        component_bitsize: 32,
+        max_value_bitsize: 16,
+        min_modulus_bitsize: 512,
+    };
+    check_full_aggregation(Aggregation {
+        masking_scheme: LinearMaskingScheme::Full { modulus: 433 },
+        recipient_encryption_scheme: scheme.clone(),
+        committee_encryption_scheme: scheme,
+        ..agg_default()
+    });
+}
+
+
+#[test]
+pub fn with_laplace_noise() {
+    check_noisy_aggregation(DifferentialPrivacy {
+        epsilon: 2.0,
+        delta: 0.0,
+        sensitivity: 1.0,
+        mechanism: NoiseMechanism::DiscreteLaplace,
+        budget: None,
+    }, 20);
+}
+
+#[test]
+pub fn with_gaussian_noise() {
+    check_noisy_aggregation(DifferentialPrivacy {
+        epsilon: 0.9,
+        delta: 1e-5,
+        sensitivity: 1.0,
+        mechanism: NoiseMechanism::DiscreteGaussian,
+        budget: None,
+    }, 50);
+}
+
+/// Check that the output is close to the exact one, and that the release is accounted for.
+pub fn check_noisy_aggregation(privacy: DifferentialPrivacy, tolerance: i64) {
     with_service(move |ctx| {
 
         // prepare recipient
@@ -77,6 +152,222 @@ pub fn check_full_aggregation(aggregation: Aggregation) {
         recipient.upload_agent().unwrap();
         recipient.upload_encryption_key(&recipient_key).unwrap();
 
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            differential_privacy: Some(privacy.clone()),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // participate
+        let participants_store: Vec<::tempdir::TempDir> = (0..2)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        for store in &participants_store {
+            let participant = new_client(store, &ctx.service);
+            participant.upload_agent().unwrap();
+            participant.participate(vec![1, 2, 3, 4], &aggregation.id).unwrap();
+        }
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // the single snapshot is accounted for
+        let status =
+            ctx.service.get_aggregation_status(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let (epsilon, delta) = privacy.cost();
+        assert_eq!(Some(PrivacyBudget {
+            releases: 1,
+            epsilon_spent: epsilon,
+            delta_spent: delta,
+        }), status.privacy_budget);
+
+        // reveal aggregation, which is only approximately the sum
+        let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
+        for (value, exact) in output.positive().values.iter().zip(vec![2, 4, 6, 8]) {
+            let distance = (value - exact + output.modulus) % output.modulus;
+            assert!(distance <= tolerance || output.modulus - distance <= tolerance,
+                "noisy output {} too far from {}", value, exact);
+        }
+    });
+}
+
+#[test]
+pub fn with_u128_values() {
+    // modulus 2^100, so the first and third sums wrap around
+    let modulus = Binary([vec![0x10], vec![0; 12]].concat());
+    let half: u128 = 1 << 99;
+    check_wide_aggregation(Aggregation {
+        masking_scheme: LinearMaskingScheme::Full { modulus: 433 },
+        numeric_representation: NumericRepresentation::U128 { modulus: modulus },
+        ..agg_default()
+    }, vec![half, 3, half + 1, 0], |output| {
+        assert_eq!(vec![0, 6, 2, 0], output.values);
+    });
+}
+
+#[test]
+pub fn with_u128_values_and_packedpaillier() {
+    // modulus 2^100, leaving a few bits of headroom in the components for summing up encryptions
+    let modulus = Binary([vec![0x10], vec![0; 12]].concat());
+    let scheme = AdditiveEncryptionScheme::PackedPaillier {
+        component_count: 4,
+        component_bitsize: 112,
+        max_value_bitsize: 101,
+        min_modulus_bitsize: 512,
+    };
+    let half: u128 = 1 << 99;
+    check_wide_aggregation(Aggregation {
+        masking_scheme: LinearMaskingScheme::Full { modulus: 433 },
+        recipient_encryption_scheme: scheme.clone(),
+        committee_encryption_scheme: scheme,
+        numeric_representation: NumericRepresentation::U128 { modulus: modulus },
+        ..agg_default()
+    }, vec![half, 3, half + 1, 0], |output| {
+        assert_eq!(vec![0, 6, 2, 0], output.values);
+    });
+}
+
+#[test]
+pub fn with_u128_values_and_laplace_noise() {
+    // modulus 2^100, so noise below zero wraps around
+    let modulus = Binary([vec![0x10], vec![0; 12]].concat());
+    let tolerance: u128 = 20;
+    check_wide_aggregation(Aggregation {
+        numeric_representation: NumericRepresentation::U128 { modulus: modulus },
+        differential_privacy: Some(DifferentialPrivacy {
+            epsilon: 2.0,
+            delta: 0.0,
+            sensitivity: 1.0,
+            mechanism: NoiseMechanism::DiscreteLaplace,
+            budget: None,
+        }),
+        ..agg_default()
+    }, vec![1_u128, 2, 3, 4], move |output: RecipientOutput<u128>| {
+        for (value, exact) in output.values.iter().zip(vec![2, 4, 6, 8]) {
+            let distance = if *value >= exact { value - exact } else { output.modulus - (exact - value) };
+            assert!(distance <= tolerance || output.modulus - distance <= tolerance,
+                "noisy output {} too far from {}", value, exact);
+        }
+    });
+}
+
+#[test]
+pub fn with_biguint_values() {
+    // modulus 2^200
+    let modulus = Binary([vec![0x01], vec![0; 25]].concat());
+    let zero = num::BigUint::from_bytes_be(&[]);
+    let big = |exponent: usize| num::BigUint::from_bytes_be(&[1]) << exponent;
+    let expected = vec![zero, big(151), big(1), big(2)];
+    check_wide_aggregation(Aggregation {
+        masking_scheme: LinearMaskingScheme::Full { modulus: 433 },
+        numeric_representation: NumericRepresentation::BigUint { modulus: modulus },
+        ..agg_default()
+    }, vec![big(199), big(150), big(0), big(199) + big(1)], move |output| {
+        assert_eq!(expected, output.values);
+    });
+}
+
+/// Check that each of two participants giving `input` to an aggregation using wide values leads to an output passing `check`.
+///
+/// The 64 bit moduli of the aggregation are unused by wide representations.
+pub fn check_wide_aggregation<T, F>(aggregation: Aggregation, input: Vec<T>, check: F)
+    where T: Numeric, F: FnOnce(RecipientOutput<T>)
+{
+    with_service(move |ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key_for_scheme(&aggregation.recipient_encryption_scheme).unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            ..aggregation
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key_for_scheme(&aggregation.committee_encryption_scheme).unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // participate, which is only possible with wide values
+        let participants_store: Vec<::tempdir::TempDir> = (0..2)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        for store in &participants_store {
+            let participant = new_client(store, &ctx.service);
+            participant.upload_agent().unwrap();
+            assert!(participant.new_participation(&ParticipantInput(vec![1, 2, 3, 4]), &aggregation.id).is_err());
+            participant.participate_numeric(input.clone(), &aggregation.id).unwrap();
+        }
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // reveal aggregation
+        assert!(recipient.reveal_aggregation(&aggregation.id).is_err());
+        let output: RecipientOutput<T> = recipient.reveal_numeric_aggregation(&aggregation.id).unwrap();
+        check(output);
+    });
+}
+
+pub fn check_full_aggregation(aggregation: Aggregation) {
+    with_service(move |ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key_for_scheme(&aggregation.recipient_encryption_scheme).unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
         let aggregation = Aggregation {
             recipient: recipient.agent.id().clone(),
             recipient_key: recipient_key.clone(),
@@ -92,13 +383,17 @@ pub fn check_full_aggregation(aggregation: Aggregation) {
         let clerks: Vec<SdaClient> =
             clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
         for clerk in clerks.iter() {
-            let clerk_key = clerk.new_encryption_key().unwrap();
+            let clerk_key = clerk.new_encryption_key_for_scheme(&aggregation.committee_encryption_scheme).unwrap();
             clerk.upload_agent().unwrap();
             clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
         }
 
         // assign committee
         recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
 
         // prepare participants
         let participants_store: Vec<::tempdir::TempDir> = (0..2)
@@ -143,8 +438,773 @@ pub fn check_full_aggregation(aggregation: Aggregation) {
             snapshot_status.number_of_clerking_results);
         assert_eq!(true, snapshot_status.result_ready);
 
+        // audit participations
+        let audited = recipient.audit_participations(&aggregation.id, &snapshot_status.id).unwrap();
+        assert_eq!(participants.len(), audited.len());
+
+        // reveal aggregation
+        let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
+        assert_eq!(vec![2, 4, 6, 8], output.positive().values);
+    });
+}
+
+#[test]
+pub fn with_pairwisemask_and_dropout() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            masking_scheme: LinearMaskingScheme::Pairwise {
+                modulus: 433,
+                dimension: 4,
+            },
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // prepare participants, all enrolling in the masking cohort
+        let participants_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let participants: Vec<SdaClient> =
+            participants_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for participant in &participants {
+            participant.upload_agent().unwrap();
+        }
+
+        // masking keys must carry a valid signature of the participant
+        let mut forged_key = ctx.service.get_encryption_key(&participants[2].agent, &recipient_key).unwrap().unwrap();
+        forged_key.signer = participants[2].agent.id;
+        let forged = Enrollment {
+            participant: participants[2].agent.id,
+            aggregation: aggregation.id,
+            masking_key: forged_key,
+            clerk_encryptions: clerks.iter()
+                .map(|clerk| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                .collect(),
+        };
+        assert!(ctx.service.create_enrollment(&participants[2].agent, &forged).is_err());
+
+        for participant in &participants {
+            participant.enroll(&aggregation.id).unwrap();
+        }
+        recipient.form_masking_cohort(&aggregation.id).unwrap();
+
+        // late enrollment is refused
+        assert!(participants[0].enroll(&aggregation.id).is_err());
+
+        // last participant drops out
+        for participant in &participants[0..2] {
+            participant.participate(vec![1, 2, 3, 4], &aggregation.id).unwrap();
+        }
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
         // reveal aggregation
         let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
         assert_eq!(vec![2, 4, 6, 8], output.positive().values);
     });
 }
+
+#[test]
+pub fn verifiableshamir_names_misbehaving_clerk() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(3, 1, 433).unwrap(),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+        let committee = ctx.service.get_committee(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let misbehaving = committee.clerks_and_keys[0].0;
+
+        // participate
+        let participants_store: Vec<::tempdir::TempDir> = (0..2)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        for store in &participants_store {
+            let participant = new_client(store, &ctx.service);
+            participant.upload_agent().unwrap();
+            participant.participate(vec![1, 2, 3, 4], &aggregation.id).unwrap();
+        }
+        recipient.end_aggregation(&aggregation.id).unwrap();
+
+        // honest clerking
+        let mut clients: Vec<&SdaClient> = clerks.iter().collect();
+        clients.push(&recipient);
+        for client in clients.iter().filter(|client| client.agent.id != misbehaving) {
+            client.run_chores(-1).unwrap();
+        }
+
+        // the misbehaving clerk returns the result of another clerk as its own
+        let client = clients.iter().find(|client| client.agent.id == misbehaving).unwrap();
+        let job = ctx.service.get_clerking_job(&client.agent, &misbehaving).unwrap().unwrap();
+        let status = ctx.service.get_aggregation_status(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let result = ctx.service
+            .get_snapshot_result(&recipient.agent, &aggregation.id, &status.snapshots[0].id)
+            .unwrap().unwrap();
+        let copied = &result.clerk_encryptions[0].body;
+
+        // .. but cannot pass it off with the other clerk's signature
+        let mut forged = result.clerk_encryptions[0].clone();
+        forged.signer = misbehaving;
+        forged.body.job = job.id;
+        forged.body.clerk = misbehaving;
+        assert!(ctx.service.create_clerking_result(&client.agent, &forged).is_err());
+
+        client.upload_clerking_result(&ClerkingResult {
+            job: job.id,
+            clerk: misbehaving,
+            aggregation: job.aggregation,
+            snapshot: job.snapshot,
+            encryption: copied.encryption.clone(),
+            commitments: copied.commitments.clone(),
+            recovery_encryptions: vec![],
+            rejected_participations: vec![],
+        }).unwrap();
+
+        // reveal aggregation
+        match recipient.reveal_aggregation(&aggregation.id) {
+            Err(SdaClientError(SdaClientErrorKind::MisbehavingClerks(clerks), _)) => assert_eq!(vec![misbehaving], clerks),
+            other => panic!("expected misbehaving clerk to be named, got {:?}", other),
+        }
+    });
+}
+
+#[test]
+pub fn onehot_input_bounds_are_enforced() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(3, 1, 433).unwrap(),
+            input_bounds: Some(InputBounds::OneHot),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // prepare participants
+        let participants_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let participants: Vec<SdaClient> =
+            participants_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for participant in &participants {
+            participant.upload_agent().unwrap();
+        }
+
+        // honest participations go through
+        participants[0].participate(vec![0, 1, 0, 0], &aggregation.id).unwrap();
+        participants[1].participate(vec![0, 0, 0, 1], &aggregation.id).unwrap();
+
+        // input that is not one-hot cannot be proven
+        assert!(participants[2].participate(vec![1, 1, 0, 0], &aggregation.id).is_err());
+
+        // participations without proof are refused by the service
+        let committee = ctx.service.get_committee(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let unproven = Participation {
+            id: ParticipationId::random(),
+            participant: participants[2].agent.id().clone(),
+            aggregation: aggregation.id,
+            recipient_encryption: None,
+            clerk_encryptions: committee.clerks_and_keys.iter()
+                .map(|&(clerk, _)| (clerk, Encryption::Sodium(Binary(vec![0; 8]))))
+                .collect(),
+            commitments: vec![Commitment::default(); aggregation.committee_sharing_scheme.commitment_count(4)],
+            input_proof: None,
+        };
+        assert!(participants[2].upload_participation(&unproven).is_err());
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // reveal aggregation
+        let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
+        assert_eq!(vec![0, 1, 0, 1], output.positive().values);
+    });
+}
+
+#[test]
+pub fn clerks_leave_out_shares_not_matching_commitments() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(3, 1, 433).unwrap(),
+            input_bounds: Some(InputBounds::OneHot),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // prepare participants
+        let participants_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let participants: Vec<SdaClient> =
+            participants_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for participant in &participants {
+            participant.upload_agent().unwrap();
+        }
+
+        // honest participations
+        participants[0].participate(vec![0, 1, 0, 0], &aggregation.id).unwrap();
+        participants[1].participate(vec![0, 0, 0, 1], &aggregation.id).unwrap();
+
+        // the last participant hands out shares of one input along with the commitments and proof of another,
+        // which the service cannot tell apart from an honest participation
+        let shared = participants[2].new_participation(&ParticipantInput(vec![1, 0, 0, 0]), &aggregation.id).unwrap();
+        let committed = participants[2].new_participation(&ParticipantInput(vec![0, 0, 1, 0]), &aggregation.id).unwrap();
+        let forged = Participation {
+            commitments: committed.commitments,
+            input_proof: committed.input_proof,
+            ..shared
+        };
+        participants[2].upload_participation(&forged).unwrap();
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // every clerk left out the forged participation
+        let status = ctx.service.get_aggregation_status(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let result = ctx.service
+            .get_snapshot_result(&recipient.agent, &aggregation.id, &status.snapshots[0].id)
+            .unwrap().unwrap();
+        assert_eq!(3, result.clerk_encryptions.len());
+        for clerking_result in &result.clerk_encryptions {
+            assert_eq!(vec![forged.id], clerking_result.body.rejected_participations);
+        }
+
+        // .. so only the honest participations make it into the output
+        let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
+        assert_eq!(vec![0, 1, 0, 1], output.positive().values);
+    });
+}
+
+#[test]
+pub fn verifiableshamir_does_not_blame_clerks_for_misbehaving_participant() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            committee_sharing_scheme: LinearSecretSharingScheme::new_verifiable_shamir(3, 1, 433).unwrap(),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+        let committee = ctx.service.get_committee(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let deceived = committee.clerks_and_keys[0].0;
+
+        // prepare participants
+        let participants_store: Vec<::tempdir::TempDir> = (0..2)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let participants: Vec<SdaClient> =
+            participants_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for participant in &participants {
+            participant.upload_agent().unwrap();
+        }
+
+        // honest participation
+        participants[0].participate(vec![1, 2, 3, 4], &aggregation.id).unwrap();
+
+        // the other participant gives a single clerk shares not matching its commitments
+        let honest = participants[1].new_participation(&ParticipantInput(vec![1, 1, 1, 1]), &aggregation.id).unwrap();
+        let other = participants[1].new_participation(&ParticipantInput(vec![5, 5, 5, 5]), &aggregation.id).unwrap();
+        let forged = Participation {
+            clerk_encryptions: honest.clerk_encryptions.iter()
+                .zip(&other.clerk_encryptions)
+                .map(|(&(clerk, ref encryption), &(_, ref other_encryption))| {
+                    (clerk, if clerk == deceived { other_encryption.clone() } else { encryption.clone() })
+                })
+                .collect(),
+            ..honest
+        };
+        participants[1].upload_participation(&forged).unwrap();
+
+        // close aggregation and perform clerking
+        recipient.end_aggregation(&aggregation.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // only the deceived clerk left out the forged participation
+        let status = ctx.service.get_aggregation_status(&recipient.agent, &aggregation.id).unwrap().unwrap();
+        let result = ctx.service
+            .get_snapshot_result(&recipient.agent, &aggregation.id, &status.snapshots[0].id)
+            .unwrap().unwrap();
+        for clerking_result in &result.clerk_encryptions {
+            if clerking_result.body.clerk == deceived {
+                assert_eq!(vec![forged.id], clerking_result.body.rejected_participations);
+            } else {
+                assert!(clerking_result.body.rejected_participations.is_empty());
+            }
+        }
+
+        // .. and no clerk is blamed, the output being reconstructed by the other two
+        let output = recipient.reveal_aggregation(&aggregation.id).unwrap();
+        assert_eq!(vec![2, 3, 4, 5], output.positive().values);
+    });
+}
+
+#[test]
+pub fn participants_reject_altered_aggregation() {
+    with_service(|ctx| {
+
+        // prepare recipient
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let aggregation = Aggregation {
+            recipient: recipient.agent.id().clone(),
+            recipient_key: recipient_key.clone(),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&aggregation).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+
+        // assign committee
+        recipient.begin_aggregation(&aggregation.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // prepare participant
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        // the service swaps in a key of its own choosing, bypassing its own checks
+        let mallory_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let mallory = new_client(&mallory_store, &ctx.service);
+        let mallory_key = mallory.new_encryption_key().unwrap();
+        mallory.upload_agent().unwrap();
+        mallory.upload_encryption_key(&mallory_key).unwrap();
+
+        let mut altered = ctx.service.get_aggregation(&participant.agent, &aggregation.id).unwrap().unwrap();
+        altered.body.recipient_key = mallory_key;
+        let store = &ctx.server.0.aggregation_store;
+        let committee = store.get_committee(&aggregation.id).unwrap().unwrap();
+        let acceptances = store.list_seat_acceptances(&aggregation.id).unwrap();
+        store.delete_aggregation(&aggregation.id).unwrap();
+        store.create_aggregation(&altered).unwrap();
+        store.create_committee(&committee).unwrap();
+        for acceptance in acceptances.iter() {
+            store.create_seat_acceptance(acceptance).unwrap();
+        }
+        store.set_aggregation_state(&aggregation.id, AggregationState::Open).unwrap();
+
+        assert!(participant.participate(vec![0, 1, 0, 0], &aggregation.id).is_err());
+    });
+}
+
+#[test]
+pub fn recipient_rotates_verification_key() {
+    with_service(|ctx| {
+
+        // prepare recipient, whose encryption key gets signed with its first verification key
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        // only the agent can rotate its key, and only with a signature of the current key
+        let mallory_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let mallory = new_client(&mallory_store, &ctx.service);
+        mallory.upload_agent().unwrap();
+        let hijack = VerificationKeyRotation {
+            agent: recipient.agent.id,
+            previous_key: recipient.agent.verification_key.clone(),
+            new_key: mallory.agent.verification_key.clone(),
+        };
+        let mut hijack = mallory.sign(hijack).unwrap();
+        assert!(ctx.service.rotate_verification_key(&mallory.agent, &hijack).is_err());
+        hijack.signer = recipient.agent.id;
+        assert!(ctx.service.rotate_verification_key(&recipient.agent, &hijack).is_err());
+
+        let rotated_agent = recipient.rotate_verification_key().unwrap();
+        assert_eq!(Some(&rotated_agent), ctx.service.get_agent(&mallory.agent, &recipient.agent.id).unwrap().as_ref());
+        let history = ctx.service.get_verification_key_history(&mallory.agent, &recipient.agent.id).unwrap();
+        assert_eq!(1, history.len());
+        assert_eq!(recipient.agent.verification_key, history[0].body.previous_key);
+
+        // the replaced key can neither sign for the agent nor come back
+        let agg = Aggregation {
+            recipient: recipient.agent.id,
+            recipient_key: recipient_key,
+            ..agg_default()
+        };
+        assert!(recipient.upload_aggregation(&agg).is_err());
+        let keystore = ::std::sync::Arc::new(::sda_client_store::Filebased::new(recipient_store.path()).unwrap());
+        let recipient = SdaClient::new(rotated_agent, keystore, ctx.service.clone());
+        let comeback = VerificationKeyRotation {
+            agent: recipient.agent.id,
+            previous_key: recipient.agent.verification_key.clone(),
+            new_key: history[0].body.previous_key.clone(),
+        };
+        let comeback = recipient.sign(comeback).unwrap();
+        assert!(ctx.service.rotate_verification_key(&recipient.agent, &comeback).is_err());
+
+        // resources name the key they were signed with, and the service only stores those naming the current one
+        let signed_key = ctx.service.get_encryption_key(&mallory.agent, &recipient_key).unwrap().unwrap();
+        assert_eq!(Some(history[0].body.previous_key.id), signed_key.key);
+        let mut misnamed = recipient.sign(agg.clone()).unwrap();
+        misnamed.key = Some(history[0].body.previous_key.id);
+        assert!(ctx.service.create_aggregation(&recipient.agent, &misnamed).is_err());
+
+        recipient.upload_aggregation(&agg).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+        recipient.begin_aggregation(&agg.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        // participants and clerks verify the recipient key against the key it was signed with
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+        participant.participate(vec![1, 2, 3, 4], &agg.id).unwrap();
+
+        recipient.end_aggregation(&agg.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks {
+            clerk.run_chores(-1).unwrap();
+        }
+        let output = recipient.reveal_aggregation(&agg.id).unwrap();
+        assert_eq!(vec![1, 2, 3, 4], output.positive().values);
+    });
+}
+
+#[test]
+pub fn with_recipient_group() {
+    with_service(|ctx| {
+
+        // prepare recipient and the members of its group, any two of which must cooperate
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+
+        let members_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let members: Vec<SdaClient> =
+            members_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        let mut members_and_keys = vec![];
+        for member in members.iter() {
+            let member_key = member.new_encryption_key().unwrap();
+            member.upload_agent().unwrap();
+            member.upload_encryption_key(&member_key).unwrap();
+            members_and_keys.push((member.agent.id, member_key));
+        }
+
+        let agg = Aggregation {
+            recipient: recipient.agent.id,
+            recipient_key: recipient_key,
+            masking_scheme: LinearMaskingScheme::Full { modulus: 433 },
+            recipient_group: Some(RecipientGroup {
+                members: members_and_keys,
+                sharing_scheme: LinearSecretSharingScheme::BasicShamir {
+                    share_count: 3,
+                    privacy_threshold: 1,
+                    prime_modulus: 433,
+                },
+            }),
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&agg).unwrap();
+
+        // prepare clerks
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+        recipient.begin_aggregation(&agg.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        let participants_store: Vec<::tempdir::TempDir> = (0..2)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        for store in participants_store.iter() {
+            let participant = new_client(store, &ctx.service);
+            participant.upload_agent().unwrap();
+            participant.participate(vec![1, 2, 3, 4], &agg.id).unwrap();
+        }
+
+        recipient.end_aggregation(&agg.id).unwrap();
+        recipient.run_chores(-1).unwrap();
+        for clerk in clerks.iter() {
+            clerk.run_chores(-1).unwrap();
+        }
+
+        // neither the recipient nor a single member can reveal
+        let snapshot = ctx.service.get_aggregation_status(&recipient.agent, &agg.id).unwrap().unwrap().snapshots[0].id;
+        assert!(recipient.reveal_aggregation(&agg.id).is_err());
+        assert!(recipient.contribute_to_reveal(&agg.id, &snapshot).is_err());
+        assert!(members[0].contribute_to_reveal(&agg.id, &SnapshotId::random()).is_err());
+        members[0].contribute_to_reveal(&agg.id, &snapshot).unwrap();
+        assert!(members[0].contribute_to_reveal(&agg.id, &snapshot).is_err());
+        assert!(recipient.reveal_aggregation(&agg.id).is_err());
+
+        members[2].contribute_to_reveal(&agg.id, &snapshot).unwrap();
+        let output = recipient.reveal_aggregation(&agg.id).unwrap();
+        assert_eq!(vec![2, 4, 6, 8], output.positive().values);
+    });
+}
+
+#[test]
+pub fn with_incremental_snapshots() {
+    with_service(|ctx| {
+
+        let recipient_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let recipient = new_client(&recipient_store, &ctx.service);
+        let recipient_key = recipient.new_encryption_key().unwrap();
+        recipient.upload_agent().unwrap();
+        recipient.upload_encryption_key(&recipient_key).unwrap();
+        let agg = Aggregation {
+            recipient: recipient.agent.id,
+            recipient_key: recipient_key,
+            ..agg_default()
+        };
+        recipient.upload_aggregation(&agg).unwrap();
+
+        let clerks_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let clerks: Vec<SdaClient> =
+            clerks_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for clerk in clerks.iter() {
+            let clerk_key = clerk.new_encryption_key().unwrap();
+            clerk.upload_agent().unwrap();
+            clerk.upload_encryption_key(&clerk_key).unwrap();
+            register_clerk(clerk);
+        }
+        recipient.begin_aggregation(&agg.id).unwrap();
+        for clerk in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        let participants_store: Vec<::tempdir::TempDir> = (0..3)
+            .map(|_| ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap())
+            .collect();
+        let participants: Vec<SdaClient> =
+            participants_store.iter().map(|store| new_client(store, &ctx.service)).collect();
+        for participant in &participants {
+            participant.upload_agent().unwrap();
+        }
+        // the recipient may sit in the committee as well
+        let run_clerks = || {
+            recipient.run_chores(-1).unwrap();
+            for clerk in clerks.iter() {
+                clerk.run_chores(-1).unwrap();
+            }
+        };
+
+        // each delta only covers the participations received since the previous one
+        participants[0].participate(vec![1, 2, 3, 4], &agg.id).unwrap();
+        participants[1].participate(vec![1, 2, 3, 4], &agg.id).unwrap();
+        let first = recipient.snapshot_new_participations(&agg.id).unwrap();
+        assert!(recipient.snapshot_new_participations(&agg.id).is_err());
+        participants[2].participate(vec![5, 5, 5, 5], &agg.id).unwrap();
+        let second = recipient.snapshot_new_participations(&agg.id).unwrap();
+        run_clerks();
+        assert_eq!(vec![2, 4, 6, 8], recipient.reveal_snapshot(&agg.id, &first).unwrap().positive().values);
+        assert_eq!(vec![5, 5, 5, 5], recipient.reveal_snapshot(&agg.id, &second).unwrap().positive().values);
+        assert_eq!(2, recipient.audit_participations(&agg.id, &first).unwrap().len());
+        assert_eq!(1, recipient.audit_participations(&agg.id, &second).unwrap().len());
+
+        // while a full snapshot still covers everything, and closes the aggregation
+        recipient.end_aggregation(&agg.id).unwrap();
+        let status = ctx.service.get_aggregation_status(&recipient.agent, &agg.id).unwrap().unwrap();
+        assert_eq!(AggregationState::Closed, status.state);
+        let last = status.snapshots.iter().find(|s| s.id != first && s.id != second).unwrap().id;
+        run_clerks();
+        assert_eq!(vec![7, 9, 11, 13], recipient.reveal_snapshot(&agg.id, &last).unwrap().positive().values);
+    });
+}
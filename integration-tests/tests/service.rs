@@ -5,6 +5,7 @@ extern crate sda_client_store;
 extern crate sda_tests;
 extern crate tempdir;
 use sda_protocol::*;
+use sda_client::*;
 use sda_tests::*;
 
 fn small_aggregation(recipient: &AgentId, recipient_key: &EncryptionKeyId) -> Aggregation {
@@ -22,19 +23,30 @@ fn small_aggregation(recipient: &AgentId, recipient_key: &EncryptionKeyId) -> Ag
         },
         recipient_encryption_scheme: AdditiveEncryptionScheme::Sodium,
         committee_encryption_scheme: AdditiveEncryptionScheme::Sodium,
+        input_bounds: None,
+        differential_privacy: None,
+        numeric_representation: NumericRepresentation::I64,
+        recipient_group: None,
+        participation_policy: ParticipationPolicy::KeepAll,
+        schedule: None,
     }
 }
 
 #[test]
 pub fn full_mocked_loop() {
     with_service(|ctx| {
-        let agents: Vec<(Agent, SignedEncryptionKey)> =
-            (0..20).map(|_| new_full_agent(&ctx.service)).collect();
-        let (ref alice, ref alice_key) = agents[0];
-        let agg = small_aggregation(&alice.id(), &alice_key.body.id());
-        ctx.service.create_aggregation(&alice, &agg).unwrap();
-        let candidates = ctx.service.suggest_committee(&alice, &agg.id).unwrap();
+        // clerking results must be signed, so clerks need actual signature keypairs
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..20).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0.agent;
+        let alice_key = &agents[0].1;
+        let agg = small_aggregation(&alice.id(), alice_key);
+        ctx.service.create_aggregation(&alice, &agents[0].0.sign(agg.clone()).unwrap()).unwrap();
+        let candidates = ctx.service.suggest_committee(&alice, &agg.id, &CommitteeCriteria::default()).unwrap();
         assert_eq!(agents.len(), candidates.len());
+        let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
+        assert_eq!(AggregationState::Draft, status.state);
 
         let clerks = &candidates[0..agg.committee_sharing_scheme.output_size()];
 
@@ -42,36 +54,70 @@ pub fn full_mocked_loop() {
             aggregation: agg.id,
             clerks_and_keys: clerks.iter().map(|cc| (cc.id, cc.keys[0])).collect(),
         };
-        ctx.service.create_committee(&alice, &committee).unwrap();
+        // committees must be signed by the recipient as well
+        assert!(ctx.service.create_committee(&alice, &agents[1].0.sign(committee.clone()).unwrap()).is_err());
+        ctx.service.create_committee(&alice, &agents[0].0.sign(committee.clone()).unwrap()).unwrap();
         let committee_again = ctx.service.get_committee(&alice, &agg.id).unwrap();
-        assert_eq!(Some(&committee), committee_again.as_ref());
+        assert_eq!(Some(&committee), committee_again.as_ref().map(|signed| &signed.body));
 
-        let participants: Vec<(Agent, SignedEncryptionKey)> =
-            (0..100).map(|_| new_full_agent(&ctx.service)).collect();
+        // participations only open once every clerk accepted its seat
+        let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
+        assert_eq!(AggregationState::Draft, status.state);
+        assert_eq!(clerks.len(), status.pending_clerks.len());
+        for agent in agents.iter() {
+            agent.0.accept_committee_seats().unwrap();
+        }
+        let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
+        assert!(status.pending_clerks.is_empty());
+
+        // participations must be signed, so participants need actual signature keypairs
+        let participants_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participants: Vec<SdaClient> = (0..100)
+            .map(|_| new_client(&participants_store, &ctx.service))
+            .collect();
         for (pi, p) in participants.iter().enumerate() {
+            p.upload_agent().unwrap();
             let participation = Participation {
                 id: ParticipationId::random(),
-                participant: p.0.id().clone(),
+                participant: p.agent.id().clone(),
                 aggregation: agg.id,
                 recipient_encryption: None,
                 clerk_encryptions: clerks.iter()
                     .enumerate()
                     .map(|(ci, c)| (c.id, Encryption::Sodium(Binary(vec![ci as u8, pi as u8]))))
                     .collect(),
+                commitments: vec![],
+                input_proof: None,
             };
-            ctx.service.create_participation(&p.0, &participation).unwrap();
+            p.upload_participation(&participation).unwrap();
         }
 
         let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
         assert_eq!(agg.id, status.aggregation);
         assert_eq!(participants.len(), status.number_of_participations);
         assert_eq!(0, status.snapshots.len());
+        assert_eq!(AggregationState::Open, status.state);
         let snapshot = Snapshot {
             id: SnapshotId::random(),
             aggregation: agg.id.clone(),
+            incremental: false,
         };
         ctx.service.create_snapshot(&alice, &snapshot).unwrap();
 
+        // the recipient and the clerks may audit the signed participations, participants may not
+        let signed_participations = ctx.service.get_snapshot_participations(&alice, &agg.id, &snapshot.id).unwrap();
+        assert_eq!(participants.len(), signed_participations.len());
+        let clerk_agent = agents.iter().find(|a| a.0.agent.id == clerks[0].id).unwrap();
+        assert!(ctx.service.get_snapshot_participations(&clerk_agent.0.agent, &agg.id, &snapshot.id).is_ok());
+        assert!(ctx.service.get_snapshot_participations(&participants[0].agent, &agg.id, &snapshot.id).is_err());
+
+        // participations whose signature does not match are refused
+        let mut tampered = signed_participations[0].clone();
+        tampered.body.id = ParticipationId::random();
+        tampered.body.clerk_encryptions.reverse();
+        let owner = participants.iter().find(|p| p.agent.id == tampered.signer).unwrap();
+        assert!(ctx.service.create_participation(&owner.agent, &tampered).is_err());
+
         let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
         assert_eq!(agg.id, status.aggregation);
         assert_eq!(participants.len(), status.number_of_participations);
@@ -80,23 +126,45 @@ pub fn full_mocked_loop() {
             id: snapshot.id.clone(),
             number_of_clerking_results: 0,
             result_ready: false,
+            unresponsive_clerks: vec![],
         }], status.snapshots);
+        assert_eq!(AggregationState::Closed, status.state);
 
         for (ci, c) in clerks.iter().enumerate() {
-            let agent = agents.iter().find(|a| a.0.id == c.id).unwrap();
-            let job = ctx.service.get_clerking_job(&agent.0, &c.id).unwrap().unwrap();
+            let agent = agents.iter().find(|a| a.0.agent.id == c.id).unwrap();
+            let job = ctx.service.get_clerking_job(&agent.0.agent, &c.id).unwrap().unwrap();
             assert_eq!(snapshot.id, job.snapshot);
             for enc in job.encryptions.iter() {
-                let &Encryption::Sodium(ref data) = enc;
-                assert_eq!(ci as u8, data.0[0]);
+                match enc {
+                    &Encryption::Sodium(ref data) => assert_eq!(ci as u8, data.0[0]),
+                    e => panic!("unexpected encryption: {:?}", e),
+                }
             }
 
-            ctx.service.create_clerking_result(&agent.0, &ClerkingResult {
+            let result = ClerkingResult {
                 job: job.id,
                 clerk: c.id.clone(),
-                encryption: Encryption::Sodium(Binary(vec![ci as u8]))
-            }).unwrap();
+                aggregation: job.aggregation,
+                snapshot: job.snapshot,
+                encryption: Encryption::Sodium(Binary(vec![ci as u8])),
+                commitments: vec![],
+                recovery_encryptions: vec![],
+                rejected_participations: vec![],
+            };
+
+            // a result must name the snapshot of its job
+            assert!(agent.0.upload_clerking_result(&ClerkingResult {
+                snapshot: SnapshotId::random(),
+                ..result.clone()
+            }).is_err());
+
+            agent.0.upload_clerking_result(&result).unwrap();
 
+            // .. and a clerk returns only one
+            assert!(agent.0.upload_clerking_result(&ClerkingResult {
+                encryption: Encryption::Sodium(Binary(vec![42])),
+                ..result
+            }).is_err());
         }
 
         let status = ctx.service.get_aggregation_status(&alice, &agg.id).unwrap().unwrap();
@@ -107,21 +175,909 @@ pub fn full_mocked_loop() {
             id: snapshot.id.clone(),
             number_of_clerking_results: clerks.len(),
             result_ready: true,
+            unresponsive_clerks: vec![],
         }], status.snapshots);
+        assert_eq!(AggregationState::Revealed, status.state);
 
         for c in clerks.iter() {
-            let agent = agents.iter().find(|a| a.0.id == c.id).unwrap();
-            let job = ctx.service.get_clerking_job(&agent.0, &c.id).unwrap();
+            let agent = agents.iter().find(|a| a.0.agent.id == c.id).unwrap();
+            let job = ctx.service.get_clerking_job(&agent.0.agent, &c.id).unwrap();
             assert!(job.is_none());
         }
 
         let final_result = ctx.service.get_snapshot_result(&alice, &agg.id, &snapshot.id).unwrap().unwrap();
         assert_eq!(3, final_result.clerk_encryptions.len());
         for (ci, c) in clerks.iter().enumerate() {
-            let agent = agents.iter().find(|a| a.0.id == c.id).unwrap();
-            let Encryption::Sodium(ref enc) = final_result.clerk_encryptions.iter().find(|enc| enc.clerk == agent.0.id).unwrap().encryption;
-            assert_eq!(enc, &Binary(vec!(ci as u8)));
+            let agent = agents.iter().find(|a| a.0.agent.id == c.id).unwrap();
+            match final_result.clerk_encryptions.iter().find(|enc| enc.body.clerk == agent.0.agent.id).unwrap().body.encryption {
+                Encryption::Sodium(ref enc) => assert_eq!(enc, &Binary(vec!(ci as u8))),
+                ref e => panic!("unexpected encryption: {:?}", e),
+            }
+        }
+    });
+}
+
+
+#[test]
+pub fn revoked_keys() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0.agent;
+        let alice_key = &agents[0].1;
+        assert_eq!(vec![*alice_key], ctx.service.list_encryption_keys(&alice, &alice.id).unwrap());
+
+        // rotation revokes previous keys, which are then no longer suggested
+        let (ref bob, bob_old_key) = agents[1];
+        let bob_new_key = bob.rotate_encryption_key().unwrap();
+        assert_eq!(vec![bob_new_key], ctx.service.list_encryption_keys(&alice, &bob.agent.id).unwrap());
+        assert!(ctx.service.get_encryption_key(&alice, &bob_old_key).unwrap().is_some());
+
+        // only the owner of a key can revoke it
+        let (ref carol, carol_key) = agents[2];
+        let forged = agents[3].0.sign(EncryptionKeyRevocation { key: carol_key }).unwrap();
+        assert!(ctx.service.revoke_encryption_key(&agents[3].0.agent, &forged).is_err());
+        assert_eq!(vec![carol_key], ctx.service.list_encryption_keys(&alice, &carol.agent.id).unwrap());
+
+        let agg = small_aggregation(&alice.id, alice_key);
+        ctx.service.create_aggregation(&alice, &agents[0].0.sign(agg.clone()).unwrap()).unwrap();
+        let candidates = ctx.service.suggest_committee(&alice, &agg.id, &CommitteeCriteria::default()).unwrap();
+        let bob_candidate = candidates.iter().find(|c| c.id == bob.agent.id).unwrap();
+        assert_eq!(vec![bob_new_key], bob_candidate.keys);
+
+        // revoked keys are refused in committees and aggregations
+        let committee = |bob_key| Committee {
+            aggregation: agg.id,
+            clerks_and_keys: vec![(bob.agent.id, bob_key),
+                                  (carol.agent.id, carol_key),
+                                  (agents[3].0.agent.id, agents[3].1)],
+        };
+        assert!(ctx.service.create_committee(&alice, &agents[0].0.sign(committee(bob_old_key)).unwrap()).is_err());
+        ctx.service.create_committee(&alice, &agents[0].0.sign(committee(bob_new_key)).unwrap()).unwrap();
+
+        agents[0].0.revoke_encryption_key(alice_key).unwrap();
+        assert!(ctx.service.list_encryption_keys(&alice, &alice.id).unwrap().is_empty());
+        let other_agg = small_aggregation(&alice.id, alice_key);
+        assert!(ctx.service.create_aggregation(&alice, &agents[0].0.sign(other_agg).unwrap()).is_err());
+    });
+}
+
+#[test]
+pub fn participation_policies() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        for policy in vec![ParticipationPolicy::Reject, ParticipationPolicy::KeepLatest, ParticipationPolicy::KeepAll] {
+            let agg = Aggregation {
+                id: AggregationId::random(),
+                participation_policy: policy,
+                ..small_aggregation(&alice.agent.id, &agents[0].1)
+            };
+            ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+            let committee = Committee {
+                aggregation: agg.id,
+                clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+            };
+            ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+            for &(ref clerk, _) in clerks.iter() {
+                clerk.accept_committee_seats().unwrap();
+            }
+
+            let participation = |value: u8| Participation {
+                id: ParticipationId::random(),
+                participant: participant.agent.id,
+                aggregation: agg.id,
+                recipient_encryption: None,
+                clerk_encryptions: clerks.iter()
+                    .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![value]))))
+                    .collect(),
+                commitments: vec![],
+                input_proof: None,
+            };
+
+            // retries are idempotent, while ids cannot be reused for other participations
+            let first = participation(1);
+            participant.upload_participation(&first).unwrap();
+            participant.upload_participation(&first).unwrap();
+            let reused = Participation {
+                id: first.id,
+                ..participation(3)
+            };
+            assert!(participant.upload_participation(&reused).is_err());
+
+            let second = participation(2);
+            let uploaded = participant.upload_participation(&second);
+            let (expected_count, expected_ids) = match policy {
+                ParticipationPolicy::Reject => {
+                    assert!(uploaded.is_err());
+                    (1, vec![first.id])
+                }
+                ParticipationPolicy::KeepLatest => {
+                    uploaded.unwrap();
+                    (1, vec![second.id])
+                }
+                ParticipationPolicy::KeepAll => {
+                    uploaded.unwrap();
+                    (2, vec![first.id, second.id])
+                }
+            };
+            let status = ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+            assert_eq!(expected_count, status.number_of_participations);
+
+            let snapshot = Snapshot {
+                id: SnapshotId::random(),
+                aggregation: agg.id,
+                incremental: false,
+            };
+            ctx.service.create_snapshot(&alice.agent, &snapshot).unwrap();
+            let mut snapped: Vec<ParticipationId> = ctx.service
+                .get_snapshot_participations(&alice.agent, &agg.id, &snapshot.id)
+                .unwrap()
+                .into_iter()
+                .map(|signed| signed.body.id)
+                .collect();
+            let mut expected_ids = expected_ids;
+            snapped.sort_by_key(|id| id.to_string());
+            expected_ids.sort_by_key(|id| id.to_string());
+            assert_eq!(expected_ids, snapped);
+        }
+    });
+}
+
+#[test]
+pub fn concurrent_participations() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        // uploads several participations at once, returning how many were accepted and how many count
+        let upload_concurrently = |policy| {
+            let agg = Aggregation {
+                id: AggregationId::random(),
+                participation_policy: policy,
+                ..small_aggregation(&alice.agent.id, &agents[0].1)
+            };
+            ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+            let committee = Committee {
+                aggregation: agg.id,
+                clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+            };
+            ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+            for &(ref clerk, _) in clerks.iter() {
+                clerk.accept_committee_seats().unwrap();
+            }
+
+            let threads: Vec<_> = (0..8)
+                .map(|value| {
+                    let signed = participant.sign(Participation {
+                        id: ParticipationId::random(),
+                        participant: participant.agent.id,
+                        aggregation: agg.id,
+                        recipient_encryption: None,
+                        clerk_encryptions: clerks.iter()
+                            .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![value]))))
+                            .collect(),
+                        commitments: vec![],
+                        input_proof: None,
+                    }).unwrap();
+                    let server = ctx.server.clone();
+                    ::std::thread::spawn(move || server.0.create_participation(&signed).is_ok())
+                })
+                .collect();
+            let accepted = threads.into_iter().map(|thread| thread.join().unwrap()).filter(|&ok| ok).count();
+            let status = ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+            (accepted, status.number_of_participations)
+        };
+
+        // a single one gets through when rejecting later participations
+        assert_eq!((1, 1), upload_concurrently(ParticipationPolicy::Reject));
+
+        // all get through when keeping the latest, yet exactly one of them remains
+        assert_eq!((8, 1), upload_concurrently(ParticipationPolicy::KeepLatest));
+    });
+}
+
+#[test]
+pub fn aggregation_lifecycle() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        let agg = small_aggregation(&alice.agent.id, &agents[0].1);
+        ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+        let state = || ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap().state;
+        assert_eq!(AggregationState::Draft, state());
+
+        let participation = || Participation {
+            id: ParticipationId::random(),
+            participant: participant.agent.id,
+            aggregation: agg.id,
+            recipient_encryption: None,
+            clerk_encryptions: clerks.iter()
+                .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                .collect(),
+            commitments: vec![],
+            input_proof: None,
+        };
+        let snapshot = || Snapshot {
+            id: SnapshotId::random(),
+            aggregation: agg.id,
+            incremental: false,
+        };
+
+        // nothing happens before the committee is set
+        let early = participant.sign(participation()).unwrap();
+        match ctx.service.create_participation(&participant.agent, &early) {
+            Err(SdaError(SdaErrorKind::Invalid(ref msg), _)) => {
+                assert_eq!("Cannot accept participations while the aggregation is draft", msg)
+            }
+            e => panic!("unexpected result: {:?}", e),
+        }
+        assert!(ctx.service.create_snapshot(&alice.agent, &snapshot()).is_err());
+        assert!(ctx.service.close_aggregation(&alice.agent, &agg.id).is_err());
+
+        let committee = Committee {
+            aggregation: agg.id,
+            clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+        };
+        ctx.service.create_committee(&alice.agent, &alice.sign(committee.clone()).unwrap()).unwrap();
+        assert_eq!(AggregationState::Draft, state());
+        for &(ref clerk, _) in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+        assert_eq!(AggregationState::Open, state());
+        assert!(ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).is_err());
+        participant.upload_participation(&participation()).unwrap();
+
+        // only the recipient may close an aggregation, and only once
+        assert!(ctx.service.close_aggregation(&participant.agent, &agg.id).is_err());
+        ctx.service.close_aggregation(&alice.agent, &agg.id).unwrap();
+        assert_eq!(AggregationState::Closed, state());
+        assert!(ctx.service.close_aggregation(&alice.agent, &agg.id).is_err());
+        assert!(participant.upload_participation(&participation()).is_err());
+
+        // participations received before closing can still be snapshotted
+        ctx.service.create_snapshot(&alice.agent, &snapshot()).unwrap();
+        let status = ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+        assert_eq!(AggregationState::Closed, status.state);
+        assert_eq!(1, status.number_of_participations);
+
+        assert!(ctx.service.archive_aggregation(&participant.agent, &agg.id).is_err());
+        ctx.service.archive_aggregation(&alice.agent, &agg.id).unwrap();
+        assert_eq!(AggregationState::Archived, state());
+        assert!(ctx.service.archive_aggregation(&alice.agent, &agg.id).is_err());
+        assert!(ctx.service.create_snapshot(&alice.agent, &snapshot()).is_err());
+
+        // aggregations stored before states were recorded only open once all seats are accepted
+        let legacy = Aggregation {
+            id: AggregationId::random(),
+            ..small_aggregation(&alice.agent.id, &agents[0].1)
+        };
+        let store = &ctx.server.0.aggregation_store;
+        store.create_aggregation(&alice.sign(legacy.clone()).unwrap()).unwrap();
+        store.create_committee(&alice.sign(Committee {
+            aggregation: legacy.id,
+            clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+        }).unwrap()).unwrap();
+        let legacy_state = || ctx.service.get_aggregation_status(&alice.agent, &legacy.id).unwrap().unwrap().state;
+        assert_eq!(AggregationState::Draft, legacy_state());
+        clerks[0].0.accept_committee_seats().unwrap();
+        assert_eq!(AggregationState::Draft, legacy_state());
+        for &(ref clerk, _) in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+        assert_eq!(AggregationState::Open, legacy_state());
+    });
+}
+
+#[test]
+pub fn scheduled_snapshots() {
+    use sda_server::scheduler::{now, Scheduler};
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participants_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participants: Vec<SdaClient> = (0..3)
+            .map(|_| new_client(&participants_store, &ctx.service))
+            .collect();
+        for p in participants.iter() {
+            p.upload_agent().unwrap();
+        }
+
+        let start = now().unwrap();
+        let scheduled = |schedule: AggregationSchedule| {
+            let agg = Aggregation {
+                id: AggregationId::random(),
+                schedule: Some(schedule),
+                ..small_aggregation(&alice.agent.id, &agents[0].1)
+            };
+            ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+            let committee = Committee {
+                aggregation: agg.id,
+                clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+            };
+            ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+            for &(ref clerk, _) in clerks.iter() {
+                clerk.accept_committee_seats().unwrap();
+            }
+            agg
+        };
+        let participation = |participant: &SdaClient, agg: &Aggregation| Participation {
+            id: ParticipationId::random(),
+            participant: participant.agent.id,
+            aggregation: agg.id,
+            recipient_encryption: None,
+            clerk_encryptions: clerks.iter()
+                .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                .collect(),
+            commitments: vec![],
+            input_proof: None,
+        };
+        let status = |agg: &Aggregation| ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+
+        // participations are only accepted within the window
+        let not_yet = scheduled(AggregationSchedule {
+            opens_at: Some(start + 3600),
+            ..AggregationSchedule::default()
+        });
+        assert!(participants[0].upload_participation(&participation(&participants[0], &not_yet)).is_err());
+        let too_late = scheduled(AggregationSchedule {
+            opens_at: Some(start - 3600),
+            closes_at: Some(start - 60),
+            ..AggregationSchedule::default()
+        });
+        assert!(participants[0].upload_participation(&participation(&participants[0], &too_late)).is_err());
+
+        let agg = scheduled(AggregationSchedule {
+            opens_at: Some(start - 10),
+            closes_at: Some(start + 3600),
+            min_participations: 2,
+            snapshot_interval: Some(60),
+            incremental_snapshots: false,
+        });
+        participants[0].upload_participation(&participation(&participants[0], &agg)).unwrap();
+
+        // snapshots wait for the interval and the minimum number of participations
+        let mut scheduler = Scheduler::new();
+        scheduler.tick(&ctx.server.0, start).unwrap();
+        assert_eq!(0, status(&agg).snapshots.len());
+        scheduler.tick(&ctx.server.0, start + 60).unwrap();
+        assert_eq!(0, status(&agg).snapshots.len());
+        participants[1].upload_participation(&participation(&participants[1], &agg)).unwrap();
+        scheduler.tick(&ctx.server.0, start + 60).unwrap();
+        assert_eq!(1, status(&agg).snapshots.len());
+        assert_eq!(AggregationState::Open, status(&agg).state);
+        scheduler.tick(&ctx.server.0, start + 90).unwrap();
+        assert_eq!(1, status(&agg).snapshots.len());
+
+        // periodic snapshots leave the aggregation open, while the last one closes it
+        participants[2].upload_participation(&participation(&participants[2], &agg)).unwrap();
+        scheduler.tick(&ctx.server.0, start + 3600).unwrap();
+        let closed = status(&agg);
+        assert_eq!(2, closed.snapshots.len());
+        assert_eq!(3, closed.number_of_participations);
+        assert_eq!(AggregationState::Closed, closed.state);
+        scheduler.tick(&ctx.server.0, start + 7200).unwrap();
+        assert_eq!(2, status(&agg).snapshots.len());
+        assert!(ctx.service.get_aggregation_status(&alice.agent, &not_yet.id).unwrap().unwrap().snapshots.is_empty());
+    });
+}
+
+#[test]
+pub fn privacy_budgets() {
+    use sda_server::scheduler::{now, Scheduler};
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participants_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participants: Vec<SdaClient> = (0..3)
+            .map(|_| new_client(&participants_store, &ctx.service))
+            .collect();
+        for p in participants.iter() {
+            p.upload_agent().unwrap();
+        }
+
+        // the budget allows two releases
+        let start = now().unwrap();
+        let agg = Aggregation {
+            id: AggregationId::random(),
+            differential_privacy: Some(DifferentialPrivacy {
+                epsilon: 1.0,
+                delta: 0.0,
+                sensitivity: 1.0,
+                mechanism: NoiseMechanism::DiscreteLaplace,
+                budget: Some((2.0, 0.0)),
+            }),
+            schedule: Some(AggregationSchedule {
+                opens_at: Some(start - 10),
+                closes_at: Some(start + 3600),
+                min_participations: 1,
+                snapshot_interval: Some(60),
+                incremental_snapshots: true,
+            }),
+            ..small_aggregation(&alice.agent.id, &agents[0].1)
+        };
+        ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+        let committee = Committee {
+            aggregation: agg.id,
+            clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+        };
+        ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+        for &(ref clerk, _) in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+
+        let participate = |participant: &SdaClient| {
+            participant.upload_participation(&Participation {
+                id: ParticipationId::random(),
+                participant: participant.agent.id,
+                aggregation: agg.id,
+                recipient_encryption: None,
+                clerk_encryptions: clerks.iter()
+                    .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                    .collect(),
+                commitments: vec![],
+                input_proof: None,
+            }).unwrap();
+        };
+        let snapshot = || Snapshot {
+            id: SnapshotId::random(),
+            aggregation: agg.id,
+            incremental: true,
+        };
+        let status = || ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+        assert_eq!(Some(PrivacyBudget::default()), status().privacy_budget);
+
+        // snapshots taken by the recipient and by the service both spend the budget
+        participate(&participants[0]);
+        ctx.service.create_snapshot(&alice.agent, &snapshot()).unwrap();
+        participate(&participants[1]);
+        let mut scheduler = Scheduler::new();
+        assert_eq!(1, scheduler.tick(&ctx.server.0, start + 60).unwrap());
+        assert_eq!(Some(PrivacyBudget {
+            releases: 2,
+            epsilon_spent: 2.0,
+            delta_spent: 0.0,
+        }), status().privacy_budget);
+
+        // once spent, further snapshots are refused and the aggregation closes without one
+        participate(&participants[2]);
+        match ctx.service.create_snapshot(&alice.agent, &snapshot()) {
+            Err(SdaError(SdaErrorKind::Invalid(ref msg), _)) => {
+                assert_eq!("Snapshot would exceed the privacy budget", msg)
+            }
+            e => panic!("unexpected result: {:?}", e),
         }
+        assert_eq!(0, scheduler.tick(&ctx.server.0, start + 120).unwrap());
+        assert_eq!(0, scheduler.tick(&ctx.server.0, start + 3600).unwrap());
+        let closed = status();
+        assert_eq!(2, closed.snapshots.len());
+        assert_eq!(AggregationState::Closed, closed.state);
+        assert_eq!(2, closed.privacy_budget.unwrap().releases);
     });
 }
 
+#[test]
+pub fn clerking_leases() {
+    use sda_server::{CLERKING_LEASE, MAX_CLERKING_ATTEMPTS};
+    use sda_server::scheduler::now;
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        // any two clerks out of three can reveal
+        let agg = Aggregation {
+            committee_sharing_scheme: LinearSecretSharingScheme::BasicShamir {
+                share_count: 3,
+                privacy_threshold: 1,
+                prime_modulus: 13,
+            },
+            ..small_aggregation(&alice.agent.id, &agents[0].1)
+        };
+        ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+        let committee = Committee {
+            aggregation: agg.id,
+            clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+        };
+        ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+        for &(ref clerk, _) in clerks.iter() {
+            clerk.accept_committee_seats().unwrap();
+        }
+        participant.upload_participation(&Participation {
+            id: ParticipationId::random(),
+            participant: participant.agent.id,
+            aggregation: agg.id,
+            recipient_encryption: None,
+            clerk_encryptions: clerks.iter()
+                .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                .collect(),
+            commitments: vec![],
+            input_proof: None,
+        }).unwrap();
+        ctx.service.create_snapshot(&alice.agent, &Snapshot {
+            id: SnapshotId::random(),
+            aggregation: agg.id,
+            incremental: false,
+        }).unwrap();
+        let start = now().unwrap();
+        let upload = |clerk: &SdaClient, job: &ClerkingJob| {
+            clerk.upload_clerking_result(&ClerkingResult {
+                job: job.id,
+                clerk: clerk.agent.id,
+                aggregation: job.aggregation,
+                snapshot: job.snapshot,
+                encryption: Encryption::Sodium(Binary(vec![1])),
+                commitments: vec![],
+                recovery_encryptions: vec![],
+                rejected_participations: vec![],
+            }).unwrap()
+        };
+
+        // a leased job is not handed out again until its lease expires
+        let first = &clerks[0].0;
+        let job = ctx.server.0.lease_clerking_job(&first.agent.id, start).unwrap().unwrap();
+        assert!(ctx.server.0.lease_clerking_job(&first.agent.id, start + 1).unwrap().is_none());
+        let again = ctx.server.0.lease_clerking_job(&first.agent.id, start + CLERKING_LEASE).unwrap().unwrap();
+        assert_eq!(job.id, again.id);
+        upload(first, &again);
+        assert!(ctx.server.0.lease_clerking_job(&first.agent.id, start + 2 * CLERKING_LEASE).unwrap().is_none());
+
+        // silent clerks are given up on, as long as enough clerks remain to reveal
+        assert_eq!(0, ctx.server.0.note_unresponsive_clerks(start).unwrap());
+        let late = start + MAX_CLERKING_ATTEMPTS as u64 * CLERKING_LEASE;
+        assert_eq!(1, ctx.server.0.note_unresponsive_clerks(late).unwrap());
+        assert_eq!(0, ctx.server.0.note_unresponsive_clerks(late).unwrap());
+        let status = ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+        assert_eq!(1, status.snapshots[0].unresponsive_clerks.len());
+        assert!(!status.snapshots[0].result_ready);
+
+        // the remaining clerk still gets its job, the unresponsive one no longer does
+        let unresponsive = status.snapshots[0].unresponsive_clerks[0];
+        let remaining = &clerks.iter()
+            .find(|&&(ref clerk, _)| clerk.agent.id != first.agent.id && clerk.agent.id != unresponsive)
+            .unwrap()
+            .0;
+        assert!(ctx.server.0.lease_clerking_job(&unresponsive, late).unwrap().is_none());
+        let job = ctx.server.0.lease_clerking_job(&remaining.agent.id, late).unwrap().unwrap();
+        upload(remaining, &job);
+        let status = ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+        assert!(status.snapshots[0].result_ready);
+        assert_eq!(AggregationState::Revealed, status.state);
+    });
+}
+
+#[test]
+pub fn committee_suggestions() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks: Vec<&SdaClient> = agents[1..].iter().map(|&(ref clerk, _)| clerk).collect();
+        let paillier = new_client(&agents_store, &ctx.service);
+        paillier.upload_agent().unwrap();
+        let paillier_key = paillier.new_encryption_key_for_scheme(&AdditiveEncryptionScheme::PackedPaillier {
+            component_count: 2,
+            component_bitsize: 32,
+            max_value_bitsize: 16,
+            min_modulus_bitsize: 512,
+        }).unwrap();
+        paillier.upload_encryption_key(&paillier_key).unwrap();
+        register_clerk(&paillier);
+
+        let agg = small_aggregation(&alice.agent.id, &agents[0].1);
+        ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+        let suggest = |criteria: CommitteeCriteria| {
+            let mut ids: Vec<AgentId> = ctx.service.suggest_committee(&alice.agent, &agg.id, &criteria)
+                .unwrap()
+                .iter()
+                .map(|candidate| candidate.id)
+                .collect();
+            ids.sort_by_key(|id| id.to_string());
+            ids
+        };
+        let sorted = |mut ids: Vec<AgentId>| {
+            ids.sort_by_key(|id| id.to_string());
+            ids
+        };
+
+        // by default, every registered clerk with a key is a candidate
+        assert_eq!(5, suggest(CommitteeCriteria::default()).len());
+        let suitable = CommitteeCriteria {
+            suitable_keys: true,
+            exclude_recipient: true,
+            ..CommitteeCriteria::default()
+        };
+        assert_eq!(sorted(clerks.iter().map(|clerk| clerk.agent.id).collect()), suggest(suitable.clone()));
+
+        // clerks are seen when polling for jobs or pinging
+        assert!(ctx.service.get_clerking_job(&clerks[0].agent, &clerks[0].agent.id).unwrap().is_none());
+        ctx.service.clerk_ping(&clerks[1].agent).unwrap();
+        let live = CommitteeCriteria {
+            max_staleness: Some(60),
+            ..suitable.clone()
+        };
+        let expected = sorted(vec![clerks[0].agent.id, clerks[1].agent.id]);
+        assert_eq!(expected, suggest(live.clone()));
+        let candidates = ctx.service.suggest_committee(&alice.agent, &agg.id, &live).unwrap();
+        assert!(candidates.iter().all(|candidate| candidate.last_seen.is_some()));
+
+        for _ in 0..10 {
+            let picked = suggest(CommitteeCriteria {
+                count: Some(1),
+                randomize: true,
+                ..live.clone()
+            });
+            assert_eq!(1, picked.len());
+            assert!(expected.contains(&picked[0]));
+        }
+    });
+}
+
+#[test]
+pub fn clerk_registrations() {
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let bob = &agents[1].0;
+        let dave = new_client(&agents_store, &ctx.service);
+        dave.upload_agent().unwrap();
+        let dave_key = dave.new_encryption_key().unwrap();
+        dave.upload_encryption_key(&dave_key).unwrap();
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        let aggregation = |recipient: &(SdaClient, EncryptionKeyId)| {
+            let agg = Aggregation {
+                id: AggregationId::random(),
+                ..small_aggregation(&recipient.0.agent.id, &recipient.1)
+            };
+            ctx.service.create_aggregation(&recipient.0.agent, &recipient.0.sign(agg.clone()).unwrap()).unwrap();
+            agg
+        };
+        let suggested = |recipient: &SdaClient, agg: &Aggregation| -> Vec<AgentId> {
+            ctx.service.suggest_committee(&recipient.agent, &agg.id, &CommitteeCriteria::default())
+                .unwrap()
+                .iter()
+                .map(|candidate| candidate.id)
+                .collect()
+        };
+        let committee = |agg: &Aggregation, clerks_and_keys: Vec<(AgentId, EncryptionKeyId)>| Committee {
+            aggregation: agg.id,
+            clerks_and_keys: clerks_and_keys,
+        };
+
+        // clerks must opt in before being suggested or seated
+        let agg = aggregation(&agents[0]);
+        assert!(!suggested(alice, &agg).contains(&dave.agent.id));
+        let clerks_and_keys = vec![(agents[1].0.agent.id, agents[1].1),
+                                   (agents[2].0.agent.id, agents[2].1),
+                                   (dave.agent.id, dave_key)];
+        let seated = committee(&agg, clerks_and_keys.clone());
+        assert!(ctx.service.create_committee(&alice.agent, &alice.sign(seated.clone()).unwrap()).is_err());
+
+        // registrations must be signed by the clerk itself
+        let registration = ClerkRegistration {
+            clerk: dave.agent.id,
+            capacity: Some(1),
+            supported_schemes: vec![AdditiveEncryptionScheme::Sodium],
+            allowed_recipients: vec![alice.agent.id],
+        };
+        assert!(ctx.service.create_clerk_registration(&alice.agent, &alice.sign(registration.clone()).unwrap()).is_err());
+        dave.register_as_clerk(&registration).unwrap();
+        let registration_again = ctx.service.get_clerk_registration(&alice.agent, &dave.agent.id).unwrap();
+        assert_eq!(Some(&registration), registration_again.as_ref().map(|signed| &signed.body));
+        assert!(suggested(alice, &agg).contains(&dave.agent.id));
+        ctx.service.create_committee(&alice.agent, &alice.sign(seated).unwrap()).unwrap();
+
+        // participations are refused until every clerk accepted its seat
+        let participation = || Participation {
+            id: ParticipationId::random(),
+            participant: participant.agent.id,
+            aggregation: agg.id,
+            recipient_encryption: None,
+            clerk_encryptions: clerks_and_keys.iter()
+                .map(|&(clerk, _)| (clerk, Encryption::Sodium(Binary(vec![1]))))
+                .collect(),
+            commitments: vec![],
+            input_proof: None,
+        };
+        let status = || ctx.service.get_aggregation_status(&alice.agent, &agg.id).unwrap().unwrap();
+        assert_eq!(AggregationState::Draft, status().state);
+        assert_eq!(3, status().pending_clerks.len());
+        assert_eq!(vec![agg.id], ctx.service.list_pending_seats(&dave.agent, &dave.agent.id).unwrap());
+        assert_eq!(1, agents[1].0.accept_committee_seats().unwrap());
+        assert_eq!(1, agents[2].0.accept_committee_seats().unwrap());
+        assert_eq!(vec![dave.agent.id], status().pending_clerks);
+        assert!(participant.upload_participation(&participation()).is_err());
+        assert_eq!(1, dave.accept_committee_seats().unwrap());
+        assert!(status().pending_clerks.is_empty());
+        assert_eq!(AggregationState::Open, status().state);
+        participant.upload_participation(&participation()).unwrap();
+
+        // capacity is honoured until the aggregation is revealed
+        let other_agg = aggregation(&agents[0]);
+        assert!(!suggested(alice, &other_agg).contains(&dave.agent.id));
+        let other_seated = committee(&other_agg, clerks_and_keys.clone());
+        assert!(ctx.service.create_committee(&alice.agent, &alice.sign(other_seated).unwrap()).is_err());
+
+        // as are allowed recipients and schemes
+        let bobs_agg = aggregation(&agents[1]);
+        assert!(!suggested(bob, &bobs_agg).contains(&dave.agent.id));
+        let paillier_agg = Aggregation {
+            id: AggregationId::random(),
+            committee_encryption_scheme: AdditiveEncryptionScheme::PackedPaillier {
+                component_count: 2,
+                component_bitsize: 32,
+                max_value_bitsize: 16,
+                min_modulus_bitsize: 512,
+            },
+            ..small_aggregation(&alice.agent.id, &agents[0].1)
+        };
+        ctx.service.create_aggregation(&alice.agent, &alice.sign(paillier_agg.clone()).unwrap()).unwrap();
+        assert!(!suggested(alice, &paillier_agg).contains(&dave.agent.id));
+
+        // only members of the committee may accept seats
+        let bobs_committee = committee(&bobs_agg, vec![(agents[0].0.agent.id, agents[0].1),
+                                                       (agents[2].0.agent.id, agents[2].1),
+                                                       (agents[3].0.agent.id, agents[3].1)]);
+        ctx.service.create_committee(&bob.agent, &bob.sign(bobs_committee).unwrap()).unwrap();
+        let intruder = SeatAcceptance {
+            aggregation: bobs_agg.id,
+            clerk: bob.agent.id,
+            key: agents[1].1,
+        };
+        assert!(ctx.service.create_seat_acceptance(&bob.agent, &bob.sign(intruder).unwrap()).is_err());
+        assert_eq!(3, ctx.service.get_aggregation_status(&bob.agent, &bobs_agg.id).unwrap().unwrap().pending_clerks.len());
+
+        // clerks themselves leave pending any seat their registration does not admit, or not with one
+        // of their keys, even when seated behind the service's back
+        let others = vec![(agents[2].0.agent.id, agents[2].1), (agents[3].0.agent.id, agents[3].1)];
+        let unwanted_agg = aggregation(&agents[1]);
+        let mut unwanted = committee(&unwanted_agg, others.clone());
+        unwanted.clerks_and_keys.push((dave.agent.id, dave_key));
+        ctx.server.0.aggregation_store.create_committee(&bob.sign(unwanted).unwrap()).unwrap();
+        let foreign_key_agg = aggregation(&agents[0]);
+        let mut foreign_key = committee(&foreign_key_agg, others);
+        foreign_key.clerks_and_keys.push((dave.agent.id, EncryptionKeyId::random()));
+        ctx.server.0.aggregation_store.create_committee(&alice.sign(foreign_key).unwrap()).unwrap();
+        assert_eq!(0, dave.accept_committee_seats().unwrap());
+        assert_eq!(2, ctx.service.list_pending_seats(&dave.agent, &dave.agent.id).unwrap().len());
+    });
+}
+
+#[test]
+pub fn deleted_aggregations() {
+    use sda_server::scheduler::now;
+    with_service(|ctx| {
+        let agents_store = ::tempdir::TempDir::new("sda-tests-agents-keystores").unwrap();
+        let agents: Vec<(SdaClient, EncryptionKeyId)> =
+            (0..4).map(|_| new_full_client(&agents_store, &ctx.service)).collect();
+        let alice = &agents[0].0;
+        let clerks = &agents[1..];
+        let participant_store = ::tempdir::TempDir::new("sda-tests-clients-keystores").unwrap();
+        let participant = new_client(&participant_store, &ctx.service);
+        participant.upload_agent().unwrap();
+
+        let snapshotted = || {
+            let agg = Aggregation {
+                id: AggregationId::random(),
+                ..small_aggregation(&alice.agent.id, &agents[0].1)
+            };
+            ctx.service.create_aggregation(&alice.agent, &alice.sign(agg.clone()).unwrap()).unwrap();
+            let committee = Committee {
+                aggregation: agg.id,
+                clerks_and_keys: clerks.iter().map(|&(ref clerk, key)| (clerk.agent.id, key)).collect(),
+            };
+            ctx.service.create_committee(&alice.agent, &alice.sign(committee).unwrap()).unwrap();
+            for &(ref clerk, _) in clerks.iter() {
+                clerk.accept_committee_seats().unwrap();
+            }
+            participant.upload_participation(&Participation {
+                id: ParticipationId::random(),
+                participant: participant.agent.id,
+                aggregation: agg.id,
+                recipient_encryption: None,
+                clerk_encryptions: clerks.iter()
+                    .map(|&(ref clerk, _)| (clerk.agent.id, Encryption::Sodium(Binary(vec![1]))))
+                    .collect(),
+                commitments: vec![],
+                input_proof: None,
+            }).unwrap();
+            let snapshot = Snapshot {
+                id: SnapshotId::random(),
+                aggregation: agg.id,
+                incremental: false,
+            };
+            ctx.service.create_snapshot(&alice.agent, &snapshot).unwrap();
+            (agg, snapshot)
+        };
+        let (agg, snapshot) = snapshotted();
+
+        // one job done, one leased, one still queued
+        let start = now().unwrap();
+        let first = &clerks[0].0;
+        let done = ctx.server.0.lease_clerking_job(&first.agent.id, start).unwrap().unwrap();
+        first.upload_clerking_result(&ClerkingResult {
+            job: done.id,
+            clerk: first.agent.id,
+            aggregation: done.aggregation,
+            snapshot: done.snapshot,
+            encryption: Encryption::Sodium(Binary(vec![1])),
+            commitments: vec![],
+            recovery_encryptions: vec![],
+            rejected_participations: vec![],
+        }).unwrap();
+        let leased = ctx.server.0.lease_clerking_job(&clerks[1].0.agent.id, start).unwrap().unwrap();
+        let (kept, kept_snapshot) = snapshotted();
+
+        // only the recipient may delete an aggregation
+        assert!(ctx.service.delete_aggregation(&participant.agent, &agg.id).is_err());
+        ctx.service.delete_aggregation(&alice.agent, &agg.id).unwrap();
+        assert!(ctx.service.get_aggregation(&alice.agent, &agg.id).unwrap().is_none());
+
+        // nothing is left behind in the stores
+        let aggregations = &ctx.server.0.aggregation_store;
+        assert!(aggregations.get_aggregation(&agg.id).unwrap().is_none());
+        assert!(aggregations.get_aggregation_state(&agg.id).unwrap().is_none());
+        assert!(aggregations.get_committee(&agg.id).unwrap().is_none());
+        assert!(aggregations.list_seat_acceptances(&agg.id).unwrap().is_empty());
+        assert!(aggregations.list_enrollments(&agg.id).unwrap().is_empty());
+        assert!(aggregations.get_masking_cohort(&agg.id).unwrap().is_none());
+        assert_eq!(0, aggregations.count_participations(&agg.id).unwrap());
+        assert!(aggregations.list_snapshots(&agg.id).unwrap().is_empty());
+        assert!(aggregations.get_snapshot(&agg.id, &snapshot.id).unwrap().is_none());
+        assert!(aggregations.get_snapshot_mask(&snapshot.id).unwrap().is_none());
+        assert!(aggregations.list_reveal_contributions(&snapshot.id).unwrap().is_empty());
+        let jobs = &ctx.server.0.clerking_job_store;
+        assert!(jobs.list_results(&snapshot.id).unwrap().is_empty());
+        assert!(jobs.get_result(&snapshot.id, &done.id).unwrap().is_none());
+        assert!(jobs.get_clerking_job(&first.agent.id, &done.id).unwrap().is_none());
+        assert!(jobs.get_clerking_job(&clerks[1].0.agent.id, &leased.id).unwrap().is_none());
+        assert!(jobs.list_clerking_job_leases().unwrap().iter().all(|lease| lease.snapshot == kept_snapshot.id));
+
+        // while other aggregations are untouched
+        assert!(aggregations.get_committee(&kept.id).unwrap().is_some());
+        assert_eq!(1, aggregations.count_participations(&kept.id).unwrap());
+        assert_eq!(vec![kept_snapshot.id], aggregations.list_snapshots(&kept.id).unwrap());
+        for &(ref clerk, _) in clerks.iter() {
+            let job = ctx.server.0.lease_clerking_job(&clerk.agent.id, start).unwrap().unwrap();
+            assert_eq!(kept_snapshot.id, job.snapshot);
+        }
+    });
+}
@@ -0,0 +1,37 @@
+//! Generators of the Pedersen commitments used by verifiable secret sharing.
+//!
+//! Participants commit with them, clerks and recipients check shares against them, and the service
+//! verifies input proofs with them, so all sides must derive them in exactly the same way.
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use sodiumoxide::crypto::hash::sha512;
+
+/// Label hashed into the group to obtain the generator committing to values.
+pub const COMMITMENT_GENERATOR_LABEL: &'static [u8] = b"sda verifiable shamir generator";
+
+/// Label hashed into the group to obtain the generator committing to blindings.
+pub const COMMITMENT_BLINDING_GENERATOR_LABEL: &'static [u8] = b"sda verifiable shamir blinding generator";
+
+/// Generators of the Pedersen commitments.
+///
+/// Both are obtained by hashing fixed labels into the group, so nobody knows the discrete logarithm
+/// of one relative to the other.
+#[derive(Clone, Copy, Debug)]
+pub struct CommitmentGenerators {
+    pub generator: RistrettoPoint,
+    pub blinding_generator: RistrettoPoint,
+}
+
+impl CommitmentGenerators {
+    pub fn new() -> CommitmentGenerators {
+        CommitmentGenerators {
+            generator: hash_to_group(COMMITMENT_GENERATOR_LABEL),
+            blinding_generator: hash_to_group(COMMITMENT_BLINDING_GENERATOR_LABEL),
+        }
+    }
+}
+
+/// Group element derived from the hash of `label`, with unknown discrete logarithm.
+pub fn hash_to_group(label: &[u8]) -> RistrettoPoint {
+    RistrettoPoint::from_uniform_bytes(&sha512::hash(label).0)
+}
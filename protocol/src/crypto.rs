@@ -1,20 +1,34 @@
 
 //! Parameters for the cryptographic primitives supported by the system.
 
+use byte_arrays::B32;
 use helpers::Binary;
+use errors::*;
 
 /// Encryption (or ciphertext).
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Encryption {
     /// Standard sodium encryption (Curve25519, XSalsa20, Poly1305).
-    Sodium(Binary)
+    Sodium(Binary),
+    /// Packed Paillier encryption, with each ciphertext holding up to `component_count` values.
+    PackedPaillier {
+        /// Number of values encrypted; the last ciphertext may be padded with zeros.
+        value_count: usize,
+        /// Ciphertexts, as big-endian integers modulo the square of the key modulus.
+        ciphertexts: Vec<Binary>,
+    },
+    /// Encryption for a recipient group: the plaintext is shared among the members using the sharing
+    /// scheme of the group, with each share encrypted under the key of the corresponding member.
+    Shared(Vec<Encryption>),
 }
 
 /// Encryption key (aka public key).
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum EncryptionKey {
     /// Standard sodium encryption key (Curve25519, XSalsa20, Poly1305).
-    Sodium(::byte_arrays::B32)
+    Sodium(::byte_arrays::B32),
+    /// Paillier encryption key, given by its modulus as a big-endian integer.
+    PackedPaillier(Binary),
 }
 
 /// Signature.
@@ -59,19 +73,97 @@ pub enum LinearMaskingScheme {
         modulus: i64,
         dimension: usize,
         seed_bitsize: usize,
-    }
+    },
+
+    /// Secure masking, using masks agreed pairwise between participants that cancel out in the sum.
+    ///
+    /// Participants first enroll with a key exchange key, secret-sharing the corresponding secret key
+    /// with the committee. Once the recipient has fixed the masking cohort, each participant masks its
+    /// input using a mask expanded (`rand::chacha::ChaChaRng`) from the secret it shares with every other
+    /// member, added or subtracted according to the order of their keys.
+    ///
+    /// Compared to `LinearMaskingScheme::Full` and `LinearMaskingScheme::ChaCha` the recipient receives no
+    /// mask data for participants; only the masks of members dropping out before the snapshot must be
+    /// removed, which is done in a recovery round where the clerks reveal their secret keys to the recipient.
+    Pairwise {
+        modulus: i64,
+        dimension: usize,
+    },
 
 }
 
 impl LinearMaskingScheme {
-    /// Derived property indicating whether or not this scheme results in masks being generated or not.
+    /// Derived property indicating whether or not this scheme results in masks being sent to the recipient.
     pub fn has_mask(&self) -> bool {
         match *self {
             LinearMaskingScheme::None => false,
             LinearMaskingScheme::Full {..} => true,
             LinearMaskingScheme::ChaCha {..} => true,
+            LinearMaskingScheme::Pairwise {..} => false,
+        }
+    }
+
+    /// Derived property indicating whether or not participants must enroll in a masking cohort before participating.
+    pub fn is_pairwise(&self) -> bool {
+        match *self {
+            LinearMaskingScheme::Pairwise {..} => true,
+            _ => false,
+        }
+    }
+
+    /// Derived property indicating whether or not the scheme may mask values of a wide numeric representation,
+    /// in which case masks are generated modulo the modulus of the representation.
+    pub fn supports_wide_values(&self) -> bool {
+        match *self {
+            LinearMaskingScheme::None => true,
+            LinearMaskingScheme::Full {..} => true,
+            LinearMaskingScheme::ChaCha {..} => false,
+            LinearMaskingScheme::Pairwise {..} => false,
+        }
+    }
+
+    /// Modulus in which masks are generated, if any.
+    pub fn modulus(&self) -> Option<i64> {
+        match *self {
+            LinearMaskingScheme::None => None,
+            LinearMaskingScheme::Full { modulus } => Some(modulus),
+            LinearMaskingScheme::ChaCha { modulus, .. } => Some(modulus),
+            LinearMaskingScheme::Pairwise { modulus, .. } => Some(modulus),
         }
     }
+
+    /// Check that the parameters of the scheme are consistent with each other.
+    pub fn validate(&self) -> SdaResult<()> {
+        match *self {
+            LinearMaskingScheme::None => {}
+            LinearMaskingScheme::Full { modulus } => {
+                if modulus < 2 {
+                    Err(SdaErrorKind::Invalid(format!("Masking modulus must be at least 2, found {}", modulus)))?
+                }
+            }
+            LinearMaskingScheme::ChaCha { modulus, dimension, seed_bitsize } => {
+                if modulus < 2 {
+                    Err(SdaErrorKind::Invalid(format!("Masking modulus must be at least 2, found {}", modulus)))?
+                }
+                if dimension == 0 {
+                    Err(SdaErrorKind::Invalid("ChaCha masking needs a positive dimension".to_string()))?
+                }
+                // seeds are used directly as ChaCha keys
+                if seed_bitsize == 0 || seed_bitsize > 256 {
+                    Err(SdaErrorKind::Invalid(format!("ChaCha seed size must be between 1 and 256 bits, found {}", seed_bitsize)))?
+                }
+            }
+            LinearMaskingScheme::Pairwise { modulus, dimension } => {
+                if modulus < 2 {
+                    Err(SdaErrorKind::Invalid(format!("Masking modulus must be at least 2, found {}", modulus)))?
+                }
+                if dimension == 0 {
+                    Err(SdaErrorKind::Invalid("Pairwise masking needs a positive dimension".to_string()))?
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Supported secret sharing schemes and their parameters.
@@ -86,14 +178,16 @@ pub enum LinearSecretSharingScheme {
         modulus: i64,
     },
 
-    // BasicShamir {
-    //     /// Number of shares to generate for each secret.
-    //     share_count: usize,
-    //     /// Number of shares needed to reconstruct.
-    //     privacy_threshold: usize,
-    //     /// Prime number specifying the prime field in which to operate.
-    //     prime_modulus: i64,
-    // },
+    /// Basic Shamir scheme allowing for a committee to lose clerks and still be able to reconstruct.
+    BasicShamir {
+        /// Number of shares to generate for each secret.
+        share_count: usize,
+        /// Upper bound for the number of shares that may be seen without leaking the secret;
+        /// reconstruction needs one more.
+        privacy_threshold: usize,
+        /// Prime number specifying the prime field in which to operate.
+        prime_modulus: i64,
+    },
 
     /// Packed Shamir scheme allowing for work to be distributed across many clerks.
     PackedShamir {
@@ -105,23 +199,158 @@ pub enum LinearSecretSharingScheme {
         privacy_threshold: usize,
         /// Prime number specifying the prime field in which to operate.
         prime_modulus: i64,
-        /// TODO
+        /// Principal root of unity of order `secret_count + privacy_threshold + 1`, which must be a power of 2.
+        ///
+        /// Used for embedding the secrets in the sharing polynomial.
         omega_secrets: i64,
-        /// TODO
+        /// Principal root of unity of order `share_count + 1`, which must be a power of 3.
+        ///
+        /// Used for evaluating the sharing polynomial into shares.
         omega_shares: i64,
     },
 
+    /// Shamir scheme where participants commit to their sharing polynomials, allowing the recipient
+    /// to detect clerks returning incorrect shares.
+    ///
+    /// Sharing happens in the scalar field of the Ristretto group, of prime order close to 2^252, with
+    /// Pedersen commitments in that group. Both generators are derived by hashing fixed labels into the
+    /// group, so that nobody knows their discrete logarithms relative to each other. Each scalar takes
+    /// four `i64` limbs in share vectors, and reconstructed sums are reduced modulo `modulus`.
+    VerifiableShamir {
+        /// Number of shares to generate for each secret.
+        share_count: usize,
+        /// Upper bound for the number of shares that may be seen without leaking the secret;
+        /// reconstruction needs one more.
+        privacy_threshold: usize,
+        /// Modulus of the reconstructed outputs.
+        modulus: i64,
+    },
+
 }
 
+/// Commitment to a coefficient of a sharing polynomial, as a compressed Ristretto point.
+pub type Commitment = B32;
+
+/// Scalar of the Ristretto group, in its canonical little-endian encoding.
+pub type CommitmentScalar = B32;
+
 /// Derived properties of the secret sharing schemes.
 impl LinearSecretSharingScheme {
 
+    /// Packed Shamir scheme with automatically generated field and roots of unity.
+    ///
+    /// Searches for the smallest prime of at least `min_modulus_bitsize` bits for which the
+    /// multiplicative group contains roots of unity of the orders required by the scheme,
+    /// i.e. `secret_count + privacy_threshold + 1` (a power of 2) and `share_count + 1` (a power of 3).
+    ///
+    /// To keep arithmetic on shares within `i64`, primes are limited to 31 bits.
+    pub fn new_packed_shamir(secret_count: usize, share_count: usize, privacy_threshold: usize, min_modulus_bitsize: usize) -> SdaResult<LinearSecretSharingScheme> {
+        if min_modulus_bitsize > MAX_PRIME_BITSIZE {
+            Err(SdaErrorKind::Invalid(format!(
+                "Packed Shamir supports primes of at most {} bits", MAX_PRIME_BITSIZE)))?
+        }
+        let min_modulus: i64 = if min_modulus_bitsize == 0 { 2 } else { 1 << (min_modulus_bitsize - 1) };
+        LinearSecretSharingScheme::new_packed_shamir_with_modulus(secret_count, share_count, privacy_threshold, min_modulus)
+    }
+
+    /// Packed Shamir scheme as in `new_packed_shamir`, with a prime no smaller than `min_modulus`.
+    ///
+    /// Useful when shares must hold values modulo a given aggregation modulus, which is
+    /// then not rounded down to a power of two.
+    pub fn new_packed_shamir_with_modulus(secret_count: usize, share_count: usize, privacy_threshold: usize, min_modulus: i64) -> SdaResult<LinearSecretSharingScheme> {
+        if secret_count == 0 {
+            Err(SdaErrorKind::Invalid("Packed Shamir needs at least one secret".to_string()))?
+        }
+        if secret_count + privacy_threshold > share_count {
+            Err(SdaErrorKind::Invalid(format!(
+                "Packed Shamir needs at least {} shares for reconstruction, only {} requested",
+                secret_count + privacy_threshold, share_count)))?
+        }
+        let order_secrets = secret_count + privacy_threshold + 1;
+        if !is_power_of(order_secrets as i64, 2) {
+            Err(SdaErrorKind::Invalid(format!(
+                "Packed Shamir needs secret_count + privacy_threshold + 1 to be a power of 2, found {}",
+                order_secrets)))?
+        }
+        let order_shares = share_count + 1;
+        if !is_power_of(order_shares as i64, 3) {
+            Err(SdaErrorKind::Invalid(format!(
+                "Packed Shamir needs share_count + 1 to be a power of 3, found {}",
+                order_shares)))?
+        }
+        let upper_bound: i64 = 1 << MAX_PRIME_BITSIZE;
+        if min_modulus >= upper_bound {
+            Err(SdaErrorKind::Invalid(format!(
+                "Packed Shamir supports primes of at most {} bits", MAX_PRIME_BITSIZE)))?
+        }
+
+        // both roots exist exactly when their orders divide prime - 1
+        let order = order_secrets as i64 * order_shares as i64;
+        let lower_bound = ::std::cmp::max(min_modulus, 2);
+        let mut prime = ((lower_bound - 1 + order - 1) / order) * order + 1;
+        while !is_prime(prime) {
+            prime += order;
+            if prime >= upper_bound {
+                Err(SdaErrorKind::Invalid("Could not find a suitable prime for packed Shamir".to_string()))?
+            }
+        }
+
+        let omega_secrets = root_of_unity(prime, order_secrets as i64, 2);
+        let omega_shares = root_of_unity(prime, order_shares as i64, 3);
+
+        Ok(LinearSecretSharingScheme::PackedShamir {
+            secret_count: secret_count,
+            share_count: share_count,
+            privacy_threshold: privacy_threshold,
+            prime_modulus: prime,
+            omega_secrets: omega_secrets,
+            omega_shares: omega_shares,
+        })
+    }
+
+    /// Verifiable Shamir scheme with outputs modulo `modulus`, checking the parameters.
+    pub fn new_verifiable_shamir(share_count: usize, privacy_threshold: usize, modulus: i64) -> SdaResult<LinearSecretSharingScheme> {
+        let scheme = LinearSecretSharingScheme::VerifiableShamir {
+            share_count: share_count,
+            privacy_threshold: privacy_threshold,
+            modulus: modulus,
+        };
+        scheme.validate()?;
+        Ok(scheme)
+    }
+
+    /// Whether participants publish commitments that clerks' shares can be checked against.
+    pub fn is_verifiable(&self) -> bool {
+        match *self {
+            LinearSecretSharingScheme::VerifiableShamir { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Derived property indicating whether or not the scheme may share values of a wide numeric representation,
+    /// in which case shares are computed modulo the modulus of the representation.
+    pub fn supports_wide_values(&self) -> bool {
+        match *self {
+            LinearSecretSharingScheme::Additive { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Number of commitments accompanying the sharing of a vector of `dimension` secrets.
+    pub fn commitment_count(&self, dimension: usize) -> usize {
+        match *self {
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, .. } => dimension * (privacy_threshold + 1),
+            _ => 0,
+        }
+    }
+
     /// Number of secrets shared together.
     pub fn input_size(&self) -> usize {
         match *self {
             LinearSecretSharingScheme::Additive {..} => 1,
-            // LinearSecretSharingScheme::BasicShamir {..} => 1,
+            LinearSecretSharingScheme::BasicShamir {..} => 1,
             LinearSecretSharingScheme::PackedShamir { secret_count, .. } => secret_count,
+            LinearSecretSharingScheme::VerifiableShamir {..} => 1,
         }
     }
 
@@ -129,8 +358,9 @@ impl LinearSecretSharingScheme {
     pub fn output_size(&self) -> usize {
         match *self {
             LinearSecretSharingScheme::Additive { share_count, .. } => share_count,
-            // LinearSecretSharingScheme::BasicShamir { share_count, .. } => share_count,
+            LinearSecretSharingScheme::BasicShamir { share_count, .. } => share_count,
             LinearSecretSharingScheme::PackedShamir { share_count, .. } => share_count,
+            LinearSecretSharingScheme::VerifiableShamir { share_count, .. } => share_count,
         }
     }
 
@@ -138,8 +368,9 @@ impl LinearSecretSharingScheme {
     pub fn privacy_threshold(&self) -> usize {
         match *self {
             LinearSecretSharingScheme::Additive { share_count, .. } => share_count - 1,
-            // LinearSecretSharingScheme::BasicShamir { privacy_threshold, .. } => privacy_threshold,
+            LinearSecretSharingScheme::BasicShamir { privacy_threshold, .. } => privacy_threshold,
             LinearSecretSharingScheme::PackedShamir { privacy_threshold, .. } => privacy_threshold,
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, .. } => privacy_threshold,
         }
     }
 
@@ -147,11 +378,163 @@ impl LinearSecretSharingScheme {
     pub fn reconstruction_threshold(&self) -> usize {
         match *self {
             LinearSecretSharingScheme::Additive { share_count, .. } => share_count,
-            // LinearSecretSharingScheme::BasicShamir { privacy_threshold, .. } => privacy_threshold + 1,
+            LinearSecretSharingScheme::BasicShamir { privacy_threshold, .. } => privacy_threshold + 1,
             LinearSecretSharingScheme::PackedShamir { privacy_threshold, secret_count, .. } => privacy_threshold + secret_count,
+            LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, .. } => privacy_threshold + 1,
+        }
+    }
+
+    /// Modulus of the group or field in which shares are computed.
+    pub fn modulus(&self) -> i64 {
+        match *self {
+            LinearSecretSharingScheme::Additive { modulus, .. } => modulus,
+            LinearSecretSharingScheme::BasicShamir { prime_modulus, .. } => prime_modulus,
+            LinearSecretSharingScheme::PackedShamir { prime_modulus, .. } => prime_modulus,
+            LinearSecretSharingScheme::VerifiableShamir { modulus, .. } => modulus,
+        }
+    }
+
+    /// Check that the parameters of the scheme are consistent with each other.
+    ///
+    /// Besides sizes and thresholds this verifies that Shamir moduli are primes small enough
+    /// for the arithmetic used, and that packed Shamir roots of unity have the required orders.
+    pub fn validate(&self) -> SdaResult<()> {
+        if self.output_size() == 0 {
+            Err(SdaErrorKind::Invalid("Sharing scheme must output at least one share".to_string()))?
+        }
+        match *self {
+            LinearSecretSharingScheme::Additive { modulus, .. } => {
+                if modulus < 2 {
+                    Err(SdaErrorKind::Invalid(format!("Sharing modulus must be at least 2, found {}", modulus)))?
+                }
+            }
+            LinearSecretSharingScheme::BasicShamir { share_count, privacy_threshold, prime_modulus } => {
+                check_shamir_prime(prime_modulus)?;
+                if privacy_threshold >= share_count {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Privacy threshold {} must be smaller than the share count {}", privacy_threshold, share_count)))?
+                }
+                // shares are evaluations in the distinct non-zero points 1..share_count
+                if share_count as i64 >= prime_modulus {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Prime modulus {} too small for {} shares", prime_modulus, share_count)))?
+                }
+            }
+            LinearSecretSharingScheme::PackedShamir { secret_count, share_count, privacy_threshold, prime_modulus, omega_secrets, omega_shares } => {
+                check_shamir_prime(prime_modulus)?;
+                if secret_count == 0 {
+                    Err(SdaErrorKind::Invalid("Packed Shamir needs at least one secret".to_string()))?
+                }
+                let order_secrets = (secret_count + privacy_threshold + 1) as i64;
+                let order_shares = (share_count + 1) as i64;
+                if !is_power_of(order_secrets, 2) || !is_power_of(order_shares, 3) {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Packed Shamir needs secret_count + privacy_threshold + 1 = {} to be a power of 2 and share_count + 1 = {} to be a power of 3",
+                        order_secrets, order_shares)))?
+                }
+                if self.reconstruction_threshold() > share_count {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Reconstruction threshold {} exceeds the share count {}", self.reconstruction_threshold(), share_count)))?
+                }
+                if !has_order(omega_secrets, order_secrets, 2, prime_modulus) {
+                    Err(SdaErrorKind::Invalid(format!("omega_secrets must have order {} modulo {}", order_secrets, prime_modulus)))?
+                }
+                if !has_order(omega_shares, order_shares, 3, prime_modulus) {
+                    Err(SdaErrorKind::Invalid(format!("omega_shares must have order {} modulo {}", order_shares, prime_modulus)))?
+                }
+            }
+            LinearSecretSharingScheme::VerifiableShamir { share_count, privacy_threshold, modulus } => {
+                if privacy_threshold >= share_count {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Privacy threshold {} must be smaller than the share count {}", privacy_threshold, share_count)))?
+                }
+                if modulus < 2 {
+                    Err(SdaErrorKind::Invalid(format!("Invalid modulus {}", modulus)))?
+                }
+            }
+        }
+        Ok(())
+    }
+
+}
+
+const MAX_PRIME_BITSIZE: usize = 31;
+
+fn is_power_of(mut value: i64, base: i64) -> bool {
+    if value < 1 {
+        return false;
+    }
+    while value % base == 0 {
+        value /= base;
+    }
+    value == 1
+}
+
+fn is_prime(candidate: i64) -> bool {
+    if candidate < 2 {
+        return false;
+    }
+    let mut divisor = 2;
+    while divisor * divisor <= candidate {
+        if candidate % divisor == 0 {
+            return false;
         }
+        divisor += 1;
     }
+    true
+}
 
+
+/// Multiplication modulo `modulus` of at most 62 bits, avoiding overflow.
+fn mul_mod(a: i64, b: i64, modulus: i64) -> i64 {
+    let mut a = a % modulus;
+    let mut b = b % modulus;
+    let mut result = 0;
+    while b > 0 {
+        if b & 1 == 1 {
+            result = (result + a) % modulus;
+        }
+        a = (a + a) % modulus;
+        b >>= 1;
+    }
+    result
+}
+
+/// Exponentiation modulo `modulus` of at most 62 bits, avoiding overflow.
+fn mod_pow(mut base: i64, mut exponent: i64, modulus: i64) -> i64 {
+    let mut result = 1;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, modulus);
+        }
+        base = mul_mod(base, base, modulus);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn check_shamir_prime(prime_modulus: i64) -> SdaResult<()> {
+    if prime_modulus >= 1 << MAX_PRIME_BITSIZE || !is_prime(prime_modulus) {
+        Err(SdaErrorKind::Invalid(format!(
+            "Shamir modulus must be a prime of at most {} bits, found {}", MAX_PRIME_BITSIZE, prime_modulus)))?
+    }
+    Ok(())
+}
+
+/// Check that `element` has order exactly `order` modulo `prime`, where `order` is a power of the prime `factor`.
+fn has_order(element: i64, order: i64, factor: i64, prime: i64) -> bool {
+    element > 0 && element < prime
+        && mod_pow(element, order, prime) == 1
+        && (order == 1 || mod_pow(element, order / factor, prime) != 1)
+}
+
+/// Find an element of order exactly `order` modulo `prime`, where `order` is a power of the prime `factor` dividing `prime - 1`.
+fn root_of_unity(prime: i64, order: i64, factor: i64) -> i64 {
+    (2..prime)
+        .map(|candidate| mod_pow(candidate, (prime - 1) / order, prime))
+        .find(|&root| mod_pow(root, order / factor, prime) != 1)
+        .expect("prime - 1 not divisible by order")
 }
 
 /// Supported additive encryption schemes and their parameters.
@@ -161,28 +544,248 @@ pub enum AdditiveEncryptionScheme {
     /// Standard sodium encryption (Curve25519, XSalsa20, Poly1305).
     Sodium,
 
-    // PackedPaillier {
-    //     /// Number of components in a plaintext/ciphertext.
-    //     component_count: usize,
-    //     /// Number of bits allocated to each component in a ciphertext.
-    //     component_bitsize: usize,
-    //     /// Maximum number of bits each component value may occupy in a fresh ciphertext, i.e.
-    //     /// in a fresh ciphertext each value must be strictly upper bounded by 2^value_max_bitsize.
-    //     max_value_bitsize: usize,
-    //     /// Minimum size of the (plaintext) modulus in bits.
-    //     min_modulus_bitsize: usize,
-    // }
+    /// Additively homomorphic Paillier encryption, packing several values into each ciphertext.
+    ///
+    /// Ciphertexts may be summed by the server without knowledge of the decryption key,
+    /// at the cost of much larger keys and ciphertexts than `AdditiveEncryptionScheme::Sodium`.
+    PackedPaillier {
+        /// Number of components in a plaintext/ciphertext.
+        component_count: usize,
+        /// Number of bits allocated to each component in a ciphertext.
+        component_bitsize: usize,
+        /// Maximum number of bits each component value may occupy in a fresh ciphertext, i.e.
+        /// in a fresh ciphertext each value must be strictly upper bounded by 2^max_value_bitsize.
+        max_value_bitsize: usize,
+        /// Minimum size of the (plaintext) modulus in bits.
+        min_modulus_bitsize: usize,
+    }
 
 }
 
 /// Derived properties of the additive encryption schemes.
 impl AdditiveEncryptionScheme {
 
+    /// Number of values encrypted together.
     pub fn batch_size(&self) -> usize {
         match self {
             &AdditiveEncryptionScheme::Sodium {..} => 1,
-            // &AdditiveEncryptionScheme::PackedPaillier { component_count, .. } => component_count
+            &AdditiveEncryptionScheme::PackedPaillier { component_count, .. } => component_count
+        }
+    }
+
+    /// Derived property indicating whether or not ciphertexts may be summed without decryption.
+    pub fn is_homomorphic(&self) -> bool {
+        match self {
+            &AdditiveEncryptionScheme::Sodium {..} => false,
+            &AdditiveEncryptionScheme::PackedPaillier {..} => true,
+        }
+    }
+
+    /// Check that the parameters of the scheme are consistent with each other.
+    pub fn validate(&self) -> SdaResult<()> {
+        match *self {
+            AdditiveEncryptionScheme::Sodium => {}
+            AdditiveEncryptionScheme::PackedPaillier { component_count, component_bitsize, max_value_bitsize, .. } => {
+                if component_count == 0 {
+                    Err(SdaErrorKind::Invalid("Paillier packing needs at least one component".to_string()))?
+                }
+                // whether decrypted components fit in a share depends on the numeric representation
+                if component_bitsize == 0 {
+                    Err(SdaErrorKind::Invalid("Paillier component size must be positive".to_string()))?
+                }
+                if max_value_bitsize == 0 || max_value_bitsize > component_bitsize {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Paillier value size {} must be positive and fit in the component size {}", max_value_bitsize, component_bitsize)))?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Derived property indicating whether or not values in `0..modulus` may be encrypted with this scheme.
+    pub fn supports_modulus(&self, modulus: i64) -> bool {
+        match *self {
+            AdditiveEncryptionScheme::Sodium => true,
+            AdditiveEncryptionScheme::PackedPaillier { max_value_bitsize, .. } => {
+                max_value_bitsize >= 63 || modulus <= 1_i64 << max_value_bitsize
+            }
+        }
+    }
+
+    /// Derived property indicating whether or not values of `value_bitsize` bits may be encrypted with this scheme,
+    /// and decrypted into values of a representation holding at most `max_bitsize` bits, if bounded.
+    pub fn supports_value_bitsize(&self, value_bitsize: usize, max_bitsize: Option<usize>) -> bool {
+        match *self {
+            AdditiveEncryptionScheme::Sodium => true,
+            AdditiveEncryptionScheme::PackedPaillier { component_bitsize, max_value_bitsize, .. } => {
+                value_bitsize <= max_value_bitsize
+                    && max_bitsize.map_or(true, |max_bitsize| component_bitsize <= max_bitsize)
+            }
+        }
+    }
+
+    /// Derived property indicating whether or not the given key may be used with this scheme.
+    pub fn is_suitable_key(&self, key: &EncryptionKey) -> bool {
+        match (self, key) {
+            (&AdditiveEncryptionScheme::Sodium, &EncryptionKey::Sodium(_)) => true,
+            (&AdditiveEncryptionScheme::PackedPaillier { component_count, component_bitsize, min_modulus_bitsize, .. }, &EncryptionKey::PackedPaillier(ref raw_n)) => {
+                let modulus_bitsize = bitsize_be(&raw_n.0);
+                modulus_bitsize >= min_modulus_bitsize && modulus_bitsize > component_count * component_bitsize
+            }
+            _ => false,
+        }
+    }
+
+    /// Maximum number of fresh ciphertexts that may be summed before components overflow.
+    pub fn max_summands(&self) -> Option<usize> {
+        match self {
+            &AdditiveEncryptionScheme::Sodium {..} => None,
+            &AdditiveEncryptionScheme::PackedPaillier { component_bitsize, max_value_bitsize, .. } => {
+                let headroom = component_bitsize.saturating_sub(max_value_bitsize);
+                Some(if headroom >= 63 { ::std::usize::MAX } else { 1_usize << headroom })
+            }
         }
     }
 
 }
+
+/// Keys and parameters that may or may not be used with a given scheme.
+pub trait Suitable<S> {
+    fn suitable_for(&self, scheme: &S) -> bool;
+}
+
+impl Suitable<AdditiveEncryptionScheme> for EncryptionKey {
+    fn suitable_for(&self, scheme: &AdditiveEncryptionScheme) -> bool {
+        scheme.is_suitable_key(self)
+    }
+}
+
+/// Numeric representation of the values of an aggregation, that is of its secrets, masks and shares.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum NumericRepresentation {
+    /// Signed 64 bit integers modulo `Aggregation.modulus`, supported by all schemes.
+    I64,
+    /// Unsigned 128 bit integers modulo a modulus of at most 127 bits.
+    U128 {
+        /// Modulus, as a big-endian integer.
+        modulus: Binary,
+    },
+    /// Arbitrary precision unsigned integers.
+    BigUint {
+        /// Modulus, as a big-endian integer.
+        modulus: Binary,
+    },
+}
+
+impl Default for NumericRepresentation {
+    fn default() -> NumericRepresentation {
+        NumericRepresentation::I64
+    }
+}
+
+impl NumericRepresentation {
+    /// Derived property indicating whether or not values may exceed 64 bits, in which case the
+    /// 64 bit moduli of the aggregation and its schemes are not used.
+    pub fn is_wide(&self) -> bool {
+        match *self {
+            NumericRepresentation::I64 => false,
+            _ => true,
+        }
+    }
+
+    /// Maximum number of bits of the values of the representation, if bounded.
+    pub fn max_value_bitsize(&self) -> Option<usize> {
+        match *self {
+            NumericRepresentation::I64 => Some(63),
+            NumericRepresentation::U128 { .. } => Some(128),
+            NumericRepresentation::BigUint { .. } => None,
+        }
+    }
+
+    /// Number of significant bits of the modulus of a wide representation.
+    pub fn modulus_bitsize(&self) -> Option<usize> {
+        match *self {
+            NumericRepresentation::I64 => None,
+            NumericRepresentation::U128 { ref modulus } => Some(bitsize_be(&modulus.0)),
+            NumericRepresentation::BigUint { ref modulus } => Some(bitsize_be(&modulus.0)),
+        }
+    }
+
+    /// Check that the modulus of a wide representation fits the representation.
+    pub fn validate(&self) -> SdaResult<()> {
+        let bitsize = match self.modulus_bitsize() {
+            None => return Ok(()),
+            Some(bitsize) => bitsize,
+        };
+        // a modulus below 2 has a single significant bit at most
+        if bitsize < 2 {
+            Err(SdaErrorKind::Invalid("Modulus must be at least 2".to_string()))?
+        }
+        if let NumericRepresentation::U128 { .. } = *self {
+            // sums of two values must not overflow
+            if bitsize > 127 {
+                Err(SdaErrorKind::Invalid(format!("Modulus of {} bits does not fit in 127 bits", bitsize)))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Number of significant bits in a big-endian integer.
+fn bitsize_be(bytes: &[u8]) -> usize {
+    match bytes.iter().position(|&byte| byte != 0) {
+        None => 0,
+        Some(first) => (bytes.len() - first) * 8 - bytes[first].leading_zeros() as usize,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_representations() {
+        assert!(NumericRepresentation::I64.validate().is_ok());
+        assert!(NumericRepresentation::U128 { modulus: Binary(vec![0x7f; 16]) }.validate().is_ok());
+        assert!(NumericRepresentation::U128 { modulus: Binary(vec![0x80; 16]) }.validate().is_err());
+        assert!(NumericRepresentation::BigUint { modulus: Binary(vec![0xff; 64]) }.validate().is_ok());
+        assert!(NumericRepresentation::BigUint { modulus: Binary(vec![0, 1]) }.validate().is_err());
+        assert_eq!(Some(512), NumericRepresentation::BigUint { modulus: Binary(vec![0xff; 64]) }.modulus_bitsize());
+    }
+
+    #[test]
+    fn packed_shamir_parameters() {
+        let scheme = LinearSecretSharingScheme::new_packed_shamir(3, 8, 4, 9).unwrap();
+        match scheme {
+            LinearSecretSharingScheme::PackedShamir { prime_modulus, omega_secrets, omega_shares, .. } => {
+                assert_eq!(433, prime_modulus);
+                assert_eq!(1, mod_pow(omega_secrets, 8, prime_modulus));
+                assert!(mod_pow(omega_secrets, 4, prime_modulus) != 1);
+                assert_eq!(1, mod_pow(omega_shares, 9, prime_modulus));
+                assert!(mod_pow(omega_shares, 3, prime_modulus) != 1);
+            }
+            _ => panic!("expected packed Shamir scheme"),
+        }
+        // moduli that are not powers of two are not rounded down
+        let scheme = LinearSecretSharingScheme::new_packed_shamir_with_modulus(3, 8, 4, 1000).unwrap();
+        assert_eq!(1009, scheme.modulus());
+    }
+
+    #[test]
+    fn verifiable_shamir_parameters() {
+        let scheme = LinearSecretSharingScheme::new_verifiable_shamir(5, 2, 1000).unwrap();
+        assert_eq!(1000, scheme.modulus());
+        assert_eq!(3, scheme.reconstruction_threshold());
+        assert_eq!(12, scheme.commitment_count(4));
+        assert!(LinearSecretSharingScheme::new_verifiable_shamir(5, 5, 433).is_err());
+        assert!(LinearSecretSharingScheme::new_verifiable_shamir(5, 2, 1).is_err());
+    }
+
+    #[test]
+    fn packed_shamir_invalid_sizes() {
+        assert!(LinearSecretSharingScheme::new_packed_shamir(3, 8, 3, 9).is_err());
+        assert!(LinearSecretSharingScheme::new_packed_shamir(3, 7, 4, 9).is_err());
+        assert!(LinearSecretSharingScheme::new_packed_shamir(3, 8, 4, 40).is_err());
+        assert!(LinearSecretSharingScheme::new_packed_shamir_with_modulus(3, 8, 4, 1 << 40).is_err());
+    }
+}
@@ -103,6 +103,13 @@ pub struct Signed<M>
 {
     pub signature: Signature,
     pub signer: AgentId,
+    /// Verification key the signature was made with.
+    ///
+    /// Services only store resources signed with the current key of their signer, so a retired key is only
+    /// ever found on resources stored before it was replaced. Resources naming no key are checked against
+    /// the current key of their signer only.
+    #[serde(default)]
+    pub key: Option<VerificationKeyId>,
     pub body: M,
 }
 
@@ -172,7 +179,7 @@ pub fn label<ID, M>(id: &ID, body: &M) -> Labelled<ID, M>
 }
 
 /// Blob of binary data.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Binary(pub Vec<u8>);
 
 impl Binary {
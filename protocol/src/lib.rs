@@ -17,6 +17,8 @@ extern crate serde_derive;
 #[cfg(test)]
 extern crate serde_test;
 extern crate uuid;
+extern crate sodiumoxide;
+extern crate curve25519_dalek;
 
 mod errors {
     error_chain! {
@@ -47,11 +49,13 @@ pub use errors::*;
 pub mod helpers;
 
 mod crypto;
+mod commitments;
 mod resources;
 mod methods;
 pub mod byte_arrays;
 
 pub use helpers::*;
 pub use crypto::*;
+pub use commitments::*;
 pub use resources::*;
 pub use methods::*;
@@ -36,6 +36,12 @@ pub trait SdaAgentService : SdaBaseService {
     /// Retrieve the agent description.
     fn get_agent(&self, caller: &Agent, agent: &AgentId) -> SdaResult<Option<Agent>>;
 
+    /// Replace the verification key of the caller, as authorised by a signature with the current key.
+    fn rotate_verification_key(&self, caller: &Agent, rotation: &SignedVerificationKeyRotation) -> SdaResult<()>;
+
+    /// Retrieve the verification key rotations of an agent, oldest first.
+    fn get_verification_key_history(&self, caller: &Agent, agent: &AgentId) -> SdaResult<Vec<SignedVerificationKeyRotation>>;
+
     /// Register the given public profile; updates any existing profile.
     fn upsert_profile(&self, caller: &Agent, profile: &Profile) -> SdaResult<()>;
 
@@ -47,6 +53,12 @@ pub trait SdaAgentService : SdaBaseService {
 
     /// Retrieve agent encryption key.
     fn get_encryption_key(&self, caller: &Agent, key: &EncryptionKeyId) -> SdaResult<Option<SignedEncryptionKey>>;
+
+    /// List the encryption keys of an agent that have not been revoked.
+    fn list_encryption_keys(&self, caller: &Agent, owner: &AgentId) -> SdaResult<Vec<EncryptionKeyId>>;
+
+    /// Revoke an encryption key of the caller; revocations are final.
+    fn revoke_encryption_key(&self, caller: &Agent, revocation: &SignedEncryptionKeyRevocation) -> SdaResult<()>;
 }
 
 /// Methods used mainly for discovering aggregation objects.
@@ -56,19 +68,28 @@ pub trait SdaAggregationService : SdaBaseService {
     /// recipient.
     fn list_aggregations(&self, caller: &Agent, filter: Option<&str>, recipient: Option<&AgentId>) -> SdaResult<Vec<AggregationId>>;
 
-    /// Retrieve an aggregation and its description.
-    fn get_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<Aggregation>>;
+    /// Retrieve an aggregation and its description, as signed by the recipient.
+    fn get_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<SignedAggregation>>;
+
+    /// Retrieve the associated committee, as signed by the recipient.
+    fn get_committee(&self, caller: &Agent, owner: &AggregationId) -> SdaResult<Option<SignedCommittee>>;
 
-    /// Retrieve the associated committee.
-    fn get_committee(&self, caller: &Agent, owner: &AggregationId) -> SdaResult<Option<Committee>>;
+    /// Retrieve the signed participations included in a snapshot, allowing the recipient and the clerks to audit them.
+    fn get_snapshot_participations(&self, caller: &Agent, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaResult<Vec<SignedParticipation>>;
 }
 
 
 /// Methods used for participation in particular.
 pub trait SdaParticipationService : SdaBaseService {
 
-    /// Provide user input to an aggregation.
-    fn create_participation(&self, caller: &Agent, participation: &Participation) -> SdaResult<()>;
+    /// Provide user input to an aggregation, signed by the participant.
+    fn create_participation(&self, caller: &Agent, participation: &SignedParticipation) -> SdaResult<()>;
+
+    /// Enroll in the masking cohort of an aggregation using pairwise masking.
+    fn create_enrollment(&self, caller: &Agent, enrollment: &Enrollment) -> SdaResult<()>;
+
+    /// Retrieve the masking cohort of an aggregation, once formed.
+    fn get_masking_cohort(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<MaskingCohort>>;
 
 }
 
@@ -78,8 +99,25 @@ pub trait SdaClerkingService : SdaBaseService {
     /// Pull any job waiting to be performed by the speficied clerk.
     fn get_clerking_job(&self, caller: &Agent, clerk: &AgentId) -> SdaResult<Option<ClerkingJob>>;
 
-    /// Push the result of a finished job.
-    fn create_clerking_result(&self, caller: &Agent, result: &ClerkingResult) -> SdaResult<()>;
+    /// Let the service know the calling clerk is available, without pulling a job.
+    fn clerk_ping(&self, caller: &Agent) -> SdaResult<Pong>;
+
+    /// Register as willing to sit in committees, replacing any earlier registration.
+    ///
+    /// The registration must be signed by the clerk.
+    fn create_clerk_registration(&self, caller: &Agent, registration: &SignedClerkRegistration) -> SdaResult<()>;
+
+    /// Retrieve the registration of a clerk, if any.
+    fn get_clerk_registration(&self, caller: &Agent, clerk: &AgentId) -> SdaResult<Option<SignedClerkRegistration>>;
+
+    /// List the aggregations whose committee awaits the acceptance of the specified clerk.
+    fn list_pending_seats(&self, caller: &Agent, clerk: &AgentId) -> SdaResult<Vec<AggregationId>>;
+
+    /// Accept a seat in the committee of an aggregation, signed by the clerk.
+    fn create_seat_acceptance(&self, caller: &Agent, acceptance: &SignedSeatAcceptance) -> SdaResult<()>;
+
+    /// Push the result of a finished job, signed by the clerk.
+    fn create_clerking_result(&self, caller: &Agent, result: &SignedClerkingResult) -> SdaResult<()>;
 
 }
 
@@ -88,25 +126,47 @@ pub trait SdaRecipientService : SdaBaseService {
 
     /// Create a new aggregation on the service (without any associated result).
     /// If successful, the original id has been replaced by the returned id.
-    fn create_aggregation(&self, caller: &Agent, aggregation: &Aggregation) -> SdaResult<()>;
+    ///
+    /// The aggregation must be signed by its recipient.
+    fn create_aggregation(&self, caller: &Agent, aggregation: &SignedAggregation) -> SdaResult<()>;
 
-    /// Delete all information (including results) regarding an aggregation.
+    /// Delete all information regarding an aggregation, from its committee and participations to
+    /// its snapshots, clerking jobs and results.
     fn delete_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()>;
 
-    /// Propose suitable members for a committee, taking into account the aggregation constraints.
-    // TODO allow additional criteria, as max number, liveliness, etc.
-    fn suggest_committee(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Vec<ClerkCandidate>>;
+    /// Propose suitable members for a committee, taking into account the aggregation constraints
+    /// and the given criteria.
+    fn suggest_committee(&self, caller: &Agent, aggregation: &AggregationId, criteria: &CommitteeCriteria) -> SdaResult<Vec<ClerkCandidate>>;
+
+    /// Set the committee for an aggregation, signed by its recipient.
+    ///
+    /// All clerks must be registered for the aggregation, and the aggregation opens once they all accepted their seat.
+    fn create_committee(&self, caller: &Agent, committee: &SignedCommittee) -> SdaResult<()>;
 
-    /// Set the committee for an aggregation.
-    fn create_committee(&self, caller: &Agent, committee: &Committee) -> SdaResult<()>;
+    /// Close enrollment for an aggregation using pairwise masking, forming its masking cohort from the current enrollments.
+    fn create_masking_cohort(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()>;
 
     /// Poll status of an aggregation.
     fn get_aggregation_status(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<AggregationStatus>>;
 
-    /// Create a snapshot for an aggregation.
+    /// Create a snapshot for an aggregation, closing it to further participations.
     fn create_snapshot(&self, caller: &Agent, snapshot: &Snapshot) -> SdaResult<()>;
 
+    /// Close an open aggregation to further participations, without taking a snapshot.
+    fn close_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()>;
+
+    /// Archive an aggregation, after which no further operation is accepted on it.
+    fn archive_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()>;
+
     /// Retrieve results of an aggregation.
+    ///
+    /// Also available to members of the recipient group, if any.
     fn get_snapshot_result(&self, caller: &Agent, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaResult<Option<SnapshotResult>>;
 
+    /// Push the contribution of a recipient group member to revealing a snapshot, signed by the member.
+    fn create_reveal_contribution(&self, caller: &Agent, contribution: &SignedRevealContribution) -> SdaResult<()>;
+
+    /// Retrieve the reveal contributions received so far for a snapshot.
+    fn get_reveal_contributions(&self, caller: &Agent, aggregation: &AggregationId, snapshot: &SnapshotId) -> SdaResult<Vec<SignedRevealContribution>>;
+
 }
@@ -19,6 +19,45 @@ pub struct Agent {
 uuid_id!{ #[doc="Unique agent identifier."] AgentId }
 identify!(Agent,AgentId);
 
+/// Replacement of the verification key of an agent, e.g. after a compromise of the signature keypair.
+///
+/// Signed with the key being replaced; the service keeps the chain of rotations so that resources signed
+/// before a rotation can still be verified against the key in use at the time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct VerificationKeyRotation {
+    /// Agent whose key is replaced.
+    pub agent: AgentId,
+    /// Key being replaced, i.e. the current key of the agent.
+    pub previous_key: LabelledVerificationKey,
+    /// Key used for verifying resources signed from now on.
+    pub new_key: LabelledVerificationKey,
+}
+
+/// Verification key rotation, signed with the key being replaced.
+pub type SignedVerificationKeyRotation = Signed<VerificationKeyRotation>;
+
+/// Digest of a single request to a service, signed by the calling agent to authenticate it.
+///
+/// This proves ownership of the agent's verification key without sharing any secret with the service.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RequestDigest {
+    /// Agent making the request.
+    pub agent: AgentId,
+    /// Request method, e.g. `GET`.
+    pub method: String,
+    /// Request path, including any query.
+    pub path: String,
+    /// Time of the request, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// SHA-256 hash of the request body, empty for requests without one.
+    pub body_hash: ::byte_arrays::B32,
+    /// Random value chosen by the agent, so that identical requests made close together differ.
+    pub nonce: u64,
+}
+
+/// Request digest signed by the calling agent.
+pub type SignedRequestDigest = Signed<RequestDigest>;
+
 /// Extended profile of an agent, providing information intended for increasing trust such as name and social handles.
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Profile {
@@ -39,6 +78,19 @@ uuid_id!{ #[doc="Unique encryption key identifier."] EncryptionKeyId }
 /// Encryption key with its associated, signed by the owner of the corresponding keypair.
 pub type SignedEncryptionKey = Signed<Labelled<EncryptionKeyId, EncryptionKey>>;
 
+/// Revocation of an encryption key by its owner, e.g. when rotating keys or after a compromise.
+///
+/// Revoked keys remain available for past aggregations, but are no longer suggested to recipients and
+/// can not be used for new aggregations and committees.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EncryptionKeyRevocation {
+    /// Revoked key.
+    pub key: EncryptionKeyId,
+}
+
+/// Revocation of an encryption key, signed by the owner of the key.
+pub type SignedEncryptionKeyRevocation = Signed<EncryptionKeyRevocation>;
+
 /// Description of an aggregation.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Aggregation {
@@ -64,11 +116,471 @@ pub struct Aggregation {
     pub recipient_encryption_scheme: AdditiveEncryptionScheme,
     /// Scheme and parameters used for encrypting shares of masked secrets for the committee.
     pub committee_encryption_scheme: AdditiveEncryptionScheme,
+    /// Optional policy restricting the values participants may input.
+    ///
+    /// Participations must then carry a proof that their input satisfies the policy.
+    #[serde(default)]
+    pub input_bounds: Option<InputBounds>,
+    /// Optional differential privacy guarantee for the output, obtained by noise added by the committee.
+    #[serde(default)]
+    pub differential_privacy: Option<DifferentialPrivacy>,
+    /// Representation of values; wide representations allow moduli beyond 64 bits.
+    #[serde(default)]
+    pub numeric_representation: NumericRepresentation,
+    /// Optional group of key holders that must cooperate to reveal the output.
+    ///
+    /// Masks and clerk outputs are then shared among the members instead of being encrypted for the
+    /// recipient, who needs the reveal contributions of enough members to decrypt.
+    #[serde(default)]
+    pub recipient_group: Option<RecipientGroup>,
+    /// Handling of several participations from the same participant.
+    #[serde(default)]
+    pub participation_policy: ParticipationPolicy,
+    /// Optional window for participations and snapshots taken automatically by the service.
+    #[serde(default)]
+    pub schedule: Option<AggregationSchedule>,
 }
 
 uuid_id!{ #[doc="Unique aggregation identifier."] AggregationId }
 identify!(Aggregation, AggregationId);
 
+/// Aggregation signed by its recipient, allowing participants and clerks to check it was not altered by the service.
+pub type SignedAggregation = Signed<Aggregation>;
+
+impl Aggregation {
+
+    /// Check that the parameters of the aggregation are consistent with each other.
+    ///
+    /// Services must reject aggregations failing this check, and clients should run it before uploading.
+    pub fn validate(&self) -> SdaResult<()> {
+        if self.vector_dimension == 0 {
+            Err(SdaErrorKind::Invalid("Vector dimension must be positive".to_string()))?
+        }
+        if let Some(ref schedule) = self.schedule {
+            schedule.validate()?;
+            if schedule.incremental_snapshots && self.masking_scheme.is_pairwise() {
+                Err(SdaErrorKind::Invalid("Incremental snapshots cannot be used with pairwise masking".to_string()))?
+            }
+        }
+        // wide representations leave the 64 bit moduli of the aggregation and its schemes unused
+        let wide = self.numeric_representation.is_wide();
+        if wide {
+            self.numeric_representation.validate()?;
+        } else if self.modulus < 2 {
+            Err(SdaErrorKind::Invalid(format!("Modulus must be at least 2, found {}", self.modulus)))?
+        }
+
+        self.masking_scheme.validate()?;
+        if wide && !self.masking_scheme.supports_wide_values() {
+            Err(SdaErrorKind::Invalid("Masking scheme does not support wide values".to_string()))?
+        }
+        if let Some(modulus) = self.masking_scheme.modulus() {
+            if !wide && modulus != self.modulus {
+                Err(SdaErrorKind::Invalid(format!(
+                    "Masking modulus {} differs from aggregation modulus {}", modulus, self.modulus)))?
+            }
+        }
+        let masking_dimension = match self.masking_scheme {
+            LinearMaskingScheme::ChaCha { dimension, .. } => Some(dimension),
+            LinearMaskingScheme::Pairwise { dimension, .. } => Some(dimension),
+            _ => None,
+        };
+        if let Some(dimension) = masking_dimension {
+            if dimension != self.vector_dimension {
+                Err(SdaErrorKind::Invalid(format!(
+                    "Masking dimension {} differs from vector dimension {}", dimension, self.vector_dimension)))?
+            }
+        }
+
+        self.committee_sharing_scheme.validate()?;
+        if wide && !self.committee_sharing_scheme.supports_wide_values() {
+            Err(SdaErrorKind::Invalid("Sharing scheme does not support wide values".to_string()))?
+        }
+        if !wide && self.committee_sharing_scheme.modulus() != self.modulus {
+            Err(SdaErrorKind::Invalid(format!(
+                "Sharing modulus {} differs from aggregation modulus {}",
+                self.committee_sharing_scheme.modulus(), self.modulus)))?
+        }
+
+        let value_bitsize = self.value_bitsize(self.modulus);
+        self.recipient_encryption_scheme.validate()?;
+        if !self.supports_values(&self.recipient_encryption_scheme, value_bitsize) {
+            Err(SdaErrorKind::Invalid("Recipient encryption scheme cannot hold the values of the aggregation".to_string()))?
+        }
+        self.committee_encryption_scheme.validate()?;
+        if !self.supports_values(&self.committee_encryption_scheme, value_bitsize) {
+            Err(SdaErrorKind::Invalid("Committee encryption scheme cannot hold the values of the aggregation".to_string()))?
+        }
+        // shares of verifiable schemes are scalars spread over several limbs, which only Sodium carries unchanged
+        if self.committee_sharing_scheme.is_verifiable()
+            && (self.recipient_encryption_scheme != AdditiveEncryptionScheme::Sodium
+                || self.committee_encryption_scheme != AdditiveEncryptionScheme::Sodium
+                || self.recipient_group.is_some()) {
+            Err(SdaErrorKind::Invalid(
+                "Verifiable sharing requires Sodium encryption and no recipient group".to_string()))?
+        }
+
+        if let Some(ref bounds) = self.input_bounds {
+            // proofs are about the committed inputs, so these must be neither masked nor hidden from commitments
+            if !self.committee_sharing_scheme.is_verifiable() {
+                Err(SdaErrorKind::Invalid("Input bounds require a verifiable sharing scheme".to_string()))?
+            }
+            if self.masking_scheme != LinearMaskingScheme::None {
+                Err(SdaErrorKind::Invalid("Input bounds cannot be used with masking".to_string()))?
+            }
+            bounds.validate(self.vector_dimension, self.modulus)?;
+        }
+
+        if let Some(ref privacy) = self.differential_privacy {
+            // clerks add noise to their shares, which only sums up to noise on the output for additive sharing
+            match self.committee_sharing_scheme {
+                LinearSecretSharingScheme::Additive { .. } => {}
+                _ => Err(SdaErrorKind::Invalid("Differential privacy requires additive sharing".to_string()))?,
+            }
+            privacy.validate()?;
+        }
+
+        if let Some(ref group) = self.recipient_group {
+            group.validate()?;
+            if wide && !group.sharing_scheme.supports_wide_values() {
+                Err(SdaErrorKind::Invalid("Recipient group sharing scheme does not support wide values".to_string()))?
+            }
+            // members' shares must determine values modulo the aggregation modulus
+            if !wide && group.sharing_scheme.modulus() < self.modulus {
+                Err(SdaErrorKind::Invalid(format!(
+                    "Recipient group sharing modulus {} is smaller than aggregation modulus {}",
+                    group.sharing_scheme.modulus(), self.modulus)))?
+            }
+            if !self.supports_values(&self.recipient_encryption_scheme, self.value_bitsize(group.sharing_scheme.modulus())) {
+                Err(SdaErrorKind::Invalid(
+                    "Recipient encryption scheme cannot hold the values of the recipient group".to_string()))?
+            }
+            // seeds and pairwise recovery data are not values modulo the aggregation modulus
+            match self.masking_scheme {
+                LinearMaskingScheme::None | LinearMaskingScheme::Full { .. } => {}
+                _ => Err(SdaErrorKind::Invalid("Recipient groups only support no masking or full masking".to_string()))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of bits of values modulo the given 64 bit modulus, or modulo the modulus of a wide representation.
+    fn value_bitsize(&self, modulus: i64) -> usize {
+        match self.numeric_representation.modulus_bitsize() {
+            Some(bitsize) => bitsize,
+            None => 64 - (modulus - 1).leading_zeros() as usize,
+        }
+    }
+
+    /// Check that an encryption scheme may carry values of the given number of bits in the numeric representation.
+    fn supports_values(&self, scheme: &AdditiveEncryptionScheme, value_bitsize: usize) -> bool {
+        scheme.supports_value_bitsize(value_bitsize, self.numeric_representation.max_value_bitsize())
+    }
+
+    /// Check that the given key may be used for encryptions intended for the recipient.
+    pub fn validate_recipient_key(&self, key: &SignedEncryptionKey) -> SdaResult<()> {
+        if key.body.id != self.recipient_key {
+            Err(SdaErrorKind::Invalid(format!("Expected recipient key {:?}, found {:?}", self.recipient_key, key.body.id)))?
+        }
+        if key.signer != self.recipient {
+            Err(SdaErrorKind::Invalid("Recipient key must belong to the recipient".to_string()))?
+        }
+        if !self.recipient_encryption_scheme.is_suitable_key(&key.body.body) {
+            Err(SdaErrorKind::Invalid("Recipient key is not suitable for the recipient encryption scheme".to_string()))?
+        }
+        Ok(())
+    }
+
+}
+
+/// Policy for participants uploading several participations to the same aggregation.
+///
+/// Uploads of the same participation, i.e. with the same `ParticipationId`, are always idempotent.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ParticipationPolicy {
+    /// Refuse any further participation once a participant has participated.
+    Reject,
+    /// Supersede earlier participations, so only the latest one is included in later snapshots.
+    KeepLatest,
+    /// Include every participation in snapshots.
+    KeepAll,
+}
+
+impl Default for ParticipationPolicy {
+    fn default() -> ParticipationPolicy {
+        ParticipationPolicy::KeepAll
+    }
+}
+
+/// Timing of an aggregation, enforced by the service.
+///
+/// Times are in seconds since the Unix epoch. Once the aggregation is open, the service takes a snapshot
+/// every `snapshot_interval` seconds, and a last one at `closes_at` before closing the aggregation,
+/// as long as at least `min_participations` participations were received.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AggregationSchedule {
+    /// Time before which participations are refused.
+    #[serde(default)]
+    pub opens_at: Option<u64>,
+    /// Time from which participations are refused.
+    #[serde(default)]
+    pub closes_at: Option<u64>,
+    /// Number of participations needed before the service takes any snapshot on its own.
+    #[serde(default)]
+    pub min_participations: usize,
+    /// Seconds between snapshots taken while the aggregation is open.
+    #[serde(default)]
+    pub snapshot_interval: Option<u64>,
+    /// Take incremental snapshots, each covering the participations received since the previous one.
+    #[serde(default)]
+    pub incremental_snapshots: bool,
+}
+
+impl AggregationSchedule {
+
+    /// Check that the schedule describes a non-empty window and a positive interval.
+    pub fn validate(&self) -> SdaResult<()> {
+        if let (Some(opens_at), Some(closes_at)) = (self.opens_at, self.closes_at) {
+            if closes_at <= opens_at {
+                Err(SdaErrorKind::Invalid("Aggregation must close after it opens".to_string()))?
+            }
+        }
+        if self.snapshot_interval == Some(0) {
+            Err(SdaErrorKind::Invalid("Snapshot interval must be positive".to_string()))?
+        }
+        Ok(())
+    }
+
+    /// Check that participations are accepted at the given time.
+    pub fn accepts_participations_at(&self, now: u64) -> SdaResult<()> {
+        if let Some(opens_at) = self.opens_at {
+            if now < opens_at {
+                Err(SdaErrorKind::Invalid(format!("Participations are only accepted from {}", opens_at)))?
+            }
+        }
+        if let Some(closes_at) = self.closes_at {
+            if now >= closes_at {
+                Err(SdaErrorKind::Invalid(format!("Participations are no longer accepted since {}", closes_at)))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Group of key holders among which the output of an aggregation is shared.
+///
+/// Any `sharing_scheme.reconstruction_threshold()` members can reveal the output together
+/// with the recipient, while fewer learn nothing about it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecipientGroup {
+    /// Members and the encryption keys to be used for encryptions intended for them, in share order.
+    pub members: Vec<(AgentId, EncryptionKeyId)>,
+    /// Scheme used for sharing values among the members; must output a share per member.
+    pub sharing_scheme: LinearSecretSharingScheme,
+}
+
+impl RecipientGroup {
+
+    /// Number of members that must contribute to a reveal.
+    pub fn threshold(&self) -> usize {
+        self.sharing_scheme.reconstruction_threshold()
+    }
+
+    /// Index of the share intended for the given agent, if a member.
+    pub fn position(&self, agent: &AgentId) -> Option<usize> {
+        self.members.iter().position(|&(ref member, _)| member == agent)
+    }
+
+    /// Check that the group is consistent with its sharing scheme.
+    pub fn validate(&self) -> SdaResult<()> {
+        self.sharing_scheme.validate()?;
+        match self.sharing_scheme {
+            LinearSecretSharingScheme::Additive { .. } | LinearSecretSharingScheme::BasicShamir { .. } => {}
+            _ => Err(SdaErrorKind::Invalid("Recipient groups only support additive or basic Shamir sharing".to_string()))?,
+        }
+        if self.sharing_scheme.output_size() != self.members.len() {
+            Err(SdaErrorKind::Invalid(format!(
+                "Recipient group has {} members but its sharing scheme outputs {} shares",
+                self.members.len(), self.sharing_scheme.output_size())))?
+        }
+        for (index, &(ref member, _)) in self.members.iter().enumerate() {
+            if self.position(member) != Some(index) {
+                Err(SdaErrorKind::Invalid("Recipient group members must be distinct".to_string()))?
+            }
+        }
+        Ok(())
+    }
+
+}
+
+/// Differential privacy guarantee for each release of the output of an aggregation.
+///
+/// Every clerk adds noise to its combined shares, such that the noise of all clerks sums up to
+/// noise calibrated to the guarantee; no single party hence learns the exact output.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DifferentialPrivacy {
+    pub epsilon: f64,
+    /// Ignored by the Laplace mechanism, which gives pure differential privacy.
+    pub delta: f64,
+    /// Largest change a single participation can make to the output, in L1 norm for the
+    /// Laplace mechanism and in L2 norm for the Gaussian mechanism.
+    pub sensitivity: f64,
+    pub mechanism: NoiseMechanism,
+    /// Total privacy loss allowed over all releases, as `(epsilon, delta)`; the service refuses
+    /// snapshots that would exceed it. Releases are unlimited when absent.
+    #[serde(default)]
+    pub budget: Option<(f64, f64)>,
+}
+
+/// Distribution of the noise added to the output.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NoiseMechanism {
+    DiscreteLaplace,
+    DiscreteGaussian,
+}
+
+impl DifferentialPrivacy {
+
+    /// Check that the parameters are meaningful for the mechanism.
+    pub fn validate(&self) -> SdaResult<()> {
+        if !(self.epsilon > 0.0 && self.epsilon.is_finite()) {
+            Err(SdaErrorKind::Invalid(format!("Epsilon must be positive, found {}", self.epsilon)))?
+        }
+        if !(self.sensitivity > 0.0 && self.sensitivity.is_finite()) {
+            Err(SdaErrorKind::Invalid(format!("Sensitivity must be positive, found {}", self.sensitivity)))?
+        }
+        if self.mechanism == NoiseMechanism::DiscreteGaussian {
+            // the calibration of the Gaussian mechanism only holds in this regime
+            if !(self.delta > 0.0 && self.delta < 1.0) {
+                Err(SdaErrorKind::Invalid(format!("Delta must lie strictly between 0 and 1, found {}", self.delta)))?
+            }
+            if self.epsilon >= 1.0 {
+                Err(SdaErrorKind::Invalid(format!("Epsilon must be below 1 for the Gaussian mechanism, found {}", self.epsilon)))?
+            }
+        }
+        if let Some((epsilon, delta)) = self.budget {
+            if !PrivacyBudget::default().after_release(self).is_within(self) {
+                Err(SdaErrorKind::Invalid(format!(
+                    "Privacy budget ({}, {}) does not allow a single release", epsilon, delta)))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Scale of the total noise: the parameter `t` of the discrete Laplace distribution, with
+    /// probabilities proportional to `exp(-|x|/t)`, or the standard deviation of the discrete Gaussian.
+    pub fn noise_scale(&self) -> f64 {
+        match self.mechanism {
+            NoiseMechanism::DiscreteLaplace => self.sensitivity / self.epsilon,
+            NoiseMechanism::DiscreteGaussian => self.sensitivity * (2.0 * (1.25 / self.delta).ln()).sqrt() / self.epsilon,
+        }
+    }
+
+    /// Privacy lost by a single release of the output, as `(epsilon, delta)`.
+    pub fn cost(&self) -> (f64, f64) {
+        match self.mechanism {
+            NoiseMechanism::DiscreteLaplace => (self.epsilon, 0.0),
+            NoiseMechanism::DiscreteGaussian => (self.epsilon, self.delta),
+        }
+    }
+
+}
+
+/// Policy restricting the values participants may input to an aggregation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputBounds {
+    /// Each component must lie between the corresponding `min` and `max`, both inclusive.
+    Range {
+        min: Vec<i64>,
+        max: Vec<i64>,
+    },
+    /// Each component must be 0 or 1, and exactly one component must be 1.
+    OneHot,
+}
+
+impl InputBounds {
+
+    /// Number of bits used for proving that a component lies within its range.
+    pub fn range_bitsize(min: i64, max: i64) -> usize {
+        let width = max - min;
+        ::std::cmp::max(1, 64 - width.leading_zeros() as usize)
+    }
+
+    /// Check that the policy is consistent with the dimension and modulus of the aggregation.
+    pub fn validate(&self, dimension: usize, modulus: i64) -> SdaResult<()> {
+        match *self {
+            InputBounds::Range { ref min, ref max } => {
+                if min.len() != dimension || max.len() != dimension {
+                    Err(SdaErrorKind::Invalid(format!(
+                        "Input bounds must be given for each of the {} components", dimension)))?
+                }
+                for (&min, &max) in min.iter().zip(max) {
+                    if min > max {
+                        Err(SdaErrorKind::Invalid(format!("Empty input range [{}, {}]", min, max)))?
+                    }
+                    // both distances to the bounds are proven to be below 2^bitsize; their sum must not wrap around
+                    let bitsize = InputBounds::range_bitsize(min, max);
+                    if bitsize + 1 >= 63 || (1_i64 << (bitsize + 1)) > modulus {
+                        Err(SdaErrorKind::Invalid(format!(
+                            "Input range [{}, {}] too wide for modulus {}", min, max, modulus)))?
+                    }
+                }
+            }
+            InputBounds::OneHot => {}
+        }
+        Ok(())
+    }
+
+}
+
+/// Zero-knowledge proof that the input of a participation satisfies the input bounds of the aggregation.
+///
+/// Proofs are about the commitments to the constant terms of the sharing polynomials, as found in the
+/// participation, and are bound to the participant and aggregation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputProof {
+    /// For each component, proofs that the distances to the lower and to the upper bound are small.
+    Range {
+        lower: Vec<RangeProof>,
+        upper: Vec<RangeProof>,
+    },
+    /// Proofs that each component is a bit, together with the sum of the blindings of the
+    /// commitments, showing that the components sum to one.
+    OneHot {
+        bits: Vec<BitProof>,
+        blinding_sum: CommitmentScalar,
+    },
+}
+
+impl InputProof {
+    /// Context binding proofs to a participant and aggregation, used when deriving challenges.
+    pub fn context(participant: &AgentId, aggregation: &AggregationId) -> Vec<u8> {
+        let mut context = participant.0.as_bytes().to_vec();
+        context.extend_from_slice(aggregation.0.as_bytes());
+        context
+    }
+}
+
+/// Proof that a committed value lies in `[0, 2^n)`, using commitments to its `n` bits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RangeProof {
+    /// Commitments to the bits of the value, least significant first.
+    pub bit_commitments: Vec<Commitment>,
+    /// Proofs that each commitment is to a bit.
+    pub bit_proofs: Vec<BitProof>,
+}
+
+/// Non-interactive proof that a commitment is to either 0 or 1.
+///
+/// One branch for each possible value, with challenges summing to the hash of the commitment and
+/// the announcements recomputed from the responses.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BitProof {
+    pub challenges: (CommitmentScalar, CommitmentScalar),
+    pub responses: (CommitmentScalar, CommitmentScalar),
+}
+
 /// Suggested clerk for a given aggregation.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ClerkCandidate {
@@ -76,6 +588,31 @@ pub struct ClerkCandidate {
     pub id: AgentId,
     /// Available and matching encryption keys for candidate.
     pub keys: Vec<EncryptionKeyId>,
+    /// Last time the clerk polled for jobs or pinged the service, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub last_seen: Option<u64>,
+}
+
+/// Criteria for the clerks suggested for the committee of an aggregation.
+///
+/// The default criteria suggest every candidate with at least one key, in a stable order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitteeCriteria {
+    /// Maximum number of candidates to suggest.
+    #[serde(default)]
+    pub count: Option<usize>,
+    /// Only suggest clerks seen within that many seconds.
+    #[serde(default)]
+    pub max_staleness: Option<u64>,
+    /// Only suggest keys suitable for the committee encryption scheme of the aggregation.
+    #[serde(default)]
+    pub suitable_keys: bool,
+    /// Leave the recipient of the aggregation out.
+    #[serde(default)]
+    pub exclude_recipient: bool,
+    /// Pick candidates at random rather than in a stable order.
+    #[serde(default)]
+    pub randomize: bool,
 }
 
 /// Description of committee elected for an aggregation.
@@ -87,6 +624,79 @@ pub struct Committee {
     pub clerks_and_keys: Vec<(AgentId, EncryptionKeyId)>,
 }
 
+/// Committee signed by the recipient of its aggregation.
+pub type SignedCommittee = Signed<Committee>;
+
+/// Willingness of an agent to sit in committees.
+///
+/// Only registered clerks are suggested for committees, and committees may only include them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClerkRegistration {
+    /// Registered clerk.
+    pub clerk: AgentId,
+    /// Maximum number of committees to sit in at once, counting aggregations not yet revealed nor
+    /// archived; no limit when absent.
+    #[serde(default)]
+    pub capacity: Option<usize>,
+    /// Committee encryption schemes the clerk supports; any scheme when empty.
+    #[serde(default)]
+    pub supported_schemes: Vec<AdditiveEncryptionScheme>,
+    /// Recipients the clerk is willing to work for; any recipient when empty.
+    #[serde(default)]
+    pub allowed_recipients: Vec<AgentId>,
+}
+
+/// Clerk registration signed by the clerk.
+pub type SignedClerkRegistration = Signed<ClerkRegistration>;
+
+impl ClerkRegistration {
+    /// Check whether the clerk is willing to work on the aggregation, capacity aside.
+    pub fn admits(&self, aggregation: &Aggregation) -> bool {
+        (self.supported_schemes.is_empty() ||
+         self.supported_schemes.contains(&aggregation.committee_encryption_scheme)) &&
+        (self.allowed_recipients.is_empty() || self.allowed_recipients.contains(&aggregation.recipient))
+    }
+}
+
+/// Acceptance by a clerk of its seat in the committee of an aggregation.
+///
+/// Participations are only accepted once every clerk of the committee accepted its seat.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeatAcceptance {
+    /// Aggregation identifier.
+    pub aggregation: AggregationId,
+    /// Clerk accepting the seat.
+    pub clerk: AgentId,
+    /// Encryption key of the clerk in the committee.
+    pub key: EncryptionKeyId,
+}
+
+/// Seat acceptance signed by the clerk.
+pub type SignedSeatAcceptance = Signed<SeatAcceptance>;
+
+/// Enrollment of a participant in the masking cohort of an aggregation using `LinearMaskingScheme::Pairwise`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Enrollment {
+    /// Participant identifier.
+    pub participant: AgentId,
+    /// Aggregation identifier.
+    pub aggregation: AggregationId,
+    /// Key used for agreeing on pairwise masks with the other members of the cohort, signed by the participant.
+    pub masking_key: SignedEncryptionKey,
+    /// Encryptions of shares of the corresponding secret key intended for the clerks in the committee,
+    /// allowing the masks of the participant to be removed should it drop out.
+    pub clerk_encryptions: Vec<(AgentId, Encryption)>,
+}
+
+/// Fixed set of participants masking their inputs for each other, formed from the enrollments to an aggregation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MaskingCohort {
+    /// Aggregation identifier.
+    pub aggregation: AggregationId,
+    /// Members of the cohort, with corresponding masking key.
+    pub members: Vec<(AgentId, SignedEncryptionKey)>,
+}
+
 /// Description of a participant's input to an aggregation.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Participation {
@@ -97,7 +707,8 @@ pub struct Participation {
     pub id: ParticipationId,
     /// Participant identifier.
     ///
-    /// This allows a service to keep track, and possible discard, multiple participations from each participant.
+    /// This allows a service to keep track, and possible discard, multiple participations from each participant,
+    /// following the participation policy of the aggregation.
     pub participant: AgentId,
     /// Aggregation identifier.
     pub aggregation: AggregationId,
@@ -105,11 +716,20 @@ pub struct Participation {
     pub recipient_encryption: Option<Encryption>,
     /// Encryptions intended for the clerks in the committee.
     pub clerk_encryptions: Vec<(AgentId, Encryption)>,
+    /// Commitments to the sharing polynomials, when the committee sharing scheme is verifiable.
+    #[serde(default)]
+    pub commitments: Vec<Commitment>,
+    /// Proof that the input satisfies the input bounds of the aggregation, if any.
+    #[serde(default)]
+    pub input_proof: Option<InputProof>,
 }
 
 uuid_id!{ #[doc="Unique participation identifier."] ParticipationId }
 identify!(Participation, ParticipationId);
 
+/// Participation signed by its participant, as uploaded to and kept by the service.
+pub type SignedParticipation = Signed<Participation>;
+
 /// Captures a subset of the current participations to an agggregation in order to 
 /// create a consistent set of clerkable shares.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -118,6 +738,11 @@ pub struct Snapshot {
     pub id: SnapshotId,
     /// Associated aggregation.
     pub aggregation: AggregationId,
+    /// Only include participations left out of every earlier snapshot, instead of all of them.
+    ///
+    /// Incremental snapshots leave the aggregation open, so its output can be revealed in periodic deltas.
+    #[serde(default)]
+    pub incremental: bool,
 }
 
 uuid_id!{ #[doc="Unique snapshot identifier."] SnapshotId }
@@ -136,6 +761,9 @@ pub struct ClerkingJob {
     pub snapshot: SnapshotId,
     /// Encryptions containing shares for clerking.
     pub encryptions: Vec<Encryption>,
+    /// Encryptions containing shares of the masking secret keys of cohort members missing from the snapshot.
+    #[serde(default)]
+    pub recovery_encryptions: Vec<(AgentId, Encryption)>,
 }
 
 uuid_id!{ #[doc="Unique job identifier."] ClerkingJobId }
@@ -148,10 +776,44 @@ pub struct ClerkingResult {
     pub job: ClerkingJobId,
     /// Executing clerk.
     pub clerk: AgentId,
+    /// Associated aggregation.
+    pub aggregation: AggregationId,
+    /// Associated snapshot of aggregation.
+    pub snapshot: SnapshotId,
     /// Encryption of combined shares. 
     pub encryption: Encryption,
+    /// Combined commitments of the participations included in the combined shares, for verifiable sharing schemes.
+    #[serde(default)]
+    pub commitments: Vec<Commitment>,
+    /// Encryptions of shares of the masking secret keys of cohort members missing from the snapshot, for the recipient.
+    #[serde(default)]
+    pub recovery_encryptions: Vec<(AgentId, Encryption)>,
+    /// Participations left out of the combined shares, as the shares they gave the clerk did not match their commitments.
+    #[serde(default)]
+    pub rejected_participations: Vec<ParticipationId>,
+}
+
+/// Clerking result signed by the executing clerk, so the recipient can check its origin.
+pub type SignedClerkingResult = Signed<ClerkingResult>;
+
+/// Contribution of a recipient group member to revealing the result of a snapshot.
+///
+/// The member decrypts its shares of the snapshot result and re-encrypts them for the recipient.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RevealContribution {
+    /// Associated aggregation.
+    pub aggregation: AggregationId,
+    /// Associated snapshot of aggregation.
+    pub snapshot: SnapshotId,
+    /// Contributing member.
+    pub member: AgentId,
+    /// Encryptions intended for the member, each paired with its plaintext re-encrypted for the recipient.
+    pub decryptions: Vec<(Encryption, Encryption)>,
 }
 
+/// Reveal contribution signed by the member, so the recipient can check its origin.
+pub type SignedRevealContribution = Signed<RevealContribution>;
+
 /// Current status of an aggregation.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AggregationStatus {
@@ -160,7 +822,76 @@ pub struct AggregationStatus {
     /// Current number of participations received.
     pub number_of_participations: usize,
     /// Associated anapshots and their status for this aggregation.
-    pub snapshots: Vec<SnapshotStatus>
+    pub snapshots: Vec<SnapshotStatus>,
+    /// Privacy spent so far, when the aggregation is differentially private.
+    #[serde(default)]
+    pub privacy_budget: Option<PrivacyBudget>,
+    /// Current stage of the aggregation in its lifecycle.
+    #[serde(default)]
+    pub state: AggregationState,
+    /// Clerks of the committee yet to accept their seat.
+    #[serde(default)]
+    pub pending_clerks: Vec<AgentId>,
+}
+
+/// Stage of an aggregation in its lifecycle, as kept by the service.
+///
+/// An aggregation moves forward through the stages only; the operations allowed depend on the stage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AggregationState {
+    /// Created, waiting for its committee and for the clerks to accept their seats; participations are refused.
+    Draft,
+    /// Committee set and accepted, accepting enrollments and participations.
+    Open,
+    /// No longer accepting participations, closed by the recipient, by a snapshot or at its scheduled closing time.
+    Closed,
+    /// Enough clerking results were received for a snapshot to be revealed.
+    Revealed,
+    /// Retired by the recipient; nothing more happens to the aggregation.
+    Archived,
+}
+
+impl Default for AggregationState {
+    fn default() -> AggregationState {
+        AggregationState::Draft
+    }
+}
+
+/// Accounting of the privacy lost through releases of the output of an aggregation.
+///
+/// Every snapshot is a release with fresh noise, and losses add up following basic composition.
+/// The service records it as snapshots are taken.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PrivacyBudget {
+    /// Number of snapshots taken.
+    pub releases: usize,
+    pub epsilon_spent: f64,
+    pub delta_spent: f64,
+}
+
+impl PrivacyBudget {
+
+    /// Budget spent once one more release is made under `privacy`.
+    pub fn after_release(&self, privacy: &DifferentialPrivacy) -> PrivacyBudget {
+        let (epsilon, delta) = privacy.cost();
+        let releases = self.releases + 1;
+        PrivacyBudget {
+            releases: releases,
+            epsilon_spent: epsilon * releases as f64,
+            delta_spent: delta * releases as f64,
+        }
+    }
+
+    /// Whether the spent budget stays within the budget allowed by `privacy`, if any.
+    pub fn is_within(&self, privacy: &DifferentialPrivacy) -> bool {
+        // leave room for rounding, so that a budget of exactly `n` releases allows them all
+        let fits = |spent: f64, allowed: f64| spent <= allowed * (1.0 + 1e-9);
+        match privacy.budget {
+            None => true,
+            Some((epsilon, delta)) => fits(self.epsilon_spent, epsilon) && fits(self.delta_spent, delta),
+        }
+    }
+
 }
 
 /// Current status of a snapshot.
@@ -172,6 +903,9 @@ pub struct SnapshotStatus {
     pub number_of_clerking_results: usize,
     /// Indication of whether a result of the aggregation can be produced from the current clerking results.
     pub result_ready: bool,
+    /// Clerks given up on by the service, for threshold sharing schemes only.
+    #[serde(default)]
+    pub unresponsive_clerks: Vec<AgentId>,
 }
 
 /// Result of an aggregation snapshot, including output, ready for reconstruction.
@@ -181,8 +915,8 @@ pub struct SnapshotResult {
     pub snapshot: SnapshotId,
     /// Number of participations used in this result.
     pub number_of_participations: usize,
-    /// Encrypted shares of the masked result.
-    pub clerk_encryptions: Vec<ClerkingResult>,
+    /// Encrypted shares of the masked result, as signed by the clerks.
+    pub clerk_encryptions: Vec<SignedClerkingResult>,
     /// Encrypted mask for the result.
     pub recipient_encryptions: Option<Vec<Encryption>>,
 }
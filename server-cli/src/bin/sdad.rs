@@ -1,5 +1,6 @@
 extern crate clap;
 extern crate sda_protocol;
+extern crate sda_server;
 extern crate sda_server_cli;
 extern crate sda_server_http;
 #[macro_use]
@@ -17,7 +18,8 @@ fn main() {
     let app = sda_server_cli::add_store_args(app);
     let app = app.subcommand(clap::SubCommand::with_name("httpd")
                    .about("Run a http server")
-                   .arg_from_usage("-b, --bind [ip_and_port] 'defaults to 127.0.0.1:8888'"));
+                   .arg_from_usage("-b, --bind [ip_and_port] 'defaults to 127.0.0.1:8888'")
+                   .arg_from_usage("--schedule_period [seconds] 'time between checks for scheduled snapshots, defaults to 60'"));
 
     if let Err(e) = run(&app.get_matches()) {
         error!("{}", e);
@@ -32,8 +34,14 @@ fn run(matches: &clap::ArgMatches) -> SdaResult<()> {
     match matches.subcommand() {
         ("httpd", Some(m)) => {
             let port = m.value_of("ip_and_port").unwrap_or("127.0.0.1:8888");
+            let period = match m.value_of("schedule_period") {
+                Some(seconds) => seconds.parse().map_err(|_| "Schedule period must be a number of seconds")?,
+                None => 60,
+            };
+            let server_service = sync::Arc::new(server_service);
+            sda_server::scheduler::spawn(server_service.clone(), ::std::time::Duration::from_secs(period));
             info!("Starting server on {}", port);
-            sda_server_http::listen(port, sync::Arc::new(server_service))
+            sda_server_http::listen(port, server_service)
         },
         (_, _) => Err("Unknown subcommand")?
     }
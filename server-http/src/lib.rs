@@ -21,6 +21,11 @@
 //! 
 //! (GET)  (/v1/agents/{AgentId}) => SdaAgentService::get_agent
 //! (POST) (/v1/agents/me) => SdaAgentService::create_agent
+//! (POST) (/v1/agents/me/auth_token) => SdaServer::rotate_auth_token
+//! (POST) (/v1/agents/me/verification_keys) =>
+//!                         SdaAgentService::rotate_verification_key
+//! (GET)  (/v1/agents/{AgentId}/verification_keys) =>
+//!                         SdaAgentService::get_verification_key_history
 //! 
 //! (GET)  (/v1/agents/{AgentId}/profile) => SdaAgentService::get_profile
 //! (POST) (/v1/agents/me/profile) => SdaAgentService::upsert_profile
@@ -28,6 +33,9 @@
 //! (GET)   (/v1/agents/any/keys/{EncryptionKeyId}) =>
 //!                         SdaAgentService::get_encryption_key
 //! (POST)  (/v1/agents/me/keys) => SdaAgentService::create_encryption_key
+//! (GET)   (/v1/agents/{AgentId}/keys) => SdaAgentService::list_encryption_keys
+//! (POST)  (/v1/agents/me/keys/revocations) =>
+//!                         SdaAgentService::revoke_encryption_key
 //! 
 //! (POST)  (/v1/aggregations) => SdaRecipientService::create_aggregation
 //! (GET)   (/v1/aggregations) => SdaAggregationService::list_aggregations
@@ -38,33 +46,70 @@
 //! 
 //! (GET)   (/v1/aggregations/{AggregationId}/committee/suggestions) =>
 //!                         SdaRecipientService::suggest_committee
+//!         with optional count, max_staleness, suitable_keys, exclude_recipient
+//!         and randomize query parameters
 //! (POST)  (/v1/aggregations/implied/committee) =>
 //!                         SdaRecipientService::create_committee
 //! (GET)   (/v1/aggregations/{AggregationId}/committee)
 //!                         SdaAggregationService::get_committee
+//! (GET)   (/v1/aggregations/{AggregationId}/snapshots/{SnapshotId}/participations) =>
+//!                         SdaAggregationService::get_snapshot_participations
 //! 
 //! (POST)  (/v1/aggregations/participations) =>
 //!                         SdaParticipationService::create_participation
+//! (POST)  (/v1/aggregations/enrollments) =>
+//!                         SdaParticipationService::create_enrollment
+//! (GET)   (/v1/aggregations/{AggregationId}/cohort) =>
+//!                         SdaParticipationService::get_masking_cohort
+//! (POST)  (/v1/aggregations/{AggregationId}/cohort) =>
+//!                         SdaRecipientService::create_masking_cohort
 //! (GET)   (/v1/aggregations/{AggregationId}/status) =>
 //!                         SdaRecipientService::get_aggregation_status
+//! (POST)  (/v1/aggregations/{AggregationId}/close) =>
+//!                         SdaRecipientService::close_aggregation
+//! (POST)  (/v1/aggregations/{AggregationId}/archive) =>
+//!                         SdaRecipientService::archive_aggregation
 //! 
 //! (POST)  (/v1/aggregations/implied/snapshot) =>
 //!                         SdaRecipientService::create_committee
 //! 
 //! (GET)   (/v1/aggregations/any/jobs) => SdaClerkingService::get_clerking_job
+//! (POST)  (/v1/aggregations/any/jobs/ping) => SdaClerkingService::clerk_ping
+//! (POST)  (/v1/agents/me/clerk_registration) =>
+//!                         SdaClerkingService::create_clerk_registration
+//! (GET)   (/v1/agents/{AgentId}/clerk_registration) =>
+//!                         SdaClerkingService::get_clerk_registration
+//! (GET)   (/v1/aggregations/any/seats) => SdaClerkingService::list_pending_seats
+//! (POST)  (/v1/aggregations/implied/seats) =>
+//!                         SdaClerkingService::create_seat_acceptance
 //! (POST)  (/v1/aggregations/implied/jobs/{id}/result) =>
 //!                         SdaClerkingService::create_clerking_result
 //! 
 //! (GET)   (/v1/aggregations/{AggregationId}/snapshots/{SnapshotId}/result) =>
 //!                         SdaRecipientService::get_snapshot_result
+//! (GET)   (/v1/aggregations/{AggregationId}/snapshots/{SnapshotId}/reveal) =>
+//!                         SdaRecipientService::get_reveal_contributions
+//! (POST)  (/v1/aggregations/{AggregationId}/snapshots/{SnapshotId}/reveal) =>
+//!                         SdaRecipientService::create_reveal_contribution
 //! ```
 //!
 //! ## Authentication
 //!
-//! Authentication relies on the use of then Authenticate header in Basic mode.
-//! The username is the caller agent id, the password used at the time of the
-//! initial creation is recorded by the server and must then be reused for all
-//! subsequent requests.
+//! Authentication relies on the use of the Authorization header, in one of two modes.
+//!
+//! In Basic mode, the username is the caller agent id, the password used at the
+//! time of the initial creation is recorded by the server and must then be reused
+//! for all subsequent requests. Only a salted hash of the password is kept, and it
+//! can be replaced by posting a new `AuthToken` to `/v1/agents/me/auth_token`.
+//!
+//! In SdaSignature mode, the credentials are the base64 encoding of a JSON
+//! `SignedRequestDigest`, signed with the verification key of the caller agent
+//! and covering the method, path (including query), body hash and time of the
+//! request, together with a random nonce. The server remembers the signatures
+//! it accepted for as long as their timestamp is acceptable, and refuses
+//! to see any of them twice.
+//!
+//! Which modes are accepted is up to the `Authenticator` of the server.
 //!
 
 extern crate data_encoding;
@@ -80,14 +125,18 @@ extern crate serde_json;
 extern crate slog;
 #[macro_use]
 extern crate slog_scope;
+extern crate sodiumoxide;
 
 use std::sync;
+use std::io::Read;
 use std::net::ToSocketAddrs;
 use std::str::FromStr;
 
 use rouille::{Request, Response};
+use sodiumoxide::crypto::hash::sha256;
 
 use sda_protocol::*;
+use sda_server::auth::Credentials;
 use sda_server::stores::AuthToken;
 use errors::*;
 
@@ -133,56 +182,88 @@ pub fn listen<A>(addr: A, server: sync::Arc<sda_server::SdaServerService>) -> !
 /// This is useful for integration tests, not meant to be used as the main API.
 pub fn handle(server: &sda_server::SdaServerService, req: &Request) -> Response {
     debug!("Incoming {} {}", req.method(), req.raw_url());
-    wrap! { req, router! { req,
-        (GET)  (/v1/ping) => { H(&server).ping(req) },
+    wrap! { req, read_body(req).and_then(|body| route(server, req, &body)) }
+}
+
+fn route(server: &sda_server::SdaServerService, req: &Request, body: &[u8]) -> Result<Response> {
+    router! { req,
+        (GET)  (/v1/ping) => { H(&server, body).ping(req) },
 
-        (GET)  (/v1/agents/{id: AgentId}) => { H(&server).get_agent(&id, req) },
-        (POST) (/v1/agents/me) => { H(&server).create_agent(req) },
+        (GET)  (/v1/agents/{id: AgentId}) => { H(&server, body).get_agent(&id, req) },
+        (POST) (/v1/agents/me) => { H(&server, body).create_agent(req) },
+        (POST) (/v1/agents/me/auth_token) => { H(&server, body).rotate_auth_token(req) },
+        (POST) (/v1/agents/me/verification_keys) => { H(&server, body).rotate_verification_key(req) },
+        (GET)  (/v1/agents/{id: AgentId}/verification_keys) =>
+            { H(&server, body).get_verification_key_history(&id, req) },
 
-        (GET)  (/v1/agents/{id: AgentId}/profile) => { H(&server).get_profile(&id, req) },
-        (POST) (/v1/agents/me/profile) => { H(&server).upsert_profile(req) },
+        (GET)  (/v1/agents/{id: AgentId}/profile) => { H(&server, body).get_profile(&id, req) },
+        (POST) (/v1/agents/me/profile) => { H(&server, body).upsert_profile(req) },
 
         (GET)    (/v1/agents/any/keys/{id: EncryptionKeyId}) =>
-            { H(&server).get_encryption_key(&id, req) },
-        (POST)   (/v1/agents/me/keys) => { H(&server).create_encryption_key(req) },
+            { H(&server, body).get_encryption_key(&id, req) },
+        (POST)   (/v1/agents/me/keys) => { H(&server, body).create_encryption_key(req) },
+        (GET)    (/v1/agents/{id: AgentId}/keys) => { H(&server, body).list_encryption_keys(&id, req) },
+        (POST)   (/v1/agents/me/keys/revocations) => { H(&server, body).revoke_encryption_key(req) },
 
-        (POST)  (/v1/aggregations) => { H(&server).create_aggregation(req) },
-        (GET)   (/v1/aggregations) => { H(&server).list_aggregations(req) },
-        (GET)   (/v1/aggregations/{id: AggregationId}) => { H(&server).get_aggregation(&id, req) },
-        (DELETE)(/v1/aggregations/{id: AggregationId}) => { H(&server).delete_aggregation(&id, req) },
+        (POST)  (/v1/aggregations) => { H(&server, body).create_aggregation(req) },
+        (GET)   (/v1/aggregations) => { H(&server, body).list_aggregations(req) },
+        (GET)   (/v1/aggregations/{id: AggregationId}) => { H(&server, body).get_aggregation(&id, req) },
+        (DELETE)(/v1/aggregations/{id: AggregationId}) => { H(&server, body).delete_aggregation(&id, req) },
 
         (GET)   (/v1/aggregations/{id: AggregationId}/committee/suggestions) =>
-            { H(&server).suggest_committee(&id, req) },
-        (POST)  (/v1/aggregations/implied/committee) => { H(&server).create_committee(req) },
+            { H(&server, body).suggest_committee(&id, req) },
+        (POST)  (/v1/aggregations/implied/committee) => { H(&server, body).create_committee(req) },
         (GET)   (/v1/aggregations/{id: AggregationId}/committee) =>
-            { H(&server).get_committee(&id, req) },
-
-        (POST)  (/v1/aggregations/participations) => { H(&server).create_participation(req) },
+            { H(&server, body).get_committee(&id, req) },
+
+        (POST)  (/v1/aggregations/participations) => { H(&server, body).create_participation(req) },
+        (POST)  (/v1/aggregations/enrollments) => { H(&server, body).create_enrollment(req) },
+        (GET)   (/v1/aggregations/{id: AggregationId}/cohort) =>
+            { H(&server, body).get_masking_cohort(&id, req) },
+        (POST)  (/v1/aggregations/{id: AggregationId}/cohort) =>
+            { H(&server, body).create_masking_cohort(&id, req) },
         (GET)   (/v1/aggregations/{id: AggregationId}/status) =>
-            { H(&server).get_aggregation_status(&id, req) },
+            { H(&server, body).get_aggregation_status(&id, req) },
+        (POST)  (/v1/aggregations/{id: AggregationId}/close) =>
+            { H(&server, body).close_aggregation(&id, req) },
+        (POST)  (/v1/aggregations/{id: AggregationId}/archive) =>
+            { H(&server, body).archive_aggregation(&id, req) },
 
-        (POST)  (/v1/aggregations/implied/snapshot) => { H(&server).create_snapshot(req) },
+        (POST)  (/v1/aggregations/implied/snapshot) => { H(&server, body).create_snapshot(req) },
 
         // FIXME. should we revisit these 3 ? the urls feel a bit awkward
-        (GET)   (/v1/aggregations/any/jobs) => { H(&server).get_clerking_job(req) },
-        (POST)  (/v1/aggregations/implied/jobs/{id}/result) => { H(&server).create_clerking_result(&id, req) },
+        (GET)   (/v1/aggregations/any/jobs) => { H(&server, body).get_clerking_job(req) },
+        (POST)  (/v1/aggregations/any/jobs/ping) => { H(&server, body).clerk_ping(req) },
+        (POST)  (/v1/agents/me/clerk_registration) => { H(&server, body).create_clerk_registration(req) },
+        (GET)   (/v1/agents/{id: AgentId}/clerk_registration) =>
+            { H(&server, body).get_clerk_registration(&id, req) },
+        (GET)   (/v1/aggregations/any/seats) => { H(&server, body).list_pending_seats(req) },
+        (POST)  (/v1/aggregations/implied/seats) => { H(&server, body).create_seat_acceptance(req) },
+        (POST)  (/v1/aggregations/implied/jobs/{id}/result) => { H(&server, body).create_clerking_result(&id, req) },
 
         (GET)   (/v1/aggregations/{aid}/snapshots/{sid}/result) =>
-            { H(&server).get_snapshot_result(&aid, &sid, req) },
+            { H(&server, body).get_snapshot_result(&aid, &sid, req) },
+        (GET)   (/v1/aggregations/{aid}/snapshots/{sid}/participations) =>
+            { H(&server, body).get_snapshot_participations(&aid, &sid, req) },
+        (GET)   (/v1/aggregations/{aid}/snapshots/{sid}/reveal) =>
+            { H(&server, body).get_reveal_contributions(&aid, &sid, req) },
+        (POST)  (/v1/aggregations/{aid: AggregationId}/snapshots/{sid: SnapshotId}/reveal) =>
+            { H(&server, body).create_reveal_contribution(&aid, &sid, req) },
 
         _ => {
             error!("Route not found: {} {}", req.method(), req.raw_url());
             Ok(Response::empty_404())
         }
-    } }
+    }
 }
 
-struct H<'a>(&'a sda_server::SdaServerService);
+/// Handlers, given the body of the request as it was already read.
+struct H<'a>(&'a sda_server::SdaServerService, &'a [u8]);
 
 impl<'a> H<'a> {
     fn caller(&self, req: &Request) -> Result<Agent> {
-        let auth = auth_token(&req)?;
-        Ok((self.0).0.check_auth_token(&auth)?)
+        let credentials = credentials(req, self.1)?;
+        Ok((self.0).0.authenticate(&credentials)?)
     }
 
     fn ping(&self, _req: &Request) -> Result<Response> {
@@ -190,13 +271,18 @@ impl<'a> H<'a> {
     }
 
     fn create_agent(&self, req: &Request) -> Result<Response> {
-        let auth = auth_token(&req)?;
-        let agent: Agent = read_json(&req)?;
-        if agent.id != auth.id {
+        let credentials = credentials(req, self.1)?;
+        let agent: Agent = read_json(self.1)?;
+        if agent.id != *credentials.agent() {
             return Ok(client_error("inconsistent agent ids"));
         }
+        (self.0).0.enroll(&agent, &credentials)?;
         self.0.create_agent(&agent, &agent)?;
-        (self.0).0.upsert_auth_token(&auth)?;
+        send_empty_201()
+    }
+
+    fn rotate_auth_token(&self, req: &Request) -> Result<Response> {
+        (self.0).0.rotate_auth_token(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -204,12 +290,21 @@ impl<'a> H<'a> {
         send_json_option(self.0.get_agent(&self.caller(req)?, id)?)
     }
 
+    fn rotate_verification_key(&self, req: &Request) -> Result<Response> {
+        self.0.rotate_verification_key(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
+    fn get_verification_key_history(&self, id: &AgentId, req: &Request) -> Result<Response> {
+        send_json_option(Some(self.0.get_verification_key_history(&self.caller(req)?, id)?))
+    }
+
     fn get_profile(&self, id: &AgentId, req: &Request) -> Result<Response> {
         send_json_option(self.0.get_profile(&self.caller(req)?, &id)?)
     }
 
     fn upsert_profile(&self, req: &Request) -> Result<Response> {
-        self.0.upsert_profile(&self.caller(req)?, &read_json(req)?)?;
+        self.0.upsert_profile(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -218,7 +313,16 @@ impl<'a> H<'a> {
     }
 
     fn create_encryption_key(&self, req: &Request) -> Result<Response> {
-        self.0.create_encryption_key(&self.caller(req)?, &read_json(req)?)?;
+        self.0.create_encryption_key(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
+    fn list_encryption_keys(&self, owner: &AgentId, req: &Request) -> Result<Response> {
+        send_json_option(Some(self.0.list_encryption_keys(&self.caller(req)?, owner)?))
+    }
+
+    fn revoke_encryption_key(&self, req: &Request) -> Result<Response> {
+        self.0.revoke_encryption_key(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -227,7 +331,7 @@ impl<'a> H<'a> {
     }
 
     fn create_aggregation(&self, req: &Request) -> Result<Response> {
-        self.0.create_aggregation(&self.caller(req)?, &read_json(&req)?)?;
+        self.0.create_aggregation(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -250,11 +354,25 @@ impl<'a> H<'a> {
     }
 
     fn suggest_committee(&self, id: &AggregationId, req: &Request) -> Result<Response> {
-        send_json(self.0.suggest_committee(&self.caller(req)?, &id)?)
+        let flag = |name: &str| req.get_param(name).map_or(false, |p| p == "true");
+        let criteria = CommitteeCriteria {
+            count: match req.get_param("count") {
+                Some(p) => Some(p.parse().map_err(|_| "Invalid count")?),
+                None => None,
+            },
+            max_staleness: match req.get_param("max_staleness") {
+                Some(p) => Some(p.parse().map_err(|_| "Invalid max_staleness")?),
+                None => None,
+            },
+            suitable_keys: flag("suitable_keys"),
+            exclude_recipient: flag("exclude_recipient"),
+            randomize: flag("randomize"),
+        };
+        send_json(self.0.suggest_committee(&self.caller(req)?, &id, &criteria)?)
     }
 
     fn create_committee(&self, req: &Request) -> Result<Response> {
-        self.0.create_committee(&self.caller(req)?, &read_json(&req)?)?;
+        self.0.create_committee(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -262,8 +380,30 @@ impl<'a> H<'a> {
         send_json_option(self.0.get_committee(&self.caller(req)?, id)?)
     }
 
+    fn get_snapshot_participations(&self,
+                                   aggregation: &AggregationId,
+                                   snapshot: &SnapshotId,
+                                   req: &Request)
+                                   -> Result<Response> {
+        send_json(self.0.get_snapshot_participations(&self.caller(req)?, aggregation, snapshot)?)
+    }
+
     fn create_participation(&self, req: &Request) -> Result<Response> {
-        self.0.create_participation(&self.caller(req)?, &read_json(&req)?)?;
+        self.0.create_participation(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
+    fn create_enrollment(&self, req: &Request) -> Result<Response> {
+        self.0.create_enrollment(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
+    fn get_masking_cohort(&self, id: &AggregationId, req: &Request) -> Result<Response> {
+        send_json_option(self.0.get_masking_cohort(&self.caller(req)?, id)?)
+    }
+
+    fn create_masking_cohort(&self, id: &AggregationId, req: &Request) -> Result<Response> {
+        self.0.create_masking_cohort(&self.caller(req)?, id)?;
         send_empty_201()
     }
 
@@ -271,8 +411,18 @@ impl<'a> H<'a> {
         send_json_option(self.0.get_aggregation_status(&self.caller(req)?, id)?)
     }
 
+    fn close_aggregation(&self, id: &AggregationId, req: &Request) -> Result<Response> {
+        self.0.close_aggregation(&self.caller(req)?, id)?;
+        send_empty_201()
+    }
+
+    fn archive_aggregation(&self, id: &AggregationId, req: &Request) -> Result<Response> {
+        self.0.archive_aggregation(&self.caller(req)?, id)?;
+        send_empty_201()
+    }
+
     fn create_snapshot(&self, req: &Request) -> Result<Response> {
-        self.0.create_snapshot(&self.caller(req)?, &read_json(&req)?)?;
+        self.0.create_snapshot(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -281,8 +431,31 @@ impl<'a> H<'a> {
         send_json_option(self.0.get_clerking_job(&caller, &caller.id)?)
     }
 
+    fn clerk_ping(&self, req: &Request) -> Result<Response> {
+        send_json_option(Some(self.0.clerk_ping(&self.caller(req)?)?))
+    }
+
+    fn create_clerk_registration(&self, req: &Request) -> Result<Response> {
+        self.0.create_clerk_registration(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
+    fn get_clerk_registration(&self, id: &AgentId, req: &Request) -> Result<Response> {
+        send_json_option(self.0.get_clerk_registration(&self.caller(req)?, id)?)
+    }
+
+    fn list_pending_seats(&self, req: &Request) -> Result<Response> {
+        let caller = self.caller(req)?;
+        send_json(self.0.list_pending_seats(&caller, &caller.id)?)
+    }
+
+    fn create_seat_acceptance(&self, req: &Request) -> Result<Response> {
+        self.0.create_seat_acceptance(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+
     fn create_clerking_result(&self, _id: &ClerkingJobId, req: &Request) -> Result<Response> {
-        self.0.create_clerking_result(&self.caller(req)?, &read_json(&req)?)?;
+        self.0.create_clerking_result(&self.caller(req)?, &read_json(self.1)?)?;
         send_empty_201()
     }
 
@@ -293,6 +466,47 @@ impl<'a> H<'a> {
                            -> Result<Response> {
         send_json_option(self.0.get_snapshot_result(&self.caller(req)?, aggregation, snapshot)?)
     }
+
+    fn get_reveal_contributions(&self,
+                                aggregation: &AggregationId,
+                                snapshot: &SnapshotId,
+                                req: &Request)
+                                -> Result<Response> {
+        send_json(self.0.get_reveal_contributions(&self.caller(req)?, aggregation, snapshot)?)
+    }
+
+    fn create_reveal_contribution(&self,
+                                  _aggregation: &AggregationId,
+                                  _snapshot: &SnapshotId,
+                                  req: &Request)
+                                  -> Result<Response> {
+        self.0.create_reveal_contribution(&self.caller(req)?, &read_json(self.1)?)?;
+        send_empty_201()
+    }
+}
+
+fn credentials(req: &Request, body: &[u8]) -> Result<Credentials> {
+    let header = req.header("Authorization").ok_or("Mandatory Authorization header")?.trim();
+    if header.starts_with("SdaSignature ") {
+        let digest = signed_request_digest(req, body)?;
+        Ok(Credentials::Signature(digest))
+    } else {
+        Ok(Credentials::Token(auth_token(req)?))
+    }
+}
+
+fn signed_request_digest(req: &Request, body: &[u8]) -> Result<SignedRequestDigest> {
+    let header = req.header("Authorization").ok_or("Mandatory Authorization header")?.trim();
+    let value = header.replace("SdaSignature ", "");
+    let decoded = data_encoding::base64::decode(value.as_bytes())
+        .map_err(|e| format!("Invalid Auth header (base64: {:?})",e))?;
+    let digest: SignedRequestDigest = serde_json::from_slice(&decoded)?;
+    // the signature only vouches for the request it was made for
+    if digest.body.method != req.method() || digest.body.path != req.raw_url()
+        || digest.body.body_hash != byte_arrays::B32(sha256::hash(body).0) {
+        Err(SdaError::from(SdaErrorKind::InvalidCredentials))?
+    }
+    Ok(digest)
 }
 
 fn auth_token(req: &Request) -> Result<AuthToken> {
@@ -327,8 +541,20 @@ fn send_empty_201() -> Result<Response> {
     Ok(Response::empty_404().with_status_code(201))
 }
 
-fn read_json<T: ::serde::Deserialize>(req: &Request) -> Result<T> {
-    Ok(serde_json::from_reader(req.data().ok_or("Expected a body")?)?)
+/// Read the whole body of a request, as signatures cover it.
+fn read_body(req: &Request) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    if let Some(mut data) = req.data() {
+        data.read_to_end(&mut body).map_err(|e| format!("Could not read body: {}", e))?;
+    }
+    Ok(body)
+}
+
+fn read_json<T: ::serde::Deserialize>(body: &[u8]) -> Result<T> {
+    if body.is_empty() {
+        Err("Expected a body")?
+    }
+    Ok(serde_json::from_slice(body)?)
 }
 
 fn send_json<T: ::serde::Serialize>(t: T) -> Result<Response> {
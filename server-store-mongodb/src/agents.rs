@@ -10,6 +10,24 @@ struct AgentDocument {
     profile: Option<Profile>,
     #[serde(default)]
     keys: Vec<Labelled<EncryptionKeyId, SignedEncryptionKey>>,
+    #[serde(default)]
+    revocations: Vec<Labelled<EncryptionKeyId, SignedEncryptionKeyRevocation>>,
+    #[serde(default)]
+    verification_key_rotations: Vec<SignedVerificationKeyRotation>,
+    #[serde(default)]
+    last_seen: Option<u64>,
+    #[serde(default)]
+    clerk_registration: Option<SignedClerkRegistration>,
+}
+
+impl AgentDocument {
+    fn unrevoked_keys(&self) -> Vec<EncryptionKeyId> {
+        self.keys
+            .iter()
+            .map(|it| it.id)
+            .filter(|id| !self.revocations.iter().any(|r| r.id == *id))
+            .collect()
+    }
 }
 
 pub struct MongoAgentsStore(Dao<AgentId, AgentDocument>);
@@ -40,6 +58,18 @@ impl stores::AgentsStore for MongoAgentsStore {
             .map(|opt| opt.map(|ad| ad.agent))
     }
 
+    fn rotate_verification_key(&self, rotation: &SignedVerificationKeyRotation) -> SdaServerResult<()> {
+        self.0.modify_by_id(&rotation.agent,
+                            d!("$set" => d!("agent.verification_key" => to_doc(&rotation.new_key)?),
+                               "$push" => d!("verification_key_rotations" => to_doc(rotation)?)))
+    }
+
+    fn get_verification_key_history(&self, id: &AgentId) -> SdaServerResult<Vec<SignedVerificationKeyRotation>> {
+        self.0
+            .get_by_id(id)
+            .map(|opt| opt.map(|ad| ad.verification_key_rotations).unwrap_or(vec![]))
+    }
+
     fn upsert_profile(&self, profile: &Profile) -> SdaServerResult<()> {
         self.0.modify_by_id(&profile.owner,
                             d!("$set" => d!("profile" => to_doc(profile)?)))
@@ -69,17 +99,55 @@ impl stores::AgentsStore for MongoAgentsStore {
         })
     }
 
-    fn suggest_committee(&self) -> SdaServerResult<Vec<ClerkCandidate>> {
+    fn list_encryption_keys(&self, owner: &AgentId) -> SdaServerResult<Vec<EncryptionKeyId>> {
         self.0
+            .get_by_id(owner)
+            .map(|opt| opt.map(|ad| ad.unrevoked_keys()).unwrap_or(vec![]))
+    }
+
+    fn revoke_encryption_key(&self, revocation: &SignedEncryptionKeyRevocation) -> SdaServerResult<()> {
+        self.0.modify_by_id(&revocation.signer,
+                            d!("$addToSet" => d!("revocations" => to_doc(&label(&revocation.key, revocation))?)))
+    }
+
+    fn get_encryption_key_revocation(&self,
+                                     key: &EncryptionKeyId)
+                                     -> SdaServerResult<Option<SignedEncryptionKeyRevocation>> {
+        let selector = d!("revocations.id" => to_bson(key)?);
+        self.0.get(selector).map(|opt| {
+            opt.and_then(|ad| ad.revocations.into_iter().find(|r| r.id == *key))
+                .map(|r| r.body)
+        })
+    }
+
+    fn upsert_clerk_registration(&self, registration: &SignedClerkRegistration) -> SdaServerResult<()> {
+        self.0.modify_by_id(&registration.body.clerk,
+                            d!("$set" => d!("clerk_registration" => to_doc(registration)?)))
+    }
+
+    fn get_clerk_registration(&self, clerk: &AgentId) -> SdaServerResult<Option<SignedClerkRegistration>> {
+        self.0
+            .get_by_id(clerk)
+            .map(|opt| opt.and_then(|ad| ad.clerk_registration))
+    }
+
+    fn record_clerk_seen(&self, clerk: &AgentId, now: u64) -> SdaServerResult<()> {
+        self.0.modify_by_id(clerk, d!("$set" => d!("last_seen" => to_bson(&now)?)))
+    }
+
+    fn suggest_committee(&self) -> SdaServerResult<Vec<ClerkCandidate>> {
+        let candidates = self.0
             .find(d!())?
             .map(|res| {
                 res.map(|ad| {
                     ClerkCandidate {
                         id: ad.id,
-                        keys: ad.keys.iter().map(|it| it.id).collect(),
+                        keys: ad.unrevoked_keys(),
+                        last_seen: ad.last_seen,
                     }
                 })
             })
-            .collect()
+            .collect::<SdaServerResult<Vec<ClerkCandidate>>>()?;
+        Ok(candidates.into_iter().filter(|c| !c.keys.is_empty()).collect())
     }
 }
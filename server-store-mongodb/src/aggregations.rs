@@ -3,11 +3,38 @@ use sda_server::stores;
 use sda_server::errors::*;
 use {to_bson, to_doc, Dao, from_bson};
 
+/// Error code of writes breaking a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct AggregationDocument {
     id: AggregationId,
     aggregation: Aggregation,
+    signature: Signature,
+    #[serde(default)]
+    signature_key: Option<VerificationKeyId>,
     committee: Option<Committee>,
+    #[serde(default)]
+    committee_signature: Option<Signature>,
+    #[serde(default)]
+    committee_signature_key: Option<VerificationKeyId>,
+    masking_cohort: Option<MaskingCohort>,
+    #[serde(default)]
+    state: Option<AggregationState>,
+    #[serde(default)]
+    seat_acceptances: Vec<SignedSeatAcceptance>,
+    #[serde(default)]
+    privacy_budget: Option<PrivacyBudget>,
+    /// Number of participations stored under the keep latest policy, used to order them.
+    #[serde(default)]
+    participation_sequence: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct EnrollmentDocument {
+    aggregation: AggregationId,
+    participant: AgentId,
+    enrollment: Enrollment,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -15,19 +42,36 @@ struct SnapshotDocument {
     id: SnapshotId,
     snapshot: Snapshot,
     mask: Option<Vec<Encryption>>,
+    #[serde(default)]
+    reveal_contributions: Vec<SignedRevealContribution>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct ParticipationDocument {
     id: SnapshotId,
     participation: Participation,
+    signature: Signature,
+    #[serde(default)]
+    signature_key: Option<VerificationKeyId>,
     #[serde(default)]
     snapshots: Vec<SnapshotId>,
+    /// Set once a later participation of the same participant replaces this one.
+    #[serde(default)]
+    superseded: bool,
+    /// Aggregation and participant, only set under the reject policy so that the unique index on it
+    /// refuses a second participation even when both arrive at once.
+    #[serde(default)]
+    exclusive: Option<String>,
+    /// Order of arrival among the participations of the aggregation, only set under the keep latest
+    /// policy so that a participation only ever supersedes strictly older ones.
+    #[serde(default)]
+    sequence: Option<i64>,
 }
 
 pub struct MongoAggregationsStore {
     aggregations: Dao<AggregationId, AggregationDocument>,
     participations: Dao<ParticipationId, ParticipationDocument>,
+    enrollments: Dao<AgentId, EnrollmentDocument>,
     snapshots: Dao<SnapshotId, SnapshotDocument>,
 }
 
@@ -37,10 +81,13 @@ impl MongoAggregationsStore {
         let store = MongoAggregationsStore {
             aggregations: Dao::new(db.collection("aggregations")),
             participations: Dao::new(db.collection("participations")),
+            enrollments: Dao::new(db.collection("enrollments")),
             snapshots: Dao::new(db.collection("snapshots")),
         };
         store.aggregations.ensure_index(d!("id" => 1), true)?;
         store.participations.ensure_index(d!("id" => 1), true)?;
+        store.participations.ensure_sparse_index(d!("exclusive" => 1), true)?;
+        store.enrollments.ensure_index(d!("aggregation" => 1, "participant" => 1), true)?;
         store.snapshots.ensure_index(d!("id" => 1), true)?;
         Ok(store)
     }
@@ -71,34 +118,201 @@ impl stores::AggregationsStore for MongoAggregationsStore {
             .collect()
     }
 
-    fn create_aggregation(&self, aggregation: &Aggregation) -> SdaServerResult<()> {
-        self.aggregations.modisert_by_id(&aggregation.id,
-                                         d!("$set" => d!("id" => to_bson(&aggregation.id)?, 
-                            "aggregation" => to_doc(aggregation)?)))
+    fn create_aggregation(&self, aggregation: &SignedAggregation) -> SdaServerResult<()> {
+        // the signer is the recipient, so only the signature needs to be kept besides the aggregation;
+        // existing aggregations are never overwritten
+        self.aggregations.modisert_by_id(&aggregation.body.id,
+                                         d!("$setOnInsert" => d!("id" => to_bson(&aggregation.body.id)?, 
+                            "aggregation" => to_doc(&aggregation.body)?,
+                            "signature" => to_bson(&aggregation.signature)?,
+                            "signature_key" => to_bson(&aggregation.key)?)))
     }
 
-    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<Aggregation>> {
-        self.aggregations.get_by_id(aggregation).map(|opt| opt.map(|a| a.aggregation))
+    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<SignedAggregation>> {
+        self.aggregations.get_by_id(aggregation).map(|opt| {
+            opt.map(|a| {
+                SignedAggregation {
+                    signer: a.aggregation.recipient,
+                    signature: a.signature,
+                    key: a.signature_key,
+                    body: a.aggregation,
+                }
+            })
+        })
     }
 
     fn delete_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
+        // masks and reveal contributions are kept with the snapshots
+        m!(self.snapshots.coll.delete_many(d!("snapshot.aggregation" => to_bson(aggregation)?), None))?;
+        m!(self.participations.coll.delete_many(d!("participation.aggregation" => to_bson(aggregation)?), None))?;
+        m!(self.enrollments.coll.delete_many(d!("aggregation" => to_bson(aggregation)?), None))?;
+        // state, committee, seat acceptances and masking cohort are kept with the aggregation
         m!(self.aggregations.coll.delete_one(d!("id" => m!(to_bson(aggregation))?), None))?;
         Ok(())
     }
 
-    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<Committee>> {
-        self.aggregations.get_by_id(owner).map(|opt| opt.and_then(|a| a.committee))
+    fn get_aggregation_state(&self, aggregation: &AggregationId) -> SdaServerResult<Option<AggregationState>> {
+        self.aggregations.get_by_id(aggregation).map(|opt| opt.and_then(|a| a.state))
     }
 
-    fn create_committee(&self, committee: &Committee) -> SdaServerResult<()> {
-        self.aggregations.modify_by_id(&committee.aggregation,
-                                       d!("$set" => d!("committee" => to_doc(committee)?)))
+    fn set_aggregation_state(&self, aggregation: &AggregationId, state: AggregationState) -> SdaServerResult<()> {
+        self.aggregations.modify_by_id(aggregation, d!("$set" => d!("state" => to_bson(&state)?)))
     }
 
-    fn create_participation(&self, participation: &Participation) -> SdaServerResult<()> {
-        self.participations.modisert_by_id(&participation.id,
-                                           d!("$set" => d!("id" => to_bson(&participation.id)?, 
-                            "participation" => to_doc(participation)?)))
+    fn get_privacy_budget(&self, aggregation: &AggregationId) -> SdaServerResult<Option<PrivacyBudget>> {
+        self.aggregations.get_by_id(aggregation).map(|opt| opt.and_then(|a| a.privacy_budget))
+    }
+
+    fn update_privacy_budget(&self, aggregation: &AggregationId, previous: Option<&PrivacyBudget>, budget: &PrivacyBudget) -> SdaServerResult<bool> {
+        // matching on the previous budget makes concurrent snapshots spend it one at a time
+        let previous = match previous {
+            Some(previous) => to_bson(previous)?,
+            None => ::bson::Bson::Null,
+        };
+        let updated = self.aggregations.get_and_modify(d!("id" => to_bson(aggregation)?, "privacy_budget" => previous),
+                                                       d!("$set" => d!("privacy_budget" => to_bson(budget)?)))?;
+        Ok(updated.is_some())
+    }
+
+    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<SignedCommittee>> {
+        self.aggregations.get_by_id(owner).map(|opt| {
+            opt.and_then(|a| {
+                let recipient = a.aggregation.recipient;
+                match (a.committee, a.committee_signature) {
+                    (Some(committee), Some(signature)) => Some(SignedCommittee {
+                        signer: recipient,
+                        signature: signature,
+                        key: a.committee_signature_key,
+                        body: committee,
+                    }),
+                    _ => None,
+                }
+            })
+        })
+    }
+
+    fn create_committee(&self, committee: &SignedCommittee) -> SdaServerResult<()> {
+        // signed by the recipient as well
+        self.aggregations.modify_by_id(&committee.body.aggregation,
+                                       d!("$set" => d!("committee" => to_doc(&committee.body)?,
+                                                       "committee_signature" => to_bson(&committee.signature)?,
+                                                       "committee_signature_key" => to_bson(&committee.key)?)))
+    }
+
+    fn create_seat_acceptance(&self, acceptance: &SignedSeatAcceptance) -> SdaServerResult<()> {
+        self.aggregations.modify_by_id(&acceptance.body.aggregation,
+                                       d!("$push" => d!("seat_acceptances" => to_doc(acceptance)?)))
+    }
+
+    fn list_seat_acceptances(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<SignedSeatAcceptance>> {
+        self.aggregations
+            .get_by_id(aggregation)
+            .map(|opt| opt.map(|a| a.seat_acceptances).unwrap_or(vec![]))
+    }
+
+    fn create_participation(&self,
+                            participation: &SignedParticipation,
+                            policy: ParticipationPolicy)
+                            -> SdaServerResult<()> {
+        // the signer is the participant, so only the signature needs to be kept besides the participation
+        let mut fields = d!("id" => to_bson(&participation.body.id)?,
+                            "participation" => to_doc(&participation.body)?,
+                            "signature" => to_bson(&participation.signature)?,
+                            "signature_key" => to_bson(&participation.key)?);
+        if policy == ParticipationPolicy::Reject {
+            fields.insert("exclusive",
+                          format!("{}/{}",
+                                  participation.body.aggregation.to_string(),
+                                  participation.body.participant.to_string()));
+        }
+        let sequence = if policy == ParticipationPolicy::KeepLatest {
+            let aggregation = self.aggregations.get_and_modify(d!("id" => to_bson(&participation.body.aggregation)?),
+                                                               d!("$inc" => d!("participation_sequence" => 1_i64)))?
+                .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Aggregation not found".to_string())))?;
+            let sequence = aggregation.participation_sequence + 1;
+            fields.insert("sequence", sequence);
+            Some(sequence)
+        } else {
+            None
+        };
+        // inserting only when the id is new leaves an existing participation untouched
+        let result = m!(self.participations.coll.update_one(d!("id" => to_bson(&participation.body.id)?),
+                                                            d!("$setOnInsert" => fields),
+                                                            Some(::mongodb::coll::options::UpdateOptions {
+                                                                upsert: Some(true),
+                                                                write_concern: None,
+                                                            })))?;
+        let duplicate = match result.write_exception {
+            None => false,
+            Some(::mongodb::coll::error::WriteException { write_error: Some(ref error), .. }) if error.code == DUPLICATE_KEY => true,
+            Some(exception) => Err(format!("Mongodb Error: {:?}", exception))?,
+        };
+        if duplicate || result.upserted_id.is_none() {
+            match self.participations.get_by_id(&participation.body.id)? {
+                Some(existing) => {
+                    if existing.participation != participation.body || existing.signature != participation.signature {
+                        Err(SdaError::from(SdaErrorKind::Invalid("Participation id already used".to_string())))?
+                    }
+                    return Ok(());
+                }
+                None => Err(SdaError::from(SdaErrorKind::Invalid("Participant already participated".to_string())))?,
+            }
+        }
+        if let Some(sequence) = sequence {
+            // both sides of two concurrent uploads run this once stored, so the older one is always
+            // superseded, either by the newer one or by itself
+            let same_participant = d!("participation.aggregation" => to_bson(&participation.body.aggregation)?,
+                                      "participation.participant" => to_bson(&participation.body.participant)?);
+            // participations stored before sequences were kept are older than any with one
+            let older = ::bson::Bson::Array(vec![
+                ::bson::Bson::Document(d!("sequence" => d!("$lt" => sequence))),
+                ::bson::Bson::Document(d!("sequence" => d!("$exists" => false))),
+            ]);
+            let mut earlier = same_participant.clone();
+            earlier.insert("$or", older);
+            m!(self.participations.coll.update_many(earlier, d!("$set" => d!("superseded" => true)), None))?;
+            let mut later = same_participant;
+            later.insert("sequence", d!("$gt" => sequence));
+            if m!(self.participations.coll.count(Some(later), None))? > 0 {
+                self.participations.modify_by_id(&participation.body.id, d!("$set" => d!("superseded" => true)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_enrollment(&self, enrollment: &Enrollment) -> SdaServerResult<()> {
+        let doc = EnrollmentDocument {
+            aggregation: enrollment.aggregation,
+            participant: enrollment.participant,
+            enrollment: enrollment.clone(),
+        };
+        m!(self.enrollments.coll.insert_one(to_doc(&doc)?, None))?;
+        Ok(())
+    }
+
+    fn get_enrollment(&self,
+                      aggregation: &AggregationId,
+                      participant: &AgentId)
+                      -> SdaServerResult<Option<Enrollment>> {
+        self.enrollments
+            .get(d!("aggregation" => to_bson(aggregation)?, "participant" => to_bson(participant)?))
+            .map(|opt| opt.map(|e| e.enrollment))
+    }
+
+    fn list_enrollments(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<Enrollment>> {
+        self.enrollments
+            .find(d!("aggregation" => to_bson(aggregation)?))?
+            .map(|res| res.map(|e| e.enrollment))
+            .collect()
+    }
+
+    fn create_masking_cohort(&self, cohort: &MaskingCohort) -> SdaServerResult<()> {
+        self.aggregations.modify_by_id(&cohort.aggregation,
+                                       d!("$set" => d!("masking_cohort" => to_doc(cohort)?)))
+    }
+
+    fn get_masking_cohort(&self, aggregation: &AggregationId) -> SdaServerResult<Option<MaskingCohort>> {
+        self.aggregations.get_by_id(aggregation).map(|opt| opt.and_then(|a| a.masking_cohort))
     }
 
     fn create_snapshot(&self, snapshot: &Snapshot) -> SdaServerResult<()> {
@@ -124,18 +338,36 @@ impl stores::AggregationsStore for MongoAggregationsStore {
     fn count_participations(&self, aggregation: &AggregationId) -> SdaServerResult<usize> {
         m!(self.participations
                 .coll
-                .count(Some(d!("participation.aggregation" => to_bson(aggregation)?)),
+                .count(Some(d!("participation.aggregation" => to_bson(aggregation)?,
+                               "superseded" => d!("$ne" => true))),
+                       None))
+            .map(|i| i as _)
+    }
+
+    fn count_new_participations(&self, aggregation: &AggregationId) -> SdaServerResult<usize> {
+        m!(self.participations
+                .coll
+                .count(Some(d!("participation.aggregation" => to_bson(aggregation)?,
+                               "superseded" => d!("$ne" => true),
+                               "snapshots.0" => d!("$exists" => false))),
                        None))
             .map(|i| i as _)
     }
 
     fn snapshot_participations(&self,
                                aggregation: &AggregationId,
-                               snapshot: &SnapshotId)
+                               snapshot: &SnapshotId,
+                               incremental: bool)
                                -> SdaServerResult<()> {
+        let mut selector = d!("participation.aggregation" => to_bson(aggregation)?,
+                              "superseded" => d!("$ne" => true));
+        if incremental {
+            // missing and empty snapshot lists alike
+            selector.insert("snapshots.0", d!("$exists" => false));
+        }
         m!(self.participations
             .coll
-            .update_many(d!("participation.aggregation" => to_bson(aggregation)?),
+            .update_many(selector,
                          d!("$addToSet" => d!("snapshots" => to_bson(snapshot)?)),
                          None))?;
         Ok(())
@@ -145,12 +377,17 @@ impl stores::AggregationsStore for MongoAggregationsStore {
         (&'b self,
          _aggregation: &AggregationId,
          snapshot: &SnapshotId)
-         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<Participation>> + 'a>>
+         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<SignedParticipation>> + 'a>>
         where 'b: 'a
     {
         Ok(Box::new(self.participations
             .find(d!("snapshots" => to_bson(snapshot)?))?
-            .map(|res| res.map(|pd| pd.participation))))
+            .map(|res| res.map(|pd| Signed {
+                signature: pd.signature,
+                signer: pd.participation.participant,
+                key: pd.signature_key,
+                body: pd.participation,
+            }))))
     }
 
     fn count_participations_snapshot(&self,
@@ -204,4 +441,13 @@ impl stores::AggregationsStore for MongoAggregationsStore {
     fn get_snapshot_mask(&self, snapshot: &SnapshotId) -> SdaServerResult<Option<Vec<Encryption>>> {
         self.snapshots.get_by_id(snapshot).map(|opt| opt.and_then(|s| s.mask))
     }
+
+    fn create_reveal_contribution(&self, contribution: &SignedRevealContribution) -> SdaServerResult<()> {
+        self.snapshots.modify_by_id(&contribution.body.snapshot,
+                                    d!("$push" => d!("reveal_contributions" => to_doc(contribution)?)))
+    }
+
+    fn list_reveal_contributions(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<SignedRevealContribution>> {
+        self.snapshots.get_by_id(snapshot).map(|opt| opt.map(|s| s.reveal_contributions).unwrap_or_else(Vec::new))
+    }
 }
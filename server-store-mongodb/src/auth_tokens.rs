@@ -3,44 +3,74 @@ use sda_server::stores;
 use sda_server::errors::*;
 use {to_bson, to_doc, Dao};
 
-use sda_server::stores::AuthToken;
+use sda_server::stores::{AuthToken, HashedAuthToken};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct AuthTokenDocument {
+    id: AgentId,
+    hashed_auth_token: HashedAuthToken,
+}
+
+/// Document as stored by earlier versions, with the token in clear.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct LegacyAuthTokenDocument {
     id: AgentId,
     auth_token: AuthToken,
 }
 
-pub struct MongoAuthTokensStore(Dao<AgentId, AuthTokenDocument>);
+pub struct MongoAuthTokensStore {
+    tokens: Dao<AgentId, AuthTokenDocument>,
+    legacy_tokens: Dao<AgentId, LegacyAuthTokenDocument>,
+}
 
 impl MongoAuthTokensStore {
     pub fn new(db: &::mongodb::db::Database) -> SdaServerResult<MongoAuthTokensStore> {
         use mongodb::db::ThreadedDatabase;
         let dao = Dao::new(db.collection("auth_tokens"));
         dao.ensure_index(d!("id" => 1), true)?;
-        Ok(MongoAuthTokensStore(dao))
+        Ok(MongoAuthTokensStore {
+            tokens: dao,
+            legacy_tokens: Dao::new(db.collection("auth_tokens")),
+        })
     }
 }
 
 impl stores::BaseStore for MongoAuthTokensStore {
     fn ping(&self) -> SdaServerResult<()> {
-        self.0.ping()
+        self.tokens.ping()
     }
 }
 
 impl stores::AuthTokensStore for MongoAuthTokensStore {
-    fn upsert_auth_token(&self, token: &AuthToken) -> SdaServerResult<()> {
-        self.0.modisert_by_id(&token.id, d!("$set" => d! ( "id" => to_bson(&token.id)?, "auth_token" => to_doc(&token)?) ))
+    fn upsert_auth_token(&self, token: &HashedAuthToken) -> SdaServerResult<()> {
+        self.tokens.modisert_by_id(&token.id, d!(
+            "$set" => d!("id" => to_bson(&token.id)?, "hashed_auth_token" => to_doc(&token)?),
+            "$unset" => d!("auth_token" => "")))
     }
 
-    fn get_auth_token(&self, id: &AgentId) -> SdaServerResult<Option<AuthToken>> {
-        self.0
-            .get_by_id(id)
-            .map(|opt| opt.map(|ad| ad.auth_token))
+    fn get_auth_token(&self, id: &AgentId) -> SdaServerResult<Option<HashedAuthToken>> {
+        self.tokens
+            .get(d!("id" => to_bson(id)?, "hashed_auth_token" => d!("$exists" => true)))
+            .map(|opt| opt.map(|ad| ad.hashed_auth_token))
     }
 
     fn delete_auth_token(&self, id: &AgentId) -> SdaServerResult<()> {
-        m!(self.0.coll.delete_one(d!("id" => m!(to_bson(id))?), None))?;
+        m!(self.tokens.coll.delete_one(d!("id" => m!(to_bson(id))?), None))?;
         Ok(())
     }
+
+    fn migrate_auth_tokens(&self) -> SdaServerResult<usize> {
+        let legacy = self.legacy_tokens
+            .find(d!("auth_token" => d!("$exists" => true)))?
+            .collect::<SdaServerResult<Vec<LegacyAuthTokenDocument>>>()?;
+        for doc in &legacy {
+            // a token rotated since takes precedence over the one left in clear
+            if self.get_auth_token(&doc.id)?.is_none() {
+                self.upsert_auth_token(&HashedAuthToken::new(&doc.auth_token)?)?;
+            } else {
+                self.legacy_tokens.modify_by_id(&doc.id, d!("$unset" => d!("auth_token" => "")))?;
+            }
+        }
+        Ok(legacy.len())
+    }
 }
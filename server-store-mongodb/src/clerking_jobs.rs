@@ -8,7 +8,29 @@ struct ClerkingJobDocument {
     id: ClerkingJobId,
     clerking_job: ClerkingJob,
     done: bool,
-    result: Option<ClerkingResult>,
+    result: Option<SignedClerkingResult>,
+    #[serde(default)]
+    enqueued_at: u64,
+    #[serde(default)]
+    leased_until: Option<u64>,
+    #[serde(default)]
+    attempts: u32,
+    #[serde(default)]
+    abandoned: bool,
+}
+
+impl ClerkingJobDocument {
+    fn lease(self) -> stores::ClerkingJobLease {
+        stores::ClerkingJobLease {
+            job: self.id,
+            clerk: self.clerking_job.clerk,
+            snapshot: self.clerking_job.snapshot,
+            enqueued_at: self.enqueued_at,
+            leased_until: self.leased_until,
+            attempts: self.attempts,
+            abandoned: self.abandoned,
+        }
+    }
 }
 
 
@@ -30,19 +52,54 @@ impl stores::BaseStore for MongoClerkingJobsStore {
 }
 
 impl stores::ClerkingJobsStore for MongoClerkingJobsStore {
-    fn enqueue_clerking_job(&self, job: &ClerkingJob) -> SdaServerResult<()> {
+    fn enqueue_clerking_job(&self, job: &ClerkingJob, now: u64) -> SdaServerResult<()> {
         self.0.modisert_by_id(&job.id,
                               d!("$set" => d!("clerking_job" => to_doc(job)?,
                                       "id" => to_bson(&job.id)?,
-                                      "done" => false) ))
+                                      "done" => false,
+                                      "enqueued_at" => to_bson(&now)?,
+                                      "attempts" => 0,
+                                      "abandoned" => false) ))
     }
 
-    fn poll_clerking_job(&self, clerk: &AgentId) -> SdaServerResult<Option<ClerkingJob>> {
+    fn poll_clerking_job(&self,
+                         clerk: &AgentId,
+                         now: u64,
+                         leased_until: u64)
+                         -> SdaServerResult<Option<ClerkingJob>> {
+        // never leased, or lease expired
+        let available = ::bson::Bson::Array(vec![
+            ::bson::Bson::Document(d!("leased_until" => ::bson::Bson::Null)),
+            ::bson::Bson::Document(d!("leased_until" => d!("$lte" => to_bson(&now)?))),
+        ]);
         self.0
-            .get(d!("done" => false, "clerking_job.clerk" => to_bson(clerk)?))
+            .get_and_modify(d!("done" => false,
+                               "abandoned" => d!("$ne" => true),
+                               "clerking_job.clerk" => to_bson(clerk)?,
+                               "$or" => available),
+                            d!("$set" => d!("leased_until" => to_bson(&leased_until)?),
+                               "$inc" => d!("attempts" => 1)))
             .map(|opt| opt.map(|doc| doc.clerking_job))
     }
 
+    fn list_clerking_job_leases(&self) -> SdaServerResult<Vec<stores::ClerkingJobLease>> {
+        self.0
+            .find(d!("done" => false))?
+            .map(|res| res.map(|doc| doc.lease()))
+            .collect()
+    }
+
+    fn abandon_clerking_job(&self, job: &ClerkingJobId) -> SdaServerResult<()> {
+        self.0.modify_by_id(job, d!("$set" => d!("abandoned" => true)))
+    }
+
+    fn list_abandoned_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<stores::ClerkingJobLease>> {
+        self.0
+            .find(d!("clerking_job.snapshot" => to_bson(snapshot)?, "done" => false, "abandoned" => true))?
+            .map(|res| res.map(|doc| doc.lease()))
+            .collect()
+    }
+
     fn get_clerking_job(&self,
                         clerk: &AgentId,
                         job: &ClerkingJobId)
@@ -52,9 +109,15 @@ impl stores::ClerkingJobsStore for MongoClerkingJobsStore {
             .map(|opt| opt.map(|doc| doc.clerking_job))
     }
 
-    fn create_clerking_result(&self, result: &ClerkingResult) -> SdaServerResult<()> {
-        self.0.modisert_by_id(&result.job,
-                              d!("$set" => d!("result" => to_doc(result)?, "done" => true)))
+    fn create_clerking_result(&self, result: &SignedClerkingResult) -> SdaServerResult<()> {
+        // only a pending job takes a result, so a clerk cannot replace the one it returned
+        self.0
+            .get_and_modify(d!("id" => to_bson(&result.body.job)?,
+                               "clerking_job.clerk" => to_bson(&result.body.clerk)?,
+                               "done" => d!("$ne" => true)),
+                            d!("$set" => d!("result" => to_doc(result)?, "done" => true)))?
+            .ok_or("Job not found")?;
+        Ok(())
     }
 
     fn list_results(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<ClerkingJobId>> {
@@ -67,9 +130,15 @@ impl stores::ClerkingJobsStore for MongoClerkingJobsStore {
     fn get_result(&self,
                   snapshot: &SnapshotId,
                   job: &ClerkingJobId)
-                  -> SdaServerResult<Option<ClerkingResult>> {
+                  -> SdaServerResult<Option<SignedClerkingResult>> {
         self.0
             .get(d!("clerking_job.snapshot" => to_bson(snapshot)?, "id" => to_bson(job)?))
             .map(|opt| opt.and_then(|doc| doc.result))
     }
+
+    fn delete_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<()> {
+        // leases and results are kept with the jobs
+        m!(self.0.coll.delete_many(d!("clerking_job.snapshot" => to_bson(snapshot)?), None))?;
+        Ok(())
+    }
 }
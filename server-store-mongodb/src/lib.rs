@@ -70,9 +70,11 @@ pub fn new_mongodb_server_for_url(url: &str, db: &str) -> SdaResult<SdaServerSer
 
 pub fn new_mongodb_server(client: &mongodb::Client, db: &str) -> SdaResult<SdaServerService> {
     use mongodb::ThreadedClient;
+    use sda_server::stores::AuthTokensStore;
     let db = client.db(db);
     let agents = agents::MongoAgentsStore::new(&db).map_err(|e| format!("Error connecting to mongodb: {:?}", e))?;
     let auth = auth_tokens::MongoAuthTokensStore::new(&db).map_err(|e| format!("Error connecting to mongodb: {:?}", e))?;
+    auth.migrate_auth_tokens().map_err(|e| format!("Error migrating auth tokens: {:?}", e))?;
     let agg = aggregations::MongoAggregationsStore::new(&db).map_err(|e| format!("Error connecting to mongodb: {:?}", e))?;
     let jobs = clerking_jobs::MongoClerkingJobsStore::new(&db).map_err(|e| format!("Error connecting to mongodb: {:?}", e))?;
     Ok(SdaServerService(SdaServer {
@@ -80,6 +82,7 @@ pub fn new_mongodb_server(client: &mongodb::Client, db: &str) -> SdaResult<SdaSe
         auth_tokens_store: Box::new(auth),
         aggregation_store: Box::new(agg),
         clerking_job_store: Box::new(jobs),
+        authenticator: Box::new(sda_server::auth::TokenOrSignatureAuthenticator::default()),
     }))
 }
 
@@ -112,6 +115,19 @@ impl<ID: Id, T: Serialize + Deserialize> Dao<ID, T> {
         Ok(())
     }
 
+    /// Index only documents having the indexed fields.
+    fn ensure_sparse_index(&self, spec: bson::Document, unique: bool) -> SdaServerResult<()> {
+        use mongodb::coll::options::IndexOptions;
+        m!(self.coll.create_index(spec,
+                                  Some(IndexOptions {
+                                      unique: Some(unique),
+                                      sparse: Some(true),
+                                      background: Some(true),
+                                      ..IndexOptions::default()
+                                  })))?;
+        Ok(())
+    }
+
     fn get(&self, selector: bson::Document) -> SdaServerResult<Option<T>> {
         let option = m!(self.coll.find_one(Some(selector), None))?;
         if let Some(it) = option {
@@ -148,6 +164,16 @@ impl<ID: Id, T: Serialize + Deserialize> Dao<ID, T> {
         m!(self.coll.update_one(selector, update, None))?;
         Ok(())
     }
+
+    /// Atomically update the first document matching the selector, returning it as it was before.
+    fn get_and_modify(&self, selector: bson::Document, update: bson::Document) -> SdaServerResult<Option<T>> {
+        let option = m!(self.coll.find_one_and_update(selector, update, None))?;
+        if let Some(it) = option {
+            Ok(Some(from_doc::<T>(it)?))
+        } else {
+            Ok(None)
+        }
+    }
 }
 
 struct DaoCursor<T: Deserialize> {
@@ -0,0 +1,158 @@
+//! Pluggable authentication of the agents calling the service.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sda_protocol::*;
+
+use SdaServer;
+use errors::*;
+use stores::{AuthToken, HashedAuthToken};
+
+/// Credentials presented by a caller, as extracted from a request by the transport layer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Credentials {
+    /// Secret token chosen by the agent when created, and shared with the service.
+    Token(AuthToken),
+    /// Digest of the request signed by the agent; nothing is shared with the service.
+    Signature(SignedRequestDigest),
+}
+
+impl Credentials {
+    /// Agent the credentials are presented for.
+    pub fn agent(&self) -> &AgentId {
+        match *self {
+            Credentials::Token(ref token) => &token.id,
+            Credentials::Signature(ref signed) => &signed.signer,
+        }
+    }
+}
+
+/// Strategy used by the service for authenticating callers.
+pub trait Authenticator: Sync + Send {
+    /// Check the credentials of an existing agent, returning the agent if they are valid.
+    fn authenticate(&self, server: &SdaServer, credentials: &Credentials) -> SdaServerResult<Agent>;
+
+    /// Check the credentials presented along with a new agent, recording whatever is needed to authenticate it later.
+    fn enroll(&self, server: &SdaServer, agent: &Agent, credentials: &Credentials) -> SdaServerResult<()>;
+}
+
+/// Authentication by a secret token, of which the service only keeps a salted hash.
+pub struct TokenAuthenticator;
+
+impl Authenticator for TokenAuthenticator {
+    fn authenticate(&self, server: &SdaServer, credentials: &Credentials) -> SdaServerResult<Agent> {
+        let token = match *credentials {
+            Credentials::Token(ref token) => token,
+            _ => Err(SdaError::from(SdaErrorKind::InvalidCredentials))?,
+        };
+        let db = server.auth_tokens_store.get_auth_token(&token.id)?;
+        if !db.map(|hashed| hashed.matches(token)).unwrap_or(false) {
+            Err(SdaError::from(SdaErrorKind::InvalidCredentials))?
+        }
+        Ok(server.agents_store.get_agent(&token.id)?.ok_or("Agent not found")?)
+    }
+
+    fn enroll(&self, server: &SdaServer, agent: &Agent, credentials: &Credentials) -> SdaServerResult<()> {
+        let token = match *credentials {
+            Credentials::Token(ref token) if token.id == agent.id => token,
+            _ => Err(SdaError::from(SdaErrorKind::InvalidCredentials))?,
+        };
+        // never let a second enrollment take over the token of an existing agent
+        match server.auth_tokens_store.get_auth_token(&token.id)? {
+            Some(ref existing) if !existing.matches(token) => Err(SdaError::from(SdaErrorKind::InvalidCredentials).into()),
+            Some(_) => Ok(()),
+            None => server.auth_tokens_store.upsert_auth_token(&HashedAuthToken::new(token)?),
+        }
+    }
+}
+
+/// Authentication by a signature of the request, using the verification key of the agent.
+///
+/// Requests are only accepted within `max_clock_skew` seconds of their timestamp, and signatures accepted
+/// within that window are remembered so that none can be replayed.
+pub struct SignatureAuthenticator {
+    pub max_clock_skew: u64,
+    /// Accepted signatures, with the timestamps of their requests.
+    seen: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl Default for SignatureAuthenticator {
+    fn default() -> SignatureAuthenticator {
+        SignatureAuthenticator::new(300)
+    }
+}
+
+impl SignatureAuthenticator {
+    pub fn new(max_clock_skew: u64) -> SignatureAuthenticator {
+        SignatureAuthenticator {
+            max_clock_skew: max_clock_skew,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, agent: &Agent, credentials: &Credentials) -> SdaServerResult<()> {
+        let digest = match *credentials {
+            Credentials::Signature(ref digest) => digest,
+            _ => Err(SdaError::from(SdaErrorKind::InvalidCredentials))?,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| "Clock set before Unix epoch")?.as_secs();
+        let skew = if now > digest.body.timestamp { now - digest.body.timestamp } else { digest.body.timestamp - now };
+        if digest.body.agent != digest.signer || skew > self.max_clock_skew {
+            Err(SdaError::from(SdaErrorKind::InvalidCredentials))?
+        }
+        if !::signatures::signature_is_valid(agent, digest)? {
+            Err(SdaError::from(SdaErrorKind::InvalidCredentials))?
+        }
+        let signature = match digest.signature {
+            Signature::Sodium(ref signature) => signature.to_vec(),
+        };
+        let mut seen = self.seen.lock().map_err(|_| "Poisoned replay cache")?;
+        // signatures older than the window are refused anyway
+        let max_clock_skew = self.max_clock_skew;
+        seen.retain(|_, &mut timestamp| timestamp + max_clock_skew >= now);
+        if seen.insert(signature, digest.body.timestamp).is_some() {
+            Err(SdaError::from(SdaErrorKind::InvalidCredentials))?
+        }
+        Ok(())
+    }
+}
+
+impl Authenticator for SignatureAuthenticator {
+    fn authenticate(&self, server: &SdaServer, credentials: &Credentials) -> SdaServerResult<Agent> {
+        let agent = server.agents_store.get_agent(credentials.agent())?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::InvalidCredentials))?;
+        self.check(&agent, credentials)?;
+        Ok(agent)
+    }
+
+    fn enroll(&self, _server: &SdaServer, agent: &Agent, credentials: &Credentials) -> SdaServerResult<()> {
+        // proves ownership of the verification key being registered
+        self.check(agent, credentials)
+    }
+}
+
+/// Authentication accepting both tokens and signatures, dispatching on the kind of credentials.
+///
+/// This is the default, allowing token-based clients to keep working while moving to signatures.
+#[derive(Default)]
+pub struct TokenOrSignatureAuthenticator {
+    pub signature: SignatureAuthenticator,
+}
+
+impl Authenticator for TokenOrSignatureAuthenticator {
+    fn authenticate(&self, server: &SdaServer, credentials: &Credentials) -> SdaServerResult<Agent> {
+        match *credentials {
+            Credentials::Token(_) => TokenAuthenticator.authenticate(server, credentials),
+            Credentials::Signature(_) => self.signature.authenticate(server, credentials),
+        }
+    }
+
+    fn enroll(&self, server: &SdaServer, agent: &Agent, credentials: &Credentials) -> SdaServerResult<()> {
+        match *credentials {
+            Credentials::Token(_) => TokenAuthenticator.enroll(server, agent, credentials),
+            Credentials::Signature(_) => self.signature.enroll(server, agent, credentials),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Server-side summation of encryptions for additively homomorphic schemes.
+
+use num::{BigUint, One};
+
+use sda_protocol::*;
+use SdaServerResult;
+
+/// Sum encryptions made under the given key, resulting in a single encryption of the sum.
+pub fn sum(key: &EncryptionKey,
+           scheme: &AdditiveEncryptionScheme,
+           encryptions: Vec<Encryption>)
+           -> SdaServerResult<Encryption> {
+    match (key, scheme) {
+        (&EncryptionKey::PackedPaillier(ref raw_n), &AdditiveEncryptionScheme::PackedPaillier { .. }) => {
+            let n = BigUint::from_bytes_be(&raw_n.0);
+            let nn = &n * &n;
+            let mut value_count: Option<usize> = None;
+            let mut sums: Vec<BigUint> = vec![];
+            for encryption in encryptions {
+                let (count, ciphertexts) = match encryption {
+                    Encryption::PackedPaillier { value_count, ciphertexts } => (value_count, ciphertexts),
+                    _ => Err("expected a Paillier encryption")?,
+                };
+                match value_count {
+                    None => {
+                        value_count = Some(count);
+                        sums = vec![BigUint::one(); ciphertexts.len()];
+                    }
+                    Some(expected) if expected != count || sums.len() != ciphertexts.len() => {
+                        Err("mismatching encryption dimensions")?
+                    }
+                    _ => {}
+                }
+                // adding plaintexts amounts to multiplying ciphertexts
+                for (sum, ciphertext) in sums.iter_mut().zip(ciphertexts) {
+                    let product = &*sum * BigUint::from_bytes_be(&ciphertext.0);
+                    *sum = product % &nn;
+                }
+            }
+            Ok(Encryption::PackedPaillier {
+                value_count: value_count.unwrap_or(0),
+                ciphertexts: sums.into_iter().map(|sum| Binary(sum.to_bytes_be())).collect(),
+            })
+        }
+        _ => Err("encryption scheme does not support summation")?,
+    }
+}
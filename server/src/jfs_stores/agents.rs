@@ -3,7 +3,8 @@ use jfs;
 use std::path;
 
 use sda_protocol::Identified;
-use sda_protocol::{Agent, AgentId, ClerkCandidate, Profile, SignedEncryptionKey, EncryptionKeyId};
+use sda_protocol::{Agent, AgentId, ClerkCandidate, Profile, SignedClerkRegistration, SignedEncryptionKey,
+                   EncryptionKeyId, SignedEncryptionKeyRevocation, SignedVerificationKeyRotation};
 
 use SdaServerResult;
 use stores::{BaseStore, AgentsStore};
@@ -15,6 +16,10 @@ pub struct JfsAgentsStore {
     agents: jfs::Store,
     profiles: jfs::Store,
     encryption_keys: jfs::Store,
+    revocations: jfs::Store,
+    verification_key_rotations: jfs::Store,
+    last_seen: jfs::Store,
+    clerk_registrations: jfs::Store,
 }
 
 impl JfsAgentsStore {
@@ -22,10 +27,18 @@ impl JfsAgentsStore {
         let agents = prefix.as_ref().join("agents");
         let profiles = prefix.as_ref().join("profiles");
         let encryption_keys = prefix.as_ref().join("encryption_keys");
+        let revocations = prefix.as_ref().join("encryption_key_revocations");
+        let verification_key_rotations = prefix.as_ref().join("verification_key_rotations");
+        let last_seen = prefix.as_ref().join("clerks_last_seen");
+        let clerk_registrations = prefix.as_ref().join("clerk_registrations");
         Ok(JfsAgentsStore {
             agents: jfs::Store::new(agents.to_str().ok_or("pathbuf to string")?)?,
             profiles: jfs::Store::new(profiles.to_str().ok_or("pathbuf to string")?)?,
             encryption_keys: jfs::Store::new(encryption_keys.to_str().ok_or("pathbuf to string")?)?,
+            revocations: jfs::Store::new(revocations.to_str().ok_or("pathbuf to string")?)?,
+            verification_key_rotations: jfs::Store::new(verification_key_rotations.to_str().ok_or("pathbuf to string")?)?,
+            last_seen: jfs::Store::new(last_seen.to_str().ok_or("pathbuf to string")?)?,
+            clerk_registrations: jfs::Store::new(clerk_registrations.to_str().ok_or("pathbuf to string")?)?,
         })
     }
 }
@@ -45,6 +58,19 @@ impl AgentsStore for JfsAgentsStore {
         self.agents.get_option(id)
     }
 
+    fn rotate_verification_key(&self, rotation: &SignedVerificationKeyRotation) -> SdaServerResult<()> {
+        let mut agent: Agent = self.agents.get_option(&rotation.agent)?.ok_or("Agent not found")?;
+        let mut history = self.get_verification_key_history(&rotation.agent)?;
+        history.push(rotation.clone());
+        self.verification_key_rotations.upsert_with_id(&history, &rotation.agent)?;
+        agent.verification_key = rotation.new_key.clone();
+        self.agents.update(&agent)
+    }
+
+    fn get_verification_key_history(&self, id: &AgentId) -> SdaServerResult<Vec<SignedVerificationKeyRotation>> {
+        Ok(self.verification_key_rotations.get_option(id)?.unwrap_or(vec![]))
+    }
+
     fn upsert_profile(&self, profile: &Profile) -> SdaServerResult<()> {
         self.profiles.upsert_with_id(profile, &profile.owner)
     }
@@ -63,10 +89,45 @@ impl AgentsStore for JfsAgentsStore {
         self.encryption_keys.get_option(key)
     }
 
+    fn list_encryption_keys(&self, owner: &AgentId) -> SdaServerResult<Vec<EncryptionKeyId>> {
+        let revocations = self.revocations.all::<SignedEncryptionKeyRevocation>()?;
+        let keys = self.encryption_keys.all::<SignedEncryptionKey>()?;
+        Ok(keys.into_iter()
+            .map(|(_, v)| v)
+            .filter(|v| v.signer == *owner && !revocations.contains_key(&v.body.id().to_string()))
+            .map(|v| v.body.id().clone())
+            .collect())
+    }
+
+    fn revoke_encryption_key(&self, revocation: &SignedEncryptionKeyRevocation) -> SdaServerResult<()> {
+        self.revocations.create_with_id(revocation, &revocation.key)
+    }
+
+    fn get_encryption_key_revocation(&self,
+                                     key: &EncryptionKeyId)
+                                     -> SdaServerResult<Option<SignedEncryptionKeyRevocation>> {
+        self.revocations.get_option(key)
+    }
+
+    fn upsert_clerk_registration(&self, registration: &SignedClerkRegistration) -> SdaServerResult<()> {
+        self.clerk_registrations.upsert_with_id(registration, &registration.body.clerk)
+    }
+
+    fn get_clerk_registration(&self, clerk: &AgentId) -> SdaServerResult<Option<SignedClerkRegistration>> {
+        self.clerk_registrations.get_option(clerk)
+    }
+
+    fn record_clerk_seen(&self, clerk: &AgentId, now: u64) -> SdaServerResult<()> {
+        self.last_seen.upsert_with_id(&now, clerk)
+    }
+
     fn suggest_committee(&self) -> SdaServerResult<Vec<ClerkCandidate>> {
+        let last_seen = self.last_seen.all::<u64>()?;
+        let revocations = self.revocations.all::<SignedEncryptionKeyRevocation>()?;
         let keys = self.encryption_keys.all::<SignedEncryptionKey>()?;
         let candidates = keys.into_iter()
             .map(|(_, v)| v)
+            .filter(|v| !revocations.contains_key(&v.body.id().to_string()))
             .sorted_by(|a, b| a.signer.0.cmp(&b.signer.0))
             .into_iter()
             .group_by(|v| v.signer)
@@ -75,6 +136,7 @@ impl AgentsStore for JfsAgentsStore {
                 ClerkCandidate {
                     id: k,
                     keys: v.map(|sek| sek.body.id().clone()).collect(),
+                    last_seen: last_seen.get(&k.to_string()).cloned(),
                 }
             })
             .collect();
@@ -1,13 +1,16 @@
 use jfs;
 
+use std::collections::HashSet;
 use std::path;
 use std::str::FromStr;
+use std::sync::Mutex;
 
-use sda_protocol::{AgentId, Aggregation, AggregationId, Committee, Encryption, Participation,
-                   ParticipationId, Snapshot, SnapshotId};
+use sda_protocol::{AgentId, AggregationId, AggregationState, Encryption, Enrollment, MaskingCohort, ParticipationId,
+                   ParticipationPolicy, PrivacyBudget, SdaError, SdaErrorKind, SignedAggregation, SignedCommittee,
+                   SignedParticipation, SignedRevealContribution, SignedSeatAcceptance, Snapshot, SnapshotId};
 
 use SdaServerResult;
-use ::jfs_stores::JfsStoreExt;
+use ::jfs_stores::{remove_stores, JfsStoreExt};
 
 use stores::{BaseStore, AggregationsStore};
 
@@ -18,29 +21,52 @@ struct SnapshotContent {
 
 pub struct JfsAggregationsStore {
     participations: path::PathBuf,
+    superseded_participations: path::PathBuf,
+    enrollments: path::PathBuf,
+    reveal_contributions: path::PathBuf,
+    seat_acceptances: path::PathBuf,
     aggregations: jfs::Store,
+    aggregation_states: jfs::Store,
     committees: jfs::Store,
+    masking_cohorts: jfs::Store,
     snapshots: jfs::Store,
     snapshot_contents: jfs::Store,
     snapshot_masks: jfs::Store,
+    privacy_budgets: jfs::Store,
+    /// Serialises the creation of participations, whose checks and writes span several files.
+    participations_lock: Mutex<()>,
+    /// Serialises updates of privacy budgets, which are read-check-write on files.
+    privacy_budgets_lock: Mutex<()>,
 }
 
 impl JfsAggregationsStore {
     pub fn new<P: AsRef<path::Path>>(prefix: P) -> SdaServerResult<JfsAggregationsStore> {
         let aggregations = prefix.as_ref().join("aggregations");
+        let aggregation_states = prefix.as_ref().join("aggregation_states");
         let committees = prefix.as_ref().join("committees");
+        let masking_cohorts = prefix.as_ref().join("masking_cohorts");
         let snapshots = prefix.as_ref().join("snapshots");
         let snapshot_contents = prefix.as_ref().join("snapshot_contents");
         let snapshot_masks = prefix.as_ref().join("snapshot_masks");
+        let privacy_budgets = prefix.as_ref().join("privacy_budgets");
         Ok(JfsAggregationsStore {
             participations: prefix.as_ref().join("participations"),
+            superseded_participations: prefix.as_ref().join("superseded_participations"),
+            enrollments: prefix.as_ref().join("enrollments"),
+            reveal_contributions: prefix.as_ref().join("reveal_contributions"),
+            seat_acceptances: prefix.as_ref().join("seat_acceptances"),
             aggregations: jfs::Store::new(aggregations.to_str().ok_or("pathbuf to string")?)?,
+            aggregation_states: jfs::Store::new(aggregation_states.to_str().ok_or("pathbuf to string")?)?,
             committees: jfs::Store::new(committees.to_str().ok_or("pathbuf to string")?)?,
+            masking_cohorts: jfs::Store::new(masking_cohorts.to_str().ok_or("pathbuf to string")?)?,
             snapshots: jfs::Store::new(snapshots.to_str().ok_or("pathbuf to string")?)?,
             snapshot_contents: jfs::Store::new(snapshot_contents.to_str()
                 .ok_or("pathbuf to string")?)?,
             snapshot_masks: jfs::Store::new(snapshot_masks.to_str()
                 .ok_or("pathbuf to string")?)?,
+            privacy_budgets: jfs::Store::new(privacy_budgets.to_str().ok_or("pathbuf to string")?)?,
+            participations_lock: Mutex::new(()),
+            privacy_budgets_lock: Mutex::new(()),
         })
     }
 
@@ -48,6 +74,50 @@ impl JfsAggregationsStore {
         let path = self.participations.join(aggregation.to_string());
         Ok(jfs::Store::new(path.to_str().ok_or("path to string")?)?)
     }
+
+    fn superseded_store(&self, aggregation: &AggregationId) -> SdaServerResult<jfs::Store> {
+        let path = self.superseded_participations.join(aggregation.to_string());
+        Ok(jfs::Store::new(path.to_str().ok_or("path to string")?)?)
+    }
+
+    /// Participations of an aggregation that have not been superseded, keyed by id.
+    fn current_participations(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<(String, SignedParticipation)>> {
+        let superseded = self.superseded_store(aggregation)?.all::<ParticipationId>()?;
+        Ok(self.aggregation_store(aggregation)?
+            .all::<SignedParticipation>()?
+            .into_iter()
+            .filter(|&(ref id, _)| !superseded.contains_key(id))
+            .collect())
+    }
+
+    /// Current participations of an aggregation not included in any of its snapshots, keyed by id.
+    fn new_participations(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<(String, SignedParticipation)>> {
+        let mut snapped = HashSet::new();
+        for snapshot in self.list_snapshots(aggregation)? {
+            if let Some(content) = self.snapshot_contents.get_option::<SnapshotContent, _>(&snapshot)? {
+                snapped.extend(content.participations.iter().map(|id| id.to_string()));
+            }
+        }
+        Ok(self.current_participations(aggregation)?
+            .into_iter()
+            .filter(|&(ref id, _)| !snapped.contains(id))
+            .collect())
+    }
+
+    fn enrollments_store(&self, aggregation: &AggregationId) -> SdaServerResult<jfs::Store> {
+        let path = self.enrollments.join(aggregation.to_string());
+        Ok(jfs::Store::new(path.to_str().ok_or("path to string")?)?)
+    }
+
+    fn reveal_contributions_store(&self, snapshot: &SnapshotId) -> SdaServerResult<jfs::Store> {
+        let path = self.reveal_contributions.join(snapshot.to_string());
+        Ok(jfs::Store::new(path.to_str().ok_or("path to string")?)?)
+    }
+
+    fn seat_acceptances_store(&self, aggregation: &AggregationId) -> SdaServerResult<jfs::Store> {
+        let path = self.seat_acceptances.join(aggregation.to_string());
+        Ok(jfs::Store::new(path.to_str().ok_or("path to string")?)?)
+    }
 }
 
 impl BaseStore for JfsAggregationsStore {
@@ -62,7 +132,7 @@ impl AggregationsStore for JfsAggregationsStore {
                          recipient: Option<&AgentId>)
                          -> SdaServerResult<Vec<AggregationId>> {
         Ok(self.aggregations
-            .all::<Aggregation>()?
+            .all::<SignedAggregation>()?
             .iter()
             .filter(|&(_, ref agg)| {
                 filter.map(|f| agg.title.contains(f)).unwrap_or(true) &&
@@ -72,30 +142,128 @@ impl AggregationsStore for JfsAggregationsStore {
             .collect())
     }
 
-    fn create_aggregation(&self, aggregation: &Aggregation) -> SdaServerResult<()> {
+    fn create_aggregation(&self, aggregation: &SignedAggregation) -> SdaServerResult<()> {
         self.aggregations.create(aggregation)
     }
 
-    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<Aggregation>> {
+    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<SignedAggregation>> {
         self.aggregations.get_option(aggregation)
     }
 
     fn delete_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
-        self.aggregations.delete(&aggregation.to_string())?;
-        Ok(())
+        for snapshot in self.list_snapshots(aggregation)? {
+            remove_stores(self.reveal_contributions.join(snapshot.to_string()))?;
+            self.snapshot_masks.delete_option(&snapshot)?;
+            self.snapshot_contents.delete_option(&snapshot)?;
+            self.snapshots.delete_option(&snapshot)?;
+        }
+        remove_stores(self.participations.join(aggregation.to_string()))?;
+        remove_stores(self.superseded_participations.join(aggregation.to_string()))?;
+        remove_stores(self.enrollments.join(aggregation.to_string()))?;
+        remove_stores(self.seat_acceptances.join(aggregation.to_string()))?;
+        self.masking_cohorts.delete_option(aggregation)?;
+        self.committees.delete_option(aggregation)?;
+        self.aggregation_states.delete_option(aggregation)?;
+        self.privacy_budgets.delete_option(aggregation)?;
+        self.aggregations.delete_option(aggregation)
+    }
+
+    fn get_aggregation_state(&self, aggregation: &AggregationId) -> SdaServerResult<Option<AggregationState>> {
+        self.aggregation_states.get_option(aggregation)
+    }
+
+    fn set_aggregation_state(&self, aggregation: &AggregationId, state: AggregationState) -> SdaServerResult<()> {
+        self.aggregation_states.upsert_with_id(&state, aggregation)
+    }
+
+    fn get_privacy_budget(&self, aggregation: &AggregationId) -> SdaServerResult<Option<PrivacyBudget>> {
+        self.privacy_budgets.get_option(aggregation)
+    }
+
+    fn update_privacy_budget(&self, aggregation: &AggregationId, previous: Option<&PrivacyBudget>, budget: &PrivacyBudget) -> SdaServerResult<bool> {
+        let _guard = self.privacy_budgets_lock.lock().map_err(|_| "Poisoned privacy budget lock")?;
+        let current: Option<PrivacyBudget> = self.privacy_budgets.get_option(aggregation)?;
+        if current.as_ref() != previous {
+            return Ok(false);
+        }
+        self.privacy_budgets.upsert_with_id(budget, aggregation)?;
+        Ok(true)
     }
 
-    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<Committee>> {
+    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<SignedCommittee>> {
         self.committees.get_option(owner)
     }
 
-    fn create_committee(&self, committee: &Committee) -> SdaServerResult<()> {
-        self.committees.create_with_id(committee, &committee.aggregation)
+    fn create_committee(&self, committee: &SignedCommittee) -> SdaServerResult<()> {
+        self.committees.create_with_id(committee, &committee.body.aggregation)
     }
 
-    fn create_participation(&self, participation: &Participation) -> SdaServerResult<()> {
-        let store = self.aggregation_store(&participation.aggregation)?;
-        store.create(participation)
+    fn create_seat_acceptance(&self, acceptance: &SignedSeatAcceptance) -> SdaServerResult<()> {
+        self.seat_acceptances_store(&acceptance.body.aggregation)?.create_with_id(acceptance, &acceptance.body.clerk)
+    }
+
+    fn list_seat_acceptances(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<SignedSeatAcceptance>> {
+        Ok(self.seat_acceptances_store(aggregation)?
+            .all::<SignedSeatAcceptance>()?
+            .into_iter()
+            .map(|(_, acceptance)| acceptance)
+            .collect())
+    }
+
+    fn create_participation(&self,
+                            participation: &SignedParticipation,
+                            policy: ParticipationPolicy)
+                            -> SdaServerResult<()> {
+        let _guard = self.participations_lock.lock().map_err(|_| "Poisoned participations lock")?;
+        let store = self.aggregation_store(&participation.body.aggregation)?;
+        if let Some(existing) = store.get_option::<SignedParticipation, _>(&participation.body.id)? {
+            if existing != *participation {
+                Err(SdaError::from(SdaErrorKind::Invalid("Participation id already used".to_string())))?
+            }
+            return Ok(());
+        }
+        let earlier: Vec<ParticipationId> = store.all::<SignedParticipation>()?
+            .into_iter()
+            .filter(|&(_, ref other)| other.body.participant == participation.body.participant)
+            .map(|(_, other)| other.body.id)
+            .collect();
+        if policy == ParticipationPolicy::Reject && !earlier.is_empty() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Participant already participated".to_string())))?
+        }
+        store.create(participation)?;
+        if policy == ParticipationPolicy::KeepLatest {
+            let superseded = self.superseded_store(&participation.body.aggregation)?;
+            for id in earlier {
+                superseded.upsert_with_id(&id, &id)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_enrollment(&self, enrollment: &Enrollment) -> SdaServerResult<()> {
+        let store = self.enrollments_store(&enrollment.aggregation)?;
+        store.create_with_id(enrollment, &enrollment.participant)
+    }
+
+    fn get_enrollment(&self,
+                      aggregation: &AggregationId,
+                      participant: &AgentId)
+                      -> SdaServerResult<Option<Enrollment>> {
+        let store = self.enrollments_store(aggregation)?;
+        store.get_option(participant)
+    }
+
+    fn list_enrollments(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<Enrollment>> {
+        let store = self.enrollments_store(aggregation)?;
+        Ok(store.all::<Enrollment>()?.into_iter().map(|(_, e)| e).collect())
+    }
+
+    fn create_masking_cohort(&self, cohort: &MaskingCohort) -> SdaServerResult<()> {
+        self.masking_cohorts.create_with_id(cohort, &cohort.aggregation)
+    }
+
+    fn get_masking_cohort(&self, aggregation: &AggregationId) -> SdaServerResult<Option<MaskingCohort>> {
+        self.masking_cohorts.get_option(aggregation)
     }
 
     fn create_snapshot(&self, snapshot: &Snapshot) -> SdaServerResult<()> {
@@ -103,16 +271,24 @@ impl AggregationsStore for JfsAggregationsStore {
     }
 
     fn count_participations(&self, aggregation: &AggregationId) -> SdaServerResult<usize> {
-        let store = self.aggregation_store(aggregation)?;
-        Ok(store.all::<Participation>()?.len())
+        Ok(self.current_participations(aggregation)?.len())
+    }
+
+    fn count_new_participations(&self, aggregation: &AggregationId) -> SdaServerResult<usize> {
+        Ok(self.new_participations(aggregation)?.len())
     }
 
     fn snapshot_participations(&self,
                                aggregation: &AggregationId,
-                               snapshot: &SnapshotId)
+                               snapshot: &SnapshotId,
+                               incremental: bool)
                                -> SdaServerResult<()> {
-        let store = self.aggregation_store(aggregation)?;
-        let list: SdaServerResult<Vec<ParticipationId>> = store.all::<Participation>()?
+        let participations = if incremental {
+            self.new_participations(aggregation)?
+        } else {
+            self.current_participations(aggregation)?
+        };
+        let list: SdaServerResult<Vec<ParticipationId>> = participations
             .into_iter()
             .map(|p| Ok(ParticipationId::from_str(&p.0)?))
             .collect();
@@ -141,7 +317,7 @@ impl AggregationsStore for JfsAggregationsStore {
         (&'b self,
          aggregation: &AggregationId,
          snapshot: &SnapshotId)
-         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<Participation>> + 'a>>
+         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<SignedParticipation>> + 'a>>
         where 'b: 'a
     {
         let store = self.aggregation_store(aggregation)?;
@@ -169,4 +345,14 @@ impl AggregationsStore for JfsAggregationsStore {
     fn get_snapshot_mask(&self, snapshot: &SnapshotId) -> SdaServerResult<Option<Vec<Encryption>>> {
         self.snapshot_masks.get_option(snapshot)
     }
+
+    fn create_reveal_contribution(&self, contribution: &SignedRevealContribution) -> SdaServerResult<()> {
+        let store = self.reveal_contributions_store(&contribution.body.snapshot)?;
+        store.create_with_id(contribution, &contribution.body.member)
+    }
+
+    fn list_reveal_contributions(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<SignedRevealContribution>> {
+        let store = self.reveal_contributions_store(snapshot)?;
+        Ok(store.all::<SignedRevealContribution>()?.into_iter().map(|(_, c)| c).collect())
+    }
 }
@@ -5,18 +5,22 @@ use std::path;
 use sda_protocol::AgentId;
 
 use SdaServerResult;
-use stores::{BaseStore, AuthTokensStore, AuthToken};
+use stores::{BaseStore, AuthTokensStore, AuthToken, HashedAuthToken};
 use jfs_stores::JfsStoreExt;
 
 pub struct JfsAuthTokensStore {
     auth_tokens: jfs::Store,
+    /// Tokens in clear, as stored by earlier versions; only read when migrating.
+    legacy_auth_tokens: jfs::Store,
 }
 
 impl JfsAuthTokensStore {
     pub fn new<P: AsRef<path::Path>>(prefix: P) -> SdaServerResult<JfsAuthTokensStore> {
-        let auth_tokens = prefix.as_ref().join("auth_tokens");
+        let auth_tokens = prefix.as_ref().join("hashed_auth_tokens");
+        let legacy_auth_tokens = prefix.as_ref().join("auth_tokens");
         Ok(JfsAuthTokensStore {
             auth_tokens: jfs::Store::new(auth_tokens.to_str().ok_or("pathbuf to string")?)?,
+            legacy_auth_tokens: jfs::Store::new(legacy_auth_tokens.to_str().ok_or("pathbuf to string")?)?,
         })
     }
 }
@@ -28,11 +32,11 @@ impl BaseStore for JfsAuthTokensStore {
 }
 
 impl AuthTokensStore for JfsAuthTokensStore {
-    fn upsert_auth_token(&self, token: &AuthToken) -> SdaServerResult<()> {
+    fn upsert_auth_token(&self, token: &HashedAuthToken) -> SdaServerResult<()> {
         self.auth_tokens.upsert(token)
     }
 
-    fn get_auth_token(&self, id: &AgentId) -> SdaServerResult<Option<AuthToken>> {
+    fn get_auth_token(&self, id: &AgentId) -> SdaServerResult<Option<HashedAuthToken>> {
         self.auth_tokens.get_option(id)
     }
 
@@ -40,6 +44,18 @@ impl AuthTokensStore for JfsAuthTokensStore {
         self.auth_tokens.delete(&*id.to_string())?;
         Ok(())
     }
+
+    fn migrate_auth_tokens(&self) -> SdaServerResult<usize> {
+        let legacy = self.legacy_auth_tokens.all::<AuthToken>()?;
+        for (file, token) in &legacy {
+            // a token rotated since takes precedence over the one left in clear
+            if self.get_auth_token(&token.id)?.is_none() {
+                self.upsert_auth_token(&HashedAuthToken::new(token)?)?;
+            }
+            self.legacy_auth_tokens.delete(file)?;
+        }
+        Ok(legacy.len())
+    }
 }
 
 #[cfg(test)]
@@ -47,7 +63,7 @@ mod test {
     extern crate tempdir;
     use sda_protocol::AgentId;
     use sda_protocol::Identified;
-    use stores::{AuthTokensStore, AuthToken};
+    use stores::{AuthTokensStore, AuthToken, HashedAuthToken};
     use super::JfsAuthTokensStore;
 
     #[test]
@@ -58,11 +74,30 @@ mod test {
             body: "token".to_string(),
         };
         let store = JfsAuthTokensStore::new(tmpdir.path()).unwrap();
-        store.upsert_auth_token(&token).unwrap();
+        store.upsert_auth_token(&HashedAuthToken::new(&token).unwrap()).unwrap();
         store.get_auth_token(&token.id()).unwrap().unwrap();
         store.delete_auth_token(&token.id()).unwrap();
     }
 
+    #[test]
+    fn migrate() {
+        let tmpdir = tempdir::TempDir::new("sda-server").unwrap();
+        let token = AuthToken {
+            id: AgentId::random(),
+            body: "token".to_string(),
+        };
+        let legacy = ::jfs::Store::new(tmpdir.path().join("auth_tokens").to_str().unwrap()).unwrap();
+        legacy.save_with_id(&token, &*token.id.to_string()).unwrap();
+        let store = JfsAuthTokensStore::new(tmpdir.path()).unwrap();
+        assert!(store.get_auth_token(&token.id).unwrap().is_none());
+        assert_eq!(1, store.migrate_auth_tokens().unwrap());
+        let hashed = store.get_auth_token(&token.id).unwrap().unwrap();
+        assert!(!hashed.hash.contains("token"));
+        assert!(hashed.matches(&token));
+        assert!(legacy.all::<AuthToken>().unwrap().is_empty());
+        assert_eq!(0, store.migrate_auth_tokens().unwrap());
+    }
+
     #[test]
     fn delete_raw() {
         let tmpdir = tempdir::TempDir::new("sda-server").unwrap();
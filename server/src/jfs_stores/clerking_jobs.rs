@@ -1,12 +1,13 @@
 use jfs;
 
+use std::fs;
 use std::path;
 
 use sda_protocol::Id;
-use sda_protocol::{AgentId, ClerkingJob, ClerkingJobId, ClerkingResult, SnapshotId};
+use sda_protocol::{AgentId, ClerkingJob, ClerkingJobId, SignedClerkingResult, SnapshotId};
 
-use stores::{BaseStore, ClerkingJobsStore};
-use jfs_stores::JfsStoreExt;
+use stores::{BaseStore, ClerkingJobLease, ClerkingJobsStore};
+use jfs_stores::{remove_stores, JfsStoreExt};
 
 use SdaServerResult;
 
@@ -24,6 +25,35 @@ impl JfsClerkingJobsStore {
             .to_str()
             .ok_or("pathbuf to string")?)?)
     }
+
+    /// All the stores under a prefix, one per clerk or snapshot.
+    fn stores(&self, prefix: &str) -> SdaServerResult<Vec<jfs::Store>> {
+        let dir = self.0.join(prefix);
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut stores = vec![];
+        for entry in fs::read_dir(dir)? {
+            stores.push(jfs::Store::new(entry?.path().to_str().ok_or("pathbuf to string")?)?);
+        }
+        Ok(stores)
+    }
+
+    fn leases(&self) -> SdaServerResult<jfs::Store> {
+        Ok(jfs::Store::new(self.0.join("leases").to_str().ok_or("pathbuf to string")?)?)
+    }
+}
+
+fn new_lease(job: &ClerkingJob, now: u64) -> ClerkingJobLease {
+    ClerkingJobLease {
+        job: job.id,
+        clerk: job.clerk,
+        snapshot: job.snapshot,
+        enqueued_at: now,
+        leased_until: None,
+        attempts: 0,
+        abandoned: false,
+    }
 }
 
 impl BaseStore for JfsClerkingJobsStore {
@@ -33,12 +63,46 @@ impl BaseStore for JfsClerkingJobsStore {
 }
 
 impl ClerkingJobsStore for JfsClerkingJobsStore {
-    fn enqueue_clerking_job(&self, job: &ClerkingJob) -> SdaServerResult<()> {
+    fn enqueue_clerking_job(&self, job: &ClerkingJob, now: u64) -> SdaServerResult<()> {
+        self.leases()?.create(&new_lease(job, now))?;
         self.store("queue", &job.clerk)?.create(job)
     }
 
-    fn poll_clerking_job(&self, clerk: &AgentId) -> SdaServerResult<Option<ClerkingJob>> {
-        Ok(self.store("queue", clerk)?.all::<ClerkingJob>()?.into_iter().next().map(|a| a.1))
+    fn poll_clerking_job(&self,
+                         clerk: &AgentId,
+                         now: u64,
+                         leased_until: u64)
+                         -> SdaServerResult<Option<ClerkingJob>> {
+        let leases = self.leases()?;
+        for (_, job) in self.store("queue", clerk)?.all::<ClerkingJob>()? {
+            // jobs queued before leases were tracked have none yet
+            let mut lease = leases.get_option(&job.id)?.unwrap_or_else(|| new_lease(&job, now));
+            if lease.is_available(now) {
+                lease.leased_until = Some(leased_until);
+                lease.attempts += 1;
+                leases.upsert(&lease)?;
+                return Ok(Some(job));
+            }
+        }
+        Ok(None)
+    }
+
+    fn list_clerking_job_leases(&self) -> SdaServerResult<Vec<ClerkingJobLease>> {
+        Ok(self.leases()?.all::<ClerkingJobLease>()?.into_iter().map(|a| a.1).collect())
+    }
+
+    fn abandon_clerking_job(&self, job: &ClerkingJobId) -> SdaServerResult<()> {
+        let leases = self.leases()?;
+        let mut lease: ClerkingJobLease = leases.get_option(job)?.ok_or("Lease not found")?;
+        lease.abandoned = true;
+        leases.update(&lease)
+    }
+
+    fn list_abandoned_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<ClerkingJobLease>> {
+        Ok(self.list_clerking_job_leases()?
+            .into_iter()
+            .filter(|lease| lease.abandoned && lease.snapshot == *snapshot)
+            .collect())
     }
 
     fn get_clerking_job(&self,
@@ -48,28 +112,51 @@ impl ClerkingJobsStore for JfsClerkingJobsStore {
         self.store("queue", clerk)?.get_option(job)
     }
 
-    fn create_clerking_result(&self, result: &ClerkingResult) -> SdaServerResult<()> {
+    fn create_clerking_result(&self, signed_result: &SignedClerkingResult) -> SdaServerResult<()> {
+        let result = &signed_result.body;
         let job: ClerkingJob = self.store("queue", &result.clerk)?
             .get_option(&result.job)?
             .ok_or("Job not found")?;
-        self.store("results", &job.snapshot)?.upsert_with_id(result, &result.job)?;
+        self.store("results", &job.snapshot)?.upsert_with_id(signed_result, &result.job)?;
         self.store("done", &result.clerk)?.upsert_with_id(&job, &result.job)?;
         self.store("queue", &result.clerk)?.delete(&*result.job.to_string())?;
+        let leases = self.leases()?;
+        if leases.get_option::<ClerkingJobLease, _>(&result.job)?.is_some() {
+            leases.delete(&*result.job.to_string())?;
+        }
         Ok(())
     }
 
     fn list_results(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<ClerkingJobId>> {
         Ok(self.store("results", snapshot)?
-            .all::<ClerkingResult>()?
+            .all::<SignedClerkingResult>()?
             .iter()
-            .map(|r| r.1.job)
+            .map(|r| r.1.body.job)
             .collect::<Vec<ClerkingJobId>>())
     }
 
     fn get_result(&self,
                   snapshot: &SnapshotId,
                   job: &ClerkingJobId)
-                  -> SdaServerResult<Option<ClerkingResult>> {
+                  -> SdaServerResult<Option<SignedClerkingResult>> {
         self.store("results", snapshot)?.get_option(job)
     }
+
+    fn delete_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<()> {
+        let leases = self.leases()?;
+        for lease in self.list_clerking_job_leases()? {
+            if lease.snapshot == *snapshot {
+                leases.delete_option(&lease.job)?;
+            }
+        }
+        // queued and done jobs are kept by clerk
+        for store in self.stores("queue")?.into_iter().chain(self.stores("done")?) {
+            for (_, job) in store.all::<ClerkingJob>()? {
+                if job.snapshot == *snapshot {
+                    store.delete_option(&job.id)?;
+                }
+            }
+        }
+        remove_stores(self.0.join("results").join(snapshot.to_string()))
+    }
 }
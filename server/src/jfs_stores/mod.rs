@@ -50,6 +50,18 @@ trait JfsStoreExt {
         where T: ::serde::Serialize + ::serde::Deserialize + Identified + PartialEq {
         self.upsert_with_id(it, it.id())
     }
+
+    fn delete_option<I>(&self, id: &I) -> SdaServerResult<()>
+        where I: Id;
+}
+
+/// Remove a directory of stores, if present.
+fn remove_stores<P: AsRef<::std::path::Path>>(path: P) -> SdaServerResult<()> {
+    match ::std::fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(ref io) if io.kind() == ::std::io::ErrorKind::NotFound => Ok(()),
+        Err(io) => Err(io)?,
+    }
 }
 
 impl JfsStoreExt for jfs::Store {
@@ -104,6 +116,15 @@ impl JfsStoreExt for jfs::Store {
         self.save_with_id(it, &*id.to_string())?;
         Ok(())
     }
+
+    fn delete_option<I>(&self, id: &I) -> SdaServerResult<()>
+        where I: Id {
+        match self.delete(&*id.to_string()) {
+            Ok(()) => Ok(()),
+            Err(ref io) if io.kind() == ::std::io::ErrorKind::NotFound => Ok(()),
+            Err(io) => Err(io)?,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -181,4 +202,15 @@ mod test {
         let b_again = store.get_option_for_str("bar").unwrap().unwrap();
         assert_eq!(b, b_again);
     }
+
+    #[test]
+    fn delete_option() {
+        let tmpdir = tempdir::TempDir::new("sda").unwrap();
+        let store = ::jfs::Store::new(&tmpdir.path().to_str().unwrap()).unwrap();
+        let a = A { id: I("foo".to_string()), int: 12};
+        store.create(&a).unwrap();
+        store.delete_option(&a.id).unwrap();
+        assert!(store.get_option_for_str::<A, _>("foo").unwrap().is_none());
+        store.delete_option(&a.id).unwrap();
+    }
 }
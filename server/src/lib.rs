@@ -10,10 +10,14 @@
 extern crate error_chain;
 extern crate itertools;
 extern crate jfs;
+extern crate num;
+extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate sodiumoxide;
 extern crate uuid;
+extern crate curve25519_dalek;
 
 extern crate sda_protocol;
 #[macro_use]
@@ -24,16 +28,23 @@ extern crate slog_scope;
 pub mod errors;
 mod server;
 mod snapshot;
+mod homomorphic;
+mod proofs;
+mod signatures;
 
+pub mod auth;
+pub mod scheduler;
 pub mod stores;
 pub mod jfs_stores;
 
-pub use server::{ SdaServer, SdaServerService };
+pub use server::{ SdaServer, SdaServerService, CLERKING_LEASE, MAX_CLERKING_ATTEMPTS };
 use errors::*;
 
 pub fn new_jfs_server<P: AsRef<::std::path::Path>>(dir: P) -> sda_protocol::SdaResult<SdaServerService> {
+    use stores::AuthTokensStore;
     let agents = ::jfs_stores::JfsAgentsStore::new(dir.as_ref().join("agents")).unwrap();
     let auth = ::jfs_stores::JfsAuthTokensStore::new(dir.as_ref().join("auths")).unwrap();
+    auth.migrate_auth_tokens().unwrap();
     let agg = ::jfs_stores::JfsAggregationsStore::new(dir.as_ref().join("agg")).unwrap();
     let jobs = ::jfs_stores::JfsClerkingJobsStore::new(dir.as_ref().join("jobs")).unwrap();
     Ok(SdaServerService(SdaServer {
@@ -41,5 +52,6 @@ pub fn new_jfs_server<P: AsRef<::std::path::Path>>(dir: P) -> sda_protocol::SdaR
         auth_tokens_store: Box::new(auth),
         aggregation_store: Box::new(agg),
         clerking_job_store: Box::new(jobs),
+        authenticator: Box::new(::auth::TokenOrSignatureAuthenticator::default()),
     }))
 }
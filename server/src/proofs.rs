@@ -0,0 +1,161 @@
+//! Verification of the proofs that participations respect the input bounds of an aggregation.
+//!
+//! Mirrors the proving side of the client: proofs are about the commitments to the constant
+//! terms of the verifiable Shamir sharing polynomials, that is, to the inputs themselves.
+
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use sodiumoxide::crypto::hash::sha512;
+
+use sda_protocol::*;
+use SdaServerResult;
+
+/// Generators of the Pedersen commitments, shared with the client through `sda_protocol`.
+struct Group {
+    generator: RistrettoPoint,
+    blinding_generator: RistrettoPoint,
+}
+
+impl Group {
+    fn new() -> Group {
+        let generators = CommitmentGenerators::new();
+        Group {
+            generator: generators.generator,
+            blinding_generator: generators.blinding_generator,
+        }
+    }
+
+    /// Commitment to `value` without blinding.
+    fn unblinded(&self, value: i64) -> RistrettoPoint {
+        if value < 0 {
+            self.generator * -Scalar::from(value.wrapping_neg() as u64)
+        } else {
+            self.generator * Scalar::from(value as u64)
+        }
+    }
+}
+
+/// Check the input proof of `participation` against the input bounds of `aggregation`.
+///
+/// Returns `false` for a missing or invalid proof; aggregations without bounds accept anything.
+pub fn verify_input_proof(aggregation: &Aggregation, participation: &Participation) -> SdaServerResult<bool> {
+    let bounds = match aggregation.input_bounds {
+        None => return Ok(true),
+        Some(ref bounds) => bounds,
+    };
+    let proof = match participation.input_proof {
+        None => return Ok(false),
+        Some(ref proof) => proof,
+    };
+    let threshold = match aggregation.committee_sharing_scheme {
+        LinearSecretSharingScheme::VerifiableShamir { privacy_threshold, .. } => privacy_threshold,
+        _ => Err("input bounds require verifiable sharing")?,
+    };
+    let group = Group::new();
+
+    let dimension = aggregation.vector_dimension;
+    if participation.commitments.len() != dimension * (threshold + 1) {
+        return Ok(false);
+    }
+    let constant_commitments = match (0..dimension)
+        .map(|ix| decode_point(&participation.commitments[ix * (threshold + 1)]))
+        .collect::<Option<Vec<RistrettoPoint>>>() {
+        None => return Ok(false),
+        Some(commitments) => commitments,
+    };
+    let context = InputProof::context(&participation.participant, &aggregation.id);
+
+    match (bounds, proof) {
+
+        (&InputBounds::Range { ref min, ref max }, &InputProof::Range { ref lower, ref upper }) => {
+            if lower.len() != dimension || upper.len() != dimension {
+                return Ok(false);
+            }
+            for (ix, commitment) in constant_commitments.iter().enumerate() {
+                let bitsize = InputBounds::range_bitsize(min[ix], max[ix]);
+                let above_min = commitment - group.unblinded(min[ix]);
+                let below_max = group.unblinded(max[ix]) - commitment;
+                if !verify_range(&group, &context, &above_min, bitsize, &lower[ix])
+                    || !verify_range(&group, &context, &below_max, bitsize, &upper[ix]) {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+
+        (&InputBounds::OneHot, &InputProof::OneHot { ref bits, ref blinding_sum }) => {
+            if bits.len() != dimension {
+                return Ok(false);
+            }
+            let blinding_sum = match decode_scalar(blinding_sum) {
+                None => return Ok(false),
+                Some(blinding_sum) => blinding_sum,
+            };
+            let all_bits = constant_commitments.iter().zip(bits)
+                .all(|(commitment, bit_proof)| verify_bit(&group, &context, commitment, bit_proof));
+            // the sum of the commitments must open to one
+            let sum = constant_commitments.iter().fold(RistrettoPoint::identity(), |acc, commitment| acc + commitment);
+            let one = group.generator + group.blinding_generator * blinding_sum;
+            Ok(all_bits && sum == one)
+        }
+
+        _ => Ok(false),
+    }
+}
+
+/// Check that `commitment` is to a value in `[0, 2^bitsize)`.
+fn verify_range(group: &Group, context: &[u8], commitment: &RistrettoPoint, bitsize: usize, proof: &RangeProof) -> bool {
+    if proof.bit_commitments.len() != bitsize || proof.bit_proofs.len() != bitsize {
+        return false;
+    }
+    let bit_commitments = match proof.bit_commitments.iter()
+        .map(decode_point)
+        .collect::<Option<Vec<RistrettoPoint>>>() {
+        None => return false,
+        Some(bit_commitments) => bit_commitments,
+    };
+    let recombined = bit_commitments.iter().enumerate()
+        .fold(RistrettoPoint::identity(), |acc, (ix, bit_commitment)| acc + bit_commitment * Scalar::from(1_u64 << ix));
+    recombined == *commitment
+        && bit_commitments.iter().zip(&proof.bit_proofs)
+            .all(|(bit_commitment, bit_proof)| verify_bit(group, context, bit_commitment, bit_proof))
+}
+
+/// Check that `commitment` is to either 0 or 1.
+fn verify_bit(group: &Group, context: &[u8], commitment: &RistrettoPoint, proof: &BitProof) -> bool {
+    let scalars = [&proof.challenges.0, &proof.challenges.1, &proof.responses.0, &proof.responses.1].iter()
+        .map(|scalar| decode_scalar(scalar))
+        .collect::<Option<Vec<Scalar>>>();
+    let (c0, c1, z0, z1) = match scalars {
+        None => return false,
+        Some(scalars) => (scalars[0], scalars[1], scalars[2], scalars[3]),
+    };
+    let a0 = announcement(group, commitment, 0, &c0, &z0);
+    let a1 = announcement(group, commitment, 1, &c1, &z1);
+    c0 + c1 == challenge(context, &[*commitment, a0, a1])
+}
+
+/// Announcement `h * response - (commitment - g * value) * challenge` matching a challenge and response for the claim that `commitment` is to `value`.
+fn announcement(group: &Group, commitment: &RistrettoPoint, value: i64, challenge: &Scalar, response: &Scalar) -> RistrettoPoint {
+    let unblinded = commitment - group.unblinded(value);
+    group.blinding_generator * response - unblinded * challenge
+}
+
+/// Challenge derived from the context and the given group elements.
+fn challenge(context: &[u8], elements: &[RistrettoPoint]) -> Scalar {
+    let mut input = context.to_vec();
+    for element in elements {
+        input.extend_from_slice(element.compress().as_bytes());
+    }
+    Scalar::from_bytes_mod_order_wide(&sha512::hash(&input).0)
+}
+
+fn decode_point(commitment: &Commitment) -> Option<RistrettoPoint> {
+    CompressedRistretto(commitment.0).decompress()
+}
+
+/// Scalar in its canonical encoding; anything else is rejected to keep proofs non-malleable.
+fn decode_scalar(scalar: &CommitmentScalar) -> Option<Scalar> {
+    Scalar::from_canonical_bytes(scalar.0)
+}
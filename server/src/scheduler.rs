@@ -0,0 +1,131 @@
+//! Snapshots taken by the service on its own, following the schedules of aggregations.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sda_protocol::*;
+
+use {SdaServer, SdaServerService, SdaServerResult};
+
+/// Current time, in seconds since the Unix epoch.
+pub fn now() -> SdaServerResult<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| "Clock set before Unix epoch")?.as_secs())
+}
+
+/// Keeps track of the snapshots taken for scheduled aggregations.
+///
+/// Times of earlier snapshots are only kept in memory: after a restart, the next snapshot of an
+/// aggregation that already has some is due a full interval later.
+#[derive(Default)]
+pub struct Scheduler {
+    last_snapshots: HashMap<AggregationId, u64>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// Take the snapshots due at the given time, close the aggregations past their closing time, and
+    /// note unresponsive clerks.
+    ///
+    /// Returns the number of snapshots taken. Failures are logged, and retried on the next tick.
+    pub fn tick(&mut self, server: &SdaServer, now: u64) -> SdaServerResult<usize> {
+        if let Err(e) = server.note_unresponsive_clerks(now) {
+            error!("Noting unresponsive clerks failed: {}", e);
+        }
+        let mut taken = 0;
+        for aggregation in server.list_aggregations(None, None)? {
+            match self.tick_aggregation(server, &aggregation, now) {
+                Ok(true) => taken += 1,
+                Ok(false) => {}
+                Err(e) => error!("Scheduling aggregation {:?} failed: {}", aggregation, e),
+            }
+        }
+        Ok(taken)
+    }
+
+    fn tick_aggregation(&mut self, server: &SdaServer, aggregation: &AggregationId, now: u64) -> SdaServerResult<bool> {
+        let agg = match server.get_aggregation(aggregation)? {
+            Some(agg) => agg.body,
+            None => return Ok(false),
+        };
+        let schedule = match agg.schedule {
+            Some(ref schedule) => schedule,
+            None => return Ok(false),
+        };
+        // only open aggregations have a committee and may still receive participations
+        if server.get_aggregation_state(aggregation)? != AggregationState::Open {
+            self.last_snapshots.remove(aggregation);
+            return Ok(false);
+        }
+        // incremental snapshots need participations of their own
+        let enough_participations = if schedule.incremental_snapshots {
+            server.aggregation_store.count_new_participations(aggregation)? >= ::std::cmp::max(schedule.min_participations, 1)
+        } else {
+            server.aggregation_store.count_participations(aggregation)? >= schedule.min_participations
+        };
+        let budget_left = server.can_release(&agg)?;
+
+        if schedule.closes_at.map_or(false, |closes_at| now >= closes_at) {
+            let snapshot = enough_participations && budget_left;
+            if snapshot {
+                debug!("Taking closing snapshot of {:?}", aggregation);
+                take_snapshot(server, aggregation, schedule.incremental_snapshots)?;
+            } else if !budget_left {
+                warn!("Closing {:?} without snapshot, privacy budget spent", aggregation);
+            } else {
+                warn!("Closing {:?} without snapshot, too few participations", aggregation);
+            }
+            server.aggregation_store.set_aggregation_state(aggregation, AggregationState::Closed)?;
+            self.last_snapshots.remove(aggregation);
+            return Ok(snapshot);
+        }
+
+        let interval = match schedule.snapshot_interval {
+            Some(interval) => interval,
+            None => return Ok(false),
+        };
+        let last = match self.last_snapshots.get(aggregation) {
+            Some(&last) => last,
+            None if server.aggregation_store.list_snapshots(aggregation)?.is_empty() => {
+                schedule.opens_at.unwrap_or(now)
+            }
+            None => now,
+        };
+        self.last_snapshots.insert(*aggregation, last);
+        if now < last + interval || !enough_participations || !budget_left {
+            return Ok(false);
+        }
+        debug!("Taking periodic snapshot of {:?}", aggregation);
+        take_snapshot(server, aggregation, schedule.incremental_snapshots)?;
+        self.last_snapshots.insert(*aggregation, now);
+        Ok(true)
+    }
+}
+
+/// Snapshot an aggregation, leaving its state to the caller.
+fn take_snapshot(server: &SdaServer, aggregation: &AggregationId, incremental: bool) -> SdaServerResult<()> {
+    ::snapshot::snapshot(server, &Snapshot {
+        id: SnapshotId::random(),
+        aggregation: *aggregation,
+        incremental: incremental,
+    })
+}
+
+/// Run a scheduler in a background thread, ticking every `period`.
+pub fn spawn(server: Arc<SdaServerService>, period: Duration) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut scheduler = Scheduler::new();
+        loop {
+            match now().and_then(|now| scheduler.tick(&server.0, now)) {
+                Ok(0) => {}
+                Ok(taken) => info!("Scheduler took {} snapshots", taken),
+                Err(e) => error!("Scheduler failed: {}", e),
+            }
+            thread::sleep(period);
+        }
+    })
+}
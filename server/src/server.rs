@@ -1,14 +1,23 @@
 use sda_protocol::*;
+use auth::{Authenticator, Credentials};
 use errors::*;
 use stores::*;
+use rand::Rng;
 
 pub struct SdaServer {
     pub agents_store: Box<AgentsStore>,
     pub auth_tokens_store: Box<AuthTokensStore>,
     pub aggregation_store: Box<AggregationsStore>,
     pub clerking_job_store: Box<ClerkingJobsStore>,
+    pub authenticator: Box<Authenticator>,
 }
 
+/// Time a clerk is given to return the result of a job it polled, in seconds.
+pub const CLERKING_LEASE: u64 = 10 * 60;
+
+/// Number of times a job is handed out before its clerk is deemed unresponsive.
+pub const MAX_CLERKING_ATTEMPTS: u32 = 3;
+
 macro_rules! wrap {
     ($e:expr) => {
         match $e {
@@ -26,6 +35,12 @@ impl SdaServer {
     }
 
     pub fn create_agent(&self, agent: &Agent) -> SdaServerResult<()> {
+        // verification keys may only be replaced through a rotation
+        if let Some(existing) = self.agents_store.get_agent(&agent.id)? {
+            if existing != *agent {
+                Err(SdaError::from(SdaErrorKind::Invalid("Agent already exists".to_string())))?
+            }
+        }
         self.agents_store.create_agent(&agent)
     }
 
@@ -33,6 +48,31 @@ impl SdaServer {
         self.agents_store.get_agent(&id)
     }
 
+    pub fn rotate_verification_key(&self, signed_rotation: &SignedVerificationKeyRotation) -> SdaServerResult<()> {
+        let rotation = &signed_rotation.body;
+        if signed_rotation.signer != rotation.agent {
+            Err(SdaError::from(SdaErrorKind::Invalid("Rotation must be signed by the agent".to_string())))?
+        }
+        let agent = self.agents_store.get_agent(&rotation.agent)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Agent not found".to_string())))?;
+        if rotation.previous_key != agent.verification_key {
+            Err(SdaError::from(SdaErrorKind::Invalid("Rotation must replace the current verification key".to_string())))?
+        }
+        // signed with the key being replaced, i.e. the current one
+        self.check_signature(signed_rotation)?;
+        // a replaced key may have been compromised, so it can never come back
+        let history = self.agents_store.get_verification_key_history(&rotation.agent)?;
+        if rotation.new_key.id == agent.verification_key.id
+            || history.iter().any(|past| past.body.previous_key.id == rotation.new_key.id) {
+            Err(SdaError::from(SdaErrorKind::Invalid("Verification keys can not be reused".to_string())))?
+        }
+        self.agents_store.rotate_verification_key(signed_rotation)
+    }
+
+    pub fn get_verification_key_history(&self, id: &AgentId) -> SdaServerResult<Vec<SignedVerificationKeyRotation>> {
+        self.agents_store.get_verification_key_history(id)
+    }
+
     pub fn upsert_profile(&self, profile: &Profile) -> SdaServerResult<()> {
         self.agents_store.upsert_profile(profile)
     }
@@ -42,6 +82,9 @@ impl SdaServer {
     }
 
     pub fn create_encryption_key(&self, key: &SignedEncryptionKey) -> SdaServerResult<()> {
+        let signer = self.agents_store.get_agent(&key.signer)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Signer not found".to_string())))?;
+        check_current_key(&signer, key)?;
         self.agents_store.create_encryption_key(key)
     }
 
@@ -51,6 +94,28 @@ impl SdaServer {
         self.agents_store.get_encryption_key(key)
     }
 
+    pub fn list_encryption_keys(&self, owner: &AgentId) -> SdaServerResult<Vec<EncryptionKeyId>> {
+        self.agents_store.list_encryption_keys(owner)
+    }
+
+    pub fn revoke_encryption_key(&self, revocation: &SignedEncryptionKeyRevocation) -> SdaServerResult<()> {
+        let key = self.agents_store.get_encryption_key(&revocation.key)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Encryption key not found".to_string())))?;
+        if revocation.signer != key.signer {
+            Err(SdaError::from(SdaErrorKind::Invalid("Revocation must be signed by the owner of the key".to_string())))?
+        }
+        self.check_signature(revocation)?;
+        self.agents_store.revoke_encryption_key(revocation)
+    }
+
+    /// Refuse keys that have been revoked by their owner.
+    fn check_not_revoked(&self, key: &EncryptionKeyId) -> SdaServerResult<()> {
+        if self.agents_store.get_encryption_key_revocation(key)?.is_some() {
+            Err(SdaError::from(SdaErrorKind::Invalid(format!("Encryption key {} has been revoked", key.to_string()))))?
+        }
+        Ok(())
+    }
+
     pub fn list_aggregations(&self,
                              filter: Option<&str>,
                              recipient: Option<&AgentId>)
@@ -60,33 +125,157 @@ impl SdaServer {
 
     pub fn get_aggregation(&self,
                            aggregation: &AggregationId)
-                           -> SdaServerResult<Option<Aggregation>> {
+                           -> SdaServerResult<Option<SignedAggregation>> {
         self.aggregation_store.get_aggregation(aggregation)
     }
 
-    pub fn get_committee(&self, aggregation: &AggregationId) -> SdaServerResult<Option<Committee>> {
+    pub fn get_committee(&self, aggregation: &AggregationId) -> SdaServerResult<Option<SignedCommittee>> {
         self.aggregation_store.get_committee(aggregation)
     }
 
-    pub fn create_aggregation(&self, aggregation: &Aggregation) -> SdaServerResult<()> {
-        self.aggregation_store.create_aggregation(aggregation)
+    pub fn create_aggregation(&self, signed_aggregation: &SignedAggregation) -> SdaServerResult<()> {
+        let aggregation = &signed_aggregation.body;
+        if signed_aggregation.signer != aggregation.recipient {
+            Err(SdaError::from(SdaErrorKind::Invalid("Aggregation must be signed by the recipient".to_string())))?
+        }
+        self.check_signature(signed_aggregation)?;
+        if let Some(existing) = self.aggregation_store.get_aggregation(&aggregation.id)? {
+            // aggregations are immutable once uploaded, whatever the store does with duplicates
+            if existing.body != *aggregation {
+                Err(SdaError::from(SdaErrorKind::Invalid("Aggregation already exists with a different description".to_string())))?
+            }
+        }
+        aggregation.validate()?;
+        let recipient_key = self.agents_store.get_encryption_key(&aggregation.recipient_key)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Recipient key not found".to_string())))?;
+        aggregation.validate_recipient_key(&recipient_key)?;
+        self.check_not_revoked(&aggregation.recipient_key)?;
+        if let Some(ref group) = aggregation.recipient_group {
+            for &(ref member, ref key) in &group.members {
+                let member_key = self.agents_store.get_encryption_key(key)?
+                    .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Recipient group key not found".to_string())))?;
+                if member_key.signer != *member {
+                    Err(SdaError::from(SdaErrorKind::Invalid("Recipient group keys must belong to their member".to_string())))?
+                }
+                if !aggregation.recipient_encryption_scheme.is_suitable_key(&member_key.body.body) {
+                    Err(SdaError::from(SdaErrorKind::Invalid(
+                        "Recipient group key is not suitable for the recipient encryption scheme".to_string())))?
+                }
+                self.check_not_revoked(key)?;
+            }
+        }
+        self.aggregation_store.create_aggregation(signed_aggregation)?;
+        // uploading the same aggregation again must not reset its lifecycle
+        if self.aggregation_store.get_aggregation_state(&aggregation.id)?.is_none() {
+            self.aggregation_store.set_aggregation_state(&aggregation.id, AggregationState::Draft)?;
+        }
+        Ok(())
+    }
+
+    /// Current lifecycle state of an aggregation.
+    ///
+    /// Aggregations stored before states were recorded are considered open once every seat of their
+    /// committee has been accepted, like those going through the whole lifecycle.
+    pub fn get_aggregation_state(&self, aggregation: &AggregationId) -> SdaServerResult<AggregationState> {
+        if let Some(state) = self.aggregation_store.get_aggregation_state(aggregation)? {
+            return Ok(state);
+        }
+        if self.aggregation_store.get_committee(aggregation)?.is_some() && self.pending_clerks(aggregation)?.is_empty() {
+            Ok(AggregationState::Open)
+        } else {
+            Ok(AggregationState::Draft)
+        }
+    }
+
+    /// Refuse an operation unless the aggregation is in one of the given states.
+    fn check_state(&self,
+                   aggregation: &AggregationId,
+                   allowed: &[AggregationState],
+                   operation: &str)
+                   -> SdaServerResult<AggregationState> {
+        let state = self.get_aggregation_state(aggregation)?;
+        if !allowed.contains(&state) {
+            Err(SdaError::from(SdaErrorKind::Invalid(format!(
+                "Cannot {} while the aggregation is {}", operation, format!("{:?}", state).to_lowercase()))))?
+        }
+        Ok(state)
+    }
+
+    pub fn close_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
+        self.check_state(aggregation, &[AggregationState::Open], "close the aggregation")?;
+        self.aggregation_store.set_aggregation_state(aggregation, AggregationState::Closed)
+    }
+
+    pub fn archive_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
+        self.check_state(aggregation,
+                         &[AggregationState::Draft, AggregationState::Open, AggregationState::Closed, AggregationState::Revealed],
+                         "archive the aggregation")?;
+        self.aggregation_store.set_aggregation_state(aggregation, AggregationState::Archived)
     }
 
     pub fn delete_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
+        // jobs are only reachable through the snapshots, so they go first
+        for snapshot in self.aggregation_store.list_snapshots(aggregation)? {
+            self.clerking_job_store.delete_clerking_jobs(&snapshot)?;
+        }
         self.aggregation_store.delete_aggregation(aggregation)
     }
 
     pub fn suggest_committee(&self,
-                             aggregation: &AggregationId)
+                             aggregation: &AggregationId,
+                             criteria: &CommitteeCriteria)
                              -> SdaServerResult<Vec<ClerkCandidate>> {
-        let _aggregation =
-            self.aggregation_store.get_aggregation(aggregation)?.ok_or("aggregation not found")?;
-        self.agents_store.suggest_committee()
+        let agg = self.aggregation_store.get_aggregation(aggregation)?.ok_or("aggregation not found")?.body;
+        let now = ::scheduler::now()?;
+        let mut candidates = vec![];
+        for mut candidate in self.agents_store.suggest_committee()? {
+            if criteria.exclude_recipient && candidate.id == agg.recipient {
+                continue;
+            }
+            if !self.clerk_admits(&candidate.id, &agg)? {
+                continue;
+            }
+            if let Some(max_staleness) = criteria.max_staleness {
+                if candidate.last_seen.map_or(true, |last_seen| last_seen + max_staleness < now) {
+                    continue;
+                }
+            }
+            if criteria.suitable_keys {
+                let mut keys = vec![];
+                for key in candidate.keys {
+                    let signed_key = self.agents_store.get_encryption_key(&key)?.ok_or("inconsistent storage")?;
+                    if signed_key.body.body.suitable_for(&agg.committee_encryption_scheme) {
+                        keys.push(key);
+                    }
+                }
+                if keys.is_empty() {
+                    continue;
+                }
+                candidate.keys = keys;
+            }
+            candidates.push(candidate);
+        }
+        if criteria.randomize {
+            ::rand::thread_rng().shuffle(&mut candidates);
+        }
+        if let Some(count) = criteria.count {
+            candidates.truncate(count);
+        }
+        Ok(candidates)
     }
 
-    pub fn create_committee(&self, committee: &Committee) -> SdaServerResult<()> {
+    pub fn create_committee(&self, signed_committee: &SignedCommittee) -> SdaServerResult<()> {
+        let committee = &signed_committee.body;
         let agg =
             self.aggregation_store.get_aggregation(&committee.aggregation)?.ok_or("aggregation not found")?;
+        if signed_committee.signer != agg.recipient {
+            Err(SdaError::from(SdaErrorKind::Invalid("Committee must be signed by the recipient".to_string())))?
+        }
+        self.check_signature(signed_committee)?;
+        self.check_state(&committee.aggregation, &[AggregationState::Draft], "set the committee")?;
+        if self.aggregation_store.get_committee(&committee.aggregation)?.is_some() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Committee already set".to_string())))?
+        }
         if agg.committee_sharing_scheme.output_size() != committee.clerks_and_keys.len() {
             Err(SdaError::from(
                     SdaErrorKind::Invalid(format!("Expected {} clerks in the committee, found {} instead",
@@ -94,11 +283,219 @@ impl SdaServer {
                     committee.clerks_and_keys.len())))
                 )?
         };
-        self.aggregation_store.create_committee(committee)
+        // a clerk holding several shares would count more than once towards the privacy threshold
+        let mut clerks: Vec<&AgentId> = committee.clerks_and_keys.iter().map(|&(ref id, _)| id).collect();
+        clerks.sort_by(|a, b| a.0.cmp(&b.0));
+        clerks.dedup();
+        if clerks.len() != committee.clerks_and_keys.len() {
+            Err(SdaError::from(
+                    SdaErrorKind::Invalid("Clerks in the committee must be distinct".to_string()))
+                )?
+        };
+        for &(ref clerk, ref key) in &committee.clerks_and_keys {
+            self.check_not_revoked(key)?;
+            if !self.clerk_admits(clerk, &agg)? {
+                Err(SdaError::from(SdaErrorKind::Invalid(
+                    format!("Clerk {} is not registered for this aggregation", clerk.to_string()))))?
+            }
+        }
+        // the aggregation opens once every clerk accepted its seat
+        self.aggregation_store.create_committee(signed_committee)
+    }
+
+    pub fn create_clerk_registration(&self, registration: &SignedClerkRegistration) -> SdaServerResult<()> {
+        if registration.signer != registration.body.clerk {
+            Err(SdaError::from(SdaErrorKind::Invalid("Clerk registration must be signed by the clerk".to_string())))?
+        }
+        self.check_signature(registration)?;
+        self.agents_store.upsert_clerk_registration(registration)
     }
 
-    pub fn create_participation(&self, participation: &Participation) -> SdaServerResult<()> {
-        self.aggregation_store.create_participation(participation)
+    pub fn get_clerk_registration(&self, clerk: &AgentId) -> SdaServerResult<Option<SignedClerkRegistration>> {
+        self.agents_store.get_clerk_registration(clerk)
+    }
+
+    /// List the aggregations whose committee includes the clerk.
+    fn committee_seats(&self, clerk: &AgentId) -> SdaServerResult<Vec<AggregationId>> {
+        let mut seats = vec![];
+        for aggregation in self.aggregation_store.list_aggregations(None, None)? {
+            if let Some(committee) = self.aggregation_store.get_committee(&aggregation)? {
+                if committee.clerks_and_keys.iter().any(|&(id, _)| id == *clerk) {
+                    seats.push(aggregation);
+                }
+            }
+        }
+        Ok(seats)
+    }
+
+    /// Check that a clerk registered for an aggregation, and has room for one more committee.
+    fn clerk_admits(&self, clerk: &AgentId, aggregation: &Aggregation) -> SdaServerResult<bool> {
+        let registration = match self.agents_store.get_clerk_registration(clerk)? {
+            Some(registration) => registration.body,
+            None => return Ok(false),
+        };
+        if !registration.admits(aggregation) {
+            return Ok(false);
+        }
+        let capacity = match registration.capacity {
+            Some(capacity) => capacity,
+            None => return Ok(true),
+        };
+        let mut busy = 0;
+        for seat in self.committee_seats(clerk)? {
+            let state = self.get_aggregation_state(&seat)?;
+            if seat != aggregation.id && state != AggregationState::Revealed && state != AggregationState::Archived {
+                busy += 1;
+            }
+        }
+        Ok(busy < capacity)
+    }
+
+    /// List the clerks of the committee of an aggregation yet to accept their seat.
+    fn pending_clerks(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<AgentId>> {
+        let committee = match self.aggregation_store.get_committee(aggregation)? {
+            Some(committee) => committee.body,
+            None => return Ok(vec![]),
+        };
+        let acceptances = self.aggregation_store.list_seat_acceptances(aggregation)?;
+        Ok(committee.clerks_and_keys
+            .iter()
+            .map(|&(clerk, _)| clerk)
+            .filter(|clerk| !acceptances.iter().any(|acceptance| acceptance.body.clerk == *clerk))
+            .collect())
+    }
+
+    pub fn list_pending_seats(&self, clerk: &AgentId) -> SdaServerResult<Vec<AggregationId>> {
+        let mut pending = vec![];
+        for aggregation in self.committee_seats(clerk)? {
+            if self.get_aggregation_state(&aggregation)? == AggregationState::Draft &&
+               self.pending_clerks(&aggregation)?.contains(clerk) {
+                pending.push(aggregation);
+            }
+        }
+        Ok(pending)
+    }
+
+    pub fn create_seat_acceptance(&self, signed_acceptance: &SignedSeatAcceptance) -> SdaServerResult<()> {
+        let acceptance = &signed_acceptance.body;
+        if signed_acceptance.signer != acceptance.clerk {
+            Err(SdaError::from(SdaErrorKind::Invalid("Seat acceptance must be signed by the clerk".to_string())))?
+        }
+        self.check_signature(signed_acceptance)?;
+        self.check_state(&acceptance.aggregation, &[AggregationState::Draft], "accept committee seats")?;
+        let committee = self.aggregation_store.get_committee(&acceptance.aggregation)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("No committee set yet".to_string())))?;
+        if !committee.clerks_and_keys.contains(&(acceptance.clerk, acceptance.key)) {
+            Err(SdaError::from(SdaErrorKind::Invalid("No such seat in the committee".to_string())))?
+        }
+        let pending = self.pending_clerks(&acceptance.aggregation)?;
+        if !pending.contains(&acceptance.clerk) {
+            return Ok(());
+        }
+        self.aggregation_store.create_seat_acceptance(signed_acceptance)?;
+        if pending.len() == 1 {
+            self.aggregation_store.set_aggregation_state(&acceptance.aggregation, AggregationState::Open)?;
+        }
+        Ok(())
+    }
+
+    pub fn create_participation(&self, signed_participation: &SignedParticipation) -> SdaServerResult<()> {
+        let participation = &signed_participation.body;
+        if signed_participation.signer != participation.participant {
+            Err(SdaError::from(SdaErrorKind::Invalid("Participation must be signed by the participant".to_string())))?
+        }
+        self.check_signature(signed_participation)?;
+        let agg = self.aggregation_store.get_aggregation(&participation.aggregation)?
+            .ok_or("aggregation not found")?;
+        self.check_state(&participation.aggregation, &[AggregationState::Open], "accept participations")?;
+        if let Some(ref schedule) = agg.schedule {
+            schedule.accepts_participations_at(::scheduler::now()?)?;
+        }
+        if agg.masking_scheme.is_pairwise() {
+            // masks only cancel out for members of the cohort
+            let cohort = self.aggregation_store.get_masking_cohort(&participation.aggregation)?
+                .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Masking cohort not yet formed".to_string())))?;
+            if !cohort.members.iter().any(|&(id, _)| id == participation.participant) {
+                Err(SdaError::from(SdaErrorKind::Invalid("Participant is not a member of the masking cohort".to_string())))?
+            }
+        }
+        let commitment_count = agg.committee_sharing_scheme.commitment_count(agg.vector_dimension);
+        if participation.commitments.len() != commitment_count {
+            Err(SdaError::from(SdaErrorKind::Invalid(format!(
+                "Participation must hold {} commitments, found {}", commitment_count, participation.commitments.len()))))?
+        }
+        if agg.input_bounds.is_some() {
+            if participation.input_proof.is_none() {
+                Err(SdaError::from(SdaErrorKind::Invalid("Participation input proof missing".to_string())))?
+            }
+            if !::proofs::verify_input_proof(&agg, participation)? {
+                Err(SdaError::from(SdaErrorKind::Invalid("Participation input proof invalid".to_string())))?
+            }
+        }
+        self.aggregation_store.create_participation(signed_participation, agg.participation_policy)
+    }
+
+    pub fn create_enrollment(&self, enrollment: &Enrollment) -> SdaServerResult<()> {
+        let agg = self.aggregation_store.get_aggregation(&enrollment.aggregation)?
+            .ok_or("aggregation not found")?;
+        if !agg.masking_scheme.is_pairwise() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Aggregation does not use pairwise masking".to_string())))?
+        }
+        self.check_state(&enrollment.aggregation, &[AggregationState::Open], "accept enrollments")?;
+        if self.aggregation_store.get_masking_cohort(&enrollment.aggregation)?.is_some() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Enrollment is closed for this aggregation".to_string())))?
+        }
+        // secret keys are shared with the committee, so it must be known before enrolling
+        let committee = self.aggregation_store.get_committee(&enrollment.aggregation)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Committee not yet formed".to_string())))?;
+        let clerks_match = committee.clerks_and_keys.len() == enrollment.clerk_encryptions.len()
+            && committee.clerks_and_keys.iter().zip(&enrollment.clerk_encryptions)
+                .all(|(&(clerk, _), &(ref recipient, _))| clerk == *recipient);
+        if !clerks_match {
+            Err(SdaError::from(SdaErrorKind::Invalid("Enrollment must hold a share for each clerk in the committee".to_string())))?
+        }
+        if enrollment.masking_key.signer != enrollment.participant {
+            Err(SdaError::from(SdaErrorKind::Invalid("Masking key must belong to the participant".to_string())))?
+        }
+        self.check_signature(&enrollment.masking_key)?;
+        match enrollment.masking_key.body.body {
+            EncryptionKey::Sodium(_) => {}
+            _ => Err(SdaError::from(SdaErrorKind::Invalid("Masking key must be a Sodium key".to_string())))?
+        }
+        if self.aggregation_store.get_enrollment(&enrollment.aggregation, &enrollment.participant)?.is_some() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Participant already enrolled".to_string())))?
+        }
+        self.aggregation_store.create_enrollment(enrollment)
+    }
+
+    pub fn get_masking_cohort(&self, aggregation: &AggregationId) -> SdaServerResult<Option<MaskingCohort>> {
+        self.aggregation_store.get_masking_cohort(aggregation)
+    }
+
+    pub fn create_masking_cohort(&self, aggregation: &AggregationId) -> SdaServerResult<()> {
+        let agg = self.aggregation_store.get_aggregation(aggregation)?
+            .ok_or("aggregation not found")?;
+        if !agg.masking_scheme.is_pairwise() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Aggregation does not use pairwise masking".to_string())))?
+        }
+        self.check_state(aggregation, &[AggregationState::Open], "form the masking cohort")?;
+        if self.aggregation_store.get_masking_cohort(aggregation)?.is_some() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Masking cohort already formed".to_string())))?
+        }
+        let members: Vec<(AgentId, SignedEncryptionKey)> = self.aggregation_store
+            .list_enrollments(aggregation)?
+            .into_iter()
+            .map(|enrollment| (enrollment.participant, enrollment.masking_key))
+            .collect();
+        // a lone member would have nobody to agree masks with
+        if members.len() < 2 {
+            Err(SdaError::from(SdaErrorKind::Invalid(format!(
+                "Masking cohort needs at least 2 members, found {}", members.len()))))?
+        }
+        self.aggregation_store.create_masking_cohort(&MaskingCohort {
+            aggregation: aggregation.clone(),
+            members: members,
+        })
     }
 
     pub fn get_aggregation_status(&self,
@@ -118,22 +515,105 @@ impl SdaServer {
                     number_of_clerking_results: results_count,
                     result_ready: results_count >=
                                   agg.committee_sharing_scheme.reconstruction_threshold(),
+                    unresponsive_clerks: self.clerking_job_store
+                        .list_abandoned_clerking_jobs(id)?
+                        .into_iter()
+                        .map(|lease| lease.clerk)
+                        .collect(),
                 })
             })
             .collect::<SdaServerResult<Vec<SnapshotStatus>>>()?;
+        let privacy_budget = self.privacy_budget(&agg.body)?;
         Ok(Some(AggregationStatus {
             aggregation: aggregation.clone(),
             number_of_participations: self.aggregation_store.count_participations(aggregation)?,
             snapshots: snapshots,
+            privacy_budget: privacy_budget,
+            state: self.get_aggregation_state(aggregation)?,
+            pending_clerks: self.pending_clerks(aggregation)?,
         }))
     }
 
+    /// Privacy budget spent so far by an aggregation with differential privacy.
+    pub fn privacy_budget(&self, aggregation: &Aggregation) -> SdaServerResult<Option<PrivacyBudget>> {
+        if aggregation.differential_privacy.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(self.aggregation_store.get_privacy_budget(&aggregation.id)?.unwrap_or_default()))
+    }
+
+    /// Whether the privacy budget of an aggregation allows one more snapshot.
+    pub fn can_release(&self, aggregation: &Aggregation) -> SdaServerResult<bool> {
+        Ok(match aggregation.differential_privacy {
+            None => true,
+            Some(ref privacy) => self.privacy_budget(aggregation)?.unwrap_or_default().after_release(privacy).is_within(privacy),
+        })
+    }
+
+    pub fn get_snapshot_participations(&self,
+                                       aggregation: &AggregationId,
+                                       snapshot: &SnapshotId)
+                                       -> SdaServerResult<Vec<SignedParticipation>> {
+        self.aggregation_store
+            .iter_snapped_participations(aggregation, snapshot)?
+            .collect()
+    }
+
     pub fn create_snapshot(&self, snapshot: &Snapshot) -> SdaServerResult<()> {
-        ::snapshot::snapshot(self, snapshot)
+        self.check_state(&snapshot.aggregation,
+                         &[AggregationState::Open, AggregationState::Closed],
+                         "take a snapshot")?;
+        ::snapshot::snapshot(self, snapshot)?;
+        if snapshot.incremental {
+            return Ok(());
+        }
+        self.aggregation_store.set_aggregation_state(&snapshot.aggregation, AggregationState::Closed)
     }
 
     pub fn poll_clerking_job(&self, clerk: &AgentId) -> SdaServerResult<Option<ClerkingJob>> {
-        self.clerking_job_store.poll_clerking_job(clerk)
+        self.lease_clerking_job(clerk, ::scheduler::now()?)
+    }
+
+    /// Hand out a job to its clerk at the given time, for `CLERKING_LEASE` seconds.
+    ///
+    /// Jobs already leased are skipped until their lease expires.
+    pub fn lease_clerking_job(&self, clerk: &AgentId, now: u64) -> SdaServerResult<Option<ClerkingJob>> {
+        self.agents_store.record_clerk_seen(clerk, now)?;
+        self.clerking_job_store.poll_clerking_job(clerk, now, now + CLERKING_LEASE)
+    }
+
+    pub fn clerk_ping(&self, clerk: &AgentId) -> SdaServerResult<Pong> {
+        self.agents_store.record_clerk_seen(clerk, ::scheduler::now()?)?;
+        self.ping()
+    }
+
+    /// Give up on the jobs left unanswered for too long, so that reveal may proceed without their clerks.
+    ///
+    /// A job is overdue once handed out `MAX_CLERKING_ATTEMPTS` times, or once that many leases have passed
+    /// since it was queued, and is only given up on while the remaining clerks can still reach the
+    /// reconstruction threshold. Returns the number of jobs given up on.
+    pub fn note_unresponsive_clerks(&self, now: u64) -> SdaServerResult<usize> {
+        let mut noted = 0;
+        for lease in self.clerking_job_store.list_clerking_job_leases()? {
+            let overdue = lease.attempts >= MAX_CLERKING_ATTEMPTS ||
+                          now >= lease.enqueued_at + MAX_CLERKING_ATTEMPTS as u64 * CLERKING_LEASE;
+            if !lease.is_available(now) || !overdue {
+                continue;
+            }
+            let job = self.clerking_job_store.get_clerking_job(&lease.clerk, &lease.job)?
+                .ok_or("inconsistent storage")?;
+            let agg = self.aggregation_store.get_aggregation(&job.aggregation)?
+                .ok_or("aggregation not found")?;
+            let scheme = &agg.committee_sharing_scheme;
+            let abandoned = self.clerking_job_store.list_abandoned_clerking_jobs(&lease.snapshot)?.len();
+            if scheme.output_size() < abandoned + 1 + scheme.reconstruction_threshold() {
+                continue;
+            }
+            warn!("Clerk {:?} deemed unresponsive for snapshot {:?}", lease.clerk, lease.snapshot);
+            self.clerking_job_store.abandon_clerking_job(&lease.job)?;
+            noted += 1;
+        }
+        Ok(noted)
     }
 
     pub fn get_clerking_job(&self,
@@ -143,8 +623,35 @@ impl SdaServer {
         self.clerking_job_store.get_clerking_job(clerk, job)
     }
 
-    pub fn create_clerking_result(&self, result: &ClerkingResult) -> SdaServerResult<()> {
-        self.clerking_job_store.create_clerking_result(&result)
+    pub fn create_clerking_result(&self, result: &SignedClerkingResult) -> SdaServerResult<()> {
+        if result.signer != result.body.clerk {
+            Err(SdaError::from(SdaErrorKind::Invalid("Clerking result must be signed by the clerk".to_string())))?
+        }
+        self.check_signature(result)?;
+        let job = self.clerking_job_store.get_clerking_job(&result.body.clerk, &result.body.job)?
+            .ok_or("job not found")?;
+        if result.body.aggregation != job.aggregation || result.body.snapshot != job.snapshot {
+            Err(SdaError::from(SdaErrorKind::Invalid("Clerking result does not match the aggregation and snapshot of its job".to_string())))?
+        }
+        for id in self.clerking_job_store.list_results(&job.snapshot)? {
+            let previous = self.clerking_job_store.get_result(&job.snapshot, &id)?
+                .ok_or("inconsistent storage")?;
+            if previous.body.clerk == result.body.clerk {
+                Err(SdaError::from(SdaErrorKind::Invalid("Clerk already returned a result for this snapshot".to_string())))?
+            }
+        }
+        let agg = self.aggregation_store.get_aggregation(&job.aggregation)?
+            .ok_or("aggregation not found")?;
+        // snapshots taken on schedule leave the aggregation open
+        let state = self.check_state(&job.aggregation,
+                                     &[AggregationState::Open, AggregationState::Closed, AggregationState::Revealed],
+                                     "accept clerking results")?;
+        self.clerking_job_store.create_clerking_result(&result)?;
+        let results_count = self.clerking_job_store.list_results(&job.snapshot)?.len();
+        if state == AggregationState::Closed && results_count >= agg.committee_sharing_scheme.reconstruction_threshold() {
+            self.aggregation_store.set_aggregation_state(&job.aggregation, AggregationState::Revealed)?;
+        }
+        Ok(())
     }
 
     pub fn get_snapshot_result(&self,
@@ -157,7 +664,7 @@ impl SdaServer {
             .map(|id| {
                 Ok(self.clerking_job_store.get_result(snapshot, id)?.ok_or("inconsistent storage")?)
             })
-            .collect::<SdaServerResult<Vec<ClerkingResult>>>()?;
+            .collect::<SdaServerResult<Vec<SignedClerkingResult>>>()?;
         Ok(Some(SnapshotResult {
             snapshot: snapshot.clone(),
             number_of_participations: self.aggregation_store
@@ -167,27 +674,79 @@ impl SdaServer {
         }))
     }
 
-    pub fn upsert_auth_token(&self, token: &AuthToken) -> SdaResult<()> {
-        wrap! { self.auth_tokens_store.upsert_auth_token(token) }
-    }
-
-    pub fn check_auth_token(&self, token: &AuthToken) -> SdaResult<Agent> {
-        let db = self.auth_tokens_store
-            .get_auth_token(token.id())
-            .map_err(|e| format!("error in server: {}", e))?;
-        if db.as_ref() == Some(token) {
-            Ok(self.agents_store
-                .get_agent(&token.id)
-                .map_err(|e| format!("error in server: {}", e))?
-                .ok_or("Agent not found")?)
-        } else {
-            Err(SdaErrorKind::InvalidCredentials)?
+    pub fn create_reveal_contribution(&self, signed_contribution: &SignedRevealContribution) -> SdaServerResult<()> {
+        let contribution = &signed_contribution.body;
+        if signed_contribution.signer != contribution.member {
+            Err(SdaError::from(SdaErrorKind::Invalid("Reveal contribution must be signed by the member".to_string())))?
+        }
+        self.check_signature(signed_contribution)?;
+        let agg = self.aggregation_store.get_aggregation(&contribution.aggregation)?
+            .ok_or("aggregation not found")?;
+        let group = agg.recipient_group.as_ref()
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Aggregation has no recipient group".to_string())))?;
+        if group.position(&contribution.member).is_none() {
+            Err(SdaError::from(SdaErrorKind::Invalid("Contributor is not a member of the recipient group".to_string())))?
         }
+        let snapshot = self.aggregation_store.get_snapshot(&contribution.aggregation, &contribution.snapshot)?
+            .ok_or("snapshot not found")?;
+        if snapshot.aggregation != contribution.aggregation {
+            Err(SdaError::from(SdaErrorKind::Invalid("Snapshot belongs to another aggregation".to_string())))?
+        }
+        self.check_state(&contribution.aggregation,
+                         &[AggregationState::Open, AggregationState::Closed, AggregationState::Revealed],
+                         "accept reveal contributions")?;
+        let existing = self.aggregation_store.list_reveal_contributions(&contribution.snapshot)?;
+        if existing.iter().any(|other| other.body.member == contribution.member) {
+            Err(SdaError::from(SdaErrorKind::Invalid("Member already contributed to this snapshot".to_string())))?
+        }
+        self.aggregation_store.create_reveal_contribution(signed_contribution)
+    }
+
+    pub fn get_reveal_contributions(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<SignedRevealContribution>> {
+        self.aggregation_store.list_reveal_contributions(snapshot)
+    }
+
+    pub fn upsert_auth_token(&self, token: &AuthToken) -> SdaResult<()> {
+        wrap! { HashedAuthToken::new(token).and_then(|hashed| self.auth_tokens_store.upsert_auth_token(&hashed)) }
+    }
+
+    /// Replace the auth token of an agent, on behalf of the agent itself.
+    pub fn rotate_auth_token(&self, caller: &Agent, token: &AuthToken) -> SdaResult<()> {
+        acl_agent_is(caller, token.id)?;
+        self.upsert_auth_token(token)
+    }
+
+    /// Hash any auth token left in clear by earlier versions of the service.
+    pub fn migrate_auth_tokens(&self) -> SdaResult<usize> {
+        wrap! { self.auth_tokens_store.migrate_auth_tokens() }
+    }
+
+    /// Authenticate a caller, using the authenticator of the service.
+    pub fn authenticate(&self, credentials: &Credentials) -> SdaResult<Agent> {
+        wrap! { self.authenticator.authenticate(self, credentials) }
+    }
+
+    /// Check the credentials presented along with a new agent, using the authenticator of the service.
+    pub fn enroll(&self, agent: &Agent, credentials: &Credentials) -> SdaResult<()> {
+        wrap! { self.authenticator.enroll(self, agent, credentials) }
     }
 
     pub fn delete_auth_token(&self, agent: &AgentId) -> SdaResult<()> {
         wrap!(self.auth_tokens_store.delete_auth_token(agent))
     }
+
+    /// Check that a resource was signed by its claimed signer, as registered with the service.
+    fn check_signature<M>(&self, signed: &Signed<M>) -> SdaServerResult<()>
+        where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+    {
+        let signer = self.agents_store.get_agent(&signed.signer)?
+            .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Signer not found".to_string())))?;
+        check_current_key(&signer, signed)?;
+        if !::signatures::signature_is_valid(&signer, signed)? {
+            Err(SdaError::from(SdaErrorKind::Invalid("Signature verification failed".to_string())))?
+        }
+        Ok(())
+    }
 }
 
 pub struct SdaServerService(pub SdaServer);
@@ -208,6 +767,29 @@ fn acl_agent_is(agent: &Agent, agent_id: AgentId) -> SdaResult<()> {
     }
 }
 
+/// Members of a recipient group take part in revealing results, and may hence follow the aggregation.
+/// Refuse resources naming a verification key other than the current one of their signer.
+///
+/// Clients trust retired keys on the grounds that they were current when the resource was stored.
+fn check_current_key<M>(signer: &Agent, signed: &Signed<M>) -> SdaServerResult<()>
+    where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+{
+    if signed.key.map_or(false, |key| key != signer.verification_key.id) {
+        Err(SdaError::from(SdaErrorKind::Invalid("Signed with a retired verification key".to_string())))?
+    }
+    Ok(())
+}
+
+fn acl_agent_is_recipient_or_member(agent: &Agent, aggregation: &Aggregation) -> SdaResult<()> {
+    let is_member = aggregation.recipient_group.as_ref()
+        .map_or(false, |group| group.position(&agent.id).is_some());
+    if is_member {
+        Ok(())
+    } else {
+        acl_agent_is(agent, aggregation.recipient)
+    }
+}
+
 impl SdaAgentService for SdaServerService {
     fn create_agent(&self, caller: &Agent, agent: &Agent) -> SdaResult<()> {
         acl_agent_is(caller, agent.id)?;
@@ -219,6 +801,16 @@ impl SdaAgentService for SdaServerService {
         wrap! { self.0.get_agent(owner) }
     }
 
+    fn rotate_verification_key(&self, caller: &Agent, rotation: &SignedVerificationKeyRotation) -> SdaResult<()> {
+        acl_agent_is(caller, rotation.agent)?;
+        wrap! { self.0.rotate_verification_key(rotation) }
+    }
+
+    fn get_verification_key_history(&self, _caller: &Agent, owner: &AgentId) -> SdaResult<Vec<SignedVerificationKeyRotation>> {
+        // everything here is public, no acl
+        wrap! { self.0.get_verification_key_history(owner) }
+    }
+
     fn upsert_profile(&self, caller: &Agent, profile: &Profile) -> SdaResult<()> {
         acl_agent_is(caller, profile.owner)?;
         wrap! { self.0.upsert_profile(profile) }
@@ -241,6 +833,16 @@ impl SdaAgentService for SdaServerService {
         // everything here is public, no acl
         wrap! { self.0.get_encryption_key(key) }
     }
+
+    fn list_encryption_keys(&self, _caller: &Agent, owner: &AgentId) -> SdaResult<Vec<EncryptionKeyId>> {
+        // everything here is public, no acl
+        wrap! { self.0.list_encryption_keys(owner) }
+    }
+
+    fn revoke_encryption_key(&self, caller: &Agent, revocation: &SignedEncryptionKeyRevocation) -> SdaResult<()> {
+        acl_agent_is(caller, revocation.signer)?;
+        wrap! { self.0.revoke_encryption_key(revocation) }
+    }
 }
 
 impl SdaAggregationService for SdaServerService {
@@ -255,26 +857,44 @@ impl SdaAggregationService for SdaServerService {
     fn get_aggregation(&self,
                        _caller: &Agent,
                        aggregation: &AggregationId)
-                       -> SdaResult<Option<Aggregation>> {
+                       -> SdaResult<Option<SignedAggregation>> {
         wrap!(self.0.get_aggregation(aggregation))
     }
 
     fn get_committee(&self,
                      _caller: &Agent,
                      aggregation: &AggregationId)
-                     -> SdaResult<Option<Committee>> {
+                     -> SdaResult<Option<SignedCommittee>> {
         wrap!(self.0.get_committee(aggregation))
     }
+
+    fn get_snapshot_participations(&self,
+                                   caller: &Agent,
+                                   aggregation: &AggregationId,
+                                   snapshot: &SnapshotId)
+                                   -> SdaResult<Vec<SignedParticipation>> {
+        // only the recipient and the clerks of the committee may audit
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg = agg?.ok_or("No aggregation found")?;
+        let committee: SdaResult<Option<SignedCommittee>> = wrap! { self.0.get_committee(aggregation) };
+        let is_clerk = committee?.map_or(false, |committee| {
+            committee.clerks_and_keys.iter().any(|&(clerk, _)| clerk == caller.id)
+        });
+        if !is_clerk {
+            acl_agent_is(caller, agg.recipient)?;
+        }
+        wrap! { self.0.get_snapshot_participations(aggregation, snapshot) }
+    }
 }
 
 impl SdaRecipientService for SdaServerService {
-    fn create_aggregation(&self, caller: &Agent, aggregation: &Aggregation) -> SdaResult<()> {
+    fn create_aggregation(&self, caller: &Agent, aggregation: &SignedAggregation) -> SdaResult<()> {
         acl_agent_is(caller, aggregation.recipient)?;
         wrap! { self.0.create_aggregation(&aggregation) }
     }
 
     fn delete_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
-        let agg: SdaResult<Option<Aggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
         acl_agent_is(caller, agg.recipient)?;
@@ -283,17 +903,18 @@ impl SdaRecipientService for SdaServerService {
 
     fn suggest_committee(&self,
                          caller: &Agent,
-                         aggregation: &AggregationId)
+                         aggregation: &AggregationId,
+                         criteria: &CommitteeCriteria)
                          -> SdaResult<Vec<ClerkCandidate>> {
-        let agg: SdaResult<Option<Aggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
         acl_agent_is(caller, agg.recipient)?;
-        wrap! { self.0.suggest_committee(aggregation) }
+        wrap! { self.0.suggest_committee(aggregation, criteria) }
     }
 
-    fn create_committee(&self, caller: &Agent, committee: &Committee) -> SdaResult<()> {
-        let agg: SdaResult<Option<Aggregation>> =
+    fn create_committee(&self, caller: &Agent, committee: &SignedCommittee) -> SdaResult<()> {
+        let agg: SdaResult<Option<SignedAggregation>> =
             wrap! { self.0.get_aggregation(&committee.aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
@@ -301,19 +922,27 @@ impl SdaRecipientService for SdaServerService {
         wrap! { self.0.create_committee(committee) }
     }
 
+    fn create_masking_cohort(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg = agg?;
+        let agg = agg.ok_or("No aggregation found")?;
+        acl_agent_is(caller, agg.recipient)?;
+        wrap! { self.0.create_masking_cohort(aggregation) }
+    }
+
     fn get_aggregation_status(&self,
                               caller: &Agent,
                               aggregation: &AggregationId)
                               -> SdaResult<Option<AggregationStatus>> {
-        let agg: SdaResult<Option<Aggregation>> = wrap! { self.0.get_aggregation(&aggregation) };
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(&aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
-        acl_agent_is(caller, agg.recipient)?;
+        acl_agent_is_recipient_or_member(caller, &agg)?;
         wrap!(self.0.get_aggregation_status(aggregation))
     }
 
     fn create_snapshot(&self, caller: &Agent, snapshot: &Snapshot) -> SdaResult<()> {
-        let agg: SdaResult<Option<Aggregation>> =
+        let agg: SdaResult<Option<SignedAggregation>> =
             wrap! { self.0.get_aggregation(&snapshot.aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
@@ -321,25 +950,69 @@ impl SdaRecipientService for SdaServerService {
         wrap! { self.0.create_snapshot(snapshot) }
     }
 
+    fn close_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg = agg?;
+        let agg = agg.ok_or("No aggregation found")?;
+        acl_agent_is(caller, agg.recipient)?;
+        wrap! { self.0.close_aggregation(aggregation) }
+    }
+
+    fn archive_aggregation(&self, caller: &Agent, aggregation: &AggregationId) -> SdaResult<()> {
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(aggregation) };
+        let agg = agg?;
+        let agg = agg.ok_or("No aggregation found")?;
+        acl_agent_is(caller, agg.recipient)?;
+        wrap! { self.0.archive_aggregation(aggregation) }
+    }
+
     fn get_snapshot_result(&self,
                            caller: &Agent,
                            aggregation: &AggregationId,
                            snapshot: &SnapshotId)
                            -> SdaResult<Option<SnapshotResult>> {
         // FIXME no aggregation/snapshot spoofing
-        let agg: SdaResult<Option<Aggregation>> = wrap! { self.0.get_aggregation(&aggregation) };
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(&aggregation) };
         let agg = agg?;
         let agg = agg.ok_or("No aggregation found")?;
-        acl_agent_is(caller, agg.recipient)?;
+        acl_agent_is_recipient_or_member(caller, &agg)?;
         wrap! { self.0.get_snapshot_result(aggregation, snapshot) }
     }
+
+    fn create_reveal_contribution(&self, caller: &Agent, contribution: &SignedRevealContribution) -> SdaResult<()> {
+        acl_agent_is(caller, contribution.body.member)?;
+        wrap! { self.0.create_reveal_contribution(contribution) }
+    }
+
+    fn get_reveal_contributions(&self,
+                                caller: &Agent,
+                                aggregation: &AggregationId,
+                                snapshot: &SnapshotId)
+                                -> SdaResult<Vec<SignedRevealContribution>> {
+        let agg: SdaResult<Option<SignedAggregation>> = wrap! { self.0.get_aggregation(&aggregation) };
+        let agg = agg?;
+        let agg = agg.ok_or("No aggregation found")?;
+        acl_agent_is(caller, agg.recipient)?;
+        let contributions: SdaResult<Vec<SignedRevealContribution>> = wrap! { self.0.get_reveal_contributions(snapshot) };
+        Ok(contributions?.into_iter().filter(|contribution| contribution.body.aggregation == *aggregation).collect())
+    }
 }
 
 impl SdaParticipationService for SdaServerService {
-    fn create_participation(&self, caller: &Agent, participation: &Participation) -> SdaResult<()> {
-        acl_agent_is(caller, participation.participant)?;
+    fn create_participation(&self, caller: &Agent, participation: &SignedParticipation) -> SdaResult<()> {
+        acl_agent_is(caller, participation.body.participant)?;
         wrap!(self.0.create_participation(participation))
     }
+
+    fn create_enrollment(&self, caller: &Agent, enrollment: &Enrollment) -> SdaResult<()> {
+        acl_agent_is(caller, enrollment.participant)?;
+        wrap!(self.0.create_enrollment(enrollment))
+    }
+
+    fn get_masking_cohort(&self, _caller: &Agent, aggregation: &AggregationId) -> SdaResult<Option<MaskingCohort>> {
+        // everything here is public, no acl
+        wrap!(self.0.get_masking_cohort(aggregation))
+    }
 }
 
 impl SdaClerkingService for SdaServerService {
@@ -348,11 +1021,34 @@ impl SdaClerkingService for SdaServerService {
         wrap!(self.0.poll_clerking_job(clerk))
     }
 
-    fn create_clerking_result(&self, caller: &Agent, result: &ClerkingResult) -> SdaResult<()> {
+    fn clerk_ping(&self, caller: &Agent) -> SdaResult<Pong> {
+        wrap!(self.0.clerk_ping(&caller.id))
+    }
+
+    fn create_clerk_registration(&self, caller: &Agent, registration: &SignedClerkRegistration) -> SdaResult<()> {
+        acl_agent_is(caller, registration.body.clerk)?;
+        wrap!(self.0.create_clerk_registration(registration))
+    }
+
+    fn get_clerk_registration(&self, _caller: &Agent, clerk: &AgentId) -> SdaResult<Option<SignedClerkRegistration>> {
+        wrap!(self.0.get_clerk_registration(clerk))
+    }
+
+    fn list_pending_seats(&self, caller: &Agent, clerk: &AgentId) -> SdaResult<Vec<AggregationId>> {
+        acl_agent_is(caller, *clerk)?;
+        wrap!(self.0.list_pending_seats(clerk))
+    }
+
+    fn create_seat_acceptance(&self, caller: &Agent, acceptance: &SignedSeatAcceptance) -> SdaResult<()> {
+        acl_agent_is(caller, acceptance.body.clerk)?;
+        wrap!(self.0.create_seat_acceptance(acceptance))
+    }
+
+    fn create_clerking_result(&self, caller: &Agent, result: &SignedClerkingResult) -> SdaResult<()> {
         // double check the job really belongs to the caller (could be spoofed
         // if the store do a find_by_job_id without filtering on clerk id)
         let job: SdaResult<Option<ClerkingJob>> =
-            wrap! { self.0.get_clerking_job(&result.clerk, &result.job) };
+            wrap! { self.0.get_clerking_job(&result.body.clerk, &result.body.job) };
         let job = job?;
         let job = job.ok_or("Job not found")?;
         acl_agent_is(caller, job.clerk)?;
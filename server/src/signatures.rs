@@ -0,0 +1,23 @@
+//! Verification of the signatures agents put on the resources they upload.
+
+use sodiumoxide::crypto::sign;
+
+use sda_protocol::*;
+use SdaServerResult;
+
+/// Check that `signed` was signed by `agent`, using its verification key.
+pub fn signature_is_valid<M>(agent: &Agent, signed: &Signed<M>) -> SdaServerResult<bool>
+    where M: Clone + ::std::fmt::Debug + PartialEq + ::serde::Serialize + ::serde::Deserialize
+{
+    if signed.signer != agent.id {
+        return Ok(false);
+    }
+    match (&agent.verification_key.body, &signed.signature) {
+        (&VerificationKey::Sodium(raw_vk), &Signature::Sodium(raw_sig)) => {
+            let sig = sign::Signature(*raw_sig);
+            let vk = sign::PublicKey(*raw_vk);
+            let msg = signed.body.canonical()?;
+            Ok(sign::verify_detached(&sig, &*msg, &vk))
+        }
+    }
+}
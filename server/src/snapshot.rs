@@ -1,29 +1,75 @@
 use {SdaServer, SdaServerResult};
 use sda_protocol::*;
 
+/// Record the privacy loss of one more release, refusing it if it would exceed the budget.
+///
+/// The budget is spent before the snapshot is taken, so that a failed snapshot errs on the side of privacy.
+fn spend_privacy_budget(server: &SdaServer, aggregation: &AggregationId, privacy: &DifferentialPrivacy) -> SdaServerResult<()> {
+    let spent = server.aggregation_store.get_privacy_budget(aggregation)?;
+    let next = spent.clone().unwrap_or_default().after_release(privacy);
+    if !next.is_within(privacy) {
+        Err(SdaError::from(SdaErrorKind::Invalid("Snapshot would exceed the privacy budget".to_string())))?
+    }
+    if !server.aggregation_store.update_privacy_budget(aggregation, spent.as_ref(), &next)? {
+        Err(SdaError::from(SdaErrorKind::Invalid("Privacy budget was spent concurrently, retry the snapshot".to_string())))?
+    }
+    Ok(())
+}
+
 pub fn snapshot(server: &SdaServer, snapshot: &Snapshot) -> SdaServerResult<()> {
     let aggregation =
         server.aggregation_store.get_aggregation(&snapshot.aggregation)?.ok_or("lost aggregation")?;
+    if snapshot.incremental {
+        // recovering the masks of cohort members absent from a delta would give away their earlier inputs
+        if aggregation.masking_scheme.is_pairwise() {
+            Err(SdaError::from(SdaErrorKind::Invalid(
+                "Incremental snapshots cannot be used with pairwise masking".to_string())))?
+        }
+        if server.aggregation_store.count_new_participations(&snapshot.aggregation)? == 0 {
+            Err(SdaError::from(SdaErrorKind::Invalid("No participations since the previous snapshot".to_string())))?
+        }
+    }
+    if let Some(ref privacy) = aggregation.differential_privacy {
+        spend_privacy_budget(server, &snapshot.aggregation, privacy)?;
+    }
     debug!("Snapshot participations");
-    server.aggregation_store.snapshot_participations(&snapshot.aggregation, &snapshot.id)?;
+    server.aggregation_store.snapshot_participations(&snapshot.aggregation, &snapshot.id, snapshot.incremental)?;
     let committee = server.get_committee(&snapshot.aggregation)?.ok_or("lost committee")?;
     debug!("Transposing encryptions");
     let encryptions = server.aggregation_store
         .iter_snapshot_clerk_jobs_data(&snapshot.aggregation,
                                        &snapshot.id,
                                        committee.clerks_and_keys.len())?;
-    let clerks_ids = committee.clerks_and_keys.into_iter().map(|c| c.0);
+
+    let dropouts = if aggregation.masking_scheme.is_pairwise() {
+        debug!("Collecting enrollments of cohort members missing from snapshot");
+        missing_enrollments(server, snapshot)?
+    } else {
+        vec![]
+    };
 
     debug!("Creating ckerking jobs");
-    for (clerk, shares) in clerks_ids.zip(encryptions) {
+    let now = ::scheduler::now()?;
+    let scheme = &aggregation.committee_encryption_scheme;
+    for (clerk_index, (&(clerk, key), shares)) in committee.clerks_and_keys.iter().zip(encryptions).enumerate() {
+        let mut shares = shares?;
+        if scheme.is_homomorphic() && !shares.is_empty() {
+            debug!("Summing encryptions for clerk {:?}", clerk);
+            check_summands(scheme, shares.len())?;
+            let key = server.agents_store.get_encryption_key(&key)?.ok_or("lost clerk encryption key")?;
+            shares = vec![::homomorphic::sum(&key.body.body, scheme, shares)?];
+        }
         server.clerking_job_store
             .enqueue_clerking_job(&ClerkingJob {
                 id: ClerkingJobId::random(),
                 clerk: clerk,
                 aggregation: snapshot.aggregation.clone(),
                 snapshot: snapshot.id.clone(),
-                encryptions: shares?,
-            })?;
+                encryptions: shares,
+                recovery_encryptions: dropouts.iter()
+                    .map(|enrollment| (enrollment.participant, enrollment.clerk_encryptions[clerk_index].1.clone()))
+                    .collect(),
+            }, now)?;
     }
 
     debug!("Create snapshot");
@@ -35,13 +81,58 @@ pub fn snapshot(server: &SdaServer, snapshot: &Snapshot) -> SdaServerResult<()>
             .iter_snapped_participations(&snapshot.aggregation, &snapshot.id)?
             .map(|part| -> SdaServerResult<Encryption> {
                 Ok(part?
+                        .body
                         .recipient_encryption
                         .ok_or("participation should have had a recipient encryption")?)
             })
             .collect::<SdaServerResult<Vec<Encryption>>>()?;
+        // only full masks are additive; seeds must be expanded by the recipient before summing
+        let scheme = &aggregation.recipient_encryption_scheme;
+        let recipient_encryptions = match aggregation.masking_scheme {
+            // masks shared with a recipient group are not encrypted under a single key
+            LinearMaskingScheme::Full { .. } if scheme.is_homomorphic() && aggregation.recipient_group.is_none()
+                                                 && !recipient_encryptions.is_empty() => {
+                debug!("Summing masks");
+                check_summands(scheme, recipient_encryptions.len())?;
+                let key = server.agents_store
+                    .get_encryption_key(&aggregation.recipient_key)?
+                    .ok_or("lost recipient encryption key")?;
+                vec![::homomorphic::sum(&key.body.body, scheme, recipient_encryptions)?]
+            }
+            _ => recipient_encryptions,
+        };
         server.aggregation_store.create_snapshot_mask(&snapshot.id, recipient_encryptions)?;
     }
 
     debug!("Done snapshot");
     Ok(())
 }
+
+/// Enrollments of the masking cohort members that did not make it into the snapshot.
+fn missing_enrollments(server: &SdaServer, snapshot: &Snapshot) -> SdaServerResult<Vec<Enrollment>> {
+    let cohort = server.aggregation_store
+        .get_masking_cohort(&snapshot.aggregation)?
+        .ok_or_else(|| SdaError::from(SdaErrorKind::Invalid("Masking cohort not yet formed".to_string())))?;
+    let participants = server.aggregation_store
+        .iter_snapped_participations(&snapshot.aggregation, &snapshot.id)?
+        .map(|part| Ok(part?.body.participant))
+        .collect::<SdaServerResult<Vec<AgentId>>>()?;
+    cohort.members.iter()
+        .filter(|&&(ref member, _)| !participants.contains(member))
+        .map(|&(ref member, _)| {
+            Ok(server.aggregation_store
+                .get_enrollment(&snapshot.aggregation, member)?
+                .ok_or("lost enrollment")?)
+        })
+        .collect()
+}
+
+fn check_summands(scheme: &AdditiveEncryptionScheme, count: usize) -> SdaServerResult<()> {
+    if let Some(max) = scheme.max_summands() {
+        if count > max {
+            Err(SdaError::from(SdaErrorKind::Invalid(
+                format!("Too many participations ({}) for the encryption scheme, at most {} may be summed", count, max))))?
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use sodiumoxide::crypto::pwhash;
+
 use sda_protocol::*;
 use SdaServerResult;
 
@@ -7,15 +9,90 @@ pub trait BaseStore : Sync + Send {
 
 pub type AuthToken = Labelled<AgentId, String>;
 
+/// Auth token as kept at rest: a salted scrypt hash of the secret, never the secret itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HashedAuthToken {
+    pub id: AgentId,
+    /// Hash in the modular crypt format, embedding salt and cost parameters.
+    pub hash: String,
+}
+
+impl Identified for HashedAuthToken {
+    type I = AgentId;
+    fn id(&self) -> &AgentId {
+        &self.id
+    }
+}
+
+impl HashedAuthToken {
+    /// Hash a token, using a fresh random salt.
+    pub fn new(token: &AuthToken) -> SdaServerResult<HashedAuthToken> {
+        let hashed = pwhash::pwhash(token.body.as_bytes(), pwhash::OPSLIMIT_INTERACTIVE, pwhash::MEMLIMIT_INTERACTIVE)
+            .map_err(|_| "Could not hash auth token")?;
+        // the hash is a nul-padded ascii string
+        let length = hashed.0.iter().position(|&b| b == 0).unwrap_or(hashed.0.len());
+        Ok(HashedAuthToken {
+            id: token.id,
+            hash: String::from_utf8(hashed.0[..length].to_vec()).map_err(|_| "Invalid auth token hash")?,
+        })
+    }
+
+    /// Check a token against the hash, comparing in constant time.
+    pub fn matches(&self, token: &AuthToken) -> bool {
+        let mut padded = self.hash.clone().into_bytes();
+        if token.id != self.id || padded.len() > pwhash::HASHEDPASSWORDBYTES {
+            return false;
+        }
+        padded.resize(pwhash::HASHEDPASSWORDBYTES, 0);
+        match pwhash::HashedPassword::from_slice(&padded) {
+            Some(hashed) => pwhash::pwhash_verify(&hashed, token.body.as_bytes()),
+            None => false,
+        }
+    }
+}
+
+/// Progress of a clerking job without result, as tracked by the service.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClerkingJobLease {
+    pub job: ClerkingJobId,
+    pub clerk: AgentId,
+    pub snapshot: SnapshotId,
+    /// Time the job was queued at, in seconds since the Unix epoch.
+    pub enqueued_at: u64,
+    /// End of the current lease, if the job was ever polled; the job is handed out again once it has passed.
+    pub leased_until: Option<u64>,
+    /// Number of times the job was handed out.
+    pub attempts: u32,
+    /// Set once the clerk is deemed unresponsive; the job is then no longer handed out.
+    pub abandoned: bool,
+}
+
+impl Identified for ClerkingJobLease {
+    type I = ClerkingJobId;
+    fn id(&self) -> &ClerkingJobId {
+        &self.job
+    }
+}
+
+impl ClerkingJobLease {
+    /// Check whether the job may be handed out at the given time.
+    pub fn is_available(&self, now: u64) -> bool {
+        !self.abandoned && self.leased_until.map_or(true, |leased_until| leased_until <= now)
+    }
+}
+
 pub trait AuthTokensStore: BaseStore {
     /// Save an auth token
-    fn upsert_auth_token(&self, token:&AuthToken) -> SdaServerResult<()>;
+    fn upsert_auth_token(&self, token:&HashedAuthToken) -> SdaServerResult<()>;
 
     /// Retrieve an auth token
-    fn get_auth_token(&self, id:&AgentId) -> SdaServerResult<Option<AuthToken>>;
+    fn get_auth_token(&self, id:&AgentId) -> SdaServerResult<Option<HashedAuthToken>>;
 
     /// Delete an auth token
     fn delete_auth_token(&self, id:&AgentId) -> SdaServerResult<()>;
+
+    /// Hash the tokens left in clear by earlier versions of the store, returning how many were migrated
+    fn migrate_auth_tokens(&self) -> SdaServerResult<usize>;
 }
 
 pub trait AgentsStore: BaseStore {
@@ -25,6 +102,12 @@ pub trait AgentsStore: BaseStore {
     /// Retrieve the agent description.
     fn get_agent(&self, id: &AgentId) -> SdaServerResult<Option<Agent>>;
 
+    /// Replace the verification key of an agent, recording the rotation in its key history.
+    fn rotate_verification_key(&self, rotation: &SignedVerificationKeyRotation) -> SdaServerResult<()>;
+
+    /// Retrieve the verification key rotations of an agent, oldest first.
+    fn get_verification_key_history(&self, id: &AgentId) -> SdaServerResult<Vec<SignedVerificationKeyRotation>>;
+
     /// Register the given public profile; updates any existing profile.
     fn upsert_profile(&self, profile: &Profile) -> SdaServerResult<()>;
 
@@ -37,10 +120,28 @@ pub trait AgentsStore: BaseStore {
     /// Retrieve agent encryption key.
     fn get_encryption_key(&self, key: &EncryptionKeyId) -> SdaServerResult<Option<SignedEncryptionKey>>;
 
-    /// FIXME: very temporary interface. As logic needs to be adapted to each store
-    /// capabilities, no real need to abstract this in server, but we do need to
-    /// give more information about what is needed (supported keys, liveliness,
-    /// number, ...)
+    /// List the encryption keys of an agent, excluding revoked ones.
+    fn list_encryption_keys(&self, owner: &AgentId) -> SdaServerResult<Vec<EncryptionKeyId>>;
+
+    /// Record the revocation of an encryption key.
+    fn revoke_encryption_key(&self, revocation: &SignedEncryptionKeyRevocation) -> SdaServerResult<()>;
+
+    /// Retrieve the revocation of an encryption key, if it was revoked.
+    fn get_encryption_key_revocation(&self, key: &EncryptionKeyId) -> SdaServerResult<Option<SignedEncryptionKeyRevocation>>;
+
+    /// Record the registration of a clerk, replacing any earlier one.
+    fn upsert_clerk_registration(&self, registration: &SignedClerkRegistration) -> SdaServerResult<()>;
+
+    /// Retrieve the registration of a clerk, if any.
+    fn get_clerk_registration(&self, clerk: &AgentId) -> SdaServerResult<Option<SignedClerkRegistration>>;
+
+    /// Record the last time an agent was seen acting as a clerk.
+    fn record_clerk_seen(&self, clerk: &AgentId, now: u64) -> SdaServerResult<()>;
+
+    /// List the agents with keys that have not been revoked, along with the last time they were seen.
+    ///
+    /// Candidates are only offered keys that have not been revoked; the service narrows them
+    /// down to the criteria of the recipient.
     fn suggest_committee(&self) -> SdaServerResult<Vec<ClerkCandidate>>;
 }
 
@@ -50,20 +151,57 @@ pub trait AggregationsStore: BaseStore {
 
     /// Create a new aggregation on the service (without any associated result).
     /// If successful, the original id has been replaced by the returned id.
-    fn create_aggregation(&self, aggregation: &Aggregation) -> SdaServerResult<()>;
+    fn create_aggregation(&self, aggregation: &SignedAggregation) -> SdaServerResult<()>;
 
     /// Retrieve an aggregation and its description.
-    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<Aggregation>>;
+    fn get_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<Option<SignedAggregation>>;
 
-    /// Delete all information (including results) regarding an aggregation.
+    /// Delete all information regarding an aggregation: its state, committee, seat acceptances,
+    /// enrollments, masking cohort, participations and snapshots, along with their masks and
+    /// reveal contributions.
+    ///
+    /// The aggregation itself goes last, so that a failed deletion can be retried.
     fn delete_aggregation(&self, aggregation: &AggregationId) -> SdaServerResult<()>;
 
+    /// Retrieve the lifecycle state of an aggregation, if any was recorded.
+    fn get_aggregation_state(&self, aggregation: &AggregationId) -> SdaServerResult<Option<AggregationState>>;
+
+    /// Record the lifecycle state of an aggregation; replaces any earlier state.
+    fn set_aggregation_state(&self, aggregation: &AggregationId, state: AggregationState) -> SdaServerResult<()>;
+
+    /// Retrieve the privacy budget spent by the snapshots of an aggregation, if any was recorded.
+    fn get_privacy_budget(&self, aggregation: &AggregationId) -> SdaServerResult<Option<PrivacyBudget>>;
+
+    /// Record the privacy budget spent by the snapshots of an aggregation, provided the recorded
+    /// budget is still `previous`; returns `false` without any change otherwise.
+    fn update_privacy_budget(&self, aggregation: &AggregationId, previous: Option<&PrivacyBudget>, budget: &PrivacyBudget) -> SdaServerResult<bool>;
+
     /// Retrieve the associated committee.
-    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<Committee>>;
+    fn get_committee(&self, owner: &AggregationId) -> SdaServerResult<Option<SignedCommittee>>;
+
+    fn create_committee(&self, committee: &SignedCommittee) -> SdaServerResult<()>;
+
+    /// Record the acceptance of a committee seat by its clerk.
+    fn create_seat_acceptance(&self, acceptance: &SignedSeatAcceptance) -> SdaServerResult<()>;
 
-    fn create_committee(&self, committee: &Committee) -> SdaServerResult<()>;
+    /// List the seat acceptances received for the committee of an aggregation.
+    fn list_seat_acceptances(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<SignedSeatAcceptance>>;
 
-    fn create_participation(&self, participation: &Participation) -> SdaServerResult<()>;
+    /// Store a participation, handling earlier participations of the same participant according to the policy.
+    ///
+    /// Storing the same participation again has no effect, while reusing its id for another one is refused.
+    /// Concurrent calls must not get around either rule, nor the reject policy.
+    fn create_participation(&self, participation: &SignedParticipation, policy: ParticipationPolicy) -> SdaServerResult<()>;
+
+    fn create_enrollment(&self, enrollment: &Enrollment) -> SdaServerResult<()>;
+
+    fn get_enrollment(&self, aggregation: &AggregationId, participant: &AgentId) -> SdaServerResult<Option<Enrollment>>;
+
+    fn list_enrollments(&self, aggregation: &AggregationId) -> SdaServerResult<Vec<Enrollment>>;
+
+    fn create_masking_cohort(&self, cohort: &MaskingCohort) -> SdaServerResult<()>;
+
+    fn get_masking_cohort(&self, aggregation: &AggregationId) -> SdaServerResult<Option<MaskingCohort>>;
 
     fn create_snapshot(&self, snapshot: &Snapshot) -> SdaServerResult<()>;
 
@@ -73,10 +211,16 @@ pub trait AggregationsStore: BaseStore {
 
     fn count_participations(&self, aggregation:&AggregationId) -> SdaServerResult<usize>;
 
-    fn snapshot_participations(&self, aggregation: &AggregationId, snapshot:&SnapshotId) -> SdaServerResult<()>;
+    /// Count the participations not included in any snapshot yet.
+    fn count_new_participations(&self, aggregation:&AggregationId) -> SdaServerResult<usize>;
+
+    /// Capture the participations to include in a snapshot.
+    ///
+    /// Incremental snapshots leave out the participations included in earlier snapshots.
+    fn snapshot_participations(&self, aggregation: &AggregationId, snapshot:&SnapshotId, incremental: bool) -> SdaServerResult<()>;
 
     fn iter_snapped_participations<'a, 'b>(&'b self, aggregation:&AggregationId, snapshot:&SnapshotId)
-         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<Participation>> + 'a>>
+         -> SdaServerResult<Box<Iterator<Item = SdaServerResult<SignedParticipation>> + 'a>>
         where 'b: 'a;
 
     fn count_participations_snapshot(&self, aggregation:&AggregationId, snapshot: &SnapshotId) -> SdaServerResult<usize> {
@@ -93,7 +237,7 @@ pub trait AggregationsStore: BaseStore {
             .collect();
 
         for participation in self.iter_snapped_participations(aggregation, snapshot)? {
-            for (ix, share) in participation?.clerk_encryptions.into_iter().enumerate() {
+            for (ix, share) in participation?.body.clerk_encryptions.into_iter().enumerate() {
                 shares[ix].push(share.1);
             }
         }
@@ -103,18 +247,36 @@ pub trait AggregationsStore: BaseStore {
     fn create_snapshot_mask(&self, snapshot:&SnapshotId, mask:Vec<Encryption>) -> SdaServerResult<()>;
 
     fn get_snapshot_mask(&self, snapshot:&SnapshotId) -> SdaServerResult<Option<Vec<Encryption>>>;
+
+    fn create_reveal_contribution(&self, contribution: &SignedRevealContribution) -> SdaServerResult<()>;
+
+    fn list_reveal_contributions(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<SignedRevealContribution>>;
 }
 
 pub trait ClerkingJobsStore: BaseStore {
-    fn enqueue_clerking_job(&self, job:&ClerkingJob) -> SdaServerResult<()>;
+    /// Queue a job for its clerk, at the given time.
+    fn enqueue_clerking_job(&self, job:&ClerkingJob, now: u64) -> SdaServerResult<()>;
+
+    /// Lease the first available job of a clerk until the given time, counting an attempt.
+    fn poll_clerking_job(&self, clerk:&AgentId, now: u64, leased_until: u64) -> SdaServerResult<Option<ClerkingJob>>;
 
-    fn poll_clerking_job(&self, clerk:&AgentId) -> SdaServerResult<Option<ClerkingJob>>;
+    /// List the leases of all jobs without result.
+    fn list_clerking_job_leases(&self) -> SdaServerResult<Vec<ClerkingJobLease>>;
+
+    /// Stop handing out a job, its clerk being unresponsive.
+    fn abandon_clerking_job(&self, job: &ClerkingJobId) -> SdaServerResult<()>;
+
+    /// List the abandoned jobs of a snapshot.
+    fn list_abandoned_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<ClerkingJobLease>>;
 
     fn get_clerking_job(&self, clerk:&AgentId, job:&ClerkingJobId) -> SdaServerResult<Option<ClerkingJob>>;
 
-    fn create_clerking_result(&self, result: &ClerkingResult) -> SdaServerResult<()>;
+    fn create_clerking_result(&self, result: &SignedClerkingResult) -> SdaServerResult<()>;
 
     fn list_results(&self, snapshot: &SnapshotId) -> SdaServerResult<Vec<ClerkingJobId>>;
 
-    fn get_result(&self, snapshot: &SnapshotId, job:&ClerkingJobId) -> SdaServerResult<Option<ClerkingResult>>;
+    fn get_result(&self, snapshot: &SnapshotId, job:&ClerkingJobId) -> SdaServerResult<Option<SignedClerkingResult>>;
+
+    /// Delete the jobs of a snapshot, done or not, along with their leases and results.
+    fn delete_clerking_jobs(&self, snapshot: &SnapshotId) -> SdaServerResult<()>;
 }